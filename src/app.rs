@@ -1,20 +1,98 @@
 use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Line,
+    text::{Line, Span},
     widgets::{Block, Borders, List, ListItem, Padding, Paragraph, Wrap},
     Frame,
 };
 
-use crate::commands::CommandHandler;
-use crate::config::Config;
-use crate::formatting::format_messages_for_display;
-use crate::persistence::{Aliases, AppState, LayoutData, PaneState};
+use crate::commands::{command_candidates, command_cycle_hint, command_hint, needs_arguments, CommandHandler, ConfirmAction, COMMANDS};
+use crate::config::{Config, EnterOnEmpty, QuietHours, ReopenScroll};
+use crate::formatting::{
+    filters_label, format_messages_for_display, layout_chat_list_entry, line_owners, line_to_msg,
+    mentions_me, message_display_offset, reply_target, truncate_chars, wrap_plain_text, ChatListEntry, DisplayLine, DisplayOptions, ReactionStyle, TimestampStyle, UnreadCountStyle, WrapStyle,
+};
+use crate::persistence::{Aliases, AppState, LayoutData, Outbox, PaneState, QueuedMessage};
 use crate::split_view::{PaneNode, SplitDirection};
-use crate::whatsapp::WhatsAppClient;
-use crate::utils::{send_desktop_notification, try_autocomplete};
-use crate::widgets::ChatPane;
+use crate::theme::Theme;
+use crate::whatsapp::{mention_query, AuthProgress, AuthSession, ConnectionState, WhatsAppClient, UNKNOWN_JID};
+use crate::utils::{send_desktop_notification, try_autocomplete, EditorOutcome};
+use crate::widgets::{ChatPane, ChatSwitcher, MessageData, NewChatStep, NewChatWizard, ReactionPicker, READ_MARKER};
+
+/// Most messages a pane loads when paging back through history (e.g. for /date)
+const MAX_HISTORY_MESSAGES: usize = 2000;
+
+/// Seconds after the last keystroke before we tell the chat we stopped typing
+const TYPING_IDLE_SECS: u64 = 3;
+
+/// Minimum seconds between repeated "composing" notices to the same chat
+const TYPING_RESEND_SECS: u64 = 5;
+
+/// How many recently used reactions are remembered for the picker
+const MAX_RECENT_REACTIONS: usize = 8;
+
+/// Most people a mention hint lists
+const MAX_MENTION_HINTS: usize = 6;
+
+/// Seconds before a group whose member lookup failed is looked up again
+const MEMBERS_RETRY_SECS: u64 = 60;
+
+/// (JID, name) of a group's members
+type Members = Vec<(String, String)>;
+
+/// What startup fetches in the background: the chat list and, by chat, the messages of
+/// the chats open in saved panes
+type StartupLoad = (Result<Vec<ChatInfo>>, Vec<(String, Result<Vec<crate::whatsapp::RawMessage>>)>);
+
+/// How many recently opened chats the switcher offers
+const MAX_RECENT_CHATS: usize = 10;
+
+/// Milliseconds without another press of the switcher key before its chat opens
+pub const SWITCHER_HOLD_MS: u64 = 800;
+
+/// How many status bar notifications the activity log keeps
+const MAX_ACTIVITY: usize = 100;
+
+/// Seconds to wait before retrying the outbox after a queued message failed to send
+const OUTBOX_RETRY_SECS: u64 = 15;
+
+/// Seconds quitting waits for the send queue before keeping what's left in the outbox
+const SHUTDOWN_DRAIN_SECS: u64 = 5;
+
+/// Seconds after a layout or alias change before autosave writes it, so a burst of pane
+/// changes is saved once
+const AUTOSAVE_DEBOUNCE_SECS: u64 = 2;
+
+/// A sent message held back for the undo-send window
+struct HeldSend {
+    message: QueuedMessage,
+    claimed: Arc<AtomicBool>, // Set by whoever acts first: the send task, /unsend or quitting
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// Whether `data` is the optimistic copy shown for `message` before it synced
+fn is_echo_of(data: &MessageData, message: &QueuedMessage) -> bool {
+    data.msg_id.is_empty()
+        && data.is_outgoing
+        && data.text == message.text
+        && data.timestamp == message.queued_at
+}
+
+/// Keys the keymap file can't change, for the help overlay
+const HELP_FIXED_KEYS: &[(&str, &str)] = &[
+    ("tab / shift+tab", "Next / previous pane or chat list; completes /commands"),
+    ("up / down", "Move in the chat list, input history or list-mode selection"),
+    ("enter", "Open the selected chat / send the message"),
+    ("esc", "Cancel a reply"),
+    ("1-9", "On the chat list: choose the pane the next chat opens in"),
+    ("left / right, home / end", "Move the cursor"),
+    ("ctrl+left / ctrl+right", "Move the cursor by word"),
+    ("alt+backspace, ctrl+w", "Delete the previous word"),
+    ("?", "Show this help (on the chat list or with an empty input)"),
+];
 
 pub struct App {
     pub config: Config,
@@ -30,10 +108,12 @@ pub struct App {
     pub history_temp: String, // Save current input when browsing history
     pub aliases: Aliases,
     pub focus_on_chat_list: bool,
+    pub last_pane_before_list: Option<usize>, // Pane that Alt+C returns to from the chat list
     pub status_message: Option<String>, // Notification bar at bottom
     pub status_expire: Option<std::time::Instant>,
     pub pane_areas: std::collections::HashMap<usize, Rect>, // Track pane screen positions
     pub chat_list_area: Option<Rect>, // Track chat list area for mouse clicks
+    pub hovered_chat: Option<String>, // Chat (by ID) under the mouse in the chat list
     pub needs_redraw: bool,
 
     // Settings
@@ -46,7 +126,76 @@ pub struct App {
     pub show_chat_list: bool,
     pub show_user_colors: bool,
     pub show_borders: bool,
-    pub user_colors: std::collections::HashMap<String, Color>, // Map sender_id to color for group chats
+    pub muted_chats: std::collections::HashSet<String>,
+    pub notify_on_mention: bool,
+    pub dnd: bool,
+    pub quiet_hours: Option<QuietHours>,
+    pub archived_chats: std::collections::HashSet<String>,
+    pub auto_unarchive: bool,
+    /// Whether the chat list shows the "Archived" section (Ctrl+A)
+    pub show_archived: bool,
+    pub wrap_style: WrapStyle,
+    pub timestamp_style: TimestampStyle,
+    pub reaction_style: ReactionStyle,
+    pub chat_list_ellipsis: String,
+    pub default_country_code: String, // Put in front of wizard numbers typed without "+"
+    pub chat_list_unread: UnreadCountStyle,
+    pub reopen_scroll: ReopenScroll,
+    pub enter_on_empty: EnterOnEmpty,
+    /// Scroll offsets of chats switched away from, by chat_id, restored when they're reopened
+    pub chat_scroll: std::collections::HashMap<String, usize>,
+    /// Chats most recently opened in a pane, newest first
+    pub recent_chats: Vec<String>,
+    pub chat_switcher: Option<ChatSwitcher>,
+    pub new_chat_wizard: Option<NewChatWizard>,
+    pub chat_refresh_secs: u64,
+    pub event_poll_ms: u64,
+    pub initial_message_limit: usize, // Messages per fetch: on open, and per older batch
+    pub older_history_requested: Option<usize>, // Pane scrolled to its top; the main loop fetches older messages
+    pub show_presence: bool,
+    pub show_receipts: bool,
+    pub group_consecutive: bool,
+    pub show_status_line: bool,
+    pub send_typing: bool,
+    pub send_read_receipts: bool,
+    pub edit_resend: bool,
+    pub confirm_group_send: bool,
+    pub confirm_group_min_members: usize,
+    pub group_send_pending: Option<String>, // Group chat whose message waits for a confirming Enter
+    pub pending_confirm: Option<(String, ConfirmAction)>, // Destructive command waiting for y/n, with its prompt
+    pub undo_send_secs: u64,
+    held_sends: Vec<HeldSend>, // Sent messages still inside the undo window, oldest first
+    sends_in_flight: Vec<QueuedMessage>, // Handed to the send queue (or held for it) and not finished yet
+    pub reaction_picker: Option<ReactionPicker>,
+    /// First visible line of the help overlay, or None while it is closed
+    pub help_scroll: Option<u16>,
+    help_max_scroll: u16,
+    pub showing_activity: bool, // The help overlay shows the activity log instead
+    pub activity: Vec<(chrono::DateTime<chrono::Local>, String)>, // Recent notifications, oldest first
+    pub recent_reactions: Vec<String>,
+    pub pending_read_receipts: std::collections::HashMap<String, String>, // chat_id -> newest message read
+    pub typing_sent_to: Option<(String, std::time::Instant)>, // Chat last told we're composing, and when
+    pub presence_poll: Option<(String, tokio::task::JoinHandle<crate::whatsapp::Presence>)>, // Presence lookup in flight, by chat
+    receipts_poll: Option<(String, tokio::task::JoinHandle<crate::whatsapp::ReceiptMap>)>, // Receipts lookup in flight, by chat
+    pub connection: ConnectionState,
+    pub outbox: Outbox, // Messages written while disconnected, oldest first
+    last_outbox_attempt: Option<std::time::Instant>,
+    pub last_keystroke: Option<std::time::Instant>,
+    pub user_colors: std::collections::HashMap<String, Color>, // Colors pinned with /color, by sender_id
+    pub left_groups: std::collections::HashSet<String>, // Groups left with /leave, kept out of the chat list
+    pub group_members: std::collections::HashMap<String, Vec<(String, String)>>, // Group JID -> (JID, name) of its members, as last fetched
+    members_poll: Option<(String, tokio::task::JoinHandle<anyhow::Result<Members>>)>, // Member lookup in flight, by group
+    members_failed: std::collections::HashMap<String, std::time::Instant>, // Groups whose member lookup last failed, and when
+    pub auth: Option<AuthSession>, // `whatsapp-cli auth` shown in the login overlay
+    auth_shown: Option<AuthProgress>, // Overlay contents last drawn, to redraw on news only
+    pub autosave_secs: u64,
+    last_autosave: std::time::Instant,
+    saved_state: (String, String), // Layout with aliases, and settings, as last written
+    pub startup_pending: bool, // Chats and saved panes still to load after the first frame
+    startup_load: Option<(std::time::Instant, tokio::task::JoinHandle<StartupLoad>)>, // finish_startup's fetch in flight, and when it began
+    pub editor_requested: bool, // Ctrl+X or /compose: the main loop opens $EDITOR next
+    command_completion: Option<(usize, String, usize)>, // Pane, typed prefix and position while Tab steps through commands
+    layout_changed_at: Option<std::time::Instant>, // First unsaved layout or alias change
 }
 
 #[derive(Clone)]
@@ -55,23 +204,28 @@ pub struct ChatInfo {
     pub name: String,
     pub username: Option<String>,
     pub unread: u32,
-    pub _is_channel: bool,
+    pub is_channel: bool, // Read-only: a WhatsApp channel or the status broadcast
     pub is_group: bool,
+    pub last_message_ts: i64, // Unix time of the newest message, 0 if unknown
 }
 
 impl App {
     pub async fn new() -> Result<Self> {
         let config = Config::load()?;
         let whatsapp = WhatsAppClient::new(&config).await?;
-        let my_user_jid = whatsapp.get_me().await?;
+        // Without a login the client starts anyway and shows the login overlay
+        let (my_user_jid, needs_login) = match whatsapp.my_jid().await {
+            Some(jid) => (jid, false),
+            None => (crate::whatsapp::UNKNOWN_JID.to_string(), true),
+        };
         let app_state = AppState::load(&config).unwrap_or_else(|_| AppState {
             settings: crate::persistence::AppSettings::default(),
             aliases: Aliases::default(),
             layout: LayoutData::default(),
         });
 
-        // Load initial chats
-        let chats = whatsapp.get_dialogs().await.unwrap_or_else(|_| Vec::new());
+        // Chats and the saved panes' messages load after the first frame (finish_startup)
+        let chats = Vec::new();
 
         // Load pane tree first to know which panes we need
         let (pane_tree, required_indices) = if let Some(saved_tree) = app_state.layout.pane_tree {
@@ -106,18 +260,9 @@ impl App {
                 pane.chat_name = ps.chat_name.clone();
                 pane.scroll_offset = ps.scroll_offset;
                 // Load filter settings
-                if let Some(ref filter_type_str) = ps.filter_type {
-                    pane.filter_type = Some(match filter_type_str.as_str() {
-                        "sender" => crate::widgets::FilterType::Sender,
-                        "media" => crate::widgets::FilterType::Media,
-                        "link" => crate::widgets::FilterType::Link,
-                        _ => {
-                            panes.push(pane);
-                            continue;
-                        }
-                    });
-                }
-                pane.filter_value = ps.filter_value.clone();
+                pane.filters = ps.filters.clone();
+                pane.list_mode = ps.list_mode;
+                pane.send_as = ps.send_as.clone();
                 panes.push(pane);
             } else {
                 // Create empty pane for missing index
@@ -131,6 +276,42 @@ impl App {
             0
         };
 
+        let muted_chats = config.settings.muted_chats.clone();
+        let notify_on_mention = config.settings.notify_on_mention;
+        let dnd = config.settings.dnd;
+        let quiet_hours = config.settings.quiet_hours;
+        let archived_chats = config.settings.archived_chats.clone();
+        let auto_unarchive = config.settings.auto_unarchive;
+        let wrap_style = config.settings.wrap_style;
+        let timestamp_style = config.settings.timestamp_style;
+        let reaction_style = config.settings.reaction_style;
+        let chat_list_ellipsis = config.settings.chat_list_ellipsis.clone();
+        let default_country_code = config.settings.default_country_code.clone();
+        let chat_list_unread = config.settings.chat_list_unread;
+        let reopen_scroll = config.settings.reopen_scroll;
+        let enter_on_empty = config.settings.enter_on_empty;
+        let chat_scroll = app_state.layout.chat_scroll.clone();
+        let recent_chats = app_state.layout.recent_chats.clone();
+        let chat_refresh_secs = crate::config::clamp_chat_refresh_secs(config.settings.chat_refresh_secs);
+        let event_poll_ms = crate::config::clamp_event_poll_ms(config.settings.event_poll_ms);
+        let initial_message_limit = crate::config::clamp_message_limit(config.settings.initial_message_limit);
+        let show_presence = config.settings.show_presence;
+        let show_receipts = config.settings.show_receipts;
+        let group_consecutive = config.settings.group_consecutive;
+        let show_status_line = config.settings.show_status_line;
+        let send_typing = config.settings.send_typing;
+        let send_read_receipts = config.settings.send_read_receipts;
+        let edit_resend = config.settings.edit_resend;
+        let confirm_group_send = config.settings.confirm_group_send;
+        let confirm_group_min_members = config.settings.confirm_group_min_members;
+        let undo_send_secs = crate::config::clamp_undo_send_secs(config.settings.undo_send_secs);
+        let autosave_secs = crate::config::clamp_autosave_secs(config.settings.autosave_secs);
+        let recent_reactions = config.settings.recent_reactions.clone();
+        let user_colors = pinned_user_colors(&config.settings);
+        let outbox = Outbox::load(&config).unwrap_or_else(|e| {
+            crate::warn_log!("Failed to load outbox: {}", e);
+            Outbox::default()
+        });
         let mut app = Self {
             config,
             whatsapp,
@@ -145,9 +326,11 @@ impl App {
             history_temp: String::new(),
             aliases: app_state.aliases,
             focus_on_chat_list: true,
+            last_pane_before_list: None,
             status_message: None,
             status_expire: None,
             chat_list_area: None,
+            hovered_chat: None,
             pane_areas: std::collections::HashMap::new(),
             needs_redraw: true,
             show_reactions: app_state.settings.show_reactions,
@@ -159,48 +342,223 @@ impl App {
             show_chat_list: app_state.settings.show_chat_list,
             show_user_colors: app_state.settings.show_user_colors,
             show_borders: app_state.settings.show_borders,
-            user_colors: std::collections::HashMap::new(),
+            muted_chats,
+            notify_on_mention,
+            dnd,
+            quiet_hours,
+            archived_chats,
+            auto_unarchive,
+            show_archived: false,
+            wrap_style,
+            timestamp_style,
+            reaction_style,
+            chat_list_ellipsis,
+            default_country_code,
+            chat_list_unread,
+            reopen_scroll,
+            enter_on_empty,
+            chat_scroll,
+            recent_chats,
+            chat_switcher: None,
+            new_chat_wizard: None,
+            chat_refresh_secs,
+            event_poll_ms,
+            initial_message_limit,
+            older_history_requested: None,
+            show_presence,
+            show_receipts,
+            group_consecutive,
+            show_status_line,
+            send_typing,
+            send_read_receipts,
+            edit_resend,
+            confirm_group_send,
+            confirm_group_min_members,
+            group_send_pending: None,
+            pending_confirm: None,
+            undo_send_secs,
+            held_sends: Vec::new(),
+            sends_in_flight: Vec::new(),
+            reaction_picker: None,
+            help_scroll: None,
+            help_max_scroll: 0,
+            showing_activity: false,
+            activity: Vec::new(),
+            recent_reactions,
+            pending_read_receipts: std::collections::HashMap::new(),
+            typing_sent_to: None,
+            presence_poll: None,
+            receipts_poll: None,
+            connection: ConnectionState::Connecting,
+            outbox,
+            last_outbox_attempt: None,
+            last_keystroke: None,
+            user_colors,
+            left_groups: std::collections::HashSet::new(),
+            group_members: std::collections::HashMap::new(),
+            members_poll: None,
+            members_failed: std::collections::HashMap::new(),
+            auth: None,
+            auth_shown: None,
+            autosave_secs,
+            last_autosave: std::time::Instant::now(),
+            saved_state: (String::new(), String::new()),
+            startup_pending: true,
+            startup_load: None,
+            editor_requested: false,
+            command_completion: None,
+            layout_changed_at: None,
         };
 
-        // Load messages for all panes that have a saved chat_id
-        // This is what we had before - it works better
-        app.load_saved_chat_messages().await?;
+        let notes: Vec<String> = [
+            app.config.settings_note.clone(),
+            app.config.keymap_note.clone(),
+            app.config.theme_note.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !notes.is_empty() {
+            app.notify_with_duration(&notes.join(" | "), 10);
+        }
+        app.saved_state = app.state_fingerprint();
+        if needs_login {
+            app.start_auth();
+        }
 
         Ok(app)
     }
 
+    /// Bare app with default settings, one empty pane and no chats - no config file or CLI involved
+    #[cfg(test)]
+    pub fn new_for_test() -> Self {
+        let config = Config {
+            version: crate::config::CONFIG_VERSION,
+            whatsapp_cli_path: std::path::PathBuf::from("/nonexistent/whatsapp-cli"),
+            store_path: None,
+            settings: crate::config::Settings::default(),
+            config_dir: std::env::temp_dir().join("whatsapp_client_rs_test"),
+            keymap: crate::keymap::Keymap::default(),
+            keymap_note: None,
+            theme: crate::theme::Theme::default(),
+            theme_note: None,
+            settings_note: None,
+        };
+        let defaults = config.settings.clone();
+        let settings = crate::persistence::AppSettings::default();
+        Self {
+            config,
+            whatsapp: WhatsAppClient::new_for_test(),
+            my_user_jid: "me@s.whatsapp.net".to_string(),
+            chats: Vec::new(),
+            selected_chat_idx: 0,
+            panes: vec![ChatPane::new()],
+            focused_pane_idx: 0,
+            pane_tree: PaneNode::new_single(0),
+            input_history: Vec::new(),
+            history_idx: None,
+            history_temp: String::new(),
+            aliases: Aliases::default(),
+            focus_on_chat_list: true,
+            last_pane_before_list: None,
+            status_message: None,
+            status_expire: None,
+            chat_list_area: None,
+            hovered_chat: None,
+            pane_areas: std::collections::HashMap::new(),
+            needs_redraw: true,
+            show_reactions: settings.show_reactions,
+            show_notifications: settings.show_notifications,
+            compact_mode: settings.compact_mode,
+            show_emojis: settings.show_emojis,
+            show_line_numbers: settings.show_line_numbers,
+            show_timestamps: settings.show_timestamps,
+            show_chat_list: settings.show_chat_list,
+            show_user_colors: settings.show_user_colors,
+            show_borders: settings.show_borders,
+            muted_chats: std::collections::HashSet::new(),
+            notify_on_mention: defaults.notify_on_mention,
+            dnd: false,
+            quiet_hours: None,
+            archived_chats: std::collections::HashSet::new(),
+            auto_unarchive: defaults.auto_unarchive,
+            show_archived: false,
+            wrap_style: WrapStyle::default(),
+            timestamp_style: TimestampStyle::default(),
+            reaction_style: ReactionStyle::default(),
+            chat_list_ellipsis: defaults.chat_list_ellipsis.clone(),
+            default_country_code: defaults.default_country_code.clone(),
+            chat_list_unread: defaults.chat_list_unread,
+            reopen_scroll: defaults.reopen_scroll,
+            enter_on_empty: defaults.enter_on_empty,
+            chat_scroll: std::collections::HashMap::new(),
+            recent_chats: Vec::new(),
+            chat_switcher: None,
+            new_chat_wizard: None,
+            chat_refresh_secs: defaults.chat_refresh_secs,
+            event_poll_ms: defaults.event_poll_ms,
+            initial_message_limit: defaults.initial_message_limit,
+            older_history_requested: None,
+            show_presence: defaults.show_presence,
+            show_receipts: defaults.show_receipts,
+            group_consecutive: defaults.group_consecutive,
+            show_status_line: defaults.show_status_line,
+            send_typing: defaults.send_typing,
+            send_read_receipts: defaults.send_read_receipts,
+            edit_resend: defaults.edit_resend,
+            confirm_group_send: defaults.confirm_group_send,
+            confirm_group_min_members: defaults.confirm_group_min_members,
+            group_send_pending: None,
+            pending_confirm: None,
+            undo_send_secs: defaults.undo_send_secs,
+            held_sends: Vec::new(),
+            sends_in_flight: Vec::new(),
+            reaction_picker: None,
+            help_scroll: None,
+            help_max_scroll: 0,
+            showing_activity: false,
+            activity: Vec::new(),
+            recent_reactions: defaults.recent_reactions.clone(),
+            pending_read_receipts: std::collections::HashMap::new(),
+            typing_sent_to: None,
+            presence_poll: None,
+            receipts_poll: None,
+            connection: ConnectionState::Connecting,
+            outbox: Outbox::default(),
+            last_outbox_attempt: None,
+            last_keystroke: None,
+            user_colors: std::collections::HashMap::new(),
+            left_groups: std::collections::HashSet::new(),
+            group_members: std::collections::HashMap::new(),
+            members_poll: None,
+            members_failed: std::collections::HashMap::new(),
+            auth: None,
+            auth_shown: None,
+            autosave_secs: defaults.autosave_secs,
+            last_autosave: std::time::Instant::now(),
+            saved_state: (String::new(), String::new()),
+            startup_pending: false,
+            startup_load: None,
+            editor_requested: false,
+            command_completion: None,
+            layout_changed_at: None,
+        }
+    }
+
     /// Refresh messages for a specific pane
     async fn refresh_pane_messages(&mut self, pane_idx: usize) -> Result<()> {
         if let Some(pane) = self.panes.get(pane_idx) {
             if let Some(ref chat_id) = pane.chat_id {
-                match self.whatsapp.get_messages(&chat_id, 50).await {
+                match self.whatsapp.get_messages(chat_id, self.initial_message_limit).await {
                     Ok(raw_messages) => {
                         if !raw_messages.is_empty() {
                             let msg_data: Vec<crate::widgets::MessageData> = raw_messages
                                 .iter()
-                                .map(|(msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp)| {
-                                    let reply_to_msg_id = reply_to_id.clone();
-                                    
-                                    crate::widgets::MessageData {
-                                        msg_id: msg_id.clone(),
-                                        sender_id: sender_id.clone(),
-                                        sender_name: sender_name.clone(),
-                                        text: text.clone(),
-                                        is_outgoing: sender_id == &self.my_user_jid,
-                                        timestamp: *timestamp,
-                                        media_type: media_type.clone(),
-                                        media_label: None,
-                                        reactions: reactions.clone(),
-                                        reply_to_msg_id,
-                                        reply_sender: None,
-                                        reply_text: None,
-                                    }
-                                })
+                                .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
                                 .collect();
                             
                             if let Some(pane) = self.panes.get_mut(pane_idx) {
-                                pane.msg_data = msg_data;
-                                pane.format_cache.clear(); // Clear cache so messages are re-rendered
+                                pane.reload_messages(msg_data);
                             }
                         }
                     }
@@ -213,61 +571,201 @@ impl App {
         Ok(())
     }
 
-    /// Load messages for all panes that have a saved chat_id
-    async fn load_saved_chat_messages(&mut self) -> Result<()> {
-        for (_idx, pane) in self.panes.iter_mut().enumerate() {
-            if let Some(ref chat_id) = pane.chat_id {
-                // Try to load messages for this chat
-                match self.whatsapp.get_messages(&chat_id, 50).await {
-                    Ok(raw_messages) => {
-                        if !raw_messages.is_empty() {
-                            let msg_data: Vec<crate::widgets::MessageData> = raw_messages
-                                .iter()
-                                .map(|(msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp)| {
-                                    let reply_to_msg_id = reply_to_id.clone();
-                                    
-                                    crate::widgets::MessageData {
-                                        msg_id: msg_id.clone(),
-                                        sender_id: sender_id.clone(),
-                                        sender_name: sender_name.clone(),
-                                        text: text.clone(),
-                                        is_outgoing: sender_id == &self.my_user_jid,
-                                        timestamp: *timestamp,
-                                        media_type: media_type.clone(),
-                                        media_label: None,
-                                        reactions: reactions.clone(),
-                                        reply_to_msg_id,
-                                        reply_sender: None,
-                                        reply_text: None,
-                                    }
-                                })
-                                .collect();
-                            
-                            pane.msg_data = msg_data;
-                            pane.format_cache.clear(); // Clear cache so messages are re-rendered
-                            
-                            // Also try to find username from chats list
-                            if let Some(chat_info) = self.chats.iter().find(|c| &c.id == chat_id) {
-                                pane.username = chat_info.username.clone();
-                            }
-                        }
-                    }
-                    Err(_) => {
-                        // Silently continue loading other panes
+    /// Load older history into a pane until its oldest message is before `before_ts`, the chat
+    /// has no more history, or `MAX_HISTORY_MESSAGES` are loaded. Returns whether anything was added.
+    pub async fn load_history_before(&mut self, pane_idx: usize, before_ts: i64) -> Result<bool> {
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return Ok(false);
+        };
+        let Some(chat_id) = pane.chat_id.clone() else {
+            return Ok(false);
+        };
+
+        let mut loaded = pane.msg_data.len();
+        let mut oldest = pane.msg_data.first().map(|m| m.timestamp);
+        let mut added = false;
+        while oldest.is_none_or(|ts| ts >= before_ts) && loaded < MAX_HISTORY_MESSAGES {
+            let limit = (loaded.max(25) * 2).min(MAX_HISTORY_MESSAGES);
+            let raw_messages = self.whatsapp.get_messages(&chat_id, limit).await?;
+            if raw_messages.len() <= loaded {
+                break; // Nothing older in the store
+            }
+            crate::debug_log!("load_history_before: {} -> {} messages for {}", loaded, raw_messages.len(), chat_id);
+
+            let mut msg_data: Vec<MessageData> = raw_messages
+                .iter()
+                .map(|raw| MessageData::from_raw(raw, &self.my_user_jid))
+                .collect();
+            msg_data.sort_by_key(|m| m.timestamp);
+            loaded = msg_data.len();
+            oldest = msg_data.first().map(|m| m.timestamp);
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.reload_messages(msg_data);
+            }
+            added = true;
+        }
+        Ok(added)
+    }
+
+    /// Ask the main loop for older messages once a pane is scrolled to its first line
+    fn request_older_history_at_top(&mut self, pane_idx: usize) {
+        if self
+            .panes
+            .get(pane_idx)
+            .is_some_and(|p| p.scroll_offset == 1 && p.chat_id.is_some() && !p.history_exhausted && !p.history_unavailable)
+        {
+            self.older_history_requested = Some(pane_idx);
+        }
+    }
+
+    /// Fetch the batch of messages before a pane's oldest one and put it on top, keeping the
+    /// message that was on the top line there. Returns how many were added; when there are
+    /// none the pane stops asking and shows where the chat begins, or that older history is
+    /// out of whatsapp-cli's reach.
+    pub async fn load_older_messages(&mut self, pane_idx: usize) -> Result<usize> {
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return Ok(0);
+        };
+        let (Some(chat_id), Some(oldest)) = (pane.chat_id.clone(), pane.msg_data.first()) else {
+            return Ok(0);
+        };
+        if pane.history_exhausted || pane.history_unavailable {
+            return Ok(0);
+        }
+        let before_ts = oldest.timestamp;
+
+        let Some(raw_messages) = self
+            .whatsapp
+            .get_messages_before(&chat_id, before_ts, self.initial_message_limit)
+            .await?
+        else {
+            crate::debug_log!("load_older_messages: history before {} in {} is past the fetch cap", before_ts, chat_id);
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.history_unavailable = true;
+                pane.format_cache.clear();
+            }
+            return Ok(0);
+        };
+        crate::debug_log!("load_older_messages: {} messages before {} in {}", raw_messages.len(), before_ts, chat_id);
+        let older: Vec<MessageData> = raw_messages
+            .iter()
+            .map(|raw| MessageData::from_raw(raw, &self.my_user_jid))
+            .collect();
+        let Some(pane) = self.panes.get_mut(pane_idx) else {
+            return Ok(0);
+        };
+        let added = pane.prepend_messages(older);
+        if added == 0 {
+            pane.history_exhausted = true;
+            pane.format_cache.clear();
+            return Ok(0);
+        }
+        self.scroll_message_to_top(pane_idx, added);
+        Ok(added)
+    }
+
+    /// The slow part of startup, run once the first frame is on screen: the chat list, then
+    /// the messages of the chats left open in panes. They are fetched in the background, so
+    /// keys work meanwhile; called every loop iteration while `startup_pending`, it applies
+    /// them once they are in. Returns whether it changed anything.
+    pub async fn finish_startup(&mut self) -> bool {
+        let Some(task) = self.startup_load.take() else {
+            let whatsapp = self.whatsapp.clone();
+            let limit = self.initial_message_limit;
+            let mut chat_ids: Vec<String> = self.panes.iter().filter_map(|p| p.chat_id.clone()).collect();
+            chat_ids.sort_unstable();
+            chat_ids.dedup();
+            self.startup_load = Some((
+                std::time::Instant::now(),
+                tokio::spawn(async move {
+                    let chats = whatsapp.get_dialogs().await;
+                    let mut messages = Vec::new();
+                    for chat_id in chat_ids {
+                        let loaded = whatsapp.get_messages(&chat_id, limit).await;
+                        messages.push((chat_id, loaded));
                     }
+                    (chats, messages)
+                }),
+            ));
+            return false;
+        };
+        if !task.1.is_finished() {
+            self.startup_load = Some(task);
+            return false;
+        }
+        let (started, task) = task;
+        self.startup_pending = false;
+        let (chats, messages) = match task.await {
+            Ok(loaded) => loaded,
+            Err(e) => (Err(e.into()), Vec::new()),
+        };
+        match chats {
+            Ok(chats) => self.apply_chat_list(chats),
+            Err(e) => {
+                crate::warn_log!("finish_startup: {}", e);
+            }
+        }
+        self.load_saved_chat_messages(messages);
+        for pane_idx in 0..self.panes.len() {
+            self.show_queued_messages(pane_idx);
+        }
+        crate::info_log!("finish_startup: chats and saved panes loaded in {:?}", started.elapsed());
+
+        if self.chats.is_empty() && self.auth.is_none() {
+            self.notify_with_duration(
+                "No chats yet - normal the first time while whatsapp-cli syncs. For faster results, run `whatsapp-cli sync` in another terminal",
+                10,
+            );
+        }
+        true
+    }
+
+    /// Load messages for all panes that have a saved chat_id, from what `finish_startup`
+    /// fetched by chat. A pane switched to another chat in the meantime is left alone.
+    fn load_saved_chat_messages(&mut self, messages: Vec<(String, Result<Vec<crate::whatsapp::RawMessage>>)>) {
+        for (chat_id, loaded) in messages {
+            let raw_messages = match loaded {
+                Ok(raw_messages) if !raw_messages.is_empty() => raw_messages,
+                Ok(_) => continue,
+                Err(e) => {
+                    // Silently continue loading other panes
+                    crate::debug_log!("load_saved_chat_messages: {}: {}", chat_id, e);
+                    continue;
+                }
+            };
+            let username = self.chats.iter().find(|c| c.id == chat_id).and_then(|c| c.username.clone());
+            for pane in self.panes.iter_mut().filter(|p| p.chat_id.as_deref() == Some(chat_id.as_str())) {
+                pane.msg_data = raw_messages
+                    .iter()
+                    .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
+                    .collect();
+                pane.history_exhausted = false;
+                pane.history_unavailable = false;
+                pane.format_cache.clear(); // Clear cache so messages are re-rendered
+
+                // Also try to find username from chats list
+                if username.is_some() {
+                    pane.username = username.clone();
                 }
             }
         }
-        Ok(())
+    }
+
+    /// Drop typing indicators that stopped receiving events. Returns whether any changed,
+    /// so the main loop can redraw without waiting for other activity
+    pub fn expire_typing_indicators(&mut self) -> bool {
+        let mut changed = false;
+        for pane in &mut self.panes {
+            changed |= pane.check_typing_expired();
+        }
+        changed
     }
 
     pub fn draw(&mut self, f: &mut Frame) {
         // Update cursor blink timer for blinking cursor
         // This will be checked in draw_chat_pane_impl
         // Check typing indicators for expiry
-        for pane in &mut self.panes {
-            pane.check_typing_expired();
-        }
+        self.expire_typing_indicators();
         // Check status message expiry
         if let Some(expire) = self.status_expire {
             if std::time::Instant::now() >= expire {
@@ -276,7 +774,8 @@ impl App {
             }
         }
 
-        let has_status = self.status_message.is_some();
+        // A notification takes the status line's row until it expires
+        let has_status = self.status_message.is_some() || self.show_status_line || self.pending_confirm.is_some();
         let main_constraints = if has_status {
             vec![Constraint::Min(0), Constraint::Length(1)]
         } else {
@@ -309,53 +808,8 @@ impl App {
             self.chat_list_area = None;
         }
 
-        let colors = [
-            Color::Cyan, Color::Yellow, Color::Magenta, Color::Blue,
-            Color::Red, Color::Green, Color::White, Color::LightCyan,
-            Color::LightYellow, Color::LightMagenta, Color::LightBlue,
-            Color::LightRed, Color::LightGreen, Color::DarkGray,
-            Color::Rgb(192, 192, 192),
-            Color::Rgb(255, 165, 0),
-            Color::Rgb(255, 192, 203),
-            Color::Rgb(128, 0, 128),
-            Color::Rgb(0, 255, 255),
-            Color::Rgb(255, 20, 147)
-        ];
-        
-        let mut senders_to_color: Vec<String> = Vec::new();
-        for pane in &self.panes {
-            if let Some(ref chat_id) = pane.chat_id {
-                let is_group_chat = self.chats.iter().any(|c| &c.id == chat_id && c.is_group);
-                if is_group_chat && !pane.msg_data.is_empty() {
-                    for msg in &pane.msg_data {
-                        if !self.user_colors.contains_key(&msg.sender_id) && !senders_to_color.contains(&msg.sender_id) {
-                            senders_to_color.push(msg.sender_id.clone());
-                        }
-                    }
-                }
-            }
-        }
-        
-        for sender_id in &senders_to_color {
-            // Hash the string to get a u64
-            let mut hash: u64 = 0;
-            for byte in sender_id.bytes() {
-                hash = hash.wrapping_mul(31).wrapping_add(byte as u64);
-            }
-            hash = hash.wrapping_mul(2654435761);
-            hash = hash ^ (hash >> 16);
-            hash = hash.wrapping_mul(0x85ebca6b);
-            hash = hash ^ (hash >> 13);
-            hash = hash.wrapping_mul(0xc2b2ae35);
-            hash = hash ^ (hash >> 16);
-            
-            let color_idx = (hash as usize) % colors.len();
-            let color = colors[color_idx];
-            self.user_colors.insert(sender_id.clone(), color);
-        }
-
-        let render_fn = |f: &mut Frame, area: Rect, pane: &ChatPane, is_focused: bool| {
-            self.draw_chat_pane_impl(f, area, pane, is_focused);
+        let render_fn = |f: &mut Frame, area: Rect, pane_idx: usize, pane: &ChatPane, is_focused: bool| {
+            self.draw_chat_pane_impl(f, area, pane_idx, pane, is_focused);
         };
 
         let mut pane_areas = std::collections::HashMap::new();
@@ -363,117 +817,482 @@ impl App {
             .render(f, pane_area, &self.panes, self.focused_pane_idx, &render_fn, &mut pane_areas);
         self.pane_areas = pane_areas;
 
+        if let Some(ref picker) = self.reaction_picker {
+            if let Some(&area) = self.pane_areas.get(&picker.pane_idx) {
+                Self::draw_reaction_picker(f, area, picker, &self.config.theme);
+            }
+        }
+        if let Some(ref switcher) = self.chat_switcher {
+            Self::draw_chat_switcher(f, outer[0], switcher, &self.config.theme);
+        }
+        if let Some(ref wizard) = self.new_chat_wizard {
+            self.draw_new_chat_wizard(f, outer[0], wizard);
+        }
+
         // Draw status bar
         if has_status {
-            if let Some(ref msg) = self.status_message {
+            if let Some((ref prompt, _)) = self.pending_confirm {
+                let confirm = Paragraph::new(format!("{} Confirm? (y/n)", prompt))
+                    .style(Style::default().fg(self.config.theme.status).add_modifier(Modifier::BOLD | Modifier::REVERSED));
+                f.render_widget(confirm, outer[1]);
+            } else if let Some(ref msg) = self.status_message {
                 let status = Paragraph::new(msg.as_str())
-                    .style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+                    .style(Style::default().fg(self.config.theme.status).add_modifier(Modifier::BOLD));
                 f.render_widget(status, outer[1]);
+            } else {
+                f.render_widget(Paragraph::new(self.status_line()), outer[1]);
+            }
+        }
+
+        if let Some(scroll) = self.help_scroll {
+            self.draw_help(f, scroll);
+        }
+        if let Some(progress) = self.auth.as_ref().map(|a| a.progress()) {
+            self.draw_auth(f, &progress);
+        }
+    }
+
+    /// Footer: connection dot, unread total across chats, the focused chat and its filter
+    fn status_line(&self) -> Line<'static> {
+        let theme = &self.config.theme;
+        let (color, state) = if !self.whatsapp.sync_alive() {
+            (theme.disconnected, "sync stopped")
+        } else if self.connection == ConnectionState::Disconnected {
+            (theme.disconnected, self.connection.label())
+        } else {
+            (theme.connected, self.connection.label())
+        };
+        let unread: u32 = self.chats.iter().map(|c| c.unread).sum();
+        let pane = self.panes.get(self.focused_pane_idx);
+        let chat = match pane {
+            Some(p) if p.chat_id.is_some() => p.chat_name.clone(),
+            _ => "no chat".to_string(),
+        };
+
+        let dim = Style::default().fg(theme.dimmed);
+        let mut spans = vec![
+            Span::styled("● ", Style::default().fg(color)),
+            Span::raw(state),
+            Span::styled(" │ ", dim),
+            Span::raw(format!("{} unread", unread)),
+            Span::styled(" │ ", dim),
+            Span::raw(chat),
+        ];
+        if let Some(p) = pane.filter(|p| !p.filters.is_empty()) {
+            spans.push(Span::styled(" │ ", dim));
+            spans.push(Span::raw(format!("filter: {}", filters_label(&p.filters))));
+        }
+        if self.is_quiet_now() {
+            spans.push(Span::styled(" │ ", dim));
+            spans.push(Span::raw("DND"));
+        }
+        Line::from(spans)
+    }
+
+    /// Keys and commands listed by the help overlay, grouped by category
+    fn help_lines(&self) -> Vec<Line<'static>> {
+        let heading_color = self.config.theme.help_heading;
+        let heading = |text: String| {
+            Line::styled(text, Style::default().fg(heading_color).add_modifier(Modifier::BOLD))
+        };
+        let entry = |key: &str, text: &str| Line::from(format!("  {:<26} {}", key, text));
+
+        let mut lines = vec![heading("Keys".to_string())];
+        for (action, keys) in self.config.keymap.describe() {
+            let keys = if keys.is_empty() { "(unbound)".to_string() } else { keys };
+            lines.push(entry(&keys, action.description()));
+        }
+        lines.push(Line::default());
+        lines.push(heading("Editing keys".to_string()));
+        for (key, text) in HELP_FIXED_KEYS {
+            lines.push(entry(key, text));
+        }
+
+        let mut category = "";
+        for cmd in COMMANDS {
+            if cmd.category != category {
+                category = cmd.category;
+                lines.push(Line::default());
+                lines.push(heading(format!("Commands: {}", category)));
+            }
+            let aliases: Vec<String> = cmd.names[1..].iter().map(|a| format!("/{}", a)).collect();
+            let summary = if aliases.is_empty() {
+                cmd.summary.to_string()
+            } else {
+                format!("{} ({})", cmd.summary, aliases.join(", "))
+            };
+            lines.push(entry(cmd.usage, &summary));
+        }
+        lines
+    }
+
+    /// Recent notifications, newest first, with the time each was shown, wrapped to `width`
+    fn activity_lines(&self, width: usize) -> Vec<Line<'static>> {
+        let dim = Style::default().add_modifier(Modifier::DIM);
+        let indent = " ".repeat(9); // "12:34:56 "
+        let mut lines = Vec::new();
+        for (at, message) in self.activity.iter().rev() {
+            let wrapped = wrap_plain_text(message, width.saturating_sub(indent.len()).max(1));
+            for (idx, text) in wrapped.into_iter().enumerate() {
+                let time = if idx == 0 { at.format("%H:%M:%S ").to_string() } else { indent.clone() };
+                lines.push(Line::from(vec![Span::styled(time, dim), Span::raw(text)]));
+            }
+        }
+        lines
+    }
+
+    /// Centered box over everything else, scrolled to line `scroll`
+    fn draw_help(&mut self, f: &mut Frame, scroll: u16) {
+        let screen = f.area();
+        let width = screen.width.saturating_sub(4).min(90);
+        let (lines, title) = if self.showing_activity {
+            (self.activity_lines(width.saturating_sub(2) as usize), " Recent activity ")
+        } else {
+            (self.help_lines(), " Help ")
+        };
+        let height = (lines.len() as u16 + 2).min(screen.height.saturating_sub(2));
+        let area = Rect {
+            x: screen.x + (screen.width - width) / 2,
+            y: screen.y + (screen.height - height) / 2,
+            width,
+            height,
+        };
+
+        self.help_max_scroll = (lines.len() as u16).saturating_sub(height.saturating_sub(2));
+        let scroll = scroll.min(self.help_max_scroll);
+        self.help_scroll = Some(scroll);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_bottom(" ↑/↓ PgUp/PgDn scroll, any other key closes ")
+            .border_style(Style::default().fg(self.config.theme.border_focused));
+        f.render_widget(ratatui::widgets::Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+    }
+
+    pub fn show_help(&mut self) {
+        self.showing_activity = false;
+        self.help_scroll = Some(0);
+    }
+
+    /// Open the activity log in the help overlay, newest notification on top
+    pub fn show_activity(&mut self) {
+        if self.activity.is_empty() {
+            self.notify("No notifications yet");
+            return;
+        }
+        self.showing_activity = true;
+        self.help_scroll = Some(0);
+    }
+
+    pub fn close_help(&mut self) {
+        self.help_scroll = None;
+        self.showing_activity = false;
+    }
+
+    /// Scroll the help overlay by `delta` lines, staying within the text
+    pub fn scroll_help(&mut self, delta: i32) {
+        if let Some(scroll) = self.help_scroll {
+            let max = self.help_max_scroll as i32;
+            self.help_scroll = Some((scroll as i32 + delta).clamp(0, max) as u16);
+        }
+    }
+
+    /// Login overlay: what to do on the phone, then `whatsapp-cli auth` output and the code
+    /// to scan. We draw the code ourselves when the CLI gives it as text; if the screen is too
+    /// small for it, the code is shown as text instead. Shows the newest lines when too tall.
+    fn draw_auth(&self, f: &mut Frame, progress: &AuthProgress) {
+        let screen = f.area();
+        let mut lines = vec![
+            Line::raw("WhatsApp isn't logged in. On your phone open Settings > Linked devices >"),
+            Line::raw("Link a device and scan the code below."),
+            Line::raw(""),
+        ];
+        let qr = progress.qr.as_deref().map(|code| (code, crate::qr::render(code).unwrap_or_default()));
+        // Once we draw the code, the CLI's own drawing of it would only be in the way
+        let output = progress.lines.iter().filter(|l| qr.is_none() || !crate::qr::is_qr_art(l));
+        lines.extend(output.map(|l| Line::raw(l.clone())));
+        if let Some((code, art)) = qr {
+            let art_width = art.first().map_or(0, |l| l.chars().count() as u16);
+            let fits = art_width + 2 <= screen.width && (lines.len() + art.len() + 2) as u16 <= screen.height;
+            if fits && !art.is_empty() {
+                let style = Style::default().fg(Color::White).bg(Color::Black);
+                lines.extend(art.into_iter().map(|l| Line::styled(l, style)));
+            } else {
+                lines.push(Line::raw(format!(
+                    "Too small for the QR code (needs {}x{}); enlarge the window. The code:",
+                    art_width + 2,
+                    lines.len() + art.len() + 2
+                )));
+                let width = screen.width.saturating_sub(2).max(1) as usize;
+                let chars: Vec<char> = code.chars().collect();
+                lines.extend(chars.chunks(width).map(|chunk| Line::raw(chunk.iter().collect::<String>())));
+            }
+        }
+        let footer = match &progress.finished {
+            Some(Err(e)) => {
+                lines.push(Line::raw(""));
+                lines.push(Line::styled(format!("Login failed: {}", e), Style::default().fg(self.config.theme.status)));
+                " Enter tries again, Esc closes "
+            }
+            _ => " Esc cancels ",
+        };
+
+        let widest = lines.iter().map(|l| l.width() as u16).max().unwrap_or(0);
+        let width = (widest + 2).min(screen.width);
+        let height = (lines.len() as u16 + 2).min(screen.height);
+        let area = Rect {
+            x: screen.x + (screen.width - width) / 2,
+            y: screen.y + (screen.height - height) / 2,
+            width,
+            height,
+        };
+        let scroll = (lines.len() as u16).saturating_sub(height.saturating_sub(2));
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Log in ")
+            .title_bottom(footer)
+            .border_style(Style::default().fg(self.config.theme.border_focused));
+        f.render_widget(ratatui::widgets::Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+    }
+
+    /// Run `whatsapp-cli auth` behind the login overlay
+    pub fn start_auth(&mut self) {
+        match self.whatsapp.reauthenticate() {
+            Ok(session) => self.auth = Some(session),
+            Err(e) => self.notify(&format!("Couldn't start whatsapp-cli auth: {}", e)),
+        }
+    }
+
+    /// Esc in the login overlay: stops `whatsapp-cli auth`
+    pub fn close_auth(&mut self) {
+        self.auth = None;
+    }
+
+    /// Enter in the login overlay: start over once the last attempt failed
+    pub fn retry_auth(&mut self) {
+        if self.auth.as_ref().is_some_and(|a| matches!(a.progress().finished, Some(Err(_)))) {
+            self.start_auth();
+        }
+    }
+
+    /// /reconnect: check the login; when it's fine restart sync, otherwise log in again
+    pub async fn reconnect(&mut self) {
+        match self.whatsapp.get_me().await {
+            Ok(jid) => {
+                self.resume_after_login(jid).await;
+                self.notify("Logged in: sync restarted");
+            }
+            Err(e) => {
+                crate::info_log!("reconnect: not logged in: {}", e);
+                self.start_auth();
+            }
+        }
+    }
+
+    /// Follow the login overlay: once `whatsapp-cli auth` succeeds, close it and carry on as
+    /// after a normal start. Returns whether the overlay needs drawing again.
+    pub async fn check_auth(&mut self) -> bool {
+        let Some(progress) = self.auth.as_ref().map(|a| a.progress()) else {
+            return false;
+        };
+        if progress.finished == Some(Ok(())) {
+            self.auth = None;
+            match self.whatsapp.get_me().await {
+                Ok(jid) => {
+                    self.resume_after_login(jid).await;
+                    self.notify("Logged in");
+                }
+                Err(e) => self.notify(&format!("Still not logged in: {}", e)),
             }
+            return true;
+        }
+        let changed = self.auth_shown.as_ref() != Some(&progress);
+        self.auth_shown = Some(progress);
+        changed
+    }
+
+    /// Pick up a (new) login: our JID, contact names, a fresh sync process and the chat list
+    async fn resume_after_login(&mut self, jid: String) {
+        self.whatsapp.set_my_jid(&jid).await;
+        self.my_user_jid = jid;
+        self.whatsapp.prime_contact_cache().await;
+        self.whatsapp.restart_sync().await;
+        if let Err(e) = self.refresh_chat_list().await {
+            crate::warn_log!("resume_after_login: {}", e);
+        }
+    }
+
+    /// Small box along the bottom of the pane, over the messages just above the input
+    fn draw_reaction_picker(f: &mut Frame, pane_area: Rect, picker: &ReactionPicker, theme: &Theme) {
+        let mut spans = Vec::new();
+        for (i, emoji) in picker.choices.iter().enumerate() {
+            let style = if i == picker.selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            spans.push(ratatui::text::Span::styled(format!("{} {}", i + 1, emoji), style));
+            spans.push(ratatui::text::Span::raw("  "));
         }
+        spans.pop();
+        let line = Line::from(spans);
+
+        let width = (line.width() as u16 + 4).min(pane_area.width);
+        let height = 3.min(pane_area.height);
+        let area = Rect {
+            x: pane_area.x + 1.min(pane_area.width.saturating_sub(width)),
+            y: (pane_area.y + pane_area.height).saturating_sub(height + 4).max(pane_area.y),
+            width,
+            height,
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!("React to #{}", picker.msg_idx + 1))
+            .border_style(Style::default().fg(theme.border_focused))
+            .padding(Padding::horizontal(1));
+        f.render_widget(ratatui::widgets::Clear, area);
+        f.render_widget(Paragraph::new(line).block(block), area);
+    }
+
+    /// Box in the middle of the screen listing recent chats, the highlighted one reversed
+    fn draw_chat_switcher(f: &mut Frame, screen: Rect, switcher: &ChatSwitcher, theme: &Theme) {
+        let lines: Vec<Line> = switcher
+            .chats
+            .iter()
+            .enumerate()
+            .map(|(i, (_, name))| {
+                let style = if i == switcher.selected {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::styled(name.clone(), style)
+            })
+            .collect();
+        let title = "Recent chats";
+        let widest = lines.iter().map(Line::width).max().unwrap_or(0).max(title.len());
+        let width = (widest as u16 + 4).min(screen.width);
+        let height = (lines.len() as u16 + 2).min(screen.height);
+        let area = Rect {
+            x: screen.x + (screen.width - width) / 2,
+            y: screen.y + (screen.height - height) / 2,
+            width,
+            height,
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .border_style(Style::default().fg(theme.border_focused))
+            .padding(Padding::horizontal(1));
+        f.render_widget(ratatui::widgets::Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_new_chat_wizard(&self, f: &mut Frame, screen: Rect, wizard: &NewChatWizard) {
+        let theme = &self.config.theme;
+        let hint = Style::default().add_modifier(Modifier::DIM);
+        let jid = self.new_chat_jid();
+        let target = jid.as_deref().map(|jid| self.new_chat_target(jid));
+        let lines: Vec<Line> = match wizard.step {
+            NewChatStep::Number => {
+                let check = match (&jid, &target) {
+                    (Some(jid), Some(name)) => Line::from(format!("→ {} ({})", name, jid)),
+                    _ if wizard.number.trim().is_empty() => Line::styled(
+                        match self.default_country_code.trim().trim_start_matches('+') {
+                            "" => "Include the country code, e.g. +46 70 123 45 67".to_string(),
+                            code => format!("Numbers without + get +{} in front", code),
+                        },
+                        hint,
+                    ),
+                    _ => Line::styled("Not a phone number yet", Style::default().fg(theme.disconnected)),
+                };
+                vec![
+                    Line::from(format!("Phone number: {}▏", wizard.number)),
+                    check,
+                    Line::default(),
+                    Line::styled("Enter: next   Esc: cancel", hint),
+                ]
+            }
+            NewChatStep::Message => vec![
+                Line::from(format!("To: {}", target.unwrap_or_default())),
+                Line::from(format!("First message: {}▏", wizard.message)),
+                Line::styled("Leave it empty to just open the chat", hint),
+                Line::default(),
+                Line::styled("Enter: open chat   Esc: back", hint),
+            ],
+        };
+        let widest = lines.iter().map(Line::width).max().unwrap_or(0).max(48);
+        let width = (widest as u16 + 4).min(screen.width);
+        let height = (lines.len() as u16 + 2).min(screen.height);
+        let area = Rect {
+            x: screen.x + (screen.width - width) / 2,
+            y: screen.y + (screen.height - height) / 2,
+            width,
+            height,
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title("New chat")
+            .border_style(Style::default().fg(theme.border_focused))
+            .padding(Padding::horizontal(1));
+        f.render_widget(ratatui::widgets::Clear, area);
+        f.render_widget(Paragraph::new(lines).block(block), area);
     }
 
     fn draw_chat_list(&self, f: &mut Frame, area: Rect) {
+        let theme = &self.config.theme;
         // Find which chat is open in the focused pane
         let active_chat_id = self.panes
             .get(self.focused_pane_idx)
             .and_then(|p| p.chat_id.clone());
         
-        let max_width = area.width.saturating_sub(6).max(1) as usize;
-        let (unread_group, active_group, other_group) = self.chat_list_groups();
+        let max_width = Self::chat_list_text_width(area);
+        let sections = self.chat_list_groups();
 
         let build_item = |chat: &ChatInfo| -> ListItem {
             // Highlight if this chat is open in the focused pane
             let base_style = if Some(chat.id.clone()) == active_chat_id {
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(theme.chat_active)
                     .add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
             };
 
-            let unread_marker = if chat.unread > 0 { "▶ " } else { "" };
-            let unread_count = if chat.unread > 0 {
-                format!("({}) ", chat.unread)
-            } else {
-                String::new()
-            };
-
-            let mut name_part = chat.name.clone();
-            if let Some(ref username) = chat.username {
-                if !username.is_empty() {
-                    name_part.push_str(&format!(" {}", username));
-                }
-            }
-
-            let mut spans = Vec::new();
-            if !unread_marker.is_empty() {
-                spans.push(ratatui::text::Span::styled(
-                    unread_marker.to_string(),
-                    Style::default().fg(Color::Red),
+            let entry = self.chat_list_entry(chat, max_width);
+            let mut out_spans = Vec::new();
+            if !entry.marker.is_empty() {
+                out_spans.push(ratatui::text::Span::styled(
+                    entry.marker,
+                    Style::default().fg(theme.chat_marker),
                 ));
             }
-            if !unread_count.is_empty() {
-                spans.push(ratatui::text::Span::styled(unread_count, base_style));
-            }
-            spans.push(ratatui::text::Span::styled(name_part, base_style));
-
-            // Truncate spans to fit
-            let total_chars: usize = spans.iter().map(|s| s.content.chars().count()).sum();
-            let truncated = total_chars > max_width && max_width > 0;
-            let mut remaining = if truncated { max_width.saturating_sub(1) } else { max_width };
-            let mut out_spans: Vec<ratatui::text::Span> = Vec::new();
-
-            for span in spans.into_iter() {
-                if remaining == 0 {
-                    break;
-                }
-                let span_len = span.content.chars().count();
-                if span_len <= remaining {
-                    remaining = remaining.saturating_sub(span_len);
-                    out_spans.push(span);
-                } else {
-                    let clipped: String = span.content.chars().take(remaining).collect();
-                    out_spans.push(ratatui::text::Span::styled(clipped, span.style));
-                    break;
+            for part in [entry.prefix, entry.name, entry.suffix] {
+                if !part.is_empty() {
+                    out_spans.push(ratatui::text::Span::styled(part, base_style));
                 }
             }
 
-            if truncated {
-                out_spans.push(ratatui::text::Span::styled("…".to_string(), base_style));
-            }
-
             ListItem::new(ratatui::text::Line::from(out_spans))
         };
 
         let header_style = Style::default()
-            .fg(Color::DarkGray)
+            .fg(theme.section_header)
             .add_modifier(Modifier::BOLD);
         let mut items: Vec<ListItem> = Vec::new();
 
-        if !unread_group.is_empty() {
-            items.push(ListItem::new("Unread").style(header_style));
-            for chat_idx in unread_group.iter() {
-                items.push(build_item(&self.chats[*chat_idx]));
-            }
-        }
-
-        if !active_group.is_empty() {
-            items.push(ListItem::new("Active").style(header_style));
-            for chat_idx in active_group.iter() {
-                items.push(build_item(&self.chats[*chat_idx]));
-            }
-        }
-
-        if !other_group.is_empty() {
-            items.push(ListItem::new("Other").style(header_style));
-            for chat_idx in other_group.iter() {
+        for (title, group) in &sections {
+            items.push(ListItem::new(*title).style(header_style));
+            for chat_idx in group {
                 items.push(build_item(&self.chats[*chat_idx]));
             }
         }
 
         let border_style = if self.focus_on_chat_list {
-            Style::default().fg(Color::Green)
+            Style::default().fg(theme.border_focused)
         } else {
             Style::default()
         };
@@ -493,13 +1312,65 @@ impl App {
         f.render_widget(list, area);
     }
 
+    /// Columns available for a chat list entry before it is truncated
+    fn chat_list_text_width(area: Rect) -> usize {
+        area.width.saturating_sub(6).max(1) as usize
+    }
+
+    /// Whether messages can't be sent to `chat_id`: a channel as the chat list marks it, or
+    /// by its JID for a chat the list doesn't have
+    pub fn is_read_only_chat(&self, chat_id: &str) -> bool {
+        self.chats
+            .iter()
+            .find(|c| c.id == chat_id)
+            .map_or_else(|| crate::whatsapp::is_channel_jid(chat_id), |c| c.is_channel)
+    }
+
+    /// Whether `chat_id` is the "message yourself" chat
+    pub fn is_self_chat(&self, chat_id: &str) -> bool {
+        chat_id == self.my_user_jid && self.my_user_jid != crate::whatsapp::UNKNOWN_JID
+    }
+
+    /// Chat name as shown in the list: name, self-chat label, mute and channel markers and username
+    fn chat_list_name(&self, chat: &ChatInfo) -> String {
+        let mut name_part = chat.name.clone();
+        if self.is_self_chat(&chat.id) {
+            name_part.push_str(" (You)");
+        }
+        if self.muted_chats.contains(&chat.id) {
+            name_part.push_str(" 🔇");
+        }
+        if chat.is_channel {
+            name_part.push_str(" 📢");
+        }
+        if let Some(ref username) = chat.username {
+            if !username.is_empty() {
+                name_part.push_str(&format!(" {}", username));
+            }
+        }
+        name_part
+    }
+
+    /// A chat's list entry laid out in `width` columns with the configured count style
+    fn chat_list_entry(&self, chat: &ChatInfo, width: usize) -> ChatListEntry {
+        layout_chat_list_entry(
+            chat.unread,
+            &self.chat_list_name(chat),
+            width,
+            self.chat_list_unread,
+            &self.chat_list_ellipsis,
+        )
+    }
+
     fn draw_chat_pane_impl(
         &self,
         f: &mut Frame,
         area: Rect,
+        pane_idx: usize,
         pane: &ChatPane,
         is_focused: bool,
     ) {
+        let theme = &self.config.theme;
         let has_reply_preview = pane.reply_preview.is_some();
 
         // Calculate input height dynamically based on text width
@@ -548,28 +1419,44 @@ impl App {
             .constraints(constraints)
             .split(area);
 
+        // While picking from the chat list, everything except the target pane is dimmed
+        let dimmed = self.focus_on_chat_list && !is_focused;
+
         // Header with online status, username, pinned, typing
         let header_style = if is_focused {
             if self.focus_on_chat_list {
                 // Show which pane will receive the next chat from list
                 Style::default()
-                    .fg(Color::Yellow)
+                    .fg(theme.header_target)
                     .add_modifier(Modifier::BOLD)
             } else {
                 // Active input pane
                 Style::default()
-                    .fg(Color::Green)
+                    .fg(theme.header_focused)
                     .add_modifier(Modifier::BOLD)
             }
+        } else if dimmed {
+            Style::default().fg(theme.dimmed)
         } else {
-            Style::default().fg(Color::Cyan)
+            Style::default().fg(theme.header)
         };
 
         let mut header_text = String::new();
-        if is_focused && self.focus_on_chat_list {
-            header_text.push_str("[TARGET] ");
+        if self.focus_on_chat_list {
+            // Number each pane so it can be picked as target with 1-9 from the chat list
+            let pane_order = self.pane_tree.get_pane_indices();
+            let pane_number = pane_order.iter().position(|&idx| idx == pane_idx).map(|pos| pos + 1);
+            match (is_focused, pane_number) {
+                (true, Some(n)) if pane_order.len() > 1 => header_text.push_str(&format!("▶ [TARGET {}] ", n)),
+                (true, _) => header_text.push_str("▶ [TARGET] "),
+                (false, Some(n)) => header_text.push_str(&format!("[{}] ", n)),
+                (false, None) => {}
+            }
         }
         header_text.push_str(&pane.header_text());
+        if pane.chat_id.as_deref().is_some_and(|id| self.is_read_only_chat(id)) {
+            header_text.push_str(" 📢 read-only");
+        }
         
         let header_block = if self.show_borders {
             Block::default().borders(Borders::ALL)
@@ -591,109 +1478,27 @@ impl App {
             false
         };
         
-        let display_lines = if !pane.msg_data.is_empty() {
+        let layout = pane.line_layout(message_width, self.wrap_style);
+        let (display_lines, owners) = if !pane.msg_data.is_empty() {
             // Use msg_data for rich formatting
-            let filter_type = pane
-                .filter_type
-                .as_ref()
-                .map(|ft| match ft {
-                    crate::widgets::FilterType::Sender => "sender",
-                    crate::widgets::FilterType::Media => "media",
-                    crate::widgets::FilterType::Link => "link",
-                });
-            let filter_value = pane.filter_value.as_deref();
-
-            let mut lines = format_messages_for_display(
-                &pane.msg_data,
-                message_width,
-                self.compact_mode,
-                self.show_emojis,
-                self.show_reactions,
-                self.show_timestamps,
-                self.show_line_numbers,
-                filter_type,
-                filter_value,
-                pane.unread_count_at_load,
-                &self.aliases.map,
-            );
+
+            let (mut lines, starts) = format_messages_for_display(pane, message_width, self.display_options(), &self.aliases.map);
+            // Status lines appended below have no message, so they fall past the end of the map
+            *pane.line_to_msg.borrow_mut() = line_to_msg(&lines, &starts, &layout);
+            let owners = line_owners(&lines, &starts);
             
             // Append any status messages from pane.messages (like "✓ Replied to #5")
             if !pane.messages.is_empty() {
                 lines.push(String::new()); // Separator
                 lines.extend(pane.messages.clone());
             }
-            lines
+            (lines, owners)
         } else {
             // Fallback to plain messages (for status messages, etc.)
-            pane.messages.clone()
-        };
-
-        let wrap_plain_text = |text: &str, max_width: usize| -> Vec<String> {
-            if max_width == 0 || text.len() <= max_width {
-                return vec![text.to_string()];
-            }
-
-            let mut lines = Vec::new();
-            let mut current_line = String::new();
-
-            for word in text.split_whitespace() {
-                if current_line.len() + word.len() + 1 > max_width {
-                    if !current_line.is_empty() {
-                        lines.push(current_line.clone());
-                        current_line.clear();
-                    }
-                    if word.chars().count() > max_width {
-                        let split_at = word
-                            .char_indices()
-                            .nth(max_width)
-                            .map(|(i, _)| i)
-                            .unwrap_or(word.len());
-                        lines.push(word[..split_at].to_string());
-                        current_line = word[split_at..].to_string();
-                    } else {
-                        current_line = word.to_string();
-                    }
-                } else {
-                    if !current_line.is_empty() {
-                        current_line.push(' ');
-                    }
-                    current_line.push_str(word);
-                }
-            }
-            if !current_line.is_empty() {
-                lines.push(current_line);
-            }
-            lines
+            pane.line_to_msg.borrow_mut().clear();
+            (pane.messages.clone(), Vec::new())
         };
 
-        let wrap_message_with_indent =
-            |prefix: &str, sender_name: &str, message_text: &str, max_width: usize| -> Vec<String> {
-                let header = format!("{}{}: ", prefix, sender_name);
-                let indent_len = header.chars().count();
-
-                if max_width == 0 {
-                    return vec![format!("{}{}", header, message_text)];
-                }
-
-                if indent_len >= max_width {
-                    return wrap_plain_text(&format!("{}{}", header, message_text), max_width);
-                }
-
-                let first_width = max_width.saturating_sub(indent_len);
-                let wrapped = wrap_plain_text(message_text, first_width);
-                if wrapped.is_empty() {
-                    return vec![header.trim_end().to_string()];
-                }
-
-                let indent = " ".repeat(indent_len);
-                let mut lines = Vec::with_capacity(wrapped.len());
-                lines.push(format!("{}{}", header, wrapped[0]));
-                for line in wrapped.iter().skip(1) {
-                    lines.push(format!("{}{}", indent, line));
-                }
-                lines
-            };
-
         let style_name_in_line = |line: &str, sender_name: &str, name_style: Style| -> Line {
             if sender_name.is_empty() {
                 return Line::from(line.to_string());
@@ -715,128 +1520,172 @@ impl App {
             }
         };
 
-        let message_lines: Vec<Line> = display_lines
-            .iter()
-            .flat_map(|msg| {
-                if msg.is_empty() {
-                    return vec![Line::from("")];
+        // The quoted sender's name in a reply line, with the color it has as an author
+        let reply_name = |owner: Option<usize>| -> Option<(String, Style)> {
+            let data = pane.msg_data.get(owner?)?;
+            let original = reply_target(&pane.msg_data, data)?;
+            let name = self.aliases.map.get(&original.sender_id).unwrap_or(&original.sender_name);
+            let color = self.name_color(&original.sender_id, original.is_outgoing, is_group_chat);
+            Some((name.clone(), Style::default().fg(color)))
+        };
+        let style_reply_line = |line: String, owner: Option<usize>, idx: usize, style: Style| -> Line {
+            match reply_name(owner) {
+                Some((name, name_style)) if idx == 0 && self.show_user_colors => {
+                    style_name_in_line(&line, &name, name_style).style(style)
                 }
+                _ => Line::from(line).style(style),
+            }
+        };
 
-                if msg.starts_with("[REPLY_TO_ME]") {
-                    let clean_msg = msg.replace("[REPLY_TO_ME]", "").trim_start().to_string();
-                    return wrap_plain_text(&clean_msg, message_width)
+        // How others write a mention of us, e.g. "@46701234567"
+        let mention_marker = (self.my_user_jid != UNKNOWN_JID)
+            .then(|| self.my_user_jid.split(['@', ':']).next().unwrap_or_default())
+            .filter(|number| !number.is_empty())
+            .map(|number| format!("@{}", number));
+        let selected = pane.selected_msg.filter(|_| pane.list_mode);
+        let message_lines: Vec<Line> = display_lines
+            .iter()
+            .enumerate()
+            .flat_map(|(line_idx, msg)| {
+                let owner = owners.get(line_idx).copied().flatten();
+                let parsed = DisplayLine::parse(msg);
+                let wrapped = layout.screen_lines(&parsed, owner);
+                let lines: Vec<Line> = match parsed {
+                    DisplayLine::ReplyToMe(_) => wrapped
                         .into_iter()
-                        .map(|line| {
-                            Line::from(line).style(
+                        .enumerate()
+                        .map(|(idx, line)| {
+                            style_reply_line(
+                                line,
+                                owner,
+                                idx,
                                 Style::default()
-                                    .fg(Color::Red)
+                                    .fg(theme.reply_to_me)
                                     .add_modifier(Modifier::ITALIC),
                             )
                         })
-                        .collect();
-                }
-
-                if msg.starts_with("  ↳ Reply to") {
-                    return wrap_plain_text(msg, message_width)
+                        .collect(),
+                    DisplayLine::ReplyContext(_) => wrapped
                         .into_iter()
-                        .map(|line| {
-                            Line::from(line).style(
+                        .enumerate()
+                        .map(|(idx, line)| {
+                            style_reply_line(
+                                line,
+                                owner,
+                                idx,
                                 Style::default()
-                                    .fg(Color::DarkGray)
+                                    .fg(theme.reply_context)
                                     .add_modifier(Modifier::ITALIC),
                             )
                         })
-                        .collect();
-                }
-
-                if msg.contains("[OUT]:") || msg.contains("[IN]:") {
-                    let is_outgoing = msg.contains("[OUT]:");
-                    let marker = if is_outgoing { "[OUT]:" } else { "[IN]:" };
-                    let marker_len = marker.len();
-                    if let Some(marker_pos) = msg.find(marker) {
-                        let prefix = &msg[..marker_pos];
-                        let after_marker = &msg[marker_pos + marker_len..];
-
-                        if let Some(first_colon) = after_marker.find(':') {
-                            let sender_id_str = &after_marker[..first_colon];
-                            let after_id = &after_marker[first_colon + 1..];
-                            if let Some(second_colon) = after_id.find(':') {
-                                let sender_name = &after_id[..second_colon];
-                                let message_text = &after_id[second_colon + 1..];
-
-                                {
-                                    let sender_id = sender_id_str;
-                                    let base_color = if is_outgoing {
-                                        Color::Green
-                                    } else {
-                                        Color::Cyan
-                                    };
-                                    let color = if is_group_chat {
-                                        self.user_colors.get(sender_id).copied().unwrap_or(base_color)
-                                    } else {
-                                        base_color
-                                    };
-                                    let lines = wrap_message_with_indent(
-                                        prefix,
-                                        sender_name,
-                                        message_text,
-                                        message_width,
-                                    );
-                                    if self.show_user_colors {
-                                        return lines
-                                            .into_iter()
-                                            .enumerate()
-                                            .map(|(idx, line)| {
-                                                if idx == 0 {
-                                                    style_name_in_line(
-                                                        &line,
-                                                        sender_name,
-                                                        Style::default().fg(color),
-                                                    )
-                                                } else {
-                                                    Line::from(line)
-                                                }
-                                            })
-                                            .collect();
-                                    }
-                                    return lines.into_iter().map(Line::from).collect();
+                        .collect(),
+                    DisplayLine::DaySeparator(_) => wrapped
+                        .into_iter()
+                        .map(|line| Line::from(line).style(Style::default().add_modifier(Modifier::DIM)))
+                        .collect(),
+                    DisplayLine::Message {
+                        is_outgoing,
+                        sender_id,
+                        sender_name,
+                        ..
+                    } if self.show_user_colors => {
+                        let color = self.name_color(sender_id, is_outgoing, is_group_chat);
+                        wrapped
+                            .into_iter()
+                            .enumerate()
+                            .map(|(idx, line)| {
+                                if idx == 0 {
+                                    style_name_in_line(&line, sender_name, Style::default().fg(color))
+                                } else {
+                                    Line::from(line)
                                 }
-                            }
-                        }
+                            })
+                            .collect::<Vec<_>>()
                     }
+                    _ => wrapped.into_iter().map(Line::from).collect(),
+                };
+                // A group message that mentions us stands out, the mention itself in bold
+                let mentions_us = is_group_chat
+                    && matches!(
+                        parsed,
+                        DisplayLine::Message { is_outgoing: false, .. } | DisplayLine::Continuation { is_outgoing: false, .. }
+                    )
+                    && owner
+                        .and_then(|idx| pane.msg_data.get(idx))
+                        .is_some_and(|m| mentions_me(&m.text, &self.my_user_jid));
+                let lines = if mentions_us {
+                    let mention_style = Style::default().fg(theme.mention);
+                    lines
+                        .into_iter()
+                        .map(|line| match &mention_marker {
+                            Some(marker) => style_marker(line, marker, mention_style.add_modifier(Modifier::BOLD)),
+                            None => line,
+                        })
+                        .map(|line| style_plain_spans(line, mention_style.add_modifier(Modifier::ITALIC)))
+                        .collect()
+                } else {
+                    lines
+                };
+                let lines = match parsed {
+                    DisplayLine::Message { is_outgoing: true, prefix, .. }
+                    | DisplayLine::Continuation { is_outgoing: true, prefix, .. }
+                        if prefix.contains(READ_MARKER) =>
+                    {
+                        lines
+                            .into_iter()
+                            .map(|line| style_marker(line, READ_MARKER, Style::default().fg(theme.receipt_read)))
+                            .collect()
+                    }
+                    _ => lines,
+                };
+                // Highlight the list-mode cursor
+                match parsed {
+                    DisplayLine::Blank | DisplayLine::DaySeparator(_) | DisplayLine::Plain(_) => lines,
+                    _ if selected.is_some() && owner == selected => lines
+                        .into_iter()
+                        .map(|line| line.patch_style(Style::default().add_modifier(Modifier::REVERSED)))
+                        .collect(),
+                    _ => lines,
                 }
-
-                wrap_plain_text(msg, message_width)
-                    .into_iter()
-                    .map(Line::from)
-                    .collect()
             })
             .collect();
 
+        // Remember the width so commands like /goto can reproduce this wrapping
+        pane.last_message_width.set(message_width);
+
         let border_lines = if self.show_borders { 2 } else { 1 }; // 1 for spacing above input in borderless
         let available_height = chunks[1].height.saturating_sub(border_lines) as usize;
         let total_lines = message_lines.len();
         
-        let actual_scroll = if pane.scroll_offset == 0 && total_lines > available_height {
-            total_lines.saturating_sub(available_height)
-        } else {
-            pane.scroll_offset
+        let max_scroll = total_lines.saturating_sub(available_height);
+        let actual_scroll = match pane.scroll_offset {
+            0 => max_scroll,
+            offset => (offset - 1).min(max_scroll),
         };
+        pane.max_scroll.set(max_scroll);
 
         let messages_block = if self.show_borders {
             Block::default().borders(Borders::ALL).title("Messages")
         } else {
             Block::default().padding(Padding::left(2))
         };
+        let messages_style = if dimmed {
+            Style::default().add_modifier(Modifier::DIM)
+        } else {
+            Style::default()
+        };
+        pane.message_area.set(messages_block.inner(chunks[1]));
+        pane.message_scroll.set(actual_scroll);
         let messages = Paragraph::new(message_lines)
             .block(messages_block)
+            .style(messages_style)
             .scroll((actual_scroll as u16, 0));
         f.render_widget(messages, chunks[1]);
 
         if has_reply_preview {
             if let Some(ref preview) = pane.reply_preview {
                 let reply_bar = Paragraph::new(preview.as_str())
-                    .style(Style::default().fg(Color::Magenta).add_modifier(Modifier::ITALIC));
+                    .style(Style::default().fg(theme.reply_preview).add_modifier(Modifier::ITALIC));
                 f.render_widget(reply_bar, chunks[2]);
             }
         }
@@ -866,16 +1715,35 @@ impl App {
         f.render_widget(input, input_chunk);
     }
 
+    /// Reload the chat list straight from whatsapp-cli, skipping the short-lived cache
     pub async fn refresh_chats(&mut self) -> Result<()> {
+        self.whatsapp.invalidate_dialogs().await;
         self.chats = self.whatsapp.get_dialogs().await?;
+        self.chats.retain(|c| !self.left_groups.contains(&c.id));
         Ok(())
     }
 
+    /// Drop a group we just left from the chat list, also on later refreshes while the
+    /// store still has it, and empty every pane that showed it
+    pub fn forget_left_group(&mut self, chat_id: &str) {
+        self.left_groups.insert(chat_id.to_string());
+        self.chats.retain(|c| c.id != chat_id);
+        for pane in self.panes.iter_mut().filter(|p| p.chat_id.as_deref() == Some(chat_id)) {
+            *pane = ChatPane::new();
+        }
+        self.needs_redraw = true;
+    }
+
+    /// Hold a destructive command until the next key: `y` runs it, anything else cancels
+    /// (see `CommandHandler::answer_confirm`)
+    pub fn ask_confirm(&mut self, prompt: &str, action: ConfirmAction) {
+        self.pending_confirm = Some((prompt.to_string(), action));
+        self.needs_redraw = true;
+    }
+
     /// Show a status notification that auto-expires
     pub fn notify(&mut self, message: &str) {
-        self.status_message = Some(message.to_string());
-        self.status_expire =
-            Some(std::time::Instant::now() + std::time::Duration::from_secs(3));
+        self.notify_with_duration(message, 3);
     }
 
     /// Show a status notification with custom timeout duration
@@ -883,52 +1751,137 @@ impl App {
         self.status_message = Some(message.to_string());
         self.status_expire =
             Some(std::time::Instant::now() + std::time::Duration::from_secs(duration_secs));
+        self.log_activity(message);
+    }
+
+    /// Keep a notification for the activity log, which outlives the status bar
+    fn log_activity(&mut self, message: &str) {
+        if self.activity.len() == MAX_ACTIVITY {
+            self.activity.remove(0);
+        }
+        self.activity.push((chrono::Local::now(), message.to_string()));
     }
 
     pub async fn open_chat_in_pane(&mut self, pane_idx: usize, chat_id: String, chat_name: &str) {
-        let msg_data = match self.whatsapp.get_messages(&chat_id, 50).await {
+        let msg_data = match self.whatsapp.get_messages(&chat_id, self.initial_message_limit).await {
             Ok(raw_messages) => raw_messages
                 .iter()
-                .map(|(msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp)| {
-                    crate::widgets::MessageData {
-                        msg_id: msg_id.clone(),
-                        sender_id: sender_id.clone(),
-                        sender_name: sender_name.clone(),
-                        text: text.clone(),
-                        is_outgoing: sender_id == &self.my_user_jid,
-                        timestamp: *timestamp,
-                        media_type: media_type.clone(),
-                        media_label: None,
-                        reactions: reactions.clone(),
-                        reply_to_msg_id: reply_to_id.clone(),
-                        reply_sender: None,
-                        reply_text: None,
-                    }
-                })
+                .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
                 .collect(),
             Err(_) => Vec::new(),
         };
 
+        let switched = self.stash_scroll(pane_idx, &chat_id);
         if let Some(pane) = self.panes.get_mut(pane_idx) {
+            if pane.chat_id.as_ref() != Some(&chat_id) {
+                pane.clear_filter();
+                pane.last_filters.clear();
+                pane.unread_only = false;
+                pane.selected_msg = None;
+                pane.expanded_msg = None;
+                pane.send_as = None;
+            }
             pane.chat_id = Some(chat_id.clone());
             pane.chat_name = chat_name.to_string();
             pane.msg_data = msg_data;
+            pane.history_exhausted = false;
+            pane.history_unavailable = false;
             pane.messages.clear();
+            pane.search_hits.clear();
             pane.reply_to_message = None;
             pane.hide_reply_preview();
             pane.scroll_offset = 0;
             pane.format_cache.clear();
 
-            // Set username from chats list if available
+            // Set username and unread divider from chats list if available
             if let Some(chat_info) = self.chats.iter().find(|c| c.id == chat_id) {
                 pane.username = chat_info.username.clone();
+                pane.unread_count_at_load = chat_info.unread;
+            }
+        }
+        if switched {
+            self.restore_scroll(pane_idx);
+        }
+
+        self.show_queued_messages(pane_idx);
+        self.mark_chat_read(&chat_id);
+        self.note_recent_chat(&chat_id);
+    }
+
+    /// Remember where the pane's current chat was scrolled to before it switches to
+    /// `next_chat`. Returns whether the pane is switching chats.
+    fn stash_scroll(&mut self, pane_idx: usize, next_chat: &str) -> bool {
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return false;
+        };
+        if pane.chat_id.as_deref() == Some(next_chat) {
+            return false;
+        }
+        if let Some(chat_id) = pane.chat_id.clone() {
+            // 0 is pinned to the bottom, where every chat opens anyway
+            match pane.scroll_offset {
+                0 => self.chat_scroll.remove(&chat_id),
+                offset => self.chat_scroll.insert(chat_id, offset),
+            };
+        }
+        true
+    }
+
+    /// Scroll a pane that just switched chats to where its chat was last left, or with
+    /// `reopen_scroll: "unread"` to the unread divider when messages came in since
+    fn restore_scroll(&mut self, pane_idx: usize) {
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return;
+        };
+        let saved = pane.chat_id.as_ref().and_then(|id| self.chat_scroll.remove(id));
+        let unread = pane.unread_count_at_load as usize;
+        if unread > 0 && self.reopen_scroll == ReopenScroll::Unread {
+            let first_unread = pane.msg_data.len().saturating_sub(unread);
+            if self.scroll_message_to_top(pane_idx, first_unread) {
+                // One line up, so the divider itself is in view
+                if let Some(pane) = self.panes.get_mut(pane_idx) {
+                    if pane.scroll_offset > 1 {
+                        pane.scroll_offset -= 1;
+                    }
+                }
             }
+            return;
         }
+        if let (Some(offset), Some(pane)) = (saved, self.panes.get_mut(pane_idx)) {
+            pane.scroll_offset = offset;
+        }
+    }
 
-        // Mark chat as read
-        if let Some(chat_info) = self.chats.iter_mut().find(|c| c.id == chat_id) {
-            chat_info.unread = 0;
+    /// Show forced group syncs in the headers of panes with the group open, and reload
+    /// those panes once a sync found messages. Returns whether any header changed.
+    pub async fn update_group_sync(&mut self) -> bool {
+        let progress = self.whatsapp.group_sync_progress().await;
+        let mut changed = false;
+        let mut reload = Vec::new();
+        let mut empty = Vec::new();
+        for (idx, pane) in self.panes.iter_mut().enumerate() {
+            let sync = pane.chat_id.as_ref().and_then(|id| progress.get(id));
+            let label = sync.filter(|p| !p.done).map(|p| p.label());
+            if pane.sync_progress != label {
+                pane.sync_progress = label;
+                changed = true;
+            }
+            match sync {
+                Some(p) if p.done && p.found > 0 => reload.push(idx),
+                Some(p) if p.done => empty.push(pane.chat_name.clone()),
+                _ => {}
+            }
+        }
+        for idx in reload {
+            let _ = self.refresh_pane_messages(idx).await;
+        }
+        if let Some(name) = empty.first() {
+            self.notify(&format!(
+                "No history for {} in the sync database; it fills in as new messages arrive",
+                name
+            ));
         }
+        changed
     }
 
     pub async fn load_pane_messages_if_needed(&mut self, pane_idx: usize) {
@@ -1011,7 +1964,7 @@ impl App {
             PaneNode::Split { direction, children } => {
                 // Check if target_idx is directly a child of this split (not nested deeper)
                 let is_direct_child = children.iter().any(|child| {
-                    matches!(child.as_ref(), PaneNode::Single(idx) if *idx == target_idx)
+                    matches!(child, PaneNode::Single(idx) if *idx == target_idx)
                 });
 
                 if is_direct_child {
@@ -1085,7 +2038,7 @@ impl App {
                 } else {
                     // Last pane, go back to chat list
                     crate::debug_log!("cycle_focus: Last pane, going back to chat list");
-                    self.focus_on_chat_list = true;
+                    self.focus_chat_list();
                 }
             } else {
                 // Current pane not found, reset to first
@@ -1114,12 +2067,44 @@ impl App {
                     self.focused_pane_idx = all_panes[current_pos - 1];
                     self.mark_pane_chat_read(self.focused_pane_idx);
                 } else {
-                    self.focus_on_chat_list = true;
+                    self.focus_chat_list();
                 }
             }
         }
     }
 
+    /// Move focus to the chat list, remembering the pane it came from
+    fn focus_chat_list(&mut self) {
+        if !self.focus_on_chat_list {
+            self.last_pane_before_list = Some(self.focused_pane_idx);
+        }
+        self.focus_on_chat_list = true;
+    }
+
+    /// Jump straight between the chat list and the last-focused pane, skipping the
+    /// other panes that Tab would walk through
+    pub fn toggle_chat_list_focus(&mut self) {
+        if !self.focus_on_chat_list {
+            self.focus_chat_list();
+            return;
+        }
+
+        let all_panes = self.pane_tree.get_pane_indices();
+        let target = self
+            .last_pane_before_list
+            .filter(|idx| all_panes.contains(idx))
+            .or_else(|| all_panes.contains(&self.focused_pane_idx).then_some(self.focused_pane_idx))
+            .or_else(|| all_panes.first().copied());
+        let Some(target) = target else {
+            crate::warn_log!("toggle_chat_list_focus: No panes available!");
+            return;
+        };
+        crate::debug_log!("toggle_chat_list_focus: Returning to pane {}", target);
+        self.focus_on_chat_list = false;
+        self.focused_pane_idx = target;
+        self.mark_pane_chat_read(target);
+    }
+
     pub fn focus_next_pane(&mut self) {
         let all_panes = self.pane_tree.get_pane_indices();
         if all_panes.len() < 2 {
@@ -1157,211 +2142,675 @@ impl App {
         self.refresh_all_pane_displays();
     }
 
-    pub fn toggle_notifications(&mut self) {
-        self.show_notifications = !self.show_notifications;
-        let status = if self.show_notifications {
-            "ON"
-        } else {
-            "OFF"
-        };
-        self.notify(&format!("Desktop notifications: {}", status));
+    /// Show or hide online status in pane headers
+    pub fn toggle_presence(&mut self) {
+        self.show_presence = !self.show_presence;
+        if !self.show_presence {
+            for pane in &mut self.panes {
+                pane.online_status.clear();
+            }
+        }
+        self.notify(&format!("Online status: {}", if self.show_presence { "ON" } else { "OFF" }));
     }
 
-    pub fn toggle_compact(&mut self) {
-        self.compact_mode = !self.compact_mode;
-        let status = if self.compact_mode { "ON" } else { "OFF" };
-        self.notify(&format!("Compact mode: {}", status));
-        self.refresh_all_pane_displays();
-    }
+    /// Refresh the focused pane's online status (individual chats only). The lookup runs in
+    /// the background, one at a time; its answer is shown on a later event tick, and the
+    /// client caches presence per chat, so polling every tick is cheap. Returns whether it changed.
+    pub async fn update_presence(&mut self) -> bool {
+        if !self.show_presence {
+            return false;
+        }
+        let focused = self.panes.get(self.focused_pane_idx).and_then(|p| p.chat_id.clone());
+        let mut answer = None;
+        if self.presence_poll.as_ref().is_some_and(|(_, task)| task.is_finished()) {
+            if let Some((chat_id, task)) = self.presence_poll.take() {
+                answer = Some((chat_id, task.await.map(|p| p.label()).unwrap_or_default()));
+            }
+        }
+        if self.presence_poll.is_none() {
+            match focused {
+                Some(chat_id) if chat_id.ends_with("@g.us") => answer = Some((chat_id, String::new())),
+                Some(chat_id) => {
+                    let whatsapp = self.whatsapp.clone();
+                    let lookup = chat_id.clone();
+                    let task = tokio::spawn(async move { whatsapp.get_presence(&lookup).await });
+                    self.presence_poll = Some((chat_id, task));
+                }
+                None => {}
+            }
+        }
 
-    pub fn toggle_emojis(&mut self) {
-        self.show_emojis = !self.show_emojis;
-        let status = if self.show_emojis { "ON" } else { "OFF" };
-        self.notify(&format!("Emojis: {}", status));
-        self.refresh_all_pane_displays();
+        let Some((chat_id, status)) = answer else {
+            return false;
+        };
+        match self.panes.get_mut(self.focused_pane_idx) {
+            // The chat may have changed while the CLI was running
+            Some(pane) if pane.chat_id.as_deref() == Some(chat_id.as_str()) && pane.online_status != status => {
+                pane.online_status = status;
+                true
+            }
+            _ => false,
+        }
     }
 
-    pub fn toggle_line_numbers(&mut self) {
-        self.show_line_numbers = !self.show_line_numbers;
-        let status = if self.show_line_numbers { "ON" } else { "OFF" };
-        self.notify(&format!("Line numbers: {}", status));
+    /// Show or hide delivery and read marks on our messages
+    pub fn toggle_receipts(&mut self) {
+        self.show_receipts = !self.show_receipts;
+        self.notify(&format!("Receipts: {}", if self.show_receipts { "ON" } else { "OFF" }));
         self.refresh_all_pane_displays();
     }
 
-    pub fn toggle_timestamps(&mut self) {
-        self.show_timestamps = !self.show_timestamps;
-        let status = if self.show_timestamps { "ON" } else { "OFF" };
-        self.notify(&format!("Timestamps: {}", status));
-        self.refresh_all_pane_displays();
+    /// Show or hide the status line at the bottom
+    pub fn toggle_status_line(&mut self) {
+        self.show_status_line = !self.show_status_line;
+        self.notify(&format!("Status line: {}", if self.show_status_line { "ON" } else { "OFF" }));
     }
 
-    pub fn toggle_chat_list(&mut self) {
-        self.show_chat_list = !self.show_chat_list;
-        self.notify(&format!("Chat list: {}", if self.show_chat_list { "ON" } else { "OFF" }));
-    }
+    /// Refresh the receipts of our messages in the focused pane. Like presence, the lookup
+    /// runs in the background and is applied on a later event tick; the client caches
+    /// receipts per chat, so polling every tick is cheap. Returns whether any changed.
+    pub async fn update_receipts(&mut self) -> bool {
+        if !self.show_receipts {
+            return false;
+        }
+        let mut answer = None;
+        if self.receipts_poll.as_ref().is_some_and(|(_, task)| task.is_finished()) {
+            if let Some((chat_id, task)) = self.receipts_poll.take() {
+                answer = Some((chat_id, task.await.unwrap_or_default()));
+            }
+        }
+        if self.receipts_poll.is_none() {
+            if let Some(chat_id) = self.panes.get(self.focused_pane_idx).and_then(|p| p.chat_id.clone()) {
+                let whatsapp = self.whatsapp.clone();
+                let lookup = chat_id.clone();
+                let task = tokio::spawn(async move { whatsapp.get_receipts(&lookup).await });
+                self.receipts_poll = Some((chat_id, task));
+            }
+        }
 
-    pub fn toggle_user_colors(&mut self) {
-        self.show_user_colors = !self.show_user_colors;
-        let status = if self.show_user_colors { "ON" } else { "OFF" };
-        self.notify(&format!("User colors: {}", status));
-        self.refresh_all_pane_displays();
+        let Some((chat_id, receipts)) = answer else {
+            return false;
+        };
+        // The chat may have changed while the CLI was running
+        let Some(pane) = self
+            .panes
+            .get_mut(self.focused_pane_idx)
+            .filter(|p| p.chat_id.as_deref() == Some(chat_id.as_str()))
+        else {
+            return false;
+        };
+        let mut changed = false;
+        for msg in pane.msg_data.iter_mut().filter(|m| m.is_outgoing) {
+            if let Some(&r) = receipts.get(&msg.msg_id) {
+                if msg.receipts != Some(r) {
+                    msg.receipts = Some(r);
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            pane.format_cache.clear();
+        }
+        changed
     }
 
-    pub fn toggle_borders(&mut self) {
-        self.show_borders = !self.show_borders;
-        self.notify(&format!("Borders: {}", if self.show_borders { "ON" } else { "OFF" }));
+    /// Chat we should currently appear to be typing in: the focused pane's chat while its
+    /// input holds a message (not a command) that was edited within `TYPING_IDLE_SECS`
+    fn typing_target(&self) -> Option<String> {
+        if !self.send_typing || self.focus_on_chat_list {
+            return None;
+        }
+        let recent = self
+            .last_keystroke
+            .is_some_and(|at| at.elapsed() < std::time::Duration::from_secs(TYPING_IDLE_SECS));
+        let pane = self.panes.get(self.focused_pane_idx)?;
+        if !recent || pane.input_buffer.is_empty() || pane.input_buffer.starts_with('/') {
+            return None;
+        }
+        pane.chat_id.clone()
     }
 
-    fn chat_list_groups(&self) -> (Vec<usize>, Vec<usize>, Vec<usize>) {
-        let mut open_chat_ids = std::collections::HashSet::new();
-        for pane in &self.panes {
-            if let Some(ref chat_id) = pane.chat_id {
-                open_chat_ids.insert(chat_id);
+    /// Send "composing" at most every `TYPING_RESEND_SECS` while typing, and "paused" once
+    /// the input goes idle, empties (e.g. after sending) or focus moves to another chat.
+    /// The CLI runs in the background, so the tick never waits for it.
+    pub fn update_typing(&mut self) {
+        let target = self.typing_target();
+        let mut states = Vec::new();
+
+        if let Some((chat_id, _)) = self.typing_sent_to.clone() {
+            if target.as_deref() != Some(chat_id.as_str()) {
+                states.push((chat_id, false));
+                self.typing_sent_to = None;
             }
         }
 
-        let mut unread = Vec::new();
-        let mut active = Vec::new();
-        let mut other = Vec::new();
-
-        for (idx, chat) in self.chats.iter().enumerate() {
-            if open_chat_ids.contains(&chat.id) {
-                active.push(idx);
-            } else if chat.unread > 0 {
-                unread.push(idx);
-            } else {
-                other.push(idx);
+        if let Some(chat_id) = target {
+            let due = self.typing_sent_to.as_ref().is_none_or(|(_, at)| {
+                at.elapsed() >= std::time::Duration::from_secs(TYPING_RESEND_SECS)
+            });
+            if due {
+                states.push((chat_id.clone(), true));
+                self.typing_sent_to = Some((chat_id, std::time::Instant::now()));
             }
         }
 
-        (unread, active, other)
+        if states.is_empty() {
+            return;
+        }
+        let whatsapp = self.whatsapp.clone();
+        // One task per tick keeps "paused" for the old chat ahead of "composing" for the new
+        tokio::spawn(async move {
+            for (chat_id, composing) in states {
+                if let Err(e) = whatsapp.send_typing(&chat_id, composing).await {
+                    crate::debug_log!("update_typing: {}", e);
+                }
+            }
+        });
     }
 
-    fn chat_list_order(&self) -> Vec<usize> {
-        let (mut unread, mut active, mut other) = self.chat_list_groups();
-        
-        // Sort each group by last_message_time (most recent first)
-        // We need to parse last_message_time from chats, but since ChatInfo doesn't have it,
-        // we'll sort by unread count first, then by index (which should be roughly chronological)
-        // For now, just reverse to get most recent first within each group
-        unread.reverse();
-        active.reverse();
-        other.reverse();
-        
-        let mut ordered = Vec::with_capacity(self.chats.len());
-        ordered.extend(unread);
-        ordered.extend(active);
-        ordered.extend(other);
-        ordered
+    /// Open the reaction picker for message `msg_idx` of a pane
+    pub fn open_reaction_picker(&mut self, pane_idx: usize, msg_idx: usize) {
+        let exists = self.panes.get(pane_idx).is_some_and(|p| msg_idx < p.msg_data.len());
+        if !exists {
+            self.notify(&format!("Message #{} not found", msg_idx + 1));
+            return;
+        }
+        self.reaction_picker = Some(ReactionPicker::new(pane_idx, msg_idx, &self.recent_reactions));
     }
-    
-    /// Extract phone number from JID (e.g., "46760789806@s.whatsapp.net" -> "46760789806")
-    fn extract_phone_from_jid(jid: &str) -> Option<String> {
-        if jid.ends_with("@s.whatsapp.net") {
-            Some(jid.strip_suffix("@s.whatsapp.net")?.to_string())
-        } else if jid.ends_with("@lid") {
-            // For @lid JIDs, try to extract phone number from the numeric part
-            // Format is usually: <phone>@lid or <some_id>@lid
-            // We'll use the part before @ as a key for matching
-            Some(jid.strip_suffix("@lid")?.to_string())
-        } else {
-            None
+
+    /// Alt+R: react to the list-mode selection, or else the newest message in the pane
+    pub fn open_reaction_picker_for_selection(&mut self) {
+        let pane_idx = self.focused_pane_idx;
+        let Some(pane) = self.panes.get(pane_idx).filter(|p| p.chat_id.is_some()) else {
+            self.notify("Open a chat to react to its messages");
+            return;
+        };
+        match pane.selected_msg.or(pane.msg_data.len().checked_sub(1)) {
+            Some(msg_idx) => self.open_reaction_picker(pane_idx, msg_idx),
+            None => self.notify("No messages to react to"),
         }
     }
-    
-    /// Normalize JID - prefer @s.whatsapp.net over @lid for the same phone number
-    fn normalize_jid(jid: &str, all_chats: &[ChatInfo]) -> String {
-        if jid.ends_with("@lid") {
-            // Try to find a matching @s.whatsapp.net JID with the same name
-            let lid_phone = Self::extract_phone_from_jid(jid);
-            let chat_name = all_chats.iter().find(|c| c.id == jid).map(|c| &c.name);
-            
-            if let Some(name) = chat_name {
-                // Look for a chat with the same name but @s.whatsapp.net JID
-                if let Some(matching_chat) = all_chats.iter().find(|c| {
-                    c.id.ends_with("@s.whatsapp.net") && c.name == *name
-                }) {
-                    crate::debug_log!("refresh_chat_list: Normalizing {}@lid to {} (same name: {})", 
-                        lid_phone.as_deref().unwrap_or("unknown"), matching_chat.id, name);
-                    return matching_chat.id.clone();
+
+    pub fn move_reaction_picker(&mut self, delta: isize) {
+        if let Some(picker) = self.reaction_picker.as_mut() {
+            picker.move_by(delta);
+        }
+    }
+
+    pub fn close_reaction_picker(&mut self) {
+        self.reaction_picker = None;
+    }
+
+    /// Send the picker's highlighted reaction, or choice number `choice` (0-based), and close it
+    pub async fn pick_reaction(&mut self, choice: Option<usize>) {
+        let Some(picker) = self.reaction_picker.take() else {
+            return;
+        };
+        let emoji = match choice {
+            Some(n) => match picker.choices.get(n) {
+                Some(emoji) => emoji.clone(),
+                None => {
+                    // Keep the picker open on a number with no emoji behind it
+                    self.reaction_picker = Some(picker);
+                    return;
                 }
+            },
+            None => picker.selected_emoji().to_string(),
+        };
+        self.react_to_message(picker.pane_idx, picker.msg_idx, &emoji).await;
+    }
+
+    /// Send `emoji` as a reaction to message `msg_idx`, show it locally and remember it as recent
+    pub async fn react_to_message(&mut self, pane_idx: usize, msg_idx: usize, emoji: &str) {
+        let Some((chat_id, msg_id)) = self.panes.get(pane_idx).and_then(|p| {
+            let msg = p.msg_data.get(msg_idx)?;
+            Some((p.chat_id.clone()?, msg.msg_id.clone()))
+        }) else {
+            self.notify(&format!("Message #{} not found", msg_idx + 1));
+            return;
+        };
+
+        if let Err(e) = self.whatsapp.send_reaction(&chat_id, &msg_id, emoji).await {
+            crate::warn_log!("react_to_message: {}", e);
+            self.notify(&format!("✗ Reaction failed: {}", e));
+            return;
+        }
+
+        if let Some(pane) = self.panes.get_mut(pane_idx) {
+            if let Some(msg) = pane.msg_data.get_mut(msg_idx) {
+                *msg.reactions.entry(emoji.to_string()).or_insert(0) += 1;
             }
+            pane.format_cache.clear();
         }
-        jid.to_string()
+        self.remember_reaction(emoji);
+        self.notify(&format!("Reacted {} to #{}", emoji, msg_idx + 1));
     }
 
-    /// Refresh chat list from WhatsApp
-    pub async fn refresh_chat_list(&mut self) -> Result<()> {
-        crate::debug_log!("refresh_chat_list: Starting refresh");
-        let new_chats = self.whatsapp.get_dialogs().await?;
-        crate::debug_log!("refresh_chat_list: Got {} chats from WhatsApp", new_chats.len());
-        
-        // Get currently open chat IDs to preserve their unread status
-        let open_chat_ids: std::collections::HashSet<String> = self.panes
+    /// Move `emoji` to the front of the recent reactions, keeping the list short
+    fn remember_reaction(&mut self, emoji: &str) {
+        self.recent_reactions.retain(|e| e != emoji);
+        self.recent_reactions.insert(0, emoji.to_string());
+        self.recent_reactions.truncate(MAX_RECENT_REACTIONS);
+    }
+
+    /// Move `chat_id` to the front of the recently opened chats
+    fn note_recent_chat(&mut self, chat_id: &str) {
+        self.recent_chats.retain(|id| id != chat_id);
+        self.recent_chats.insert(0, chat_id.to_string());
+        self.recent_chats.truncate(MAX_RECENT_CHATS);
+    }
+
+    /// The switcher key: open the recent chats overlay, or move its highlight down one.
+    /// The focused pane's chat comes first, then the rest from newest to oldest.
+    pub fn cycle_recent_chats(&mut self) {
+        if let Some(switcher) = self.chat_switcher.as_mut() {
+            switcher.move_by(1);
+            switcher.last_press = Some(std::time::Instant::now());
+            return;
+        }
+        let current = self.panes.get(self.focused_pane_idx).and_then(|p| p.chat_id.clone());
+        let chats: Vec<(String, String)> = current
             .iter()
-            .filter_map(|p| p.chat_id.clone())
+            .chain(self.recent_chats.iter().filter(|id| Some(*id) != current.as_ref()))
+            .filter_map(|id| self.chats.iter().find(|c| &c.id == id))
+            .map(|c| (c.id.clone(), c.name.clone()))
             .collect();
-        crate::debug_log!("refresh_chat_list: {} chats are currently open", open_chat_ids.len());
-        
-        // Normalize JIDs - prefer @s.whatsapp.net over @lid for the same chat
-        let normalized_chats: Vec<ChatInfo> = new_chats.iter().map(|c| {
-            let normalized_id = Self::normalize_jid(&c.id, &new_chats);
-            if normalized_id != c.id {
-                crate::debug_log!("refresh_chat_list: Normalizing chat {} -> {}", c.id, normalized_id);
-                ChatInfo {
-                    id: normalized_id,
-                    name: c.name.clone(),
-                    username: c.username.clone(),
-                    unread: c.unread,
-                    _is_channel: c._is_channel,
-                    is_group: c.is_group,
-                }
-            } else {
-                c.clone()
-            }
-        }).collect();
-        
-        // Deduplicate by phone number/name - keep only one chat per phone number
-        let mut seen_phones: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
-        let mut deduplicated_chats: Vec<ChatInfo> = Vec::new();
-        
-        for chat in normalized_chats {
-            let phone_key = if chat.id.ends_with("@s.whatsapp.net") {
-                Self::extract_phone_from_jid(&chat.id)
-            } else if chat.id.ends_with("@lid") {
-                // For @lid, use name as key since we can't extract phone reliably
-                Some(chat.name.clone())
-            } else {
-                None
-            };
-            
-            if let Some(key) = phone_key {
-                if let Some(&existing_idx) = seen_phones.get(&key) {
-                    // Already have a chat with this phone/name - prefer @s.whatsapp.net over @lid
-                    let existing_chat = &deduplicated_chats[existing_idx];
-                    if existing_chat.id.ends_with("@lid") && chat.id.ends_with("@s.whatsapp.net") {
-                        // Replace @lid with @s.whatsapp.net
-                        crate::debug_log!("refresh_chat_list: Replacing {}@lid with {}@s.whatsapp.net (same phone: {})", 
-                            existing_chat.id.strip_suffix("@lid").unwrap_or("unknown"), 
-                            chat.id.strip_suffix("@s.whatsapp.net").unwrap_or("unknown"),
-                            key);
-                        deduplicated_chats[existing_idx] = chat;
-                    } else {
-                        // Keep existing, skip duplicate
-                        crate::debug_log!("refresh_chat_list: Skipping duplicate chat {} (already have {})", chat.id, existing_chat.id);
-                    }
-                } else {
-                    seen_phones.insert(key.clone(), deduplicated_chats.len());
-                    deduplicated_chats.push(chat);
+        if chats.len() < 2 {
+            self.notify("No other recent chats yet");
+            return;
+        }
+        self.chat_switcher = Some(ChatSwitcher::new(chats, current.as_deref()));
+    }
+
+    /// Arrow keys in the switcher: move the highlight and wait for a key to open it
+    pub fn move_chat_switcher(&mut self, delta: isize) {
+        if let Some(switcher) = self.chat_switcher.as_mut() {
+            switcher.move_by(delta);
+            switcher.last_press = None;
+        }
+    }
+
+    pub fn close_chat_switcher(&mut self) {
+        self.chat_switcher = None;
+    }
+
+    /// Whether the switcher key has been left alone long enough to open the highlighted chat
+    pub fn chat_switch_due(&self) -> bool {
+        self.chat_switcher
+            .as_ref()
+            .is_some_and(|s| s.due(std::time::Duration::from_millis(SWITCHER_HOLD_MS)))
+    }
+
+    /// Close the switcher and open its highlighted chat in the focused pane
+    pub async fn finish_chat_switch(&mut self) {
+        let Some(switcher) = self.chat_switcher.take() else {
+            return;
+        };
+        let (chat_id, chat_name) = switcher.selected_chat().clone();
+        self.focus_on_chat_list = false;
+        self.open_chat_in_pane(self.focused_pane_idx, chat_id, &chat_name).await;
+    }
+
+    /// Open the new chat wizard, which asks for a phone number and then a first message
+    pub fn open_new_chat_wizard(&mut self) {
+        self.new_chat_wizard = Some(NewChatWizard::default());
+    }
+
+    /// The JID the wizard's number resolves to, or None while it isn't a phone number
+    pub fn new_chat_jid(&self) -> Option<String> {
+        let wizard = self.new_chat_wizard.as_ref()?;
+        crate::whatsapp::phone_number_jid(&wizard.number, &self.default_country_code)
+    }
+
+    /// Name for a wizard target: the known chat's name, else the formatted number
+    fn new_chat_target(&self, jid: &str) -> String {
+        self.chats
+            .iter()
+            .find(|c| c.id == jid)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| crate::whatsapp::format_phone_number(jid))
+    }
+
+    pub fn new_chat_wizard_char(&mut self, c: char) {
+        if let Some(wizard) = self.new_chat_wizard.as_mut() {
+            wizard.input_mut().push(c);
+        }
+    }
+
+    pub fn new_chat_wizard_backspace(&mut self) {
+        if let Some(wizard) = self.new_chat_wizard.as_mut() {
+            wizard.input_mut().pop();
+        }
+    }
+
+    /// Esc: from the message step back to the number, otherwise close the wizard
+    pub fn new_chat_wizard_back(&mut self) {
+        match self.new_chat_wizard.as_mut() {
+            Some(wizard) if wizard.step == NewChatStep::Message => wizard.step = NewChatStep::Number,
+            _ => self.new_chat_wizard = None,
+        }
+    }
+
+    /// Enter: accept a valid number, or open the chat in the focused pane and send the first
+    /// message like one typed there
+    pub async fn new_chat_wizard_enter(&mut self) {
+        let Some(step) = self.new_chat_wizard.as_ref().map(|w| w.step) else {
+            return;
+        };
+        let Some(chat_id) = self.new_chat_jid() else {
+            self.notify("Type a phone number, with + and the country code");
+            return;
+        };
+        if step == NewChatStep::Number {
+            if let Some(wizard) = self.new_chat_wizard.as_mut() {
+                wizard.step = NewChatStep::Message;
+            }
+            return;
+        }
+
+        let message = self.new_chat_wizard.take().map(|w| w.message).unwrap_or_default();
+        let chat_name = match self.whatsapp.resolve_username(&chat_id).await {
+            Ok(Some((_, name, _))) if name != chat_id => name,
+            _ => self.new_chat_target(&chat_id),
+        };
+        self.focus_on_chat_list = false;
+        self.open_chat_in_pane(self.focused_pane_idx, chat_id, &chat_name).await;
+        if message.trim().is_empty() {
+            return;
+        }
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_buffer = message;
+            pane.input_cursor = pane.input_buffer.len();
+        }
+        if let Err(e) = self.handle_enter().await {
+            self.notify(&format!("Send failed: {}", e));
+        }
+    }
+
+    pub fn toggle_notifications(&mut self) {
+        self.show_notifications = !self.show_notifications;
+        let status = if self.show_notifications {
+            "ON"
+        } else {
+            "OFF"
+        };
+        self.notify(&format!("Desktop notifications: {}", status));
+    }
+
+    /// Whether notifications are held back now: /dnd is on, or it's within quiet hours
+    pub fn is_quiet_now(&self) -> bool {
+        self.dnd || self.quiet_hours.is_some_and(|q| q.contains(chrono::Local::now().time()))
+    }
+
+    pub fn toggle_compact(&mut self) {
+        self.compact_mode = !self.compact_mode;
+        let status = if self.compact_mode { "ON" } else { "OFF" };
+        self.notify(&format!("Compact mode: {}", status));
+        self.refresh_all_pane_displays();
+    }
+
+    pub fn toggle_emojis(&mut self) {
+        self.show_emojis = !self.show_emojis;
+        let status = if self.show_emojis { "ON" } else { "OFF" };
+        self.notify(&format!("Emojis: {}", status));
+        self.refresh_all_pane_displays();
+    }
+
+    pub fn toggle_line_numbers(&mut self) {
+        self.show_line_numbers = !self.show_line_numbers;
+        let status = if self.show_line_numbers { "ON" } else { "OFF" };
+        self.notify(&format!("Line numbers: {}", status));
+        self.refresh_all_pane_displays();
+    }
+
+    pub fn toggle_timestamps(&mut self) {
+        self.show_timestamps = !self.show_timestamps;
+        let status = if self.show_timestamps { "ON" } else { "OFF" };
+        self.notify(&format!("Timestamps: {}", status));
+        self.refresh_all_pane_displays();
+    }
+
+    pub fn toggle_chat_list(&mut self) {
+        self.show_chat_list = !self.show_chat_list;
+        self.notify(&format!("Chat list: {}", if self.show_chat_list { "ON" } else { "OFF" }));
+    }
+
+    pub fn toggle_archived(&mut self) {
+        self.show_archived = !self.show_archived;
+        let max_idx = self.chat_list_order().len().saturating_sub(1);
+        self.selected_chat_idx = self.selected_chat_idx.min(max_idx);
+        self.notify(&format!(
+            "Archived chats: {} ({})",
+            if self.show_archived { "shown" } else { "hidden" },
+            self.archived_chats.len()
+        ));
+    }
+
+    pub fn toggle_user_colors(&mut self) {
+        self.show_user_colors = !self.show_user_colors;
+        let status = if self.show_user_colors { "ON" } else { "OFF" };
+        self.notify(&format!("User colors: {}", status));
+        self.refresh_all_pane_displays();
+    }
+
+    /// A sender's color in group chats: the one pinned with /color, else their palette color
+    pub fn sender_color(&self, sender_id: &str) -> Color {
+        self.user_colors
+            .get(sender_id)
+            .copied()
+            .unwrap_or_else(|| self.config.theme.user_color(sender_id))
+    }
+
+    /// Color of a sender's name wherever it shows, as a message's author or as the sender a
+    /// reply quotes: their own color in group chats, the incoming/outgoing color elsewhere
+    pub fn name_color(&self, sender_id: &str, is_outgoing: bool, is_group_chat: bool) -> Color {
+        match (is_group_chat, is_outgoing) {
+            (true, _) => self.sender_color(sender_id),
+            (false, true) => self.config.theme.outgoing,
+            (false, false) => self.config.theme.incoming,
+        }
+    }
+
+    pub fn toggle_borders(&mut self) {
+        self.show_borders = !self.show_borders;
+        self.notify(&format!("Borders: {}", if self.show_borders { "ON" } else { "OFF" }));
+    }
+
+    /// Whether Up/Down/Enter drive the list-mode cursor: the focused pane is in list mode and
+    /// nothing is being typed or recalled from history
+    fn list_cursor_active(&self) -> bool {
+        !self.focus_on_chat_list
+            && self.history_idx.is_none()
+            && self
+                .panes
+                .get(self.focused_pane_idx)
+                .is_some_and(|p| p.list_mode && p.input_buffer.is_empty())
+    }
+
+    /// The display toggles every pane's messages are formatted with
+    pub fn display_options(&self) -> DisplayOptions {
+        DisplayOptions {
+            compact_mode: self.compact_mode,
+            show_emojis: self.show_emojis,
+            reactions: self.show_reactions.then_some(self.reaction_style),
+            timestamps: self.show_timestamps.then_some(self.timestamp_style),
+            show_line_numbers: self.show_line_numbers,
+            show_receipts: self.show_receipts,
+            group_consecutive: self.group_consecutive,
+        }
+    }
+
+    /// Move the focused pane's list-mode cursor by `delta` shown messages (negative is
+    /// older). The first move selects the newest message.
+    pub fn move_list_selection(&mut self, delta: isize) {
+        let pane_idx = self.focused_pane_idx;
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return;
+        };
+        let width = pane.last_message_width.get();
+        let (lines, starts) = format_messages_for_display(pane, width, self.display_options(), &self.aliases.map);
+        let shown: Vec<usize> = starts
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, start)| start.map(|_| idx))
+            .collect();
+        let Some(&newest) = shown.last() else {
+            return;
+        };
+        let msg_idx = match pane.selected_msg.and_then(|sel| shown.iter().position(|&i| i == sel)) {
+            Some(pos) => shown[pos.saturating_add_signed(delta).min(shown.len() - 1)],
+            None => newest,
+        };
+
+        // Scroll just enough to keep the cursor on screen
+        let layout = pane.line_layout(width, self.wrap_style);
+        let offset = message_display_offset(&lines, &starts, msg_idx, &layout);
+        let first_visible = pane.message_scroll.get();
+        let height = pane.message_area.get().height as usize;
+        let Some(pane) = self.panes.get_mut(pane_idx) else {
+            return;
+        };
+        pane.selected_msg = Some(msg_idx);
+        match offset {
+            _ if msg_idx == newest => pane.scroll_offset = 0,
+            Some(line) if line < first_visible => pane.scroll_offset = line + 1,
+            Some(line) if height > 0 && line >= first_visible + height => {
+                pane.scroll_offset = line + 2 - height;
+            }
+            _ => {}
+        }
+    }
+
+    /// Scroll a pane so the message at `msg_idx` starts on the top line.
+    /// Returns false if that message produces no display lines.
+    pub fn scroll_message_to_top(&mut self, pane_idx: usize, msg_idx: usize) -> bool {
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return false;
+        };
+
+        // The newest message is already in view when pinned to the bottom
+        if msg_idx + 1 == pane.msg_data.len() {
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.scroll_offset = 0;
+            }
+            return true;
+        }
+
+        let width = pane.last_message_width.get();
+        let (lines, starts) = format_messages_for_display(pane, width, self.display_options(), &self.aliases.map);
+
+        let layout = pane.line_layout(width, self.wrap_style);
+        match message_display_offset(&lines, &starts, msg_idx, &layout) {
+            Some(offset) => {
+                if let Some(pane) = self.panes.get_mut(pane_idx) {
+                    pane.scroll_offset = offset + 1;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switch the focused pane between its last-used filter and the full view
+    pub fn toggle_filter(&mut self) {
+        let Some(pane) = self.panes.get_mut(self.focused_pane_idx) else {
+            return;
+        };
+        if pane.toggle_filter() {
+            let label = filters_label(&pane.filters);
+            self.notify(&format!("Filter: {}", label));
+        } else if !pane.last_filters.is_empty() {
+            self.notify("Filter: OFF");
+        } else {
+            self.notify("No filter to toggle - set one with /filter");
+        }
+    }
+
+    /// Pick the pane (1-based, in layout order) that the chat list opens chats into
+    pub fn select_target_pane(&mut self, number: usize) {
+        let all_panes = self.pane_tree.get_pane_indices();
+        match number.checked_sub(1).and_then(|pos| all_panes.get(pos)) {
+            Some(&pane_idx) => {
+                self.focused_pane_idx = pane_idx;
+                self.last_pane_before_list = Some(pane_idx);
+                self.notify(&format!("Target pane: {}", number));
+            }
+            None => self.notify(&format!("No pane {} (have {})", number, all_panes.len())),
+        }
+    }
+
+    /// The chat list's sections in display order, each a header and its chat indices.
+    /// Empty sections are left out; archived chats only appear (last) while revealed.
+    fn chat_list_groups(&self) -> Vec<(&'static str, Vec<usize>)> {
+        let mut open_chat_ids = std::collections::HashSet::new();
+        for pane in &self.panes {
+            if let Some(ref chat_id) = pane.chat_id {
+                open_chat_ids.insert(chat_id);
+            }
+        }
+
+        let mut unread = Vec::new();
+        let mut active = Vec::new();
+        let mut other = Vec::new();
+        let mut archived = Vec::new();
+
+        for (idx, chat) in self.chats.iter().enumerate() {
+            if open_chat_ids.contains(&chat.id) {
+                active.push(idx);
+            } else if self.archived_chats.contains(&chat.id) {
+                if self.show_archived {
+                    archived.push(idx);
                 }
+            } else if chat.unread > 0 {
+                unread.push(idx);
             } else {
-                // No phone/normalized key, just add it
-                deduplicated_chats.push(chat);
+                other.push(idx);
             }
         }
+
+        // Most recent activity first; the sort is stable, so ties (including chats with
+        // no known messages, which sort last) keep their list order
+        let mut sections = vec![("Unread", unread), ("Active", active), ("Other", other), ("Archived", archived)];
+        for (_, group) in &mut sections {
+            group.sort_by_key(|&idx| std::cmp::Reverse(self.chats[idx].last_message_ts));
+        }
+        sections.retain(|(_, group)| !group.is_empty());
+        sections
+    }
+
+    fn chat_list_order(&self) -> Vec<usize> {
+        self.chat_list_groups()
+            .into_iter()
+            .flat_map(|(_, group)| group)
+            .collect()
+    }
+    
+    /// Refresh chat list from WhatsApp
+    pub async fn refresh_chat_list(&mut self) -> Result<()> {
+        crate::debug_log!("refresh_chat_list: Starting refresh");
+        let new_chats = self.whatsapp.get_dialogs().await?;
+        self.apply_chat_list(new_chats);
+        Ok(())
+    }
+
+    /// Merge a chat list fresh from whatsapp-cli into `chats`, keeping unread counts we
+    /// raised ourselves and the chats open in panes
+    fn apply_chat_list(&mut self, mut new_chats: Vec<ChatInfo>) {
+        new_chats.retain(|c| !self.left_groups.contains(&c.id));
+        crate::debug_log!("refresh_chat_list: Got {} chats from WhatsApp", new_chats.len());
+        
+        // Get currently open chat IDs to preserve their unread status
+        let open_chat_ids: std::collections::HashSet<String> = self.panes
+            .iter()
+            .filter_map(|p| p.chat_id.clone())
+            .collect();
+        crate::debug_log!("refresh_chat_list: {} chats are currently open", open_chat_ids.len());
         
+        // Collapse @lid and duplicate entries by the same rules as get_dialogs
+        let chat_count = new_chats.len();
+        let deduplicated_chats = crate::whatsapp::dedupe_and_normalize_chats(new_chats);
         crate::debug_log!("refresh_chat_list: After deduplication: {} chats (was {})", 
-            deduplicated_chats.len(), new_chats.len());
+            deduplicated_chats.len(), chat_count);
         
         // Create sets for efficient lookup
         let new_chat_ids: std::collections::HashSet<String> = deduplicated_chats.iter().map(|c| c.id.clone()).collect();
@@ -1391,6 +2840,8 @@ impl App {
                 
                 // Always update name (in case contact name changed)
                 existing_chat.name = new_chat.name.clone();
+                // Keep any newer activity we saw ourselves before sync stored it
+                existing_chat.last_message_ts = existing_chat.last_message_ts.max(new_chat.last_message_ts);
                 
                 // Update unread: if chat is open, don't update unread from WhatsApp
                 // (it will be cleared when marked read, or stay 0 if already read)
@@ -1435,7 +2886,6 @@ impl App {
         }
         
         crate::debug_log!("refresh_chat_list: Final chat count: {}", self.chats.len());
-        Ok(())
     }
 
     fn mark_pane_chat_read(&mut self, pane_idx: usize) {
@@ -1444,12 +2894,40 @@ impl App {
             None => return,
         };
 
-        if let Some(chat_info) = self.chats.iter_mut().find(|c| c.id == chat_id) {
-            chat_info.unread = 0;
+        // The pane keeps its unread divider until the user has caught up
+        self.mark_chat_read(&chat_id);
+    }
+
+    /// Clear a chat's unread count and, if it had any, queue a read receipt for the newest
+    /// incoming message shown in a pane. `send_read_receipts` sends the queue from the event loop.
+    fn mark_chat_read(&mut self, chat_id: &str) {
+        let Some(chat_info) = self.chats.iter_mut().find(|c| c.id == chat_id) else {
+            return;
+        };
+        let had_unread = chat_info.unread > 0;
+        chat_info.unread = 0;
+        if !had_unread || !self.send_read_receipts {
+            return;
         }
 
-        if let Some(pane) = self.panes.get_mut(pane_idx) {
-            pane.unread_count_at_load = 0;
+        let newest_incoming = self
+            .panes
+            .iter()
+            .filter(|p| p.chat_id.as_deref() == Some(chat_id))
+            .flat_map(|p| p.msg_data.iter().filter(|m| !m.is_outgoing))
+            .max_by_key(|m| m.timestamp);
+        if let Some(msg) = newest_incoming {
+            self.pending_read_receipts
+                .insert(chat_id.to_string(), msg.msg_id.clone());
+        }
+    }
+
+    /// Send queued read receipts so the phone clears its unread badges as well
+    pub async fn send_read_receipts(&mut self) {
+        for (chat_id, msg_id) in std::mem::take(&mut self.pending_read_receipts) {
+            if let Err(e) = self.whatsapp.mark_read(&chat_id, &msg_id).await {
+                crate::debug_log!("send_read_receipts: {}: {}", chat_id, e);
+            }
         }
     }
 
@@ -1469,7 +2947,100 @@ impl App {
         }
     }
 
+    /// Scroll wheel: scroll the pane under the cursor without changing focus
+    pub fn handle_mouse_scroll(&mut self, x: u16, y: u16, up: bool) {
+        let hit = self.pane_areas.iter().find(|(_, area)| {
+            x >= area.x && x < area.x + area.width && y >= area.y && y < area.y + area.height
+        });
+        let Some(pane_idx) = hit.map(|(&idx, _)| idx) else {
+            return;
+        };
+        if let Some(pane) = self.panes.get_mut(pane_idx) {
+            if up {
+                pane.scroll_up();
+                self.request_older_history_at_top(pane_idx);
+            } else {
+                pane.scroll_down();
+            }
+        }
+    }
+
+    /// Clicking a media message downloads and opens it, same as `/media N`
+    pub async fn handle_message_click(&mut self, x: u16, y: u16) -> Result<()> {
+        let pane_idx = self.focused_pane_idx;
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return Ok(());
+        };
+        let Some(msg_idx) = pane.message_at(x, y) else {
+            return Ok(());
+        };
+        // In list mode a click selects the message and expands or collapses it
+        if pane.list_mode {
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.selected_msg = Some(msg_idx);
+                pane.toggle_expanded();
+            }
+            return Ok(());
+        }
+        if pane.msg_data.get(msg_idx).is_some_and(|m| m.media_type.is_some()) {
+            CommandHandler::handle(self, &format!("/media {}", msg_idx + 1), pane_idx).await?;
+        }
+        Ok(())
+    }
+
     /// Handle mouse click on chat list
+    /// One entry per chat list row, matching exactly how draw_chat_list renders
+    /// (headers are None, chats are Some(chat_idx))
+    fn chat_list_row_map(&self) -> Vec<Option<usize>> {
+        let mut row_map: Vec<Option<usize>> = Vec::new();
+        for (_, group) in self.chat_list_groups() {
+            row_map.push(None); // Section header
+            row_map.extend(group.into_iter().map(Some));
+        }
+        row_map
+    }
+
+    /// Track the chat under the mouse and show the full name and JID in the status bar
+    /// when its entry is truncated. Returns whether anything visible changed, so plain
+    /// mouse movement doesn't force a redraw
+    pub fn handle_chat_list_hover(&mut self, x: u16, y: u16) -> bool {
+        let hovered = self.chat_list_area.and_then(|area| {
+            let border_offset = if self.show_borders { 1 } else { 0 };
+            let inside = x >= area.x
+                && x < area.x + area.width
+                && y >= area.y + border_offset
+                && y < (area.y + area.height).saturating_sub(border_offset);
+            if !inside {
+                return None;
+            }
+            let row = (y - area.y - border_offset) as usize;
+            let chat_idx = self.chat_list_row_map().get(row).copied().flatten()?;
+            let chat = self.chats.get(chat_idx)?;
+            let truncated = self.chat_list_entry(chat, Self::chat_list_text_width(area)).truncated;
+            Some((chat.id.clone(), truncated))
+        });
+
+        let hovered_id = hovered.as_ref().map(|(id, _)| id.clone());
+        if hovered_id == self.hovered_chat {
+            return false;
+        }
+        self.hovered_chat = hovered_id;
+
+        match hovered {
+            Some((chat_id, true)) => {
+                let name = self
+                    .chats
+                    .iter()
+                    .find(|c| c.id == chat_id)
+                    .map(|c| self.chat_list_name(c))
+                    .unwrap_or_default();
+                self.notify(&format!("{} ({})", name, chat_id));
+                true
+            }
+            _ => false,
+        }
+    }
+
     pub async fn handle_chat_list_click(&mut self, y: u16, list_area: Rect) -> Result<()> {
         // Calculate which chat was clicked based on Y position
         // Each chat item is 1 line, starting at list_area.y + border_offset (after top border if present)
@@ -1479,37 +3050,7 @@ impl App {
         }
         
         let relative_y = (y - list_area.y - border_offset) as usize;
-        
-        // Build row map matching exactly how draw_chat_list renders
-        // (headers are None, chats are Some(chat_idx))
-        let (unread_group, active_group, other_group) = self.chat_list_groups();
-        let _ordered_chats = self.chat_list_order();
-
-        let mut row_map: Vec<Option<usize>> = Vec::new();
-        
-        // Add unread group header and chats
-        if !unread_group.is_empty() {
-            row_map.push(None); // Header "Unread"
-            for chat_idx in unread_group.iter() {
-                row_map.push(Some(*chat_idx));
-            }
-        }
-        
-        // Add active group header and chats
-        if !active_group.is_empty() {
-            row_map.push(None); // Header "Active"
-            for chat_idx in active_group.iter() {
-                row_map.push(Some(*chat_idx));
-            }
-        }
-        
-        // Add other group header and chats
-        if !other_group.is_empty() {
-            row_map.push(None); // Header "Other"
-            for chat_idx in other_group.iter() {
-                row_map.push(Some(*chat_idx));
-            }
-        }
+        let row_map = self.chat_list_row_map();
 
         crate::debug_log!("handle_chat_list_click: row_map.len()={}, relative_y={}", row_map.len(), relative_y);
         if relative_y < row_map.len() {
@@ -1533,51 +3074,51 @@ impl App {
                         let chat_id = chat.id.clone();
                         let chat_name = chat.name.clone();
                         let chat_username = chat.username.clone();
-                        let raw_messages = self.whatsapp.get_messages(&chat_id, 50).await?;
+                        let raw_messages = self.whatsapp.get_messages(&chat_id, self.initial_message_limit).await?;
 
                         let mut msg_data: Vec<crate::widgets::MessageData> = raw_messages
                             .iter()
-                            .map(|(msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp)| {
-                                let reply_to_msg_id = reply_to_id.clone();
-                                
-                                crate::widgets::MessageData {
-                                    msg_id: msg_id.clone(),
-                                    sender_id: sender_id.clone(),
-                                    sender_name: sender_name.clone(),
-                                    text: text.clone(),
-                                    is_outgoing: sender_id == &self.my_user_jid,
-                                    timestamp: *timestamp,
-                                    media_type: media_type.clone(),
-                                    media_label: None,
-                                    reactions: reactions.clone(),
-                                    reply_to_msg_id,
-                                    reply_sender: None,
-                                    reply_text: None,
-                                }
-                            })
+                            .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
                             .collect();
                         
                         // Sort messages by timestamp (oldest first) to ensure correct order
                         msg_data.sort_by_key(|m| m.timestamp);
 
+                        let switched = self.stash_scroll(self.focused_pane_idx, &chat_id);
                         if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
                             crate::debug_log!("handle_chat_list_click: Updating pane {} with chat {}, scrolling to bottom", self.focused_pane_idx, chat_id);
+                            if pane.chat_id.as_ref() != Some(&chat_id) {
+                                pane.clear_filter();
+                                pane.last_filters.clear();
+                                pane.unread_only = false;
+                                pane.selected_msg = None;
+                                pane.expanded_msg = None;
+                                pane.send_as = None;
+                            }
                             pane.chat_id = Some(chat_id.clone());
                             pane.chat_name = chat_name;
                             pane.username = chat_username;
                             pane.msg_data = msg_data;
+                            pane.history_exhausted = false;
+                            pane.history_unavailable = false;
                             pane.messages.clear(); // Clear status messages when switching chats
+                            pane.search_hits.clear();
                             pane.reply_to_message = None;
                             pane.hide_reply_preview();
                             pane.scroll_offset = 0; // Scroll to bottom (0 means bottom when rendering)
 
-                            // Mark chat as read
-                            if let Some(chat_info) = self.chats.iter_mut().find(|c| c.id == chat_id) {
-                                chat_info.unread = 0;
+                            if let Some(chat_info) = self.chats.iter().find(|c| c.id == chat_id) {
+                                pane.unread_count_at_load = chat_info.unread;
                             }
                         } else {
                             crate::warn_log!("handle_chat_list_click: Pane {} not found!", self.focused_pane_idx);
                         }
+                        if switched {
+                            self.restore_scroll(self.focused_pane_idx);
+                        }
+                        self.show_queued_messages(self.focused_pane_idx);
+                        self.mark_chat_read(&chat_id);
+                        self.note_recent_chat(&chat_id);
                         
                         // Update selected_chat_idx to match the clicked chat in ordered_chats
                         if let Some(ordered_idx) = ordered_chats.iter().position(|&idx| idx < self.chats.len() && self.chats[idx].id == chat_id) {
@@ -1616,6 +3157,10 @@ impl App {
     // =========================================================================
 
     pub fn handle_up(&mut self) {
+        if self.list_cursor_active() {
+            self.move_list_selection(-1);
+            return;
+        }
         if self.focus_on_chat_list {
             let max_idx = self.chat_list_order().len().saturating_sub(1);
             if self.selected_chat_idx > max_idx {
@@ -1649,6 +3194,10 @@ impl App {
 
     pub fn handle_down(&mut self) {
         crate::debug_log!("handle_down: focus_on_chat_list={}, selected_chat_idx={}", self.focus_on_chat_list, self.selected_chat_idx);
+        if self.list_cursor_active() {
+            self.move_list_selection(1);
+            return;
+        }
         if self.focus_on_chat_list {
             let max_idx = self.chat_list_order().len().saturating_sub(1);
             crate::debug_log!("handle_down: max_idx={}", max_idx);
@@ -1680,6 +3229,7 @@ impl App {
             if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
                 pane.scroll_up();
             }
+            self.request_older_history_at_top(self.focused_pane_idx);
         }
     }
 
@@ -1691,314 +3241,1000 @@ impl App {
         }
     }
 
-    /// Handle Tab key: try autocomplete first, then cycle focus
+    pub fn handle_scroll_top(&mut self) {
+        if !self.focus_on_chat_list {
+            if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+                pane.scroll_to_top();
+            }
+            self.request_older_history_at_top(self.focused_pane_idx);
+        }
+    }
+
+    pub fn handle_scroll_bottom(&mut self) {
+        if !self.focus_on_chat_list {
+            if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+                pane.scroll_to_bottom();
+            }
+        }
+    }
+
+    /// Handle Tab key: try autocomplete first, then cycle focus. Repeated Tabs after a
+    /// command completion step through the other matching commands.
     pub fn handle_tab(&mut self) {
-        let is_empty = self.panes.get(self.focused_pane_idx)
-            .map_or(true, |p| p.input_buffer.is_empty());
-        
-        if is_empty {
+        let focused = self.focused_pane_idx;
+        let Some(buffer) = self.panes.get(focused).map(|p| p.input_buffer.clone()).filter(|b| !b.is_empty()) else {
             self.cycle_focus();
             return;
+        };
+
+        if let Some((pane_idx, prefix, idx)) = self.command_completion.take() {
+            let candidates = command_candidates(&prefix);
+            if pane_idx == focused && candidates.get(idx).is_some_and(|name| buffer == format!("/{}", name)) {
+                let next = (idx + 1) % candidates.len();
+                if let Some(pane) = self.panes.get_mut(focused) {
+                    pane.input_buffer = format!("/{}", candidates[next]);
+                    pane.input_cursor = pane.input_buffer.len();
+                }
+                self.command_completion = Some((pane_idx, prefix, next));
+                self.notify(&command_cycle_hint(&candidates, next));
+                return;
+            }
+        }
+
+        if self.complete_mention(&buffer) {
+            return;
         }
 
         // Try autocomplete
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            let (completed, hint) = try_autocomplete(&pane.input_buffer);
-            if let Some(completed) = completed {
+        let (completed, hint) = try_autocomplete(&buffer);
+        let changed = completed.is_some();
+        if let Some(completed) = completed {
+            if let Some(prefix) = buffer.strip_prefix('/').filter(|_| completed.starts_with('/')) {
+                self.command_completion = Some((focused, prefix.to_string(), 0));
+            }
+            if let Some(pane) = self.panes.get_mut(focused) {
                 pane.input_buffer = completed;
                 pane.input_cursor = pane.input_buffer.len();
-            } else if let Some(hint) = hint {
-                self.notify(&hint);
-            } else {
-                self.cycle_focus();
             }
         }
+        match hint {
+            Some(hint) => self.notify(&hint),
+            None if !changed => self.cycle_focus(),
+            None => {}
+        }
+    }
+
+    /// Who can be @mentioned in the group open in `pane_idx`: its members as last fetched,
+    /// then whoever wrote the loaded messages, as (JID, name) and without ourselves. Only
+    /// phone number JIDs, since a mention is written as the number.
+    fn mention_candidates(&self, pane_idx: usize) -> Vec<(String, String)> {
+        let Some(pane) = self.panes.get(pane_idx) else {
+            return Vec::new();
+        };
+        let members = pane.chat_id.as_ref().and_then(|id| self.group_members.get(id));
+        let senders = pane.msg_data.iter().map(|m| (m.sender_id.clone(), m.sender_name.clone()));
+        let mut candidates: Vec<(String, String)> = Vec::new();
+        for (jid, name) in members.into_iter().flatten().cloned().chain(senders) {
+            let jid = crate::whatsapp::user_jid(&jid);
+            if !jid.ends_with("@s.whatsapp.net") || jid == self.my_user_jid || candidates.iter().any(|(j, _)| *j == jid) {
+                continue;
+            }
+            let name = self.aliases.map.get(&jid).cloned().unwrap_or(name);
+            candidates.push((jid, name));
+        }
+        candidates
+    }
+
+    /// Tab on an `@partial` at the end of the input in a group: one matching person (by a
+    /// word of their name or the start of their number) becomes an `@<number>` mention,
+    /// several are listed in the status bar. Returns whether Tab was used up.
+    fn complete_mention(&mut self, buffer: &str) -> bool {
+        let focused = self.focused_pane_idx;
+        let in_group = self.panes.get(focused).and_then(|p| p.chat_id.as_deref()).is_some_and(|id| id.ends_with("@g.us"));
+        let Some((start, partial)) = mention_query(buffer).filter(|_| in_group) else {
+            return false;
+        };
+        let partial_lower = partial.to_lowercase();
+        let matches: Vec<(String, String)> = self
+            .mention_candidates(focused)
+            .into_iter()
+            .filter(|(jid, name)| {
+                let name = name.to_lowercase();
+                jid.starts_with(partial)
+                    || name.starts_with(&partial_lower)
+                    || name.split_whitespace().any(|word| word.starts_with(&partial_lower))
+            })
+            .collect();
+        match matches.as_slice() {
+            [] => self.notify(&format!("No one in this group matches @{}", partial)),
+            [(jid, name)] => {
+                let number = jid.split('@').next().unwrap_or_default();
+                if let Some(pane) = self.panes.get_mut(focused) {
+                    pane.input_buffer = format!("{}@{} ", &buffer[..start], number);
+                    pane.input_cursor = pane.input_buffer.len();
+                }
+                self.notify(&format!("Mentioning {}", name));
+            }
+            several => {
+                let mut names: Vec<String> = several.iter().take(MAX_MENTION_HINTS).map(|(_, name)| name.clone()).collect();
+                if several.len() > MAX_MENTION_HINTS {
+                    names.push(format!("+{} more", several.len() - MAX_MENTION_HINTS));
+                }
+                self.notify(&format!("Mention: {}", names.join(", ")));
+            }
+        }
+        true
+    }
+
+    /// Fetch the members of the group in the focused pane in the background when something
+    /// needs them (the member count for `confirm_group_min_members`, or an `@mention` being
+    /// typed, so Tab can offer people who haven't written in the loaded messages), one lookup
+    /// at a time.
+    /// A failed lookup isn't remembered as an empty group; it is tried again after
+    /// MEMBERS_RETRY_SECS.
+    pub async fn update_group_members(&mut self) {
+        if self.members_poll.as_ref().is_some_and(|(_, task)| task.is_finished()) {
+            if let Some((chat_id, task)) = self.members_poll.take() {
+                match task.await.map_err(anyhow::Error::from).and_then(|r| r) {
+                    Ok(members) => {
+                        self.members_failed.remove(&chat_id);
+                        self.group_members.insert(chat_id, members);
+                    }
+                    Err(e) => {
+                        crate::debug_log!("update_group_members: members of {} unavailable: {}", chat_id, e);
+                        self.members_failed.insert(chat_id, std::time::Instant::now());
+                    }
+                }
+            }
+        }
+        if self.members_poll.is_some() {
+            return;
+        }
+        let Some(pane) = self.panes.get(self.focused_pane_idx) else {
+            return;
+        };
+        let Some(chat_id) = pane.chat_id.clone().filter(|id| id.ends_with("@g.us")) else {
+            return;
+        };
+        let counting = self.confirm_group_send && self.confirm_group_min_members > 0;
+        if !counting && mention_query(&pane.input_buffer).is_none() {
+            return;
+        }
+        let retry = std::time::Duration::from_secs(MEMBERS_RETRY_SECS);
+        if self.group_members.contains_key(&chat_id)
+            || self.members_failed.get(&chat_id).is_some_and(|at| at.elapsed() < retry)
+        {
+            return;
+        }
+        let whatsapp = self.whatsapp.clone();
+        let lookup = chat_id.clone();
+        let task = tokio::spawn(async move {
+            let members = whatsapp.get_members(&lookup).await?;
+            Ok(members.into_iter().map(|(jid, name, _)| (jid, name)).collect())
+        });
+        self.members_poll = Some((chat_id, task));
+    }
+
+    /// Enter on a command just completed with Tab: a command that needs arguments gets a
+    /// space to type them after instead of running. Returns whether Enter was used up.
+    fn accept_command_completion(&mut self) -> bool {
+        let Some((pane_idx, prefix, idx)) = self.command_completion.take() else {
+            return false;
+        };
+        let Some(name) = command_candidates(&prefix).get(idx).copied() else {
+            return false;
+        };
+        let Some(pane) = self.panes.get_mut(pane_idx).filter(|p| pane_idx == self.focused_pane_idx && p.input_buffer == format!("/{}", name)) else {
+            return false;
+        };
+        if !needs_arguments(name) {
+            return false;
+        }
+        pane.input_buffer.push(' ');
+        pane.input_cursor = pane.input_buffer.len();
+        if let Some(hint) = command_hint(name) {
+            self.notify(&hint);
+        }
+        true
     }
 
     pub async fn handle_enter(&mut self) -> Result<()> {
         let input_empty = self.panes.get(self.focused_pane_idx)
-            .map_or(true, |p| p.input_buffer.is_empty());
+            .is_none_or(|p| p.input_buffer.is_empty());
         
         crate::debug_log!("handle_enter: input_empty={}, focus_on_chat_list={}, chats.len()={}, selected_chat_idx={}", 
             input_empty, self.focus_on_chat_list, self.chats.len(), self.selected_chat_idx);
         
+        // Enter on the chat list always opens the selected chat, regardless of what is
+        // sitting in the target pane's input buffer. The draft stays in the pane untouched.
+        if self.focus_on_chat_list {
+            if self.chats.is_empty() {
+                crate::debug_log!("handle_enter: On chat list but chats empty");
+            } else {
+                self.open_selected_chat().await;
+            }
+            return Ok(());
+        }
+
         if input_empty {
-            if self.focus_on_chat_list && !self.chats.is_empty() {
-                crate::debug_log!("handle_enter: On chat list, opening selected chat");
-                // Refresh chat list first to get latest data
-                let _ = self.refresh_chat_list().await;
-                
-                let ordered_chats = self.chat_list_order();
-                crate::debug_log!("handle_enter: ordered_chats.len()={}, selected_chat_idx={}", ordered_chats.len(), self.selected_chat_idx);
-                if let Some(&chat_idx) = ordered_chats.get(self.selected_chat_idx) {
-                    if chat_idx < self.chats.len() {
-                        let chat = &self.chats[chat_idx];
-                        let chat_id = chat.id.clone();
-                        let chat_name = chat.name.clone();
-                        let chat_username = chat.username.clone();
-                        crate::debug_log!("handle_enter: Opening chat {}: '{}'", chat_id, chat_name);
-                        let raw_messages = self.whatsapp.get_messages(&chat_id, 50).await?;
-                        crate::debug_log!("handle_enter: Got {} messages for chat {}: '{}'", raw_messages.len(), chat_id, chat_name);
+            // In list mode Enter expands or collapses the selected message
+            if let Some(pane) = self.panes.get_mut(self.focused_pane_idx).filter(|p| p.list_mode) {
+                pane.toggle_expanded();
+                return Ok(());
+            }
+            match self.enter_on_empty {
+                EnterOnEmpty::Nothing => {
+                    crate::debug_log!("handle_enter: Empty input in pane, nothing to send");
+                }
+                EnterOnEmpty::List => self.focus_chat_list(),
+                EnterOnEmpty::Recent => self.open_recent_chat().await,
+            }
+            return Ok(());
+        }
 
-                        // Convert to MessageData for proper formatting support
-                        let mut msg_data: Vec<crate::widgets::MessageData> = raw_messages
-                            .iter()
-                            .map(|(msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp)| {
-                                let reply_to_msg_id = reply_to_id.clone();
-                                
-                                crate::widgets::MessageData {
-                                    msg_id: msg_id.clone(),
-                                    sender_id: sender_id.clone(),
-                                    sender_name: sender_name.clone(),
-                                    text: text.clone(),
-                                    is_outgoing: sender_id == &self.my_user_jid,
-                                    timestamp: *timestamp, // Use actual timestamp from message
-                                    media_type: media_type.clone(),
-                                    media_label: None,
-                                    reactions: reactions.clone(),
-                                    reply_to_msg_id,
-                                    reply_sender: None,
-                                    reply_text: None,
-                                }
-                            })
-                            .collect();
-                        
-                        // Sort messages by timestamp (oldest first) to ensure correct order
-                        msg_data.sort_by_key(|m| m.timestamp);
+        if self.accept_command_completion() {
+            return Ok(());
+        }
 
-                        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-                            crate::debug_log!("handle_enter: Updating pane {} with chat {}, scrolling to bottom", self.focused_pane_idx, chat_id);
-                            pane.chat_id = Some(chat_id.clone());
-                            pane.chat_name = chat_name;
-                            pane.username = chat_username;
-                            pane.msg_data = msg_data;
-                            pane.messages.clear(); // Clear status messages when switching chats
-                            pane.reply_to_message = None;
-                            pane.hide_reply_preview();
-                            pane.scroll_offset = 0; // Scroll to bottom (0 means bottom when rendering)
+        // Get input from active pane
+        let (input_text, _chat_id, _reply_to_id) = if let Some(pane) = self.panes.get(self.focused_pane_idx) {
+            (pane.input_buffer.clone(), pane.chat_id.clone(), pane.reply_to_message.clone())
+        } else {
+            return Ok(());
+        };
 
-                            // Mark chat as read
-                            if let Some(chat_info) =
-                                self.chats.iter_mut().find(|c| c.id == chat_id)
-                            {
-                                pane.unread_count_at_load = chat_info.unread;
-                                chat_info.unread = 0;
-                            }
-                        } else {
-                            crate::warn_log!("handle_enter: Pane {} not found!", self.focused_pane_idx);
-                        }
-                        // Keep focus on chat list so user can continue navigating
-                        // self.focus_on_chat_list = false;
-                        crate::debug_log!("handle_enter: Keeping focus_on_chat_list=true to allow navigation");
-                    } else {
-                        crate::warn_log!("handle_enter: chat_idx {} >= chats.len() {}", chat_idx, self.chats.len());
-                    }
-                } else {
-                    crate::warn_log!("handle_enter: selected_chat_idx {} >= ordered_chats.len() {}", self.selected_chat_idx, ordered_chats.len());
-                }
-            } else {
-                crate::debug_log!("handle_enter: Not on chat list or chats empty");
+        // Save to history (no duplicates)
+        if self.input_history.last() != Some(&input_text) {
+            self.input_history.push(input_text.clone());
+            if self.input_history.len() > 100 {
+                self.input_history.remove(0);
             }
-        } else if !self.focus_on_chat_list {
-            // Get input from active pane
-            let (input_text, _chat_id, _reply_to_id) = if let Some(pane) = self.panes.get(self.focused_pane_idx) {
-                (pane.input_buffer.clone(), pane.chat_id.clone(), pane.reply_to_message.clone())
-            } else {
-                return Ok(());
-            };
+        }
+        self.history_idx = None;
+        self.history_temp.clear();
 
-            // Save to history (no duplicates)
-            if self.input_history.last().map_or(true, |last| last != &input_text) {
-                self.input_history.push(input_text.clone());
-                if self.input_history.len() > 100 {
-                    self.input_history.remove(0);
-                }
-            }
-            self.history_idx = None;
-            self.history_temp.clear();
-
-            // Try command handling
-            if input_text.starts_with('/') {
-                let focused = self.focused_pane_idx;
-                let handled = CommandHandler::handle(self, &input_text, focused).await?;
-                if handled {
-                    if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-                        pane.input_buffer.clear();
-                    pane.input_cursor = 0;
-                    }
-                    return Ok(());
+        // History keeps the shortcodes as typed; what is sent has the emoji
+        let input_text = crate::emoji::expand_shortcodes(&input_text);
+
+        // Try command handling
+        if input_text.starts_with('/') {
+            let focused = self.focused_pane_idx;
+            let handled = CommandHandler::handle(self, &input_text, focused).await?;
+            if handled {
+                if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+                    pane.input_buffer.clear();
+                pane.input_cursor = 0;
                 }
+                return Ok(());
             }
+        }
 
-            // Handle reply mode or normal send
-            if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-                let chat_id_opt = pane.chat_id.clone();
-                let reply_to_id_opt = pane.reply_to_message.clone();
+        // Sending to a channel would only fail in whatsapp-cli; the text stays in the input
+        let focused_chat = self.panes.get(self.focused_pane_idx).and_then(|p| p.chat_id.clone());
+        if focused_chat.is_some_and(|id| self.is_read_only_chat(&id)) {
+            self.notify("This is a read-only channel");
+            return Ok(());
+        }
+
+        if self.hold_for_group_confirmation() {
+            return Ok(());
+        }
+
+        // While disconnected the message goes to the outbox instead of a CLI call that would fail
+        let offline = self.connection == ConnectionState::Disconnected;
+        let delayed = !offline && self.undo_send_secs > 0;
+        let mut outgoing = None;
+
+        // Handle reply mode or normal send
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            let chat_id_opt = pane.chat_id.clone();
+            let reply_to_id_opt = pane.reply_to_message.clone();
+            
+            if let (Some(chat_id), Some(reply_to_id)) = (chat_id_opt, reply_to_id_opt)
+            {
+                // FIRST: Add message DIRECTLY to pane IMMEDIATELY - no waiting!
+                let new_msg = crate::widgets::MessageData {
+                    reply_to_msg_id: Some(reply_to_id.clone()),
+                    pending: offline,
+                    sending: delayed,
+                    ..crate::widgets::MessageData::echo(&self.my_user_jid, &input_text, chrono::Utc::now().timestamp())
+                };
+                let queued_at = new_msg.timestamp;
+                pane.msg_data.push(new_msg);
+                pane.mark_caught_up();
                 
-                if let (Some(chat_id), Some(reply_to_id)) = (chat_id_opt, reply_to_id_opt)
-                {
-                    // FIRST: Add message DIRECTLY to pane IMMEDIATELY - no waiting!
-                    let new_msg = crate::widgets::MessageData {
-                        msg_id: String::new(), // Temporary ID
-                        sender_id: self.my_user_jid.clone(),
-                        sender_name: "You".to_string(),
-                        text: input_text.clone(),
-                        is_outgoing: true,
-                        timestamp: chrono::Utc::now().timestamp(),
-                        media_type: None,
-                        media_label: None,
-                        reactions: std::collections::HashMap::new(),
-                        reply_to_msg_id: Some(reply_to_id.clone()),
-                        reply_sender: None,
-                        reply_text: None,
-                    };
-                    pane.msg_data.push(new_msg);
-                    pane.format_cache.clear();
-                    
-                    pane.reply_to_message = None;
-                    pane.hide_reply_preview();
-                    pane.input_buffer.clear();
-                    pane.input_cursor = 0;
-                    
-                    // THEN: Send message in background - don't wait!
-                    let whatsapp = self.whatsapp.clone();
-                    let chat_id_copy = chat_id.clone();
-                    let reply_to_id_copy = reply_to_id.clone();
-                    let input_text_copy = input_text.clone();
-                    tokio::spawn(async move {
-                        let _ = whatsapp.reply_to_message(&chat_id_copy, &reply_to_id_copy, &input_text_copy).await;
-                    });
-                } else if let Some(ref chat_id) = pane.chat_id {
-                    // FIRST: Add message DIRECTLY to pane IMMEDIATELY - no waiting!
-                    let new_msg = crate::widgets::MessageData {
-                        msg_id: String::new(), // Temporary ID
-                        sender_id: self.my_user_jid.clone(),
-                        sender_name: "You".to_string(),
-                        text: input_text.clone(),
-                        is_outgoing: true,
-                        timestamp: chrono::Utc::now().timestamp(),
-                        media_type: None,
-                        media_label: None,
-                        reactions: std::collections::HashMap::new(),
-                        reply_to_msg_id: None,
-                        reply_sender: None,
-                        reply_text: None,
-                    };
-                    pane.msg_data.push(new_msg);
-                    pane.format_cache.clear();
-                    
-                    pane.input_buffer.clear();
-                    pane.input_cursor = 0;
-                    
-                    // THEN: Send message in background - don't wait!
-                    let whatsapp = self.whatsapp.clone();
-                    let chat_id_copy = chat_id.clone();
-                    let input_text_copy = input_text.clone();
-                    tokio::spawn(async move {
-                        let _ = whatsapp.send_message(&chat_id_copy, &input_text_copy).await;
+                pane.reply_to_message = None;
+                pane.hide_reply_preview();
+                pane.input_buffer.clear();
+                pane.input_cursor = 0;
+                
+                // The original message, for an inline quote if the CLI can't reply natively
+                let quoted = pane
+                    .msg_data
+                    .iter()
+                    .find(|m| m.msg_id == reply_to_id)
+                    .map(|m| crate::whatsapp::QuotedMessage {
+                        sender_name: m.sender_name.clone(),
+                        text: m.text.clone(),
                     });
-                }
+
+                let message = QueuedMessage {
+                    chat_id,
+                    text: input_text,
+                    reply_to: Some(reply_to_id),
+                    send_as: pane.send_as.clone(),
+                    queued_at,
+                };
+                outgoing = Some((message, quoted));
+            } else if let Some(chat_id) = pane.chat_id.clone() {
+                // FIRST: Add message DIRECTLY to pane IMMEDIATELY - no waiting!
+                let new_msg = crate::widgets::MessageData {
+                    pending: offline,
+                    sending: delayed,
+                    ..crate::widgets::MessageData::echo(&self.my_user_jid, &input_text, chrono::Utc::now().timestamp())
+                };
+                let queued_at = new_msg.timestamp;
+                pane.msg_data.push(new_msg);
+                pane.mark_caught_up();
+                
+                pane.input_buffer.clear();
+                pane.input_cursor = 0;
+                
+                let message = QueuedMessage {
+                    chat_id,
+                    text: input_text,
+                    reply_to: None,
+                    send_as: pane.send_as.clone(),
+                    queued_at,
+                };
+                outgoing = Some((message, None));
             }
         }
+
+        match outgoing {
+            Some((message, _)) if offline => {
+                self.outbox.messages.push(message);
+                self.save_outbox();
+                self.notify(&format!(
+                    "Offline: message queued ({} waiting)",
+                    self.outbox.messages.len()
+                ));
+            }
+            // THEN: Send message in background - don't wait!
+            Some((message, quoted)) => self.send_in_background(message, quoted),
+            None => {}
+        }
         Ok(())
     }
 
-    pub fn handle_char(&mut self, c: char) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            pane.input_buffer.insert(pane.input_cursor, c);
-            pane.input_cursor += c.len_utf8();
+    /// Hand a message to the send queue without waiting for the CLI. With an undo window
+    /// the message is held back that long first, and `/unsend` can still take it back.
+    fn send_in_background(&mut self, message: QueuedMessage, quoted: Option<crate::whatsapp::QuotedMessage>) {
+        let delay = self.undo_send_secs;
+        let whatsapp = self.whatsapp.clone();
+        let claimed = Arc::new(AtomicBool::new(false));
+        let task_claimed = claimed.clone();
+        let task_message = message.clone();
+        self.sends_in_flight.push(message.clone());
+        if delay == 0 {
+            self.whatsapp.queue_send(message, quoted);
+            return;
         }
-        self.history_idx = None;
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(delay)).await;
+            if !task_claimed.swap(true, Ordering::SeqCst) {
+                whatsapp.queue_send(task_message, quoted);
+            }
+        });
+        self.held_sends.push(HeldSend { message, claimed, task });
     }
 
-    pub fn handle_backspace(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            if pane.input_cursor > 0 {
-                let prev = pane.input_buffer[..pane.input_cursor]
-                    .char_indices()
-                    .next_back()
-                    .map(|(i, _)| i)
-                    .unwrap_or(0);
-                pane.input_buffer.remove(prev);
-                pane.input_cursor = prev;
+    /// Take back the newest message to the focused pane's chat that is still inside the
+    /// undo window. Its text goes back into an empty input.
+    pub fn unsend_last(&mut self) {
+        let chat_id = self.panes.get(self.focused_pane_idx).and_then(|p| p.chat_id.clone());
+        let Some(idx) = self
+            .held_sends
+            .iter()
+            .rposition(|h| Some(&h.message.chat_id) == chat_id.as_ref())
+        else {
+            if self.undo_send_secs == 0 {
+                self.notify("Nothing to unsend: undo-send is off, turn it on with /set undo_send_secs 5");
+            } else {
+                self.notify("Nothing to unsend");
             }
+            return;
+        };
+        if self.held_sends[idx].claimed.swap(true, Ordering::SeqCst) {
+            self.notify("Too late, the message is already being sent");
+            return;
         }
-        self.history_idx = None;
+
+        let held = self.held_sends.remove(idx);
+        held.task.abort();
+        self.sends_in_flight.retain(|m| *m != held.message);
+        for pane in self.panes.iter_mut().filter(|p| p.chat_id.as_ref() == Some(&held.message.chat_id)) {
+            pane.msg_data.retain(|m| !is_echo_of(m, &held.message));
+            pane.format_cache.clear();
+        }
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx).filter(|p| p.input_buffer.is_empty()) {
+            pane.input_buffer = held.message.text.clone();
+            pane.input_cursor = pane.input_buffer.len();
+        }
+        self.notify("Message unsent");
     }
 
-    pub fn handle_delete(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            if pane.input_cursor < pane.input_buffer.len() {
-                pane.input_buffer.remove(pane.input_cursor);
+    /// Clear the "sending" marker of held messages whose send went out. Returns whether any
+    /// did, so the main loop can redraw
+    pub fn finish_held_sends(&mut self) -> bool {
+        let (done, waiting): (Vec<HeldSend>, Vec<HeldSend>) =
+            std::mem::take(&mut self.held_sends).into_iter().partition(|h| h.task.is_finished());
+        self.held_sends = waiting;
+        for held in &done {
+            for pane in self.panes.iter_mut().filter(|p| p.chat_id.as_ref() == Some(&held.message.chat_id)) {
+                if let Some(echo) = pane.msg_data.iter_mut().find(|m| is_echo_of(m, &held.message)) {
+                    echo.sending = false;
+                }
+                pane.format_cache.clear();
             }
         }
+        !done.is_empty()
     }
 
-    pub fn handle_input_left(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            if pane.input_cursor > 0 {
-                pane.input_cursor = pane.input_buffer[..pane.input_cursor]
-                    .char_indices()
-                    .next_back()
-                    .map(|(i, _)| i)
-                    .unwrap_or(0);
+    /// Record the send queue's outcome on the message's local echo: sent, or marked ✗ with
+    /// the error shown when every retry failed. A failed message goes (back) into the outbox,
+    /// so it is tried again after `OUTBOX_RETRY_SECS` and survives a restart.
+    fn finish_send(&mut self, message: &QueuedMessage, error: Option<&str>) {
+        if let Some(idx) = self.sends_in_flight.iter().position(|m| m == message) {
+            self.sends_in_flight.remove(idx);
+        }
+        let status = if error.is_some() {
+            crate::widgets::SendStatus::Failed
+        } else {
+            crate::widgets::SendStatus::Sent
+        };
+        let now = chrono::Utc::now().timestamp();
+        for pane in self.panes.iter_mut().filter(|p| p.chat_id.as_ref() == Some(&message.chat_id)) {
+            if let Some(echo) = pane.msg_data.iter_mut().find(|m| is_echo_of(m, message)) {
+                echo.send_status = status;
+                echo.pending = error.is_some();
+                if error.is_none() {
+                    // An ordinary local echo now, replaced once the store has the message
+                    echo.timestamp = now;
+                }
             }
+            pane.format_cache.clear();
         }
-    }
 
-    pub fn handle_input_right(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            if pane.input_cursor < pane.input_buffer.len() {
-                pane.input_cursor = pane.input_buffer[pane.input_cursor..]
-                    .char_indices()
-                    .nth(1)
-                    .map(|(i, _)| pane.input_cursor + i)
-                    .unwrap_or(pane.input_buffer.len());
+        let in_outbox = self.outbox.messages.contains(message);
+        let Some(error) = error else {
+            if in_outbox {
+                self.outbox.messages.retain(|m| m != message);
+                self.save_outbox();
             }
+            return;
+        };
+        if !in_outbox {
+            self.outbox.messages.push(message.clone());
+            self.save_outbox();
         }
+        self.last_outbox_attempt = Some(std::time::Instant::now());
+        let chat_name = self
+            .chats
+            .iter()
+            .find(|c| c.id == message.chat_id)
+            .map_or(message.chat_id.as_str(), |c| c.name.as_str());
+        self.notify_with_duration(&format!("✗ Message to {} not sent: {} (kept to retry)", chat_name, error), 10);
     }
 
-    pub fn handle_home(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            pane.input_cursor = 0;
+    /// Quitting: hand messages still inside the undo window to the send queue, give the
+    /// queue `SHUTDOWN_DRAIN_SECS` to finish, and keep whatever it didn't get to in the
+    /// outbox, which is sent after the next start
+    pub async fn drain_sends(&mut self) {
+        for held in std::mem::take(&mut self.held_sends) {
+            if held.claimed.swap(true, Ordering::SeqCst) {
+                continue; // Already on its way
+            }
+            held.task.abort();
+            let quoted = self.quoted_message(held.message.reply_to.as_deref());
+            self.whatsapp.queue_send(held.message, quoted);
         }
-    }
 
-    pub fn handle_end(&mut self) {
-        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
-            pane.input_cursor = pane.input_buffer.len();
+        let deadline = tokio::time::Instant::now() + std::time::Duration::from_secs(SHUTDOWN_DRAIN_SECS);
+        while !self.sends_in_flight.is_empty() && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            if let Err(e) = self.process_whatsapp_events().await {
+                crate::warn_log!("drain_sends: {}", e);
+            }
         }
+
+        let unsent: Vec<QueuedMessage> = std::mem::take(&mut self.sends_in_flight)
+            .into_iter()
+            .filter(|m| !self.outbox.messages.contains(m))
+            .collect();
+        if unsent.is_empty() {
+            return;
+        }
+        crate::warn_log!("drain_sends: {} message(s) not sent before quitting, kept in the outbox", unsent.len());
+        self.outbox.messages.extend(unsent);
+        self.save_outbox();
     }
 
-    // =========================================================================
-    // New message handling
-    // =========================================================================
+    /// Sender and text of a loaded message, for an inline quote when the CLI can't reply natively
+    fn quoted_message(&self, msg_id: Option<&str>) -> Option<crate::whatsapp::QuotedMessage> {
+        let msg_id = msg_id?;
+        self.panes
+            .iter()
+            .flat_map(|p| &p.msg_data)
+            .find(|m| m.msg_id == msg_id)
+            .map(|m| crate::whatsapp::QuotedMessage {
+                sender_name: m.sender_name.clone(),
+                text: m.text.clone(),
+            })
+    }
 
-    pub async fn process_whatsapp_events(&mut self) -> Result<bool> {
-        // Process incoming updates
-        let updates = self.whatsapp.poll_updates().await?;
-        let had_updates = !updates.is_empty();
-        
-        if had_updates {
-            crate::debug_log!("process_whatsapp_events: Got {} updates", updates.len());
+    /// With `confirm_group_send` on, keep a message to a group (of at least
+    /// `confirm_group_min_members`) in the input until Enter is pressed a second time.
+    /// Returns true while the send waits for that confirmation.
+    fn hold_for_group_confirmation(&mut self) -> bool {
+        if !self.confirm_group_send {
+            return false;
+        }
+        let Some(chat_id) = self.panes.get(self.focused_pane_idx).and_then(|p| p.chat_id.clone()) else {
+            return false;
+        };
+        if self.group_send_pending.take().as_ref() == Some(&chat_id) {
+            self.status_message = None;
+            return false;
         }
 
-        for update in updates {
-            match update {
-                crate::whatsapp::WhatsAppUpdate::NewMessage {
-                    chat_jid,
+        let chat = self.chats.iter().find(|c| c.id == chat_id);
+        let is_group = chat.map_or(chat_id.ends_with("@g.us"), |c| c.is_group);
+        if !is_group {
+            return false;
+        }
+        if self.confirm_group_min_members > 0 {
+            // An unknown size (members not fetched yet, or whatsapp-cli couldn't say) asks anyway
+            let size = self.group_members.get(&chat_id).map(Vec::len).filter(|&n| n > 0);
+            if size.is_some_and(|n| n < self.confirm_group_min_members) {
+                return false;
+            }
+        }
+
+        let name = self
+            .panes
+            .get(self.focused_pane_idx)
+            .map(|p| p.chat_name.clone())
+            .unwrap_or_default();
+        self.group_send_pending = Some(chat_id);
+        self.notify_with_duration(&format!("Send to {}? Enter/Esc", name), 60);
+        true
+    }
+
+    /// Drop a send waiting for group confirmation; the text stays in the input. Returns
+    /// whether one was waiting.
+    pub fn cancel_group_send(&mut self) -> bool {
+        if self.group_send_pending.take().is_none() {
+            return false;
+        }
+        self.notify("Not sent");
+        true
+    }
+
+    /// Add pending echoes for the pane's queued messages that it isn't showing yet,
+    /// so the outbox stays visible after reopening a chat or restarting
+    fn show_queued_messages(&mut self, pane_idx: usize) {
+        let Some(pane) = self.panes.get_mut(pane_idx) else {
+            return;
+        };
+        let Some(chat_id) = pane.chat_id.clone() else {
+            return;
+        };
+        for queued in self.outbox.messages.iter().filter(|q| q.chat_id == chat_id) {
+            let shown = pane.msg_data.iter().any(|m| m.pending && is_echo_of(m, queued));
+            if !shown {
+                pane.msg_data.push(MessageData {
+                    reply_to_msg_id: queued.reply_to.clone(),
+                    pending: true,
+                    ..MessageData::echo(&self.my_user_jid, &queued.text, queued.queued_at)
+                });
+            }
+        }
+        pane.format_cache.clear();
+    }
+
+    fn save_outbox(&self) {
+        if let Err(e) = self.outbox.save(&self.config) {
+            crate::warn_log!("Failed to save outbox: {}", e);
+        }
+    }
+
+    /// Hand queued messages to the send queue, oldest first, once the connection is no
+    /// longer down. They stay in the outbox until `finish_send` hears they went out; after a
+    /// failure the rest wait `OUTBOX_RETRY_SECS`. Returns whether any message was handed
+    /// over, so the main loop can redraw the changed markers
+    pub fn flush_outbox(&mut self) -> bool {
+        if self.outbox.messages.is_empty() || self.connection == ConnectionState::Disconnected {
+            return false;
+        }
+        if self
+            .last_outbox_attempt
+            .is_some_and(|t| t.elapsed() < std::time::Duration::from_secs(OUTBOX_RETRY_SECS))
+        {
+            return false;
+        }
+        self.last_outbox_attempt = None;
+
+        let ready: Vec<QueuedMessage> = self
+            .outbox
+            .messages
+            .iter()
+            .filter(|m| !self.sends_in_flight.contains(m))
+            .cloned()
+            .collect();
+        for queued in &ready {
+            // The echo shows [sending] instead of [queued] while the queue has it
+            for pane in self.panes.iter_mut().filter(|p| p.chat_id.as_ref() == Some(&queued.chat_id)) {
+                if let Some(echo) = pane.msg_data.iter_mut().find(|m| m.pending && is_echo_of(m, queued)) {
+                    echo.pending = false;
+                    echo.send_status = crate::widgets::SendStatus::Pending;
+                }
+                pane.format_cache.clear();
+            }
+            let quoted = self.quoted_message(queued.reply_to.as_deref());
+            self.sends_in_flight.push(queued.clone());
+            self.whatsapp.queue_send(queued.clone(), quoted);
+        }
+
+        if !ready.is_empty() {
+            self.notify(&format!("Sending {} queued message{}", ready.len(), if ready.len() == 1 { "" } else { "s" }));
+        }
+        !ready.is_empty()
+    }
+
+    /// Select the next chat with unread messages after the selected one, in chat list
+    /// order and wrapping around, and open it in the focused pane
+    pub async fn open_next_unread(&mut self) {
+        let order = self.chat_list_order();
+        let next = (1..=order.len())
+            .map(|step| order[(self.selected_chat_idx + step) % order.len()])
+            .find(|&idx| self.chats[idx].unread > 0);
+        let Some(chat_idx) = next else {
+            self.notify("No unread chats");
+            return;
+        };
+        let chat_id = self.chats[chat_idx].id.clone();
+        let chat_name = self.chats[chat_idx].name.clone();
+        self.open_chat_in_pane(self.focused_pane_idx, chat_id.clone(), &chat_name).await;
+        // Opening moves the chat out of the Unread section; keep the highlight on it
+        if let Some(pos) = self.chat_list_order().iter().position(|&idx| self.chats[idx].id == chat_id) {
+            self.selected_chat_idx = pos;
+        }
+    }
+
+    /// `enter_on_empty: "recent"`: open the most recently active chat other than the focused
+    /// pane's in that pane, then focus the chat list with it highlighted. Archived chats are
+    /// left out.
+    pub async fn open_recent_chat(&mut self) {
+        let pane_idx = self.focused_pane_idx;
+        let current = self.panes.get(pane_idx).and_then(|p| p.chat_id.clone());
+        let recent = self
+            .chats
+            .iter()
+            .filter(|c| Some(&c.id) != current.as_ref() && !self.archived_chats.contains(&c.id))
+            .max_by_key(|c| c.last_message_ts)
+            .map(|c| (c.id.clone(), c.name.clone()));
+        let Some((chat_id, chat_name)) = recent else {
+            self.focus_chat_list();
+            return;
+        };
+        self.open_chat_in_pane(pane_idx, chat_id.clone(), &chat_name).await;
+        self.focus_chat_list();
+        if let Some(pos) = self.chat_list_order().iter().position(|&idx| self.chats[idx].id == chat_id) {
+            self.selected_chat_idx = pos;
+        }
+    }
+
+    /// Open the chat highlighted in the chat list in the focused pane.
+    /// Leaves the pane's input buffer (draft) as-is.
+    async fn open_selected_chat(&mut self) {
+        crate::debug_log!("open_selected_chat: On chat list, opening selected chat");
+        // Refresh chat list first to get latest data
+        let _ = self.refresh_chat_list().await;
+        
+        let ordered_chats = self.chat_list_order();
+        crate::debug_log!("open_selected_chat: ordered_chats.len()={}, selected_chat_idx={}", ordered_chats.len(), self.selected_chat_idx);
+        if let Some(&chat_idx) = ordered_chats.get(self.selected_chat_idx) {
+            if chat_idx < self.chats.len() {
+                let chat = &self.chats[chat_idx];
+                let chat_id = chat.id.clone();
+                let chat_name = chat.name.clone();
+                let chat_username = chat.username.clone();
+                crate::debug_log!("open_selected_chat: Opening chat {}: '{}'", chat_id, chat_name);
+                let raw_messages = match self.whatsapp.get_messages(&chat_id, self.initial_message_limit).await {
+                    Ok(raw_messages) => raw_messages,
+                    Err(e) => {
+                        crate::warn_log!("open_selected_chat: Failed to load messages for {}: {}", chat_id, e);
+                        Vec::new()
+                    }
+                };
+                crate::debug_log!("open_selected_chat: Got {} messages for chat {}: '{}'", raw_messages.len(), chat_id, chat_name);
+
+                // Convert to MessageData for proper formatting support
+                let mut msg_data: Vec<crate::widgets::MessageData> = raw_messages
+                    .iter()
+                    .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
+                    .collect();
+                
+                // Sort messages by timestamp (oldest first) to ensure correct order
+                msg_data.sort_by_key(|m| m.timestamp);
+
+                // The pane that just received the chat is where Alt+C should land
+                self.last_pane_before_list = Some(self.focused_pane_idx);
+                let switched = self.stash_scroll(self.focused_pane_idx, &chat_id);
+                if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+                    crate::debug_log!("open_selected_chat: Updating pane {} with chat {}, scrolling to bottom", self.focused_pane_idx, chat_id);
+                    if pane.chat_id.as_ref() != Some(&chat_id) {
+                        pane.clear_filter();
+                        pane.last_filters.clear();
+                        pane.unread_only = false;
+                        pane.selected_msg = None;
+                        pane.expanded_msg = None;
+                        pane.send_as = None;
+                    }
+                    pane.chat_id = Some(chat_id.clone());
+                    pane.chat_name = chat_name;
+                    pane.username = chat_username;
+                    pane.msg_data = msg_data;
+                    pane.history_exhausted = false;
+                    pane.history_unavailable = false;
+                    pane.messages.clear(); // Clear status messages when switching chats
+                    pane.search_hits.clear();
+                    pane.reply_to_message = None;
+                    pane.hide_reply_preview();
+                    pane.scroll_offset = 0; // Scroll to bottom (0 means bottom when rendering)
+
+                    if let Some(chat_info) = self.chats.iter().find(|c| c.id == chat_id) {
+                        pane.unread_count_at_load = chat_info.unread;
+                    }
+                } else {
+                    crate::warn_log!("open_selected_chat: Pane {} not found!", self.focused_pane_idx);
+                }
+                if switched {
+                    self.restore_scroll(self.focused_pane_idx);
+                }
+                self.show_queued_messages(self.focused_pane_idx);
+                self.mark_chat_read(&chat_id);
+                self.note_recent_chat(&chat_id);
+                // Keep focus on chat list so user can continue navigating
+                // self.focus_on_chat_list = false;
+                crate::debug_log!("open_selected_chat: Keeping focus_on_chat_list=true to allow navigation");
+            } else {
+                crate::warn_log!("open_selected_chat: chat_idx {} >= chats.len() {}", chat_idx, self.chats.len());
+            }
+        } else {
+            crate::warn_log!("open_selected_chat: selected_chat_idx {} >= ordered_chats.len() {}", self.selected_chat_idx, ordered_chats.len());
+        }
+    }
+
+    pub fn handle_char(&mut self, c: char) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_buffer.insert(pane.input_cursor, c);
+            pane.input_cursor += c.len_utf8();
+        }
+        self.history_idx = None;
+        self.last_keystroke = Some(std::time::Instant::now());
+    }
+
+    /// Insert pasted text at the cursor. Newlines are kept in the buffer (normalized to `\n`)
+    /// rather than treated as Enter, so a multi-line paste becomes one message.
+    pub fn handle_paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_buffer.insert_str(pane.input_cursor, &text);
+            pane.input_cursor += text.len();
+        }
+        self.history_idx = None;
+        self.last_keystroke = Some(std::time::Instant::now());
+    }
+
+    /// Ctrl+X or /compose: have the main loop open the focused pane's input in $EDITOR
+    pub fn request_external_editor(&mut self) {
+        if self.panes.get(self.focused_pane_idx).is_none() {
+            return;
+        }
+        self.editor_requested = true;
+    }
+
+    /// Hand the focused pane's input to $EDITOR (see `utils::editor_command`), with raw mode
+    /// and the alternate screen left while it runs, then load what was saved back into it.
+    /// The caller redraws the whole screen afterwards.
+    pub async fn open_external_editor(&mut self) {
+        use crossterm::event::{DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture};
+        use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+
+        self.editor_requested = false;
+        let Some(pane) = self.panes.get(self.focused_pane_idx) else {
+            return;
+        };
+        let seed = pane.input_buffer.clone();
+        let editor = crate::utils::editor_command();
+
+        let mut stdout = std::io::stdout();
+        let suspended = disable_raw_mode().and_then(|_| {
+            crossterm::execute!(stdout, LeaveAlternateScreen, DisableMouseCapture, DisableBracketedPaste)
+        });
+        if let Err(e) = &suspended {
+            crate::warn_log!("Could not leave the alternate screen for the editor: {}", e);
+        }
+        let outcome = crate::utils::run_editor(&editor, &seed).await;
+        let resumed = crossterm::execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)
+            .and_then(|_| enable_raw_mode());
+        if let Err(e) = resumed {
+            crate::error_log!("Could not restore the terminal after the editor: {}", e);
+        }
+        self.apply_composed(outcome);
+        self.needs_redraw = true;
+    }
+
+    /// Put a message written in the editor into the focused pane's input, newlines and all,
+    /// with the cursor at its end. Anything else leaves the input as it was.
+    pub fn apply_composed(&mut self, outcome: anyhow::Result<EditorOutcome>) {
+        match outcome {
+            Ok(EditorOutcome::Saved(text)) => {
+                if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+                    pane.input_cursor = text.len();
+                    pane.input_buffer = text;
+                }
+                self.history_idx = None;
+                self.focus_on_chat_list = false;
+            }
+            Ok(EditorOutcome::Unchanged) => self.notify("Editor closed without changes"),
+            Ok(EditorOutcome::Deleted) => self.notify("The draft file was deleted; input left as it was"),
+            Err(e) => {
+                crate::warn_log!("External editor failed: {}", e);
+                self.notify(&format!("Editor failed: {}", e));
+            }
+        }
+    }
+
+    /// Insert clipboard contents read by `utils::read_clipboard` at the cursor, like a paste.
+    /// Nothing is sent; an empty or unreadable clipboard only shows a status message.
+    pub fn handle_clipboard_paste(&mut self, clipboard: anyhow::Result<String>) {
+        match clipboard {
+            Ok(text) if text.is_empty() => self.notify("Clipboard is empty"),
+            Ok(text) => self.handle_paste(&text),
+            Err(e) => {
+                crate::warn_log!("Clipboard paste failed: {}", e);
+                self.notify(&format!("Clipboard unavailable: {}", e));
+            }
+        }
+    }
+
+    pub fn handle_backspace(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            if pane.input_cursor > 0 {
+                let prev = pane.input_buffer[..pane.input_cursor]
+                    .char_indices()
+                    .next_back()
+                    .map(|(i, _)| i)
+                    .unwrap_or(0);
+                pane.input_buffer.remove(prev);
+                pane.input_cursor = prev;
+            }
+        }
+        self.history_idx = None;
+        self.last_keystroke = Some(std::time::Instant::now());
+    }
+
+    pub fn handle_delete(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            if pane.input_cursor < pane.input_buffer.len() {
+                pane.input_buffer.remove(pane.input_cursor);
+            }
+        }
+        self.last_keystroke = Some(std::time::Instant::now());
+    }
+
+    pub fn handle_input_left(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            if pane.input_cursor > 0 {
+                pane.input_cursor = pane.input_buffer[..pane.input_cursor]
+                    .char_indices()
+                    .next_back()
+                    .map(|(i, _)| i)
+                    .unwrap_or(0);
+            }
+        }
+    }
+
+    pub fn handle_input_right(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            if pane.input_cursor < pane.input_buffer.len() {
+                pane.input_cursor = pane.input_buffer[pane.input_cursor..]
+                    .char_indices()
+                    .nth(1)
+                    .map(|(i, _)| pane.input_cursor + i)
+                    .unwrap_or(pane.input_buffer.len());
+            }
+        }
+    }
+
+    /// Ctrl+Left: move to the start of the previous word
+    pub fn handle_word_left(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_cursor = prev_word_start(&pane.input_buffer, pane.input_cursor);
+        }
+    }
+
+    /// Ctrl+Right: move to the end of the next word
+    pub fn handle_word_right(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_cursor = next_word_end(&pane.input_buffer, pane.input_cursor);
+        }
+    }
+
+    /// Ctrl+W / Alt+Backspace: delete from the start of the previous word up to the cursor.
+    /// Does nothing with an empty input.
+    pub fn handle_delete_word_back(&mut self) {
+        let Some(pane) = self.panes.get_mut(self.focused_pane_idx).filter(|p| !p.input_buffer.is_empty()) else {
+            return;
+        };
+        let start = prev_word_start(&pane.input_buffer, pane.input_cursor);
+        pane.input_buffer.replace_range(start..pane.input_cursor, "");
+        pane.input_cursor = start;
+        self.history_idx = None;
+        self.last_keystroke = Some(std::time::Instant::now());
+    }
+
+    pub fn handle_home(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_cursor = 0;
+        }
+    }
+
+    pub fn handle_end(&mut self) {
+        if let Some(pane) = self.panes.get_mut(self.focused_pane_idx) {
+            pane.input_cursor = pane.input_buffer.len();
+        }
+    }
+
+    // =========================================================================
+    // New message handling
+    // =========================================================================
+
+    pub async fn process_whatsapp_events(&mut self) -> Result<bool> {
+        // Process incoming updates
+        let updates = self.whatsapp.poll_updates().await?;
+        let had_updates = !updates.is_empty();
+        
+        if had_updates {
+            crate::debug_log!("process_whatsapp_events: Got {} updates", updates.len());
+        }
+
+        for update in updates {
+            match update {
+                crate::whatsapp::WhatsAppUpdate::NewMessage {
+                    chat_jid,
                     sender_name,
                     text,
                     is_outgoing,
                 } => {
                     crate::debug_log!("NewMessage received: chat_jid={}, sender={}, text_len={}, is_outgoing={}", 
                         chat_jid, sender_name, text.len(), is_outgoing);
+                    if let Some(chat_info) = self.chats.iter_mut().find(|c| c.id == chat_jid) {
+                        chat_info.last_message_ts = chrono::Utc::now().timestamp();
+                    }
                     
                     // Don't process outgoing messages as "new" - they're already shown via local echo
                     if is_outgoing {
                         crate::debug_log!("Skipping outgoing message for chat {}", chat_jid);
                         continue;
                     }
+
+                    if self.auto_unarchive && self.archived_chats.remove(&chat_jid) {
+                        crate::debug_log!("Chat {} unarchived by new message", chat_jid);
+                    }
                     
                     // Check if any pane has this chat open
                     let matching_panes: Vec<usize> = self
@@ -2019,47 +4255,61 @@ impl App {
                     // Add a small delay to let sync process finish writing
                     tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
                     
-                    if let Ok(raw_messages) =
-                        self.whatsapp.get_messages(&chat_jid, 50).await
-                    {
-                        crate::debug_log!("Loaded {} messages for chat {}", raw_messages.len(), chat_jid);
-                        // Convert to MessageData for proper formatting support
-                        let mut msg_data: Vec<crate::widgets::MessageData> = raw_messages
-                            .iter()
-                            .map(|(msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp)| {
-                                let reply_to_msg_id = reply_to_id.clone();
-                                
-                                crate::widgets::MessageData {
-                                    msg_id: msg_id.clone(),
-                                    sender_id: sender_id.clone(),
-                                    sender_name: sender_name.clone(),
-                                    text: text.clone(),
-                                    is_outgoing: sender_id == &self.my_user_jid,
-                                    timestamp: *timestamp, // Use actual timestamp from message
-                                    media_type: media_type.clone(),
-                                    media_label: None,
-                                    reactions: reactions.clone(),
-                                    reply_to_msg_id,
-                                    reply_sender: None,
-                                    reply_text: None,
-                                }
-                            })
-                            .collect();
-                        
-                        // Sort messages by timestamp (oldest first) to ensure correct order
-                        msg_data.sort_by_key(|m| m.timestamp);
-
-                        for idx in &matching_panes {
+                    // Fetch only what's newer than each pane's last synced message, so local
+                    // echoes and the reader's scroll position survive. Full reload as fallback.
+                    let mut full_reload: Option<Vec<crate::widgets::MessageData>> = None;
+                    for idx in &matching_panes {
+                        let anchor = self.panes.get(*idx).and_then(|p| {
+                            p.msg_data.iter().rev().find(|m| !m.msg_id.is_empty()).map(|m| m.msg_id.clone())
+                        });
+                        let new_messages = match anchor {
+                            Some(ref after_msg_id) => self
+                                .whatsapp
+                                .get_messages_since(&chat_jid, after_msg_id, self.initial_message_limit)
+                                .await
+                                .unwrap_or_else(|e| {
+                                    crate::warn_log!("Incremental load failed for chat {}: {}", chat_jid, e);
+                                    None
+                                }),
+                            None => None,
+                        };
+
+                        if let Some(raw_messages) = new_messages {
+                            let msg_data: Vec<crate::widgets::MessageData> = raw_messages
+                                .iter()
+                                .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
+                                .collect();
                             if let Some(pane) = self.panes.get_mut(*idx) {
-                                crate::debug_log!("Updating pane {} with {} messages, scrolling to bottom", idx, msg_data.len());
-                                pane.msg_data = msg_data.clone();
-                                pane.format_cache.clear(); // Clear cache so messages are re-rendered
-                                pane.scroll_offset = 0; // Scroll to bottom (0 means bottom when rendering)
-                                // Don't clear messages - they may contain status messages
+                                let added = pane.merge_new_messages(msg_data);
+                                crate::debug_log!("Appended {} new messages to pane {}", added, idx);
                             }
+                            continue;
+                        }
+
+                        if full_reload.is_none() {
+                            match self.whatsapp.get_messages(&chat_jid, self.initial_message_limit).await {
+                                Ok(raw_messages) => {
+                                    crate::debug_log!("Loaded {} messages for chat {}", raw_messages.len(), chat_jid);
+                                    let mut msg_data: Vec<crate::widgets::MessageData> = raw_messages
+                                        .iter()
+                                        .map(|raw| crate::widgets::MessageData::from_raw(raw, &self.my_user_jid))
+                                        .collect();
+                                    // Sort messages by timestamp (oldest first) to ensure correct order
+                                    msg_data.sort_by_key(|m| m.timestamp);
+                                    full_reload = Some(msg_data);
+                                }
+                                Err(_) => {
+                                    crate::warn_log!("Failed to load messages for chat {}", chat_jid);
+                                    break;
+                                }
+                            }
+                        }
+                        if let (Some(pane), Some(msg_data)) = (self.panes.get_mut(*idx), full_reload.as_ref()) {
+                            crate::debug_log!("Updating pane {} with {} messages, scrolling to bottom", idx, msg_data.len());
+                            pane.reload_messages(msg_data.clone());
+                            pane.scroll_offset = 0; // Scroll to bottom (0 means bottom when rendering)
+                            // Don't clear messages - they may contain status messages
                         }
-                    } else {
-                        crate::warn_log!("Failed to load messages for chat {}", chat_jid);
                     }
                     
                     // Update chat list after loading messages (to update unread count)
@@ -2091,16 +4341,20 @@ impl App {
                         {
                             crate::debug_log!("Chat {} unread after refresh: {}", chat_jid, chat_info.unread);
                             let chat_name = chat_info.name.clone();
-                            let preview = if text.chars().count() > 50 {
-                                let truncate_at = text
-                                    .char_indices()
-                                    .nth(50)
-                                    .map(|(i, _)| i)
-                                    .unwrap_or(text.len());
-                                format!("{}...", &text[..truncate_at])
-                            } else {
-                                text.clone()
-                            };
+                            let preview = truncate_chars(&text, 50);
+
+                            let mentioned = self.notify_on_mention
+                                && !is_outgoing
+                                && chat_jid.ends_with("@g.us")
+                                && mentions_me(&text, &self.my_user_jid);
+                            if self.muted_chats.contains(&chat_jid) && !mentioned {
+                                crate::debug_log!("Chat {} is muted, skipping notification", chat_jid);
+                                continue;
+                            }
+                            if self.is_quiet_now() {
+                                crate::debug_log!("Do not disturb, skipping notification for {}", chat_jid);
+                                continue;
+                            }
 
                             // Desktop notification
                             if self.show_notifications && !is_outgoing {
@@ -2122,6 +4376,28 @@ impl App {
                         }
                     }
                 }
+                crate::whatsapp::WhatsAppUpdate::ConnectionChanged(state) => {
+                    let was_offline = self.connection == ConnectionState::Disconnected;
+                    self.connection = state;
+                    if state == ConnectionState::Disconnected {
+                        self.notify("Connection lost: new messages will be queued");
+                    } else if was_offline {
+                        // Retry straight away rather than waiting out an earlier failure
+                        self.last_outbox_attempt = None;
+                        if self.outbox.messages.is_empty() {
+                            self.notify("Connection restored");
+                        } else {
+                            let waiting = self.outbox.messages.len();
+                            self.notify(&format!("Connection restored: sending {} queued message{}", waiting, if waiting == 1 { "" } else { "s" }));
+                        }
+                    }
+                }
+                crate::whatsapp::WhatsAppUpdate::SendFinished { message, error } => {
+                    self.finish_send(&message, error.as_deref());
+                }
+                crate::whatsapp::WhatsAppUpdate::Notice(text) => {
+                    self.notify(&text);
+                }
             }
         }
 
@@ -2132,33 +4408,161 @@ impl App {
     // State persistence
     // =========================================================================
 
-    pub fn save_state(&self) -> Result<()> {
-        let layout = LayoutData {
+    /// Snapshot of the pane tree, each pane's chat and the focused pane
+    pub fn current_layout(&self) -> LayoutData {
+        LayoutData {
+            version: crate::persistence::LAYOUT_VERSION,
             panes: self
                 .panes
                 .iter()
                 .map(|p| {
-                    let filter_type_str = p.filter_type.as_ref().map(|ft| match ft {
-                        crate::widgets::FilterType::Sender => "sender".to_string(),
-                        crate::widgets::FilterType::Media => "media".to_string(),
-                        crate::widgets::FilterType::Link => "link".to_string(),
-                    });
                     PaneState {
                         chat_id: p.chat_id.clone(),
                         chat_name: p.chat_name.clone(),
                         scroll_offset: p.scroll_offset,
-                        filter_type: filter_type_str,
-                        filter_value: p.filter_value.clone(),
+                        filters: p.filters.clone(),
+                        list_mode: p.list_mode,
+                        send_as: p.send_as.clone(),
                     }
                 })
                 .collect(),
             focused_pane: self.focused_pane_idx,
             pane_tree: Some(self.pane_tree.clone()),
+            chat_scroll: self.chat_scroll.clone(),
+            recent_chats: self.recent_chats.clone(),
+        }
+    }
+
+    /// Replace the current panes with a saved layout and load each pane's chat.
+    /// Panes whose chat is no longer in the chat list are left empty.
+    pub async fn apply_layout(&mut self, layout: LayoutData) {
+        // Panes are allocated up to the largest index in the tree, so a tree pointing past the
+        // saved panes (a broken or hand-edited file) is replaced by the saved panes side by side
+        let saved_panes = layout.panes.len().max(1);
+        let pane_tree = layout.pane_tree.filter(|tree| {
+            let fits = tree.get_pane_indices().iter().all(|&idx| idx < saved_panes);
+            if !fits {
+                crate::warn_log!("apply_layout: pane tree refers to panes that weren't saved, ignoring it");
+            }
+            fits
+        });
+        let pane_tree = pane_tree.unwrap_or_else(|| {
+            let mut tree = PaneNode::new_single(0);
+            for i in 1..layout.panes.len() {
+                tree.split(SplitDirection::Vertical, i);
+            }
+            tree
+        });
+        let max_required_idx = pane_tree.get_pane_indices().into_iter().max().unwrap_or(0);
+        let total_panes_needed = (max_required_idx + 1).max(layout.panes.len());
+
+        self.panes = (0..total_panes_needed).map(|_| ChatPane::new()).collect();
+        self.pane_tree = pane_tree;
+        self.focused_pane_idx = if layout.focused_pane < self.panes.len() {
+            layout.focused_pane
+        } else {
+            0
         };
-        layout.save(&self.config)?;
 
-        self.aliases.save(&self.config)?;
+        for (pane_idx, ps) in layout.panes.into_iter().enumerate() {
+            let filters = ps.filters;
+            let list_mode = ps.list_mode;
+            let Some(chat_id) = ps.chat_id else {
+                continue;
+            };
+            let Some(chat_name) = self.chats.iter().find(|c| c.id == chat_id).map(|c| c.name.clone()) else {
+                crate::warn_log!("apply_layout: chat {} no longer exists, leaving pane {} empty", chat_id, pane_idx);
+                continue;
+            };
+            self.open_chat_in_pane(pane_idx, chat_id, &chat_name).await;
+            if let Some(pane) = self.panes.get_mut(pane_idx) {
+                pane.filters = filters;
+                pane.list_mode = list_mode;
+                pane.send_as = ps.send_as;
+            }
+        }
+        self.needs_redraw = true;
+    }
 
+    /// Read the config, keymap and theme files again and apply them without restarting:
+    /// display toggles, colors, key bindings, intervals and the other settings. Changes the
+    /// WhatsApp client was built with are kept for the next start and reported.
+    pub fn reload_config(&mut self) {
+        let config = match self.config.reload() {
+            Ok(config) => config,
+            Err(e) => {
+                crate::warn_log!("Reloading config failed: {}", e);
+                self.notify(&format!("Config not reloaded: {}", e));
+                return;
+            }
+        };
+        let restart_needed = self.config.restart_required_changes(&config);
+        // A moved whatsapp-cli is picked up straight away, so a failing client can be fixed without a restart
+        let cli_note = (config.whatsapp_cli_path != self.whatsapp.cli_path()).then(|| {
+            self.whatsapp.set_cli_path(config.whatsapp_cli_path.clone());
+            match crate::whatsapp::check_cli(&config.whatsapp_cli_path) {
+                Ok(()) => format!("using {}", config.whatsapp_cli_path.display()),
+                Err(e) => e.to_string(),
+            }
+        });
+
+        let settings = &config.settings;
+        self.show_reactions = settings.show_reactions;
+        self.show_notifications = settings.show_notifications;
+        self.compact_mode = settings.compact_mode;
+        self.show_emojis = settings.show_emojis;
+        self.show_line_numbers = settings.show_line_numbers;
+        self.show_timestamps = settings.show_timestamps;
+        self.show_user_colors = settings.show_user_colors;
+        self.show_borders = settings.show_borders;
+        self.show_chat_list = settings.show_chat_list;
+        self.muted_chats = settings.muted_chats.clone();
+        self.notify_on_mention = settings.notify_on_mention;
+        self.dnd = settings.dnd;
+        self.quiet_hours = settings.quiet_hours;
+        self.archived_chats = settings.archived_chats.clone();
+        self.auto_unarchive = settings.auto_unarchive;
+        self.wrap_style = settings.wrap_style;
+        self.timestamp_style = settings.timestamp_style;
+        self.reaction_style = settings.reaction_style;
+        self.chat_list_ellipsis = settings.chat_list_ellipsis.clone();
+        self.default_country_code = settings.default_country_code.clone();
+        self.chat_list_unread = settings.chat_list_unread;
+        self.reopen_scroll = settings.reopen_scroll;
+        self.enter_on_empty = settings.enter_on_empty;
+        self.chat_refresh_secs = crate::config::clamp_chat_refresh_secs(settings.chat_refresh_secs);
+        self.event_poll_ms = crate::config::clamp_event_poll_ms(settings.event_poll_ms);
+        self.initial_message_limit = crate::config::clamp_message_limit(settings.initial_message_limit);
+        self.show_presence = settings.show_presence;
+        self.show_receipts = settings.show_receipts;
+        self.group_consecutive = settings.group_consecutive;
+        self.show_status_line = settings.show_status_line;
+        self.send_typing = settings.send_typing;
+        self.send_read_receipts = settings.send_read_receipts;
+        self.edit_resend = settings.edit_resend;
+        self.confirm_group_send = settings.confirm_group_send;
+        self.confirm_group_min_members = settings.confirm_group_min_members;
+        self.undo_send_secs = crate::config::clamp_undo_send_secs(settings.undo_send_secs);
+        self.autosave_secs = crate::config::clamp_autosave_secs(settings.autosave_secs);
+        self.recent_reactions = settings.recent_reactions.clone();
+        self.user_colors = pinned_user_colors(settings);
+        self.config = config;
+        self.refresh_all_pane_displays();
+        self.needs_redraw = true;
+
+        let mut notes: Vec<String> = vec!["Config reloaded".to_string()];
+        notes.extend(self.config.settings_note.clone());
+        notes.extend(self.config.keymap_note.clone());
+        notes.extend(self.config.theme_note.clone());
+        notes.extend(cli_note);
+        if !restart_needed.is_empty() {
+            notes.push(format!("restart to apply {}", restart_needed.join(", ")));
+        }
+        self.notify_with_duration(&notes.join(" | "), if notes.len() > 1 { 10 } else { 3 });
+    }
+
+    /// The config with the current display toggles and other runtime settings written back
+    fn config_to_save(&self) -> Config {
         let mut config = self.config.clone();
         config.settings.show_reactions = self.show_reactions;
         config.settings.show_notifications = self.show_notifications;
@@ -2169,8 +4573,2041 @@ impl App {
         config.settings.show_user_colors = self.show_user_colors;
         config.settings.show_borders = self.show_borders;
         config.settings.show_chat_list = self.show_chat_list;
-        config.save()?;
+        config.settings.muted_chats = self.muted_chats.clone();
+        config.settings.dnd = self.dnd;
+        config.settings.quiet_hours = self.quiet_hours;
+        config.settings.archived_chats = self.archived_chats.clone();
+        config.settings.wrap_style = self.wrap_style;
+        config.settings.timestamp_style = self.timestamp_style;
+        config.settings.reaction_style = self.reaction_style;
+        config.settings.chat_refresh_secs = self.chat_refresh_secs;
+        config.settings.event_poll_ms = self.event_poll_ms;
+        config.settings.initial_message_limit = self.initial_message_limit;
+        config.settings.undo_send_secs = self.undo_send_secs;
+        config.settings.autosave_secs = self.autosave_secs;
+        config.settings.show_presence = self.show_presence;
+        config.settings.show_receipts = self.show_receipts;
+        config.settings.group_consecutive = self.group_consecutive;
+        config.settings.show_status_line = self.show_status_line;
+        config.settings.recent_reactions = self.recent_reactions.clone();
+        config.settings.user_colors = self
+            .user_colors
+            .iter()
+            .map(|(sender_id, color)| (sender_id.clone(), color.to_string()))
+            .collect();
+        config
+    }
+
+    pub fn save_state(&self) -> Result<()> {
+        self.current_layout().save(&self.config)?;
+
+        self.aliases.save(&self.config)?;
+
+        self.config_to_save().save()?;
 
         Ok(())
     }
+
+    /// What `save_state` would write, as (layout and aliases, settings), to tell whether
+    /// anything changed since the last save
+    fn state_fingerprint(&self) -> (String, String) {
+        let layout = serde_json::to_string(&(self.current_layout(), &self.aliases)).unwrap_or_default();
+        let settings = serde_json::to_string(&self.config_to_save().settings).unwrap_or_default();
+        (layout, settings)
+    }
+
+    /// Save state if it changed, so a killed process or closed terminal loses little. Layout
+    /// and alias changes are written `AUTOSAVE_DEBOUNCE_SECS` after the first one; settings
+    /// at most every `autosave_secs`. Called on every event poll; returns whether it saved.
+    pub fn autosave(&mut self) -> bool {
+        if self.autosave_secs == 0 {
+            return false;
+        }
+        let state = self.state_fingerprint();
+        if state == self.saved_state {
+            self.layout_changed_at = None;
+            return false;
+        }
+        let layout_due = state.0 != self.saved_state.0
+            && self
+                .layout_changed_at
+                .get_or_insert_with(std::time::Instant::now)
+                .elapsed()
+                >= std::time::Duration::from_secs(AUTOSAVE_DEBOUNCE_SECS);
+        let interval_due = self.last_autosave.elapsed() >= std::time::Duration::from_secs(self.autosave_secs);
+        if !layout_due && !interval_due {
+            return false;
+        }
+
+        // A failed save is tried again after the next interval, not on every poll
+        self.last_autosave = std::time::Instant::now();
+        self.layout_changed_at = None;
+        match self.save_state() {
+            Ok(()) => {
+                crate::debug_log!("autosave: state saved");
+                self.saved_state = state;
+                true
+            }
+            Err(e) => {
+                crate::warn_log!("autosave failed: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Draw `marker` in `style` wherever it appears in the line's unstyled text
+fn style_marker(line: Line<'static>, marker: &str, style: Style) -> Line<'static> {
+    let mut spans = Vec::new();
+    for span in line.spans {
+        if span.style != Style::default() || !span.content.contains(marker) {
+            spans.push(span);
+            continue;
+        }
+        let text = span.content.into_owned();
+        let mut rest = text.as_str();
+        while let Some(at) = rest.find(marker) {
+            spans.push(ratatui::text::Span::raw(rest[..at].to_string()));
+            spans.push(ratatui::text::Span::styled(marker.to_string(), style));
+            rest = &rest[at + marker.len()..];
+        }
+        spans.push(ratatui::text::Span::raw(rest.to_string()));
+    }
+    Line { spans, ..line }
+}
+
+/// `line` with `style` on the spans that have none of their own, so names and marks keep
+/// their colors
+fn style_plain_spans(line: Line<'static>, style: Style) -> Line<'static> {
+    let spans = line
+        .spans
+        .into_iter()
+        .map(|span| if span.style == Style::default() { span.style(style) } else { span })
+        .collect();
+    Line { spans, ..line }
+}
+
+/// Sender colors pinned with /color, skipping any that no longer parse
+fn pinned_user_colors(settings: &crate::config::Settings) -> std::collections::HashMap<String, Color> {
+    settings
+        .user_colors
+        .iter()
+        .filter_map(|(sender_id, color)| match crate::theme::parse_color(color) {
+            Ok(color) => Some((sender_id.clone(), color)),
+            Err(e) => {
+                crate::warn_log!("Ignoring color for {}: {}", sender_id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Byte index of the start of the word before `cursor`: skip whitespace, then the word itself.
+/// Always lands on a char boundary.
+fn prev_word_start(text: &str, cursor: usize) -> usize {
+    let cursor = cursor.min(text.len());
+    let mut chars = text[..cursor].char_indices().rev().peekable();
+    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    let mut start = chars.peek().map_or(0, |(i, _)| *i);
+    while let Some((i, _)) = chars.next_if(|(_, c)| !c.is_whitespace()) {
+        start = i;
+    }
+    if chars.peek().is_none() {
+        start = 0;
+    }
+    start
+}
+
+/// Byte index just past the word after `cursor`: skip whitespace, then the word itself.
+fn next_word_end(text: &str, cursor: usize) -> usize {
+    let cursor = cursor.min(text.len());
+    let mut chars = text[cursor..].char_indices().peekable();
+    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    while chars.next_if(|(_, c)| !c.is_whitespace()).is_some() {}
+    chars.peek().map_or(text.len(), |(i, _)| cursor + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::{Filter, FilterType};
+
+    fn chat(id: &str, name: &str) -> ChatInfo {
+        ChatInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            username: None,
+            unread: 0,
+            is_channel: false,
+            is_group: id.ends_with("@g.us"),
+            last_message_ts: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enter_on_chat_list_opens_chat_and_keeps_draft() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("111@s.whatsapp.net", "Alice")];
+        app.panes[0].input_buffer = "half-written reply".to_string();
+        app.panes[0].input_cursor = app.panes[0].input_buffer.len();
+        app.focus_on_chat_list = true;
+        app.selected_chat_idx = 0;
+
+        app.handle_enter().await.unwrap();
+
+        let pane = &app.panes[0];
+        assert_eq!(pane.chat_id.as_deref(), Some("111@s.whatsapp.net"));
+        assert_eq!(pane.chat_name, "Alice");
+        assert_eq!(pane.input_buffer, "half-written reply");
+        assert_eq!(pane.input_cursor, pane.input_buffer.len());
+        assert!(app.focus_on_chat_list);
+    }
+
+    #[tokio::test]
+    async fn test_new_chat_wizard_validates_the_number_then_opens_and_sends() {
+        let mut app = App::new_for_test();
+        app.default_country_code = "46".to_string();
+        app.focus_on_chat_list = true;
+        app.open_new_chat_wizard();
+        for c in "070-12".chars() {
+            app.new_chat_wizard_char(c);
+        }
+        assert_eq!(app.new_chat_jid(), None);
+        app.new_chat_wizard_enter().await;
+        assert_eq!(app.new_chat_wizard.as_ref().map(|w| w.step), Some(NewChatStep::Number));
+
+        for c in "3 45 678".chars() {
+            app.new_chat_wizard_char(c);
+        }
+        app.new_chat_wizard_backspace();
+        assert_eq!(app.new_chat_jid().as_deref(), Some("46701234567@s.whatsapp.net"));
+        app.new_chat_wizard_enter().await;
+        // Esc steps back to the number before it cancels
+        app.new_chat_wizard_back();
+        assert_eq!(app.new_chat_wizard.as_ref().map(|w| w.step), Some(NewChatStep::Number));
+        app.new_chat_wizard_enter().await;
+        for c in "hi there".chars() {
+            app.new_chat_wizard_char(c);
+        }
+        app.new_chat_wizard_enter().await;
+
+        assert!(app.new_chat_wizard.is_none());
+        assert!(!app.focus_on_chat_list);
+        let pane = &app.panes[0];
+        assert_eq!(pane.chat_id.as_deref(), Some("46701234567@s.whatsapp.net"));
+        assert_eq!(pane.chat_name, "+46 70 123 45 67");
+        assert_eq!(pane.msg_data.last().map(|m| m.text.as_str()), Some("hi there"));
+        assert!(pane.input_buffer.is_empty());
+
+        app.open_new_chat_wizard();
+        app.new_chat_wizard_back();
+        assert!(app.new_chat_wizard.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tab_cycles_command_completions_and_enter_accepts() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        for c in "/re".chars() {
+            app.handle_char(c);
+        }
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "/reply");
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("/reply N [text] - Reply to message #N  |  Tab: /react /receipts /reconnect /remove /reload")
+        );
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "/react");
+        // Six candidates: six more Tabs come back around
+        for _ in 0..6 {
+            app.handle_tab();
+        }
+        assert_eq!(app.panes[0].input_buffer, "/react");
+        assert!(!app.focus_on_chat_list);
+
+        // Enter takes the completion and waits for the arguments
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].input_buffer, "/react ");
+        assert_eq!(app.panes[0].input_cursor, 7);
+        assert_eq!(app.status_message.as_deref(), Some("/react N [emoji] - React to message #N"));
+        app.handle_char('4');
+        app.status_message = None;
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "/react 4");
+        assert_eq!(app.status_message.as_deref(), Some("/react N [emoji] - React to message #N"));
+
+        // A command without required arguments runs on Enter
+        app.panes[0].input_buffer = "/stat".to_string();
+        app.panes[0].input_cursor = 5;
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "/statusline");
+        let shown = app.show_status_line;
+        app.handle_enter().await.unwrap();
+        assert!(app.panes[0].input_buffer.is_empty());
+        assert_ne!(app.show_status_line, shown);
+    }
+
+    #[tokio::test]
+    async fn test_emoji_shortcodes_complete_on_tab_and_expand_on_send() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        for c in "hi :sm".chars() {
+            app.handle_char(c);
+        }
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "hi :sm");
+        assert_eq!(app.status_message.as_deref(), Some("😄 :smile:  😃 :smiley:  😏 :smirk:"));
+        for c in "ile".chars() {
+            app.handle_char(c);
+        }
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "hi 😄");
+        assert_eq!(app.panes[0].input_cursor, app.panes[0].input_buffer.len());
+
+        for c in " :thumbsup: :xyz:".chars() {
+            app.handle_char(c);
+        }
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].msg_data.last().map(|m| m.text.as_str()), Some("hi 😄 👍 :xyz:"));
+        assert_eq!(app.input_history.last().map(String::as_str), Some("hi 😄 :thumbsup: :xyz:"));
+    }
+
+    #[test]
+    fn test_multiline_paste_stays_in_one_buffer() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        app.handle_char('>');
+
+        app.handle_paste("first line\r\nsecond line\nthird line");
+
+        let pane = &app.panes[0];
+        assert_eq!(pane.input_buffer, ">first line\nsecond line\nthird line");
+        assert_eq!(pane.input_buffer.matches('\n').count(), 2);
+        assert_eq!(pane.input_cursor, pane.input_buffer.len());
+        assert!(pane.msg_data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_mode_cursor_selects_and_expands_messages() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        app.panes[0].msg_data = (1..=4)
+            .map(|i| {
+                crate::widgets::MessageData::from_raw(
+                    &(
+                        i.to_string(),
+                        "bob@s.whatsapp.net".to_string(),
+                        "Bob".to_string(),
+                        format!("message {}", i),
+                        None,
+                        None,
+                        std::collections::HashMap::new(),
+                        i,
+                    ),
+                    &app.my_user_jid,
+                )
+            })
+            .collect();
+        app.panes[0].last_message_width.set(40);
+
+        // Outside list mode Up/Down keep browsing input history
+        app.input_history.push("earlier".to_string());
+        app.handle_up();
+        assert_eq!(app.panes[0].input_buffer, "earlier");
+        app.handle_down();
+        assert_eq!(app.panes[0].selected_msg, None);
+
+        CommandHandler::handle(&mut app, "/list", 0).await.unwrap();
+        assert!(app.panes[0].list_mode);
+        app.handle_up();
+        assert_eq!(app.panes[0].selected_msg, Some(3));
+        app.handle_up();
+        app.handle_up();
+        assert_eq!(app.panes[0].selected_msg, Some(1));
+        assert_eq!(app.panes[0].scroll_offset, 0);
+
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].expanded_msg, Some(1));
+        assert!(app.panes[0].msg_data.len() == 4 && app.input_history.len() == 1);
+        app.handle_down();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].expanded_msg, Some(2));
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].expanded_msg, None);
+
+        // Typing takes Up back for history
+        app.handle_char('x');
+        app.handle_up();
+        assert_eq!(app.panes[0].selected_msg, Some(2));
+
+        CommandHandler::handle(&mut app, "/list", 0).await.unwrap();
+        assert!(!app.panes[0].list_mode);
+        assert_eq!(app.panes[0].selected_msg, None);
+    }
+
+    /// Run `update_presence` once the lookup it started has answered
+    async fn settle_presence(app: &mut App) -> bool {
+        let changed = app.update_presence().await;
+        while app.presence_poll.as_ref().is_some_and(|(_, task)| !task.is_finished()) {
+            tokio::task::yield_now().await;
+        }
+        app.update_presence().await || changed
+    }
+
+    #[tokio::test]
+    async fn test_presence_shown_for_individual_chats_only() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        app.whatsapp
+            .set_cached_presence("111@s.whatsapp.net", crate::whatsapp::Presence::Online)
+            .await;
+
+        // The lookup doesn't hold up the tick that starts it
+        assert!(!app.update_presence().await);
+        assert!(app.presence_poll.is_some());
+        assert!(settle_presence(&mut app).await);
+        assert_eq!(app.panes[0].online_status, "online");
+        assert!(app.panes[0].header_text().contains("[online]"));
+        assert!(!settle_presence(&mut app).await);
+
+        // An answer for a chat the pane has left is dropped
+        app.panes[0].chat_id = Some("222@s.whatsapp.net".to_string());
+        app.panes[0].online_status.clear();
+        app.presence_poll = Some(("111@s.whatsapp.net".to_string(), tokio::spawn(async { crate::whatsapp::Presence::Online })));
+        while !app.presence_poll.as_ref().unwrap().1.is_finished() {
+            tokio::task::yield_now().await;
+        }
+        assert!(!app.update_presence().await);
+        assert_eq!(app.panes[0].online_status, "");
+
+        app.panes[0].chat_id = Some("team@g.us".to_string());
+        app.panes[0].online_status = "online".to_string();
+        assert!(settle_presence(&mut app).await);
+        assert_eq!(app.panes[0].online_status, "");
+
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        settle_presence(&mut app).await;
+        app.toggle_presence();
+        assert_eq!(app.panes[0].online_status, "");
+        assert!(!app.update_presence().await);
+        assert_eq!(app.panes[0].online_status, "");
+    }
+
+    /// Run `update_receipts` once the lookup it started has answered
+    async fn settle_receipts(app: &mut App) -> bool {
+        let changed = app.update_receipts().await;
+        while app.receipts_poll.as_ref().is_some_and(|(_, task)| !task.is_finished()) {
+            tokio::task::yield_now().await;
+        }
+        app.update_receipts().await || changed
+    }
+
+    #[tokio::test]
+    async fn test_receipts_update_outgoing_messages_in_focused_chat() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        let raw = |id: &str, sender: &str| {
+            (
+                id.to_string(),
+                sender.to_string(),
+                "x".to_string(),
+                "hi".to_string(),
+                None,
+                None,
+                std::collections::HashMap::new(),
+                1000,
+            )
+        };
+        app.panes[0].msg_data = vec![
+            MessageData::from_raw(&raw("m1", &app.my_user_jid), &app.my_user_jid),
+            MessageData::from_raw(&raw("m2", "111@s.whatsapp.net"), &app.my_user_jid),
+        ];
+        let read = crate::widgets::Receipts { delivered: 1, read: 1, recipients: Some(1) };
+        let receipts = std::collections::HashMap::from([("m1".to_string(), read), ("m2".to_string(), read)]);
+        app.whatsapp.set_cached_receipts("111@s.whatsapp.net", receipts).await;
+
+        assert!(!app.update_receipts().await, "the lookup answers on a later tick");
+        assert!(settle_receipts(&mut app).await);
+        assert_eq!(app.panes[0].msg_data[0].receipts, Some(read));
+        assert_eq!(app.panes[0].msg_data[1].receipts, None, "incoming messages have no receipts");
+        assert!(!settle_receipts(&mut app).await);
+
+        // Messages whatsapp-cli said nothing about show no marks at all
+        app.panes[0].msg_data.push(MessageData::from_raw(&raw("m3", &app.my_user_jid), &app.my_user_jid));
+        assert!(!settle_receipts(&mut app).await);
+        assert_eq!(app.panes[0].msg_data[2].receipts, None);
+
+        CommandHandler::handle(&mut app, "/receipts", 0).await.unwrap();
+        assert!(!app.show_receipts);
+        assert!(!app.update_receipts().await);
+    }
+
+    #[tokio::test]
+    async fn test_reaction_picker_choices_and_selection() {
+        let mut app = App::new_for_test();
+        app.recent_reactions = vec!["🔥".to_string(), "👍".to_string()];
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+
+        app.open_reaction_picker_for_selection();
+        assert!(app.reaction_picker.is_none());
+        assert_eq!(app.status_message.as_deref(), Some("No messages to react to"));
+
+        app.panes[0].msg_data = (1..=3)
+            .map(|i| {
+                crate::widgets::MessageData::from_raw(
+                    &(
+                        i.to_string(),
+                        "bob@s.whatsapp.net".to_string(),
+                        "Bob".to_string(),
+                        "hi".to_string(),
+                        None,
+                        None,
+                        std::collections::HashMap::new(),
+                        i,
+                    ),
+                    &app.my_user_jid,
+                )
+            })
+            .collect();
+
+        // Without a list selection the newest message is the target; recent emoji come first
+        app.open_reaction_picker_for_selection();
+        let picker = app.reaction_picker.clone().unwrap();
+        assert_eq!(picker.msg_idx, 2);
+        assert_eq!(picker.choices[..3], ["🔥", "👍", "❤️"]);
+        assert_eq!(picker.choices.len(), 7);
+
+        app.move_reaction_picker(-1);
+        assert_eq!(app.reaction_picker.as_ref().unwrap().selected_emoji(), "🙏");
+        app.move_reaction_picker(2);
+        assert_eq!(app.reaction_picker.as_ref().unwrap().selected_emoji(), "👍");
+
+        // A number without a choice behind it leaves the picker open
+        app.pick_reaction(Some(8)).await;
+        assert!(app.reaction_picker.is_some());
+
+        // whatsapp-cli is missing here, so the send fails and nothing changes locally
+        app.pick_reaction(None).await;
+        assert!(app.reaction_picker.is_none());
+        assert!(app.status_message.as_deref().unwrap().starts_with("✗ Reaction failed"));
+        assert!(app.panes[0].msg_data[2].reactions.is_empty());
+        assert_eq!(app.recent_reactions, ["🔥", "👍"]);
+
+        app.panes[0].selected_msg = Some(0);
+        app.open_reaction_picker_for_selection();
+        assert_eq!(app.reaction_picker.as_ref().unwrap().msg_idx, 0);
+        app.close_reaction_picker();
+
+        app.open_reaction_picker(0, 9);
+        assert!(app.reaction_picker.is_none());
+    }
+
+    #[test]
+    fn test_chat_list_sorted_by_recent_activity_within_groups() {
+        let mut app = App::new_for_test();
+        let with = |id: &str, unread: u32, ts: i64| {
+            let mut c = chat(id, id);
+            c.unread = unread;
+            c.last_message_ts = ts;
+            c
+        };
+        app.chats = vec![
+            with("old@s.whatsapp.net", 0, 100),
+            with("never@s.whatsapp.net", 0, 0),
+            with("new@s.whatsapp.net", 0, 300),
+            with("unread_old@s.whatsapp.net", 2, 50),
+            with("open@s.whatsapp.net", 0, 10),
+            with("unread_new@s.whatsapp.net", 1, 400),
+            with("never2@s.whatsapp.net", 0, 0),
+        ];
+        app.panes[0].chat_id = Some("open@s.whatsapp.net".to_string());
+
+        let ids: Vec<&str> = app.chat_list_order().iter().map(|&i| app.chats[i].id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "unread_new@s.whatsapp.net",
+                "unread_old@s.whatsapp.net",
+                "open@s.whatsapp.net",
+                "new@s.whatsapp.net",
+                "old@s.whatsapp.net",
+                "never@s.whatsapp.net",
+                "never2@s.whatsapp.net",
+            ]
+        );
+        // Drawing and click mapping use the same order, headers included
+        let rows: Vec<Option<usize>> = app.chat_list_row_map();
+        let listed: Vec<usize> = rows.into_iter().flatten().collect();
+        assert_eq!(listed, app.chat_list_order());
+    }
+
+    #[test]
+    fn test_recent_reactions_are_most_recent_first_and_capped() {
+        let mut app = App::new_for_test();
+        for emoji in ["a", "b", "c", "d", "e", "f", "g", "h", "i"] {
+            app.remember_reaction(emoji);
+        }
+        app.remember_reaction("c");
+        assert_eq!(app.recent_reactions, ["c", "i", "h", "g", "f", "e", "d", "b"]);
+    }
+
+    #[tokio::test]
+    async fn test_focusing_unread_chat_queues_read_receipt() {
+        let mut app = App::new_for_test();
+        let mut unread = chat("111@s.whatsapp.net", "Bob");
+        unread.unread = 2;
+        app.chats = vec![unread];
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        let rows = [
+            ("1", "bob@s.whatsapp.net", 1),
+            ("2", "bob@s.whatsapp.net", 2),
+            ("3", "me@s.whatsapp.net", 3),
+        ];
+        app.panes[0].msg_data = rows
+            .iter()
+            .map(|&(id, sender, ts)| {
+                crate::widgets::MessageData::from_raw(
+                    &(
+                        id.to_string(),
+                        sender.to_string(),
+                        "Bob".to_string(),
+                        "hi".to_string(),
+                        None,
+                        None,
+                        std::collections::HashMap::new(),
+                        ts,
+                    ),
+                    &app.my_user_jid,
+                )
+            })
+            .collect();
+
+        // The receipt covers the newest message from the contact, not our own reply
+        app.cycle_focus();
+        assert_eq!(app.chats[0].unread, 0);
+        assert_eq!(
+            app.pending_read_receipts.get("111@s.whatsapp.net").map(String::as_str),
+            Some("2")
+        );
+        app.send_read_receipts().await;
+        assert!(app.pending_read_receipts.is_empty());
+
+        // Already read: nothing to send
+        app.cycle_focus();
+        app.cycle_focus();
+        assert!(app.pending_read_receipts.is_empty());
+
+        // Turned off: only the local count is cleared
+        app.send_read_receipts = false;
+        app.chats[0].unread = 1;
+        app.cycle_focus();
+        app.cycle_focus();
+        assert_eq!(app.chats[0].unread, 0);
+        assert!(app.pending_read_receipts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_typing_notices_are_debounced_and_stopped() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+
+        // Off by default
+        app.handle_char('h');
+        app.update_typing();
+        assert!(app.typing_sent_to.is_none());
+
+        app.send_typing = true;
+        app.update_typing();
+        let (chat, first_at) = app.typing_sent_to.clone().unwrap();
+        assert_eq!(chat, "111@s.whatsapp.net");
+
+        // Further keystrokes within the resend window don't send again
+        app.handle_char('i');
+        app.update_typing();
+        assert_eq!(app.typing_sent_to.as_ref().unwrap().1, first_at);
+
+        // Emptying the input (as sending does) stops typing
+        app.handle_backspace();
+        app.handle_backspace();
+        app.update_typing();
+        assert!(app.typing_sent_to.is_none());
+
+        // Commands and idle input don't count as typing
+        app.handle_char('/');
+        app.update_typing();
+        assert!(app.typing_sent_to.is_none());
+        app.panes[0].input_buffer = "draft".to_string();
+        app.last_keystroke = Some(std::time::Instant::now() - std::time::Duration::from_secs(TYPING_IDLE_SECS));
+        app.update_typing();
+        assert!(app.typing_sent_to.is_none());
+
+        // Deleting text is typing too, not going idle
+        app.panes[0].input_cursor = 0;
+        app.handle_delete();
+        app.update_typing();
+        assert_eq!(app.typing_sent_to.as_ref().map(|(chat, _)| chat.as_str()), Some("111@s.whatsapp.net"));
+    }
+
+    #[test]
+    fn test_chat_list_hover_shows_truncated_names_once() {
+        let mut app = App::new_for_test();
+        app.chats = vec![
+            chat("1@s.whatsapp.net", "Bo"),
+            chat("team@g.us", "A rather long group chat name"),
+        ];
+        // Bordered list, 20 columns wide: rows are "Other", Bo, the group
+        app.chat_list_area = Some(Rect::new(0, 0, 20, 10));
+
+        assert!(!app.handle_chat_list_hover(3, 2));
+        assert_eq!(app.hovered_chat.as_deref(), Some("1@s.whatsapp.net"));
+        assert!(app.status_message.is_none());
+
+        assert!(app.handle_chat_list_hover(3, 3));
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("A rather long group chat name (team@g.us)")
+        );
+        // Moving within the same row changes nothing
+        app.status_message = None;
+        assert!(!app.handle_chat_list_hover(8, 3));
+        assert!(app.status_message.is_none());
+
+        // Headers and the area outside the list clear the hover
+        assert!(!app.handle_chat_list_hover(3, 1));
+        assert!(app.hovered_chat.is_none());
+        assert!(!app.handle_chat_list_hover(30, 3));
+        assert!(app.hovered_chat.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_typing_update_shows_and_expires() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("team@g.us".to_string());
+        app.whatsapp
+            .push_update(crate::whatsapp::WhatsAppUpdate::UserTyping {
+                chat_jid: "team@g.us".to_string(),
+                user_name: "Alice".to_string(),
+            })
+            .await;
+
+        app.process_whatsapp_events().await.unwrap();
+        assert_eq!(app.panes[0].typing_indicator.as_deref(), Some("Alice is typing..."));
+        assert!(!app.expire_typing_indicators());
+
+        // No further events: the indicator clears on its own
+        app.panes[0].typing_expire = Some(std::time::Instant::now());
+        assert!(app.expire_typing_indicators());
+        assert!(app.panes[0].typing_indicator.is_none());
+    }
+
+    #[test]
+    fn test_toggle_chat_list_focus_returns_to_last_pane() {
+        let mut app = App::new_for_test();
+        app.split_vertical();
+        app.split_vertical();
+        let panes = app.pane_tree.get_pane_indices();
+        assert_eq!(panes.len(), 3);
+
+        // Leave the middle pane for the chat list and come straight back
+        app.focused_pane_idx = panes[1];
+        app.focus_on_chat_list = false;
+        app.toggle_chat_list_focus();
+        assert!(app.focus_on_chat_list);
+        app.toggle_chat_list_focus();
+        assert!(!app.focus_on_chat_list);
+        assert_eq!(app.focused_pane_idx, panes[1]);
+
+        // Tab from the last pane onto the list is remembered too
+        app.focused_pane_idx = panes[2];
+        app.cycle_focus();
+        assert!(app.focus_on_chat_list);
+        app.toggle_chat_list_focus();
+        assert_eq!(app.focused_pane_idx, panes[2]);
+
+        // Choosing a target pane on the list makes it the return target
+        app.toggle_chat_list_focus();
+        app.select_target_pane(1);
+        app.toggle_chat_list_focus();
+        assert_eq!(app.focused_pane_idx, panes[0]);
+
+        // A closed pane falls back to one that still exists
+        app.focused_pane_idx = panes[2];
+        app.toggle_chat_list_focus();
+        app.focused_pane_idx = panes[2];
+        app.focus_on_chat_list = false;
+        app.close_pane();
+        app.focus_on_chat_list = true;
+        app.toggle_chat_list_focus();
+        assert!(app.pane_tree.get_pane_indices().contains(&app.focused_pane_idx));
+    }
+
+    #[test]
+    fn test_clipboard_paste_inserts_at_cursor_without_sending() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.panes[0].input_buffer = "ab".to_string();
+        app.panes[0].input_cursor = 1;
+
+        app.handle_clipboard_paste(Ok("x\ny".to_string()));
+        assert_eq!(app.panes[0].input_buffer, "ax\nyb");
+        assert_eq!(app.panes[0].input_cursor, 4);
+        assert!(app.panes[0].msg_data.is_empty());
+
+        app.handle_clipboard_paste(Ok(String::new()));
+        assert_eq!(app.status_message.as_deref(), Some("Clipboard is empty"));
+        app.handle_clipboard_paste(Err(anyhow::anyhow!("no clipboard tool found")));
+        assert_eq!(app.status_message.as_deref(), Some("Clipboard unavailable: no clipboard tool found"));
+        assert_eq!(app.panes[0].input_buffer, "ax\nyb");
+    }
+
+    #[tokio::test]
+    async fn test_layout_tree_pointing_past_the_saved_panes_is_ignored() {
+        let mut app = App::new_for_test();
+        let mut layout = app.current_layout();
+        let mut tree = PaneNode::new_single(0);
+        tree.split(SplitDirection::Vertical, usize::MAX);
+        layout.pane_tree = Some(tree);
+
+        app.apply_layout(layout).await;
+        assert_eq!(app.panes.len(), 1);
+        assert_eq!(app.pane_tree.get_pane_indices(), vec![0]);
+    }
+
+    #[test]
+    fn test_number_key_selects_target_pane_in_layout_order() {
+        let mut app = App::new_for_test();
+        app.split_vertical();
+        app.split_horizontal();
+        app.focus_on_chat_list = true;
+
+        app.select_target_pane(1);
+        assert_eq!(app.focused_pane_idx, 0);
+        app.select_target_pane(3);
+        assert_eq!(app.focused_pane_idx, 2);
+
+        // Out of range keeps the current target
+        app.select_target_pane(7);
+        assert_eq!(app.focused_pane_idx, 2);
+        assert!(app.focus_on_chat_list);
+    }
+
+    #[test]
+    fn test_word_movement_skips_runs_of_spaces() {
+        let text = "hello   big  world";
+        assert_eq!(prev_word_start(text, text.len()), 13);
+        assert_eq!(prev_word_start(text, 13), 8);
+        assert_eq!(prev_word_start(text, 10), 8);
+        assert_eq!(prev_word_start(text, 8), 0);
+        assert_eq!(prev_word_start(text, 0), 0);
+
+        assert_eq!(next_word_end(text, 0), 5);
+        assert_eq!(next_word_end(text, 5), 11);
+        assert_eq!(next_word_end(text, 11), text.len());
+        assert_eq!(next_word_end(text, text.len()), text.len());
+    }
+
+    #[test]
+    fn test_word_movement_stays_on_char_boundaries() {
+        let text = "åäö  日本語 🎉x";
+        let mut cursor = text.len();
+        let mut stops = Vec::new();
+        while cursor > 0 {
+            cursor = prev_word_start(text, cursor);
+            assert!(text.is_char_boundary(cursor));
+            stops.push(cursor);
+        }
+        assert_eq!(stops, vec![text.find('🎉').unwrap(), text.find('日').unwrap(), 0]);
+
+        let mut cursor = 0;
+        while cursor < text.len() {
+            cursor = next_word_end(text, cursor);
+            assert!(text.is_char_boundary(cursor));
+        }
+        assert_eq!(next_word_end(text, 0), "åäö".len());
+    }
+
+    #[test]
+    fn test_delete_word_back() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.handle_paste("grüße  an  dich");
+
+        app.handle_delete_word_back();
+        assert_eq!(app.panes[0].input_buffer, "grüße  an  ");
+        app.handle_delete_word_back();
+        assert_eq!(app.panes[0].input_buffer, "grüße  ");
+        app.handle_word_left();
+        assert_eq!(app.panes[0].input_cursor, 0);
+        app.handle_word_right();
+        assert_eq!(app.panes[0].input_cursor, "grüße".len());
+        app.handle_delete_word_back();
+        assert_eq!(app.panes[0].input_buffer, "  ");
+        assert_eq!(app.panes[0].input_cursor, 0);
+
+        // Nothing typed: nothing happens
+        app.panes[0].input_buffer.clear();
+        app.last_keystroke = None;
+        app.handle_delete_word_back();
+        assert!(app.last_keystroke.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_filter_and_identity_are_cleared_when_pane_switches_chat() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("111@s.whatsapp.net", "Alice"), chat("222@s.whatsapp.net", "Bob")];
+        app.open_chat_in_pane(0, "111@s.whatsapp.net".to_string(), "Alice").await;
+        app.panes[0].set_filter(Filter::new(FilterType::Sender, "alice"));
+        app.panes[0].send_as = Some("+46700000001".to_string());
+
+        // Reopening the same chat keeps its filter and /from identity
+        app.open_chat_in_pane(0, "111@s.whatsapp.net".to_string(), "Alice").await;
+        assert_eq!(app.panes[0].filters, vec![Filter::new(FilterType::Sender, "alice")]);
+        assert_eq!(app.panes[0].send_as.as_deref(), Some("+46700000001"));
+
+        app.open_chat_in_pane(0, "222@s.whatsapp.net".to_string(), "Bob").await;
+        assert_eq!(app.panes[0].chat_name, "Bob");
+        assert!(app.panes[0].filters.is_empty());
+        assert!(app.panes[0].send_as.is_none());
+        // The filter toggle doesn't bring the old chat's filter back either
+        app.focus_on_chat_list = false;
+        app.toggle_filter();
+        assert!(app.panes[0].filters.is_empty());
+    }
+
+    #[test]
+    fn test_filter_toggle_restores_last_filter() {
+        let mut app = App::new_for_test();
+        app.toggle_filter();
+        assert!(app.panes[0].filters.is_empty());
+
+        let photos = Filter::new(FilterType::Media, "photo");
+        let from_bob = Filter::new(FilterType::Sender, "bob");
+        app.panes[0].set_filter(photos.clone());
+        app.panes[0].add_filter(from_bob.clone());
+        app.toggle_filter();
+        assert!(app.panes[0].filters.is_empty());
+        assert_eq!(app.status_message.as_deref(), Some("Filter: OFF"));
+
+        // The whole combination comes back
+        app.toggle_filter();
+        assert_eq!(app.panes[0].filters, vec![photos.clone(), from_bob.clone()]);
+
+        // Clearing the active filters keeps them for the toggle
+        app.panes[0].clear_filter();
+        app.toggle_filter();
+        assert_eq!(app.panes[0].filters, vec![photos, from_bob]);
+    }
+
+    #[tokio::test]
+    async fn test_muted_chat_refreshes_without_notifying() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("111@s.whatsapp.net", "Noisy group")];
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        CommandHandler::handle(&mut app, "/mute", 0).await.unwrap();
+        app.panes[0].chat_id = None;
+        app.status_message = None;
+
+        let incoming = crate::whatsapp::WhatsAppUpdate::NewMessage {
+            chat_jid: "111@s.whatsapp.net".to_string(),
+            sender_name: "Bob".to_string(),
+            text: "ping".to_string(),
+            is_outgoing: false,
+        };
+        app.whatsapp.push_update(incoming.clone()).await;
+        assert!(app.process_whatsapp_events().await.unwrap());
+        assert_eq!(app.chats[0].unread, 1);
+        assert!(app.status_message.is_none());
+
+        app.muted_chats.clear();
+        app.whatsapp.push_update(incoming).await;
+        assert!(app.process_whatsapp_events().await.unwrap());
+        assert_eq!(app.status_message.as_deref(), Some("Noisy group: ping"));
+    }
+
+    #[tokio::test]
+    async fn test_sending_to_a_read_only_channel_is_blocked() {
+        let mut app = App::new_for_test();
+        let mut news = chat("120363@newsletter", "News");
+        app.chats = vec![news.clone(), chat("team@g.us", "Team")];
+        assert!(!app.is_read_only_chat("team@g.us"));
+        assert!(!app.is_read_only_chat("120363@newsletter"), "the chat list has the last word");
+        news.is_channel = true;
+        app.chats[0] = news;
+        assert!(app.is_read_only_chat("120363@newsletter"));
+        assert!(app.is_read_only_chat("status@broadcast"));
+        assert!(!app.is_read_only_chat("friends@broadcast"));
+        assert!(app.chat_list_name(&app.chats[0]).ends_with(" 📢"));
+
+        app.focus_on_chat_list = false;
+        app.panes[0].chat_id = Some("120363@newsletter".to_string());
+        app.panes[0].input_buffer = "hello?".to_string();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("This is a read-only channel"));
+        assert_eq!(app.panes[0].input_buffer, "hello?");
+        assert!(app.panes[0].msg_data.is_empty());
+        // Commands still work there
+        app.panes[0].input_buffer = "/mute".to_string();
+        app.handle_enter().await.unwrap();
+        assert!(app.muted_chats.contains("120363@newsletter"));
+    }
+
+    #[tokio::test]
+    async fn test_mention_notifies_from_a_muted_group() {
+        let mut app = App::new_for_test();
+        app.my_user_jid = "46700000000@s.whatsapp.net".to_string();
+        app.chats = vec![chat("team@g.us", "Team")];
+        app.muted_chats.insert("team@g.us".to_string());
+        let incoming = |text: &str| crate::whatsapp::WhatsAppUpdate::NewMessage {
+            chat_jid: "team@g.us".to_string(),
+            sender_name: "Bob".to_string(),
+            text: text.to_string(),
+            is_outgoing: false,
+        };
+
+        app.whatsapp.push_update(incoming("lunch?")).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert!(app.status_message.is_none());
+        app.whatsapp.push_update(incoming("@46700000000 lunch?")).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Team: @46700000000 lunch?"));
+
+        app.status_message = None;
+        app.notify_on_mention = false;
+        app.whatsapp.push_update(incoming("@46700000000 lunch?")).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert!(app.status_message.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_compose_in_editor_keeps_newlines_and_handles_no_save() {
+        use crate::utils::run_editor;
+        // `sh -c script sh <file>`: the script sees the draft file as $1
+        let editor = |script: &str| ["sh", "-c", script, "sh"].map(str::to_string).to_vec();
+        let mut app = App::new_for_test();
+        app.panes[0].input_buffer = "draft".to_string();
+        app.panes[0].input_cursor = 2;
+
+        app.request_external_editor();
+        assert!(app.editor_requested);
+        app.apply_composed(run_editor(&editor(r#"printf 'draft\nsecond line\n' > "$1""#), "draft").await);
+        assert_eq!(app.panes[0].input_buffer, "draft\nsecond line");
+        assert_eq!(app.panes[0].input_cursor, app.panes[0].input_buffer.len());
+
+        // Quitting without saving, deleting the file or a failing editor keep the input
+        assert_eq!(run_editor(&editor("true"), "draft").await.unwrap(), EditorOutcome::Unchanged);
+        let deleted = run_editor(&editor(r#"rm "$1""#), "draft").await;
+        assert_eq!(deleted.as_ref().unwrap(), &EditorOutcome::Deleted);
+        app.apply_composed(deleted);
+        assert_eq!(app.status_message.as_deref(), Some("The draft file was deleted; input left as it was"));
+        app.apply_composed(run_editor(&editor("exit 3"), "draft").await);
+        assert!(app.status_message.as_deref().unwrap().starts_with("Editor failed: sh exited with"));
+        assert_eq!(app.panes[0].input_buffer, "draft\nsecond line");
+        // The draft file is private to us
+        let mode = run_editor(&editor(r#"ls -l "$1" | cut -c1-10 > "$1""#), "draft").await.unwrap();
+        assert_eq!(mode, EditorOutcome::Saved("-rw-------".to_string()));
+
+        // $EDITOR is split like a shell would, quotes and all
+        assert_eq!(
+            crate::utils::split_shell_words(r#"'/opt/My Editor/bin/ed' --wait "a \"b\"" c\ d"#),
+            vec!["/opt/My Editor/bin/ed", "--wait", "a \"b\"", "c d"]
+        );
+        assert!(crate::utils::split_shell_words("  ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_finish_startup_runs_once_and_explains_an_empty_chat_list() {
+        let mut app = App::new_for_test();
+        app.startup_pending = true;
+        assert!(!app.finish_startup().await);
+        assert!(app.startup_pending);
+        while !app.finish_startup().await {
+            tokio::task::yield_now().await;
+        }
+        assert!(!app.startup_pending);
+        assert!(app.status_message.as_deref().unwrap().starts_with("No chats yet"));
+    }
+
+    #[tokio::test]
+    async fn test_dnd_holds_back_notifications_but_still_counts_messages() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("111@s.whatsapp.net", "Bob")];
+        CommandHandler::handle(&mut app, "/dnd on", 0).await.unwrap();
+        assert!(app.dnd);
+        assert!(app.status_line().to_string().ends_with("│ DND"));
+        app.status_message = None;
+
+        let incoming = crate::whatsapp::WhatsAppUpdate::NewMessage {
+            chat_jid: "111@s.whatsapp.net".to_string(),
+            sender_name: "Bob".to_string(),
+            text: "ping".to_string(),
+            is_outgoing: false,
+        };
+        app.whatsapp.push_update(incoming.clone()).await;
+        assert!(app.process_whatsapp_events().await.unwrap());
+        assert_eq!(app.chats[0].unread, 1);
+        assert!(app.status_message.is_none());
+
+        CommandHandler::handle(&mut app, "/dnd 22:00-07:00", 0).await.unwrap();
+        assert_eq!(app.quiet_hours.map(|q| q.to_string()).as_deref(), Some("22:00-07:00"));
+        assert_eq!(app.config_to_save().settings.quiet_hours, app.quiet_hours);
+        CommandHandler::handle(&mut app, "/dnd hours off", 0).await.unwrap();
+        CommandHandler::handle(&mut app, "/dnd off", 0).await.unwrap();
+        assert!(!app.is_quiet_now());
+        app.whatsapp.push_update(incoming).await;
+        assert!(app.process_whatsapp_events().await.unwrap());
+        assert_eq!(app.status_message.as_deref(), Some("Bob: ping"));
+    }
+
+    #[test]
+    fn test_sender_keeps_one_color_as_author_and_reply_target() {
+        let (team, bob) = ("team@g.us", "bob@s.whatsapp.net");
+        let mut app = App::new_for_test();
+        app.chats = vec![chat(team, "Team")];
+        app.aliases.map.insert(bob.to_string(), "Bobby".to_string());
+        app.panes[0].chat_id = Some(team.to_string());
+        let raw = |id: &str, sender: &str, name: &str, text: &str| {
+            (id.to_string(), sender.to_string(), name.to_string(), text.to_string(), None, None, std::collections::HashMap::new(), 0)
+        };
+        let mut reply = MessageData::from_raw(&raw("m2", "alice@s.whatsapp.net", "Alice", "hi back"), &app.my_user_jid);
+        reply.reply_to_msg_id = Some("m1".to_string());
+        app.panes[0].msg_data = vec![MessageData::from_raw(&raw("m1", bob, "Bob", "hello"), &app.my_user_jid), reply];
+
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let cells = buffer.content();
+        let name_colors: Vec<Color> = (0..20usize)
+            .flat_map(|y| {
+                let row: Vec<&str> = cells[y * 80..(y + 1) * 80].iter().map(|c| c.symbol()).collect();
+                (0..75usize)
+                    .filter(move |&x| row[x..x + 6].concat() == "Bobby:")
+                    .map(move |x| cells[y * 80 + x].fg)
+            })
+            .collect();
+        // Once as the author, once in Alice's reply, both under the alias and in Bob's color
+        assert_eq!(name_colors, vec![app.sender_color(bob); 2]);
+    }
+
+    #[test]
+    fn test_mentions_complete_in_groups_and_mentions_of_you_stand_out() {
+        let team = "team@g.us";
+        let mut app = App::new_for_test();
+        app.my_user_jid = "46700000000@s.whatsapp.net".to_string();
+        app.focus_on_chat_list = false;
+        app.chats = vec![chat(team, "Team")];
+        app.panes[0].chat_id = Some(team.to_string());
+        let raw = |id: &str, sender: &str, name: &str, text: &str| {
+            (id.to_string(), sender.to_string(), name.to_string(), text.to_string(), None, None, std::collections::HashMap::new(), 0)
+        };
+        app.panes[0].msg_data = vec![
+            MessageData::from_raw(&raw("m1", "46711111111:3@s.whatsapp.net", "Anna Berg", "hi @46700000000 !"), &app.my_user_jid),
+            MessageData::from_raw(&raw("m2", "46700000000@s.whatsapp.net", "You", "hello"), &app.my_user_jid),
+            MessageData::from_raw(&raw("m3", "99@lid", "Bert", "yo"), &app.my_user_jid),
+        ];
+        app.group_members.insert(
+            team.to_string(),
+            vec![("46722222222@s.whatsapp.net".to_string(), "Björn Anders".to_string())],
+        );
+
+        app.panes[0].input_buffer = "thanks @an".to_string();
+        app.handle_tab();
+        assert_eq!(app.status_message.as_deref(), Some("Mention: Björn Anders, Anna Berg"));
+        app.panes[0].input_buffer = "thanks @berg".to_string();
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "thanks @46711111111 ");
+        assert_eq!(app.status_message.as_deref(), Some("Mentioning Anna Berg"));
+        // By number too; ourselves and @lid senders can't be picked
+        app.panes[0].input_buffer = "@4672".to_string();
+        app.handle_tab();
+        assert_eq!(app.panes[0].input_buffer, "@46722222222 ");
+        app.panes[0].input_buffer = "@bert".to_string();
+        app.handle_tab();
+        assert_eq!(app.status_message.as_deref(), Some("No one in this group matches @bert"));
+
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let cells = terminal.backend().buffer().content();
+        let marker: Vec<&ratatui::buffer::Cell> = (0..cells.len() - 12)
+            .filter(|&i| cells[i..i + 12].iter().map(|c| c.symbol()).collect::<String>() == "@46700000000")
+            .map(|i| &cells[i])
+            .collect();
+        assert_eq!(marker.len(), 1);
+        assert_eq!(marker[0].fg, app.config.theme.mention);
+    }
+
+    #[test]
+    fn test_group_message_mentioning_you_is_italic_with_the_mention_in_bold() {
+        let team = "team@g.us";
+        let anna = "46711111111@s.whatsapp.net";
+        let mut app = App::new_for_test();
+        app.my_user_jid = "46700000000@s.whatsapp.net".to_string();
+        app.chats = vec![chat(team, "Team")];
+        app.panes[0].chat_id = Some(team.to_string());
+        let raw = (
+            "m1".to_string(), anna.to_string(), "Anna Berg".to_string(), "hi @46700000000 !".to_string(),
+            None, None, std::collections::HashMap::new(), 0,
+        );
+        app.panes[0].msg_data = vec![MessageData::from_raw(&raw, &app.my_user_jid)];
+
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let cells = terminal.backend().buffer().content();
+        let row = (0..cells.len() / 80)
+            .find(|&y| cells[y * 80..(y + 1) * 80].iter().map(|c| c.symbol()).collect::<String>().contains("@46700000000"))
+            .unwrap();
+        let line = &cells[row * 80..(row + 1) * 80];
+        let at = |word: &str| (0..80 - word.len()).find(|&x| line[x..x + word.len()].iter().map(|c| c.symbol()).collect::<String>() == word);
+        let mention = &line[at("@46700000000").unwrap()];
+        assert_eq!((mention.fg, mention.modifier.contains(Modifier::BOLD)), (app.config.theme.mention, true));
+        // The rest of the message stands out too, the sender's name keeps its color
+        let hi = &line[at("hi ").unwrap()];
+        assert_eq!((hi.fg, hi.modifier.contains(Modifier::ITALIC)), (app.config.theme.mention, true));
+        assert_eq!(line[at("Anna Berg").unwrap()].fg, app.sender_color(anna));
+    }
+
+    #[test]
+    fn test_activity_log_keeps_notifications_the_status_bar_replaced() {
+        let mut app = App::new_for_test();
+        app.show_activity();
+        assert!(app.help_scroll.is_none(), "nothing to show yet");
+        app.activity.clear();
+
+        for i in 0..MAX_ACTIVITY + 5 {
+            app.notify(&format!("note {}", i));
+        }
+        app.notify_with_duration("Sent to Team, a long notification that needs more than one line in the log", 10);
+        assert_eq!(app.activity.len(), MAX_ACTIVITY);
+        assert_eq!(app.activity[0].1, "note 6", "the oldest fall off");
+        assert_eq!(app.status_message.as_deref().map(|m| m.starts_with("Sent to Team")), Some(true));
+
+        app.show_activity();
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(60, 20)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Recent activity"));
+        assert!(screen.contains("Sent to Team") && screen.contains("note 104"));
+        assert!(app.help_max_scroll > 0);
+        app.close_help();
+        assert!(!app.showing_activity);
+    }
+
+    #[test]
+    fn test_help_overlay_lists_keys_and_commands_and_scrolls() {
+        let mut app = App::new_for_test();
+        let text: Vec<String> = app.help_lines().iter().map(|l| l.to_string()).collect();
+        assert!(text.iter().any(|l| l.contains("ctrl+g") && l.contains("Toggle message numbers")));
+        for cmd in COMMANDS {
+            assert!(text.iter().any(|l| l.contains(cmd.usage)), "{} missing from help", cmd.usage);
+        }
+
+        app.show_help();
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 20)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        assert!(app.help_max_scroll > 0);
+        let screen: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(screen.contains("Help"));
+
+        app.scroll_help(i32::from(u16::MAX));
+        assert_eq!(app.help_scroll, Some(app.help_max_scroll));
+        app.scroll_help(-i32::from(u16::MAX));
+        assert_eq!(app.help_scroll, Some(0));
+        app.close_help();
+        assert!(app.help_scroll.is_none());
+    }
+
+    #[test]
+    fn test_status_line_shows_connection_unread_and_filter() {
+        let mut app = App::new_for_test();
+        let mut alice = chat("a@s.whatsapp.net", "Alice");
+        alice.unread = 2;
+        let mut bob = chat("b@s.whatsapp.net", "Bob");
+        bob.unread = 3;
+        app.chats = vec![alice, bob];
+        let bottom_row = |app: &mut App| {
+            let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 10)).unwrap();
+            terminal.draw(|f| app.draw(f)).unwrap();
+            let buffer = terminal.backend().buffer();
+            (0..80).map(|x| buffer[(x, 9)].symbol().to_string()).collect::<String>()
+        };
+
+        assert_eq!(app.status_line().to_string(), "● sync stopped │ 5 unread │ no chat");
+        app.whatsapp.set_sync_alive(true);
+        app.connection = ConnectionState::Connected;
+        app.panes[0].chat_id = Some("b@s.whatsapp.net".to_string());
+        app.panes[0].chat_name = "Bob".to_string();
+        app.panes[0].set_filter(Filter::new(FilterType::Sender, "bob"));
+        assert!(bottom_row(&mut app).starts_with("● connected │ 5 unread │ Bob │ filter: sender=bob"));
+
+        // A notification takes the row until it expires, and the line can be turned off
+        app.notify("Hello");
+        assert!(bottom_row(&mut app).starts_with("Hello"));
+        app.status_message = None;
+        app.show_status_line = false;
+        assert!(!bottom_row(&mut app).contains("unread"));
+    }
+
+    #[tokio::test]
+    async fn test_login_overlay_shows_output_and_closes_when_done() {
+        let mut app = App::new_for_test();
+        let screen = |app: &mut App| {
+            let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+            terminal.draw(|f| app.draw(f)).unwrap();
+            terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect::<String>()
+        };
+
+        let output = AuthProgress { lines: vec!["█▀▀▀▀▀█ ▄▄ █▀▀▀▀▀█".to_string()], ..Default::default() };
+        app.auth = Some(AuthSession::for_test(output));
+        assert!(app.check_auth().await);
+        assert!(!app.check_auth().await, "nothing new to draw");
+        let shown = screen(&mut app);
+        assert!(shown.contains("Log in") && shown.contains("█▀▀▀▀▀█ ▄▄ █▀▀▀▀▀█") && shown.contains("Esc cancels"));
+
+        app.auth = Some(AuthSession::for_test(AuthProgress { finished: Some(Err("timed out".to_string())), ..Default::default() }));
+        assert!(app.check_auth().await);
+        let shown = screen(&mut app);
+        assert!(shown.contains("Login failed: timed out") && shown.contains("Enter tries again"));
+        // A code given as text is drawn by us instead of the CLI's drawing, or shown as text
+        // when the window is too small
+        let code = "2@AbC+/=,KeY1=,KeY2=,SeCrEt=";
+        let progress = AuthProgress { lines: vec!["█▀▀▀▀▀█ ▄▄ █▀▀▀▀▀█".to_string()], qr: Some(code.to_string()), finished: None };
+        app.auth = Some(AuthSession::for_test(progress));
+        let shown = screen(&mut app);
+        assert!(!shown.contains("█▀▀▀▀▀█ ▄▄ █▀▀▀▀▀█"));
+        assert!(shown.contains(&crate::qr::render(code).unwrap()[1]));
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(80, 12)).unwrap();
+        terminal.draw(|f| app.draw(f)).unwrap();
+        let small: String = terminal.backend().buffer().content().iter().map(|c| c.symbol()).collect();
+        assert!(small.contains("Too small for the QR code") && small.contains(code));
+
+        app.auth = Some(AuthSession::for_test(AuthProgress { finished: Some(Err("timed out".to_string())), ..Default::default() }));
+        // Retrying needs a whatsapp-cli that runs; the test client has none
+        app.retry_auth();
+        assert!(app.status_message.as_deref().unwrap().starts_with("Couldn't start whatsapp-cli auth"));
+
+        // Success closes the overlay and checks the login again
+        app.auth = Some(AuthSession::for_test(AuthProgress { finished: Some(Ok(())), ..Default::default() }));
+        assert!(app.check_auth().await);
+        assert!(app.auth.is_none());
+        assert!(app.status_message.as_deref().unwrap().starts_with("Still not logged in"));
+        assert!(!app.check_auth().await);
+    }
+
+    #[tokio::test]
+    async fn test_group_sync_progress_shows_in_pane_header() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("team@g.us".to_string());
+        app.panes[0].chat_name = "Team".to_string();
+        assert!(!app.update_group_sync().await);
+
+        app.whatsapp.set_group_sync("team@g.us", 0, false).await;
+        assert!(app.update_group_sync().await);
+        assert_eq!(app.panes[0].header_text(), "Team | Syncing group… 0s");
+
+        app.whatsapp.set_group_sync("team@g.us", 12, false).await;
+        assert!(app.update_group_sync().await);
+        assert_eq!(app.panes[0].header_text(), "Team | Syncing group: found 12 messages…");
+        assert!(!app.update_group_sync().await, "unchanged progress needs no redraw");
+
+        // Finished syncs clear the header and are only reported once
+        app.whatsapp.set_group_sync("team@g.us", 12, true).await;
+        assert!(app.update_group_sync().await);
+        assert_eq!(app.panes[0].header_text(), "Team");
+        assert!(app.whatsapp.group_sync_progress().await.is_empty());
+
+        // A sync that found nothing says the history isn't there
+        app.whatsapp.set_group_sync("team@g.us", 0, false).await;
+        app.update_group_sync().await;
+        app.whatsapp.set_group_sync("team@g.us", 0, true).await;
+        assert!(app.update_group_sync().await);
+        assert!(app.status_message.as_deref().unwrap().starts_with("No history for Team"));
+    }
+
+    #[tokio::test]
+    async fn test_next_unread_wraps_and_keeps_the_highlight_on_the_opened_chat() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("a@s.whatsapp.net", "Alice"), chat("b@s.whatsapp.net", "Bob"), chat("c@s.whatsapp.net", "Carol")];
+        app.chats[1].unread = 2;
+        app.chats[2].unread = 1;
+        // Unread: Bob, Carol; Other: Alice. Bob is selected, so Carol is next.
+        app.open_next_unread().await;
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("c@s.whatsapp.net"));
+        assert_eq!(app.chats[2].unread, 0);
+        let order = app.chat_list_order();
+        assert_eq!(app.chats[order[app.selected_chat_idx]].name, "Carol");
+
+        // Past the end it wraps back round to Bob
+        app.open_next_unread().await;
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("b@s.whatsapp.net"));
+        let order = app.chat_list_order();
+        assert_eq!(app.chats[order[app.selected_chat_idx]].name, "Bob");
+
+        app.status_message = None;
+        app.open_next_unread().await;
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("b@s.whatsapp.net"));
+        assert_eq!(app.status_message.as_deref(), Some("No unread chats"));
+    }
+
+    #[tokio::test]
+    async fn test_enter_on_empty_input_follows_the_setting() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("a@s.whatsapp.net", "Alice"), chat("b@s.whatsapp.net", "Bob"), chat("c@s.whatsapp.net", "Carol")];
+        for (chat, ts) in app.chats.iter_mut().zip([300, 200, 100]) {
+            chat.last_message_ts = ts;
+        }
+        app.panes[0].chat_id = Some("a@s.whatsapp.net".to_string());
+        app.focus_on_chat_list = false;
+
+        // The default leaves everything as it was
+        app.handle_enter().await.unwrap();
+        assert!(!app.focus_on_chat_list);
+
+        app.enter_on_empty = EnterOnEmpty::List;
+        app.handle_enter().await.unwrap();
+        assert!(app.focus_on_chat_list);
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("a@s.whatsapp.net"));
+
+        // Alice is already in the pane, so Bob is the most recent other chat
+        app.focus_on_chat_list = false;
+        app.enter_on_empty = EnterOnEmpty::Recent;
+        app.handle_enter().await.unwrap();
+        assert!(app.focus_on_chat_list);
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("b@s.whatsapp.net"));
+        let order = app.chat_list_order();
+        assert_eq!(app.chats[order[app.selected_chat_idx]].name, "Bob");
+    }
+
+    #[tokio::test]
+    async fn test_recent_chats_switcher_cycles_and_opens_after_the_key_is_let_go() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("a@s.whatsapp.net", "Alice"), chat("b@s.whatsapp.net", "Bob"), chat("c@s.whatsapp.net", "Carol")];
+        app.cycle_recent_chats();
+        assert!(app.chat_switcher.is_none());
+        for (id, name) in [("a@s.whatsapp.net", "Alice"), ("b@s.whatsapp.net", "Bob"), ("c@s.whatsapp.net", "Carol")] {
+            app.open_chat_in_pane(0, id.to_string(), name).await;
+        }
+        app.open_chat_in_pane(0, "b@s.whatsapp.net".to_string(), "Bob").await;
+        assert_eq!(app.recent_chats, vec!["b@s.whatsapp.net", "c@s.whatsapp.net", "a@s.whatsapp.net"]);
+        assert_eq!(app.current_layout().recent_chats, app.recent_chats);
+
+        // The first press highlights the chat before this one, the next goes further back
+        app.cycle_recent_chats();
+        let names = |app: &App| {
+            let switcher = app.chat_switcher.as_ref().unwrap();
+            (switcher.chats.iter().map(|(_, n)| n.as_str()).collect::<Vec<_>>().join(","), switcher.selected)
+        };
+        assert_eq!(names(&app), ("Bob,Carol,Alice".to_string(), 1));
+        app.cycle_recent_chats();
+        assert_eq!(names(&app).1, 2);
+        assert!(!app.chat_switch_due());
+
+        // Letting go of the key opens Alice
+        app.chat_switcher.as_mut().unwrap().last_press =
+            Some(std::time::Instant::now() - std::time::Duration::from_millis(SWITCHER_HOLD_MS));
+        assert!(app.chat_switch_due());
+        app.finish_chat_switch().await;
+        assert!(app.chat_switcher.is_none());
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("a@s.whatsapp.net"));
+        assert_eq!(app.recent_chats[0], "a@s.whatsapp.net");
+
+        // After the arrows move it, only a key opens it; Esc leaves the pane alone
+        app.cycle_recent_chats();
+        app.move_chat_switcher(1);
+        assert!(!app.chat_switch_due());
+        app.close_chat_switcher();
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("a@s.whatsapp.net"));
+    }
+
+    #[tokio::test]
+    async fn test_archived_chats_hidden_until_revealed() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("a@s.whatsapp.net", "Alice"), chat("b@s.whatsapp.net", "Bob")];
+        app.panes[0].chat_id = Some("b@s.whatsapp.net".to_string());
+        CommandHandler::handle(&mut app, "/archive", 0).await.unwrap();
+        assert!(app.archived_chats.contains("b@s.whatsapp.net"));
+
+        // Still open in a pane, so it stays under Active
+        assert_eq!(app.chat_list_row_map(), vec![None, Some(1), None, Some(0)]);
+
+        app.panes[0].chat_id = None;
+        assert_eq!(app.chat_list_row_map(), vec![None, Some(0)]);
+        assert_eq!(app.chat_list_order(), vec![0]);
+
+        app.toggle_archived();
+        let sections = app.chat_list_groups();
+        assert_eq!(sections.last().unwrap().0, "Archived");
+        assert_eq!(app.chat_list_row_map(), vec![None, Some(0), None, Some(1)]);
+
+        app.panes[0].chat_id = Some("b@s.whatsapp.net".to_string());
+        CommandHandler::handle(&mut app, "/unarchive", 0).await.unwrap();
+        assert!(app.archived_chats.is_empty());
+        CommandHandler::handle(&mut app, "/unarchive", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Bob is not archived"));
+    }
+
+    #[tokio::test]
+    async fn test_new_message_auto_unarchives() {
+        let mut app = App::new_for_test();
+        app.chats = vec![chat("a@s.whatsapp.net", "Alice")];
+        app.archived_chats.insert("a@s.whatsapp.net".to_string());
+        let incoming = crate::whatsapp::WhatsAppUpdate::NewMessage {
+            chat_jid: "a@s.whatsapp.net".to_string(),
+            sender_name: "Alice".to_string(),
+            text: "hi".to_string(),
+            is_outgoing: false,
+        };
+
+        app.auto_unarchive = false;
+        app.whatsapp.push_update(incoming.clone()).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert!(app.archived_chats.contains("a@s.whatsapp.net"));
+
+        app.auto_unarchive = true;
+        app.whatsapp.push_update(incoming).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert!(app.archived_chats.is_empty());
+        assert_eq!(app.chat_list_order(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_new_message_appends_without_moving_scroll() {
+        let group = "team@g.us";
+        let mut app = App::new_for_test();
+        app.whatsapp = WhatsAppClient::new_for_test_with_messages(
+            "append",
+            &[
+                ("m1", group, "a@s.whatsapp.net", "one", "2024-05-01T10:00:00Z", false),
+                ("m2", group, "b@s.whatsapp.net", "two", "2024-05-01T10:01:00Z", false),
+            ],
+        );
+        app.panes[0].chat_id = Some(group.to_string());
+        app.panes[0].msg_data = vec![crate::widgets::MessageData::from_raw(
+            &(
+                "m1".to_string(),
+                "a@s.whatsapp.net".to_string(),
+                "A".to_string(),
+                "one".to_string(),
+                None,
+                None,
+                std::collections::HashMap::new(),
+                0,
+            ),
+            &app.my_user_jid,
+        )];
+        app.panes[0].scroll_offset = 3;
+
+        app.whatsapp
+            .push_update(crate::whatsapp::WhatsAppUpdate::NewMessage {
+                chat_jid: group.to_string(),
+                sender_name: "B".to_string(),
+                text: "two".to_string(),
+                is_outgoing: false,
+            })
+            .await;
+        assert!(app.process_whatsapp_events().await.unwrap());
+
+        let ids: Vec<&str> = app.panes[0].msg_data.iter().map(|m| m.msg_id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m2"]);
+        assert_eq!(app.panes[0].scroll_offset, 3);
+    }
+
+    #[tokio::test]
+    async fn test_chat_scroll_is_kept_per_chat_across_switches() {
+        let (team, other) = ("team@g.us", "other@g.us");
+        let mut app = App::new_for_test();
+        let rows: Vec<(String, String)> = (1..=6)
+            .map(|i| (format!("m{}", i), format!("2024-05-01T10:0{}:00Z", i)))
+            .collect();
+        let mut messages: Vec<(&str, &str, &str, &str, &str, bool)> = rows
+            .iter()
+            .map(|(id, ts)| (id.as_str(), team, "a@s.whatsapp.net", "hello", ts.as_str(), false))
+            .collect();
+        messages.push(("x1", other, "a@s.whatsapp.net", "elsewhere", "2024-05-01T10:00:00Z", false));
+        app.whatsapp = WhatsAppClient::new_for_test_with_messages("chat_scroll", &messages);
+        app.chats = vec![chat(team, "Team"), chat(other, "Other")];
+        app.panes[0].last_message_width.set(40);
+
+        app.open_chat_in_pane(0, team.to_string(), "Team").await;
+        app.panes[0].scroll_offset = 3;
+        app.open_chat_in_pane(0, other.to_string(), "Other").await;
+        assert_eq!(app.panes[0].scroll_offset, 0);
+        assert_eq!(app.current_layout().chat_scroll.get(team), Some(&3));
+
+        app.open_chat_in_pane(0, team.to_string(), "Team").await;
+        assert_eq!(app.panes[0].scroll_offset, 3);
+        // Reopening the open chat keeps nothing stale around
+        assert!(app.chat_scroll.is_empty());
+
+        // With "unread", new messages put the divider above the first unread one
+        app.reopen_scroll = ReopenScroll::Unread;
+        app.open_chat_in_pane(0, other.to_string(), "Other").await;
+        app.chats[0].unread = 2;
+        app.open_chat_in_pane(0, team.to_string(), "Team").await;
+        let divider = app.panes[0].scroll_offset;
+        assert!(app.scroll_message_to_top(0, 4));
+        assert_eq!(divider, app.panes[0].scroll_offset - 1);
+    }
+
+    #[tokio::test]
+    async fn test_scrolling_to_the_top_loads_older_messages_until_the_beginning() {
+        let team = "team@g.us";
+        let mut app = App::new_for_test();
+        let rows: Vec<(String, String)> = (1..=25)
+            .map(|i| (format!("m{}", i), format!("2024-05-01T10:{:02}:00Z", i)))
+            .collect();
+        let messages: Vec<(&str, &str, &str, &str, &str, bool)> = rows
+            .iter()
+            .map(|(id, ts)| (id.as_str(), team, "a@s.whatsapp.net", "hello", ts.as_str(), false))
+            .collect();
+        app.whatsapp = WhatsAppClient::new_for_test_with_messages("older_history", &messages);
+        app.chats = vec![chat(team, "Team")];
+        app.initial_message_limit = 10;
+        app.panes[0].last_message_width.set(40);
+        app.open_chat_in_pane(0, team.to_string(), "Team").await;
+        let loaded = app.panes[0].msg_data.len();
+        assert!(loaded < 25);
+        let first = app.panes[0].msg_data[0].msg_id.clone();
+        app.panes[0].selected_msg = Some(0);
+
+        app.focus_on_chat_list = false;
+        app.handle_scroll_top();
+        assert_eq!(app.older_history_requested.take(), Some(0));
+        let added = app.load_older_messages(0).await.unwrap();
+        assert_eq!(added, 25 - loaded);
+        let pane = &app.panes[0];
+        assert_eq!(pane.msg_data[0].msg_id, "m1");
+        assert_eq!(pane.msg_data[added].msg_id, first);
+        assert_eq!(pane.selected_msg, Some(added), "the cursor stays on its message");
+        assert!(pane.scroll_offset > 1, "the message that was on top stays there");
+
+        // Nothing older: stop asking and mark where the chat begins
+        app.handle_scroll_top();
+        assert_eq!(app.load_older_messages(0).await.unwrap(), 0);
+        assert!(app.panes[0].history_exhausted);
+        app.older_history_requested = None;
+        app.handle_scroll_top();
+        assert_eq!(app.older_history_requested, None);
+        let (lines, _) = format_messages_for_display(
+            &app.panes[0], 40, app.display_options(), &std::collections::HashMap::new(),
+        );
+        assert_eq!(lines[0].trim_start(), crate::formatting::BEGINNING_OF_CHAT);
+    }
+
+    #[tokio::test]
+    async fn test_sent_message_echo_is_replaced_when_it_syncs_back() {
+        let group = "team@g.us";
+        let mut app = App::new_for_test();
+        app.whatsapp = WhatsAppClient::new_for_test_with_messages(
+            "echo",
+            &[("m1", group, "me@s.whatsapp.net", "hello", "2024-05-01T10:00:05Z", true)],
+        );
+        app.panes[0].chat_id = Some(group.to_string());
+        // What handle_enter shows before the send has synced: no msg_id yet
+        app.panes[0].msg_data = vec![crate::widgets::MessageData::from_raw(
+            &(
+                String::new(),
+                app.my_user_jid.clone(),
+                "You".to_string(),
+                "hello".to_string(),
+                None,
+                None,
+                std::collections::HashMap::new(),
+                1_714_557_600,
+            ),
+            &app.my_user_jid,
+        )];
+
+        // The sync loop reloads the chat once the sent message is in the store
+        app.refresh_pane_messages(0).await.unwrap();
+
+        assert_eq!(app.panes[0].msg_data.len(), 1);
+        assert_eq!(app.panes[0].msg_data[0].msg_id, "m1");
+    }
+
+    #[test]
+    fn test_synced_echo_takes_server_timestamp_and_order() {
+        let mut app = App::new_for_test();
+        let message = |id: &str, text: &str, is_outgoing: bool, timestamp: i64| {
+            crate::widgets::MessageData::from_raw(
+                &(
+                    id.to_string(),
+                    if is_outgoing { app.my_user_jid.clone() } else { "bob@s.whatsapp.net".to_string() },
+                    "x".to_string(),
+                    text.to_string(),
+                    None,
+                    None,
+                    std::collections::HashMap::new(),
+                    timestamp,
+                ),
+                &app.my_user_jid,
+            )
+        };
+        // Our clock put the echo before Bob's message; the server saw ours arrive later
+        let echo = message("", "hello", true, 1000);
+        let bobs = message("m2", "hi there", false, 1010);
+        let synced = message("m1", "hello", true, 1030);
+        let pane = &mut app.panes[0];
+        pane.msg_data = vec![echo, bobs];
+        pane.list_mode = true;
+        pane.selected_msg = Some(1);
+
+        assert_eq!(pane.merge_new_messages(vec![synced]), 0);
+        let order: Vec<(&str, i64)> = pane.msg_data.iter().map(|m| (m.msg_id.as_str(), m.timestamp)).collect();
+        assert_eq!(order, vec![("m2", 1010), ("m1", 1030)]);
+        assert_eq!(pane.selected_msg, Some(0), "the selection follows Bob's message");
+    }
+
+    #[test]
+    fn test_scroll_top_bottom_and_page_keys_stay_in_range() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        // As rendered: 10 lines above the bottom page
+        app.panes[0].max_scroll.set(10);
+
+        app.handle_scroll_top();
+        assert_eq!(app.panes[0].scroll_offset, 1);
+        app.handle_page_up();
+        assert_eq!(app.panes[0].scroll_offset, 1);
+
+        app.handle_scroll_bottom();
+        assert_eq!(app.panes[0].scroll_offset, 0);
+        // Scrolling up from the bottom starts from the bottom page, not the top
+        app.handle_page_up();
+        assert_eq!(app.panes[0].scroll_offset, 8);
+
+        app.handle_page_down();
+        assert_eq!(app.panes[0].scroll_offset, 0);
+        app.handle_page_down();
+        assert_eq!(app.panes[0].scroll_offset, 0);
+    }
+
+    #[test]
+    fn test_mouse_wheel_scrolls_pane_under_cursor() {
+        let mut app = App::new_for_test();
+        app.panes.push(ChatPane::new());
+        app.pane_areas.insert(0, Rect::new(0, 0, 40, 20));
+        app.pane_areas.insert(1, Rect::new(40, 0, 40, 20));
+        app.panes[0].max_scroll.set(10);
+        app.panes[1].max_scroll.set(10);
+
+        app.handle_mouse_scroll(50, 5, true);
+        assert_eq!(app.panes[1].scroll_offset, 8);
+        assert_eq!(app.panes[0].scroll_offset, 0);
+        // The wheel doesn't move focus
+        assert_eq!(app.focused_pane_idx, 0);
+
+        app.handle_mouse_scroll(50, 5, false);
+        assert_eq!(app.panes[1].scroll_offset, 0);
+        // Outside every pane nothing scrolls
+        app.handle_mouse_scroll(90, 5, true);
+        assert_eq!(app.panes[0].scroll_offset, 0);
+        assert_eq!(app.panes[1].scroll_offset, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_offline_messages_are_queued_until_sent() {
+        use crate::whatsapp::WhatsAppUpdate;
+
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.panes[0].chat_id = Some("123@s.whatsapp.net".to_string());
+        app.whatsapp
+            .push_update(WhatsAppUpdate::ConnectionChanged(ConnectionState::Disconnected))
+            .await;
+        app.process_whatsapp_events().await.unwrap();
+        assert_eq!(app.connection, ConnectionState::Disconnected);
+
+        app.panes[0].input_buffer = "hello".to_string();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.outbox.messages.len(), 1);
+        assert_eq!(app.outbox.messages[0].text, "hello");
+        assert!(app.panes[0].msg_data[0].pending);
+        assert!(!app.flush_outbox(), "nothing is tried while offline");
+
+        // A reload keeps the queued echo, and reopening the chat shows it exactly once
+        app.panes[0].reload_messages(Vec::new());
+        assert!(app.panes[0].msg_data[0].pending);
+        app.panes[0].msg_data.clear();
+        app.show_queued_messages(0);
+        app.show_queued_messages(0);
+        assert_eq!(app.panes[0].msg_data.len(), 1);
+
+        // Back online the send queue gets it, once; the test CLI fails, so the message
+        // stays queued after the retries
+        app.whatsapp
+            .push_update(WhatsAppUpdate::ConnectionChanged(ConnectionState::Connected))
+            .await;
+        app.process_whatsapp_events().await.unwrap();
+        assert!(app.flush_outbox());
+        assert!(!app.panes[0].msg_data[0].pending);
+        assert_eq!(app.sends_in_flight.len(), 1);
+        assert!(!app.flush_outbox(), "a message the queue has isn't handed over again");
+        assert_eq!(app.outbox.messages.len(), 1);
+
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert!(app.sends_in_flight.is_empty());
+        assert_eq!(app.outbox.messages.len(), 1);
+        assert!(app.panes[0].msg_data[0].pending);
+        assert!(app.last_outbox_attempt.is_some());
+        assert!(!app.flush_outbox(), "the retry waits OUTBOX_RETRY_SECS");
+    }
+
+    #[tokio::test]
+    async fn test_group_members_are_fetched_in_the_background_for_mentions() {
+        let group = "team@g.us";
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some(group.to_string());
+        app.update_group_members().await;
+        assert!(app.members_poll.is_none(), "nothing needs the members yet");
+
+        app.panes[0].input_buffer = "hi @an".to_string();
+        app.update_group_members().await;
+        assert!(app.members_poll.is_some());
+        while app.members_poll.as_ref().is_some_and(|(_, task)| !task.is_finished()) {
+            tokio::task::yield_now().await;
+        }
+        // whatsapp-cli is missing here: the failure isn't kept as an empty group, and the
+        // lookup waits MEMBERS_RETRY_SECS before trying again
+        app.update_group_members().await;
+        assert!(!app.group_members.contains_key(group));
+        assert!(app.members_failed.contains_key(group));
+        assert!(app.members_poll.is_none());
+        let retry = std::time::Duration::from_secs(MEMBERS_RETRY_SECS);
+        app.members_failed.insert(group.to_string(), std::time::Instant::now() - retry);
+        app.update_group_members().await;
+        assert!(app.members_poll.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_group_send_waits_for_confirmation() {
+        let group = "team@g.us";
+        let rows = [
+            ("m1", group, "a@s.whatsapp.net", "hi", "2024-01-01T10:00:00Z", false),
+            ("m2", group, "b@s.whatsapp.net", "hey", "2024-01-01T10:01:00Z", false),
+        ];
+        let mut app = App::new_for_test();
+        app.whatsapp = crate::whatsapp::WhatsAppClient::new_for_test_with_messages("confirm", &rows);
+        app.focus_on_chat_list = false;
+        app.confirm_group_send = true;
+        let mut team = chat(group, "Team");
+        team.is_group = true;
+        app.chats = vec![team, chat("123@s.whatsapp.net", "Ann")];
+        app.panes[0].chat_id = Some(group.to_string());
+        app.panes[0].chat_name = "Team".to_string();
+
+        app.panes[0].input_buffer = "hello".to_string();
+        app.handle_enter().await.unwrap();
+        assert!(app.panes[0].msg_data.is_empty());
+        assert_eq!(app.panes[0].input_buffer, "hello");
+        assert_eq!(app.status_message.as_deref(), Some("Send to Team? Enter/Esc"));
+
+        assert!(app.cancel_group_send());
+        assert!(!app.cancel_group_send());
+        app.handle_enter().await.unwrap();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].msg_data.len(), 1);
+        assert!(app.panes[0].input_buffer.is_empty());
+
+        // Members aren't known (whatsapp-cli is missing here), so the threshold asks anyway
+        app.confirm_group_min_members = 5;
+        app.update_group_members().await;
+        while app.members_poll.as_ref().is_some_and(|(_, task)| !task.is_finished()) {
+            tokio::task::yield_now().await;
+        }
+        app.update_group_members().await;
+        assert!(app.members_poll.is_none());
+        assert!(!app.group_members.contains_key(group));
+        assert!(app.members_failed.contains_key(group));
+        app.panes[0].input_buffer = "again".to_string();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].msg_data.len(), 1);
+        assert!(app.cancel_group_send());
+
+        // Three members is below the threshold, so no question is asked
+        let members = ["a", "b", "c"].map(|n| (format!("{}@s.whatsapp.net", n), n.to_string())).to_vec();
+        app.group_members.insert(group.to_string(), members);
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].msg_data.len(), 2);
+
+        // Individual chats always send straight away
+        app.confirm_group_min_members = 0;
+        app.panes[0].chat_id = Some("123@s.whatsapp.net".to_string());
+        app.panes[0].input_buffer = "direct".to_string();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].msg_data.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unsend_takes_back_held_message() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.undo_send_secs = 1;
+        app.panes[0].chat_id = Some("123@s.whatsapp.net".to_string());
+
+        app.panes[0].input_buffer = "oops".to_string();
+        app.handle_enter().await.unwrap();
+        assert!(app.panes[0].msg_data[0].sending);
+        app.unsend_last();
+        assert!(app.panes[0].msg_data.is_empty());
+        assert_eq!(app.panes[0].input_buffer, "oops");
+        assert_eq!(app.status_message.as_deref(), Some("Message unsent"));
+        app.unsend_last();
+        assert_eq!(app.status_message.as_deref(), Some("Nothing to unsend"));
+
+        // Left alone, the message goes out when the window ends and loses its marker
+        app.handle_enter().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(1300)).await;
+        assert!(app.finish_held_sends());
+        assert!(!app.panes[0].msg_data[0].sending);
+        app.unsend_last();
+        assert_eq!(app.status_message.as_deref(), Some("Nothing to unsend"));
+
+        // Off by default, which /unsend points out
+        app.undo_send_secs = 0;
+        app.unsend_last();
+        assert_eq!(
+            app.status_message.as_deref(),
+            Some("Nothing to unsend: undo-send is off, turn it on with /set undo_send_secs 5")
+        );
+    }
+
+    #[test]
+    fn test_reload_config_applies_settings_in_place() {
+        let mut app = App::new_for_test();
+        let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        app.config.config_dir = dir.clone();
+        app.panes[0].chat_id = Some("123@s.whatsapp.net".to_string());
+        std::fs::write(
+            dir.join("whatsapp_config.json"),
+            r#"{
+                "whatsapp_cli_path": "/elsewhere/whatsapp-cli",
+                "store_path": "elsewhere",
+                "settings": { "compact_mode": true, "chat_refresh_secs": 0, "show_presence": false }
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("whatsapp_theme.json"), r#"{ "status": "magenta" }"#).unwrap();
+        std::fs::write(dir.join("whatsapp_keymap.json"), r#"{ "quit": "ctrl+x" }"#).unwrap();
+
+        app.reload_config();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert!(app.compact_mode);
+        assert!(!app.show_presence);
+        assert_eq!(app.chat_refresh_secs, 1, "intervals are clamped like at startup");
+        assert_eq!(app.config.theme.status, Color::Magenta);
+        let ctrl_x = crate::keymap::KeySpec::parse("ctrl+x").unwrap();
+        assert_eq!(
+            app.config.keymap.resolve(&mut Vec::new(), ctrl_x),
+            crate::keymap::KeyResolution::Action(crate::keymap::KeyAction::Quit)
+        );
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("123@s.whatsapp.net"), "panes are kept");
+        let status = app.status_message.clone().unwrap();
+        assert!(status.contains("restart to apply store_path"), "{}", status);
+        // A new whatsapp-cli path is used right away, with a hint when it can't run
+        assert_eq!(app.whatsapp.cli_path(), std::path::PathBuf::from("/elsewhere/whatsapp-cli"));
+        assert!(status.contains("whatsapp-cli not found at /elsewhere/whatsapp-cli"), "{}", status);
+
+        // A missing or broken file leaves everything as it was
+        app.reload_config();
+        assert!(app.compact_mode);
+        assert!(app.status_message.unwrap().starts_with("Config not reloaded"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_send_is_retried_then_marked() {
+        let mut app = App::new_for_test();
+        app.focus_on_chat_list = false;
+        app.undo_send_secs = 0;
+        app.panes[0].chat_id = Some("123@s.whatsapp.net".to_string());
+
+        app.panes[0].input_buffer = "hello".to_string();
+        app.handle_enter().await.unwrap();
+        assert_eq!(app.panes[0].msg_data[0].send_status, crate::widgets::SendStatus::Pending);
+
+        // The test CLI doesn't exist: every attempt fails, with backoff in between. The
+        // clock is paused, so the backoff passes without waiting for it
+        tokio::time::sleep(std::time::Duration::from_millis(1800)).await;
+        app.process_whatsapp_events().await.unwrap();
+        assert_eq!(app.panes[0].msg_data[0].send_status, crate::widgets::SendStatus::Failed);
+        assert!(app.status_message.as_deref().unwrap().starts_with("✗ Message to 123@s.whatsapp.net not sent"));
+        // Kept in the outbox to be tried again
+        assert_eq!(app.outbox.messages.len(), 1);
+        assert_eq!(app.outbox.messages[0].text, "hello");
+        assert!(app.panes[0].msg_data[0].pending);
+        assert!(app.sends_in_flight.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_quitting_keeps_unsent_messages_in_the_outbox() {
+        let mut app = App::new_for_test();
+        let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_drain_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        app.config.config_dir = dir.clone();
+        app.focus_on_chat_list = false;
+        app.undo_send_secs = 30;
+        app.panes[0].chat_id = Some("123@s.whatsapp.net".to_string());
+
+        // Held in the undo window when quitting: queued at once, and given up on in time
+        app.panes[0].input_buffer = "bye".to_string();
+        app.handle_enter().await.unwrap();
+        app.drain_sends().await;
+        assert!(app.held_sends.is_empty());
+        assert!(app.sends_in_flight.is_empty());
+        assert_eq!(app.outbox.messages.len(), 1);
+        assert_eq!(app.outbox.messages[0].text, "bye");
+        let saved = std::fs::read_to_string(app.config.outbox_path()).unwrap();
+        assert!(saved.contains("\"bye\""), "{}", saved);
+
+        // A send still going when the wait runs out is kept too, once
+        app.sends_in_flight.push(app.outbox.messages[0].clone());
+        let stuck = QueuedMessage { text: "stuck".to_string(), ..app.outbox.messages[0].clone() };
+        app.sends_in_flight.push(stuck);
+        app.drain_sends().await;
+        let texts: Vec<&str> = app.outbox.messages.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, vec!["bye", "stuck"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_autosave_writes_changes_debounced() {
+        let mut app = App::new_for_test();
+        let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_autosave_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        app.config.config_dir = dir.clone();
+        app.saved_state = app.state_fingerprint();
+        let ago = |secs| std::time::Instant::now().checked_sub(std::time::Duration::from_secs(secs)).unwrap();
+
+        assert!(!app.autosave(), "nothing changed");
+
+        // A layout or alias change waits out the debounce
+        app.aliases.insert("111@s.whatsapp.net".to_string(), "Al".to_string());
+        assert!(!app.autosave());
+        app.layout_changed_at = Some(ago(AUTOSAVE_DEBOUNCE_SECS));
+        assert!(app.autosave());
+        let aliases = std::fs::read_to_string(app.config.aliases_path()).unwrap();
+        assert!(aliases.contains("\"Al\""), "{}", aliases);
+        assert!(!app.autosave());
+
+        // Other settings wait for the interval
+        app.compact_mode = true;
+        assert!(!app.autosave());
+        app.last_autosave = ago(app.autosave_secs);
+        assert!(app.autosave());
+        let saved = std::fs::read_to_string(dir.join("whatsapp_config.json")).unwrap();
+        assert!(saved.contains("\"compact_mode\": true"));
+
+        app.autosave_secs = 0;
+        app.compact_mode = false;
+        app.last_autosave = ago(60);
+        assert!(!app.autosave());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,12 +1,19 @@
 use anyhow::Result;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
-use crate::app::App;
-use crate::widgets::FilterType;
+use crate::app::{App, ChatInfo};
+use crate::config::QuietHours;
+use crate::formatting::{filters_label, find_message_on_date, first_url, format_timestamp, format_transcript, parse_time_bound, truncate_chars};
+use crate::persistence::NamedLayouts;
+use crate::whatsapp::{media_type_for_path, MessageInfo, QuotedMessage, ReceiptStatus, SearchHit, SharedContact};
+use crate::widgets::{Filter, FilterType, MessageData, READ_MARKER};
 
 pub struct Command {
     pub name: String,
     pub args: Vec<String>,
-    pub _full_text: String,
+    pub full_text: String,
 }
 
 impl Command {
@@ -26,11 +33,414 @@ impl Command {
         Some(Command {
             name,
             args,
-            _full_text: text.to_string(),
+            full_text: text.to_string(),
         })
     }
 }
 
+/// Split `/send-media` arguments into the file path and an optional caption. A path
+/// containing spaces can be wrapped in double quotes.
+fn split_path_and_caption(args: &str) -> Option<(String, Option<String>)> {
+    let args = args.trim();
+    let (path, rest) = match args.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"')?,
+        None => args.split_once(char::is_whitespace).unwrap_or((args, "")),
+    };
+    if path.is_empty() {
+        return None;
+    }
+    let caption = rest.trim();
+    Some((path.to_string(), (!caption.is_empty()).then(|| caption.to_string())))
+}
+
+/// Expand a leading `~` and resolve relative paths against the working directory
+fn expand_user_path(arg: &str) -> PathBuf {
+    let path = match arg.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => match dirs::home_dir() {
+            Some(home) => home.join(rest.trim_start_matches('/')),
+            None => PathBuf::from(arg),
+        },
+        _ => PathBuf::from(arg),
+    };
+    if path.is_relative() {
+        std::env::current_dir().map(|dir| dir.join(&path)).unwrap_or(path)
+    } else {
+        path
+    }
+}
+
+/// What /copy puts on the clipboard: the message text as sent, without the media label or
+/// sender shown around it, or with `link` only the first URL in it
+fn copy_text(msg: &MessageData, link: bool) -> Option<&str> {
+    let text = msg.text.trim();
+    if link {
+        first_url(text)
+    } else {
+        Some(text).filter(|t| !t.is_empty())
+    }
+}
+
+/// OpenStreetMap page with a marker at the coordinates, opened by /locate
+fn map_url(lat: f64, lon: f64) -> String {
+    format!("https://www.openstreetmap.org/?mlat={lat}&mlon={lon}#map=16/{lat}/{lon}")
+}
+
+/// Most results /searchall asks the CLI for
+const SEARCH_ALL_LIMIT: usize = 200;
+
+/// How long after sending WhatsApp lets a message be edited
+const EDIT_WINDOW_SECS: i64 = 15 * 60;
+
+/// How long after sending WhatsApp lets a message be deleted for everyone (2 days 12 hours)
+const DELETE_WINDOW_SECS: i64 = 60 * 60 * 60;
+
+/// What a message deleted with /delete shows until the chat is reloaded
+const DELETED_TEXT: &str = "🚫 You deleted this message";
+
+/// Most messages /export asks the store for
+const EXPORT_LIMIT: usize = 10_000;
+
+/// Most numbered names `create_numbered` tries before giving up
+const MAX_NUMBERED_FILES: usize = 100;
+
+/// `name` reduced to characters safe in a file name
+fn safe_file_name(name: &str) -> String {
+    let safe_name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    safe_name.trim_matches('_').to_string()
+}
+
+/// `~/whatsapp_export_<chatname>_<date>.<ext>`, with the chat name reduced to characters safe in a file name
+fn default_export_path(chat_name: &str, extension: &str) -> PathBuf {
+    let file_name = format!(
+        "whatsapp_export_{}_{}.{}",
+        safe_file_name(chat_name),
+        chrono::Local::now().format("%Y-%m-%d"),
+        extension
+    );
+    dirs::home_dir().unwrap_or_default().join(file_name)
+}
+
+/// Where /save-contact writes without a path: `~/<name>.vcf`, or for several contacts
+/// `~/<first name>_and_<n>_more.vcf`
+fn default_vcf_path(contacts: &[SharedContact]) -> PathBuf {
+    let name = match contacts {
+        [] => "contact".to_string(),
+        [only] => only.name.clone(),
+        [first, rest @ ..] => format!("{} and {} more", first.name, rest.len()),
+    };
+    let safe_name = safe_file_name(&name);
+    let safe_name = if safe_name.is_empty() { "contact".to_string() } else { safe_name };
+    dirs::home_dir().unwrap_or_default().join(format!("{}.vcf", safe_name))
+}
+
+/// Create `path` for writing without replacing an existing file: when the name is taken,
+/// the next free one of `<stem>_2.<ext>`, `<stem>_3.<ext>`, ... is used instead
+fn create_numbered(path: &Path) -> std::io::Result<(PathBuf, std::fs::File)> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut candidate = path.to_path_buf();
+    let mut n = 1;
+    loop {
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && n < MAX_NUMBERED_FILES => {
+                n += 1;
+                candidate = path.with_file_name(format!("{}_{}{}", stem, n, extension));
+            }
+            result => return result.map(|file| (candidate, file)),
+        }
+    }
+}
+
+/// Write `contents` to a file that didn't exist and return its path. A path the user gave
+/// (`numbered` false) that exists is refused with `AlreadyExists`; a default name that
+/// exists gets a number instead (see `create_numbered`).
+fn write_new_file(path: &Path, numbered: bool, contents: &[u8]) -> std::io::Result<PathBuf> {
+    let (path, mut file) = if numbered {
+        create_numbered(path)?
+    } else {
+        (path.to_path_buf(), std::fs::OpenOptions::new().write(true).create_new(true).open(path)?)
+    };
+    file.write_all(contents)?;
+    Ok(path)
+}
+
+/// The notice for a file `write_new_file` couldn't write
+fn write_failed_notice(path: &Path, e: &std::io::Error) -> String {
+    if e.kind() == std::io::ErrorKind::AlreadyExists {
+        format!("{} already exists, give another path", path.display())
+    } else {
+        format!("Couldn't write {}: {}", path.display(), e)
+    }
+}
+
+/// Where /diag writes without a path: the home directory, named by date and time
+fn default_diag_path() -> PathBuf {
+    let file_name = format!("whatsapp_diag_{}.txt", chrono::Local::now().format("%Y-%m-%d_%H%M%S"));
+    dirs::home_dir().unwrap_or_default().join(file_name)
+}
+
+/// Parse the arguments of `/filter` (after any `add`) into a single filter.
+/// Dates are YYYY-MM-DD, or relative ages like 7d / 12h; anything unrecognised is a sender name.
+fn parse_filter_spec(args: &[String]) -> Option<Filter> {
+    let first = args.first()?.to_lowercase();
+
+    if matches!(first.as_str(), "since" | "before" | "between") {
+        let (since, before) = match (first.as_str(), &args[1..]) {
+            ("since", [date]) => (Some(parse_time_bound(date, false)?), None),
+            ("before", [date]) => (None, Some(parse_time_bound(date, false)?)),
+            ("between", [from, to]) => (
+                Some(parse_time_bound(from, false)?),
+                Some(parse_time_bound(to, true)?),
+            ),
+            _ => return None,
+        };
+        return Some(Filter::new(
+            FilterType::TimeRange { since, before },
+            FilterType::time_range_value(since, before),
+        ));
+    }
+
+    // Media type filters
+    let media_types: &[(&str, &str)] = &[
+        ("photo", "photo"),
+        ("photos", "photo"),
+        ("video", "video"),
+        ("videos", "video"),
+        ("audio", "audio"),
+        ("voice", "voice"),
+        ("doc", "document"),
+        ("document", "document"),
+        ("documents", "document"),
+        ("file", "document"),
+        ("files", "document"),
+        ("link", "link"),
+        ("links", "link"),
+        ("url", "link"),
+        ("sticker", "sticker"),
+        ("stickers", "sticker"),
+        ("gif", "gif"),
+        ("gifs", "gif"),
+        ("media", "media"),
+    ];
+    if let Some((_, media_type)) = media_types.iter().find(|(k, _)| *k == first) {
+        let kind = if *media_type == "link" {
+            FilterType::Link
+        } else {
+            FilterType::Media
+        };
+        return Some(Filter::new(kind, *media_type));
+    }
+
+    Some(Filter::new(FilterType::Sender, args.join(" ")))
+}
+
+/// Render /searchall hits grouped by chat (in order of each chat's first hit), numbered
+/// across groups. Returns the lines and the hits reordered to match the numbering.
+fn format_search_results(
+    query: &str,
+    hits: Vec<SearchHit>,
+    chat_names: &HashMap<String, String>,
+) -> (Vec<String>, Vec<SearchHit>) {
+    let mut groups: Vec<(String, Vec<SearchHit>)> = Vec::new();
+    for hit in hits {
+        match groups.iter_mut().find(|(jid, _)| *jid == hit.chat_jid) {
+            Some((_, group)) => group.push(hit),
+            None => groups.push((hit.chat_jid.clone(), vec![hit])),
+        }
+    }
+
+    let total: usize = groups.iter().map(|(_, g)| g.len()).sum();
+    let mut lines = vec![
+        format!("{} results for '{}' in {} chats", total, query, groups.len()),
+        String::new(),
+    ];
+    let mut ordered = Vec::with_capacity(total);
+    for (jid, group) in groups {
+        let name = chat_names
+            .get(&jid)
+            .cloned()
+            .or_else(|| group[0].chat_name.clone())
+            .unwrap_or_else(|| jid.clone());
+        lines.push(format!("── {} ({}) ──", name, group.len()));
+        for hit in group {
+            let first_line = hit.text.lines().next().unwrap_or("");
+            let preview = truncate_chars(first_line, 80);
+            lines.push(format!(
+                "  #{} {} {}: {}",
+                ordered.len() + 1,
+                format_timestamp(hit.timestamp),
+                hit.sender_name,
+                preview
+            ));
+            ordered.push(hit);
+        }
+        lines.push(String::new());
+    }
+    lines.push("Use /open N to jump to a result".to_string());
+    (lines, ordered)
+}
+
+/// The pane lines `/msginfo` shows for our message #`msg_num`: each recipient with ✓ sent,
+/// ✓✓ delivered or READ_MARKER read and when. A group starts with the totals and ends
+/// with how many members aren't listed.
+pub fn message_info_lines(msg_num: usize, info: &MessageInfo, is_group: bool) -> Vec<String> {
+    let marker = |status: ReceiptStatus| match status {
+        ReceiptStatus::Read => READ_MARKER,
+        ReceiptStatus::Delivered => "✓✓",
+        ReceiptStatus::Sent => "✓",
+    };
+    let when = |at: Option<i64>| at.map(|t| format!(" {}", format_timestamp(t))).unwrap_or_default();
+    let mut lines = vec![format!("--- Message info #{} ---", msg_num)];
+    if is_group {
+        let totals = &info.totals;
+        lines.push(match totals.recipients {
+            Some(total) => format!("  Read by {} of {}, delivered to {}", totals.read, total, totals.delivered),
+            None => format!("  Read by {}, delivered to {}", totals.read, totals.delivered),
+        });
+        for (_, name, status, at) in &info.recipients {
+            lines.push(format!("  {} {}{}", marker(*status), name, when(*at)));
+        }
+        let listed = info.recipients.len() as u32;
+        if let Some(waiting) = totals.recipients.map(|total| total.saturating_sub(listed.max(totals.delivered))) {
+            if waiting > 0 {
+                lines.push(format!("  {} more not delivered yet", waiting));
+            }
+        }
+    } else {
+        let (status, at) = info.recipients.first().map_or((ReceiptStatus::Sent, None), |r| (r.2, r.3));
+        let label = match status {
+            ReceiptStatus::Read => "Read",
+            ReceiptStatus::Delivered => "Delivered",
+            ReceiptStatus::Sent => "Sent, not delivered yet",
+        };
+        lines.push(format!("  {} {}{}", marker(status), label, when(at)));
+    }
+    lines.push("---".to_string());
+    lines
+}
+
+/// A slash command as listed in the help overlay. `CommandHandler::handle` dispatches on
+/// the first name; the others are aliases.
+pub struct CommandInfo {
+    pub names: &'static [&'static str],
+    pub usage: &'static str,
+    pub summary: &'static str,
+    pub category: &'static str,
+}
+
+/// Every slash command, grouped by category in help order
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo { names: &["reply", "r"], usage: "/reply N [text]", summary: "Reply to message #N", category: "Messages" },
+    CommandInfo { names: &["edit", "e"], usage: "/edit N text", summary: "Edit your recent message #N", category: "Messages" },
+    CommandInfo { names: &["delete", "del", "d"], usage: "/delete N", summary: "Delete message #N", category: "Messages" },
+    CommandInfo { names: &["forward", "fwd", "f"], usage: "/forward N @user [@user ...]", summary: "Forward message #N to one or more chats", category: "Messages" },
+    CommandInfo { names: &["forward-to-current", "fwdhere"], usage: "/forward-to-current P N", summary: "Forward #N of pane P into this chat", category: "Messages" },
+    CommandInfo { names: &["react"], usage: "/react N [emoji]", summary: "React to message #N", category: "Messages" },
+    CommandInfo { names: &["locate", "map"], usage: "/locate N", summary: "Show location #N on a map", category: "Messages" },
+    CommandInfo { names: &["save-contact", "vcf"], usage: "/save-contact N [path]", summary: "Save the contact(s) shared in #N as a .vcf", category: "Messages" },
+    CommandInfo { names: &["copy"], usage: "/copy N [link]", summary: "Copy the text of #N, or its first link", category: "Messages" },
+    CommandInfo { names: &["msginfo", "info"], usage: "/msginfo [N]", summary: "Who got and read your message #N", category: "Messages" },
+    CommandInfo { names: &["media", "m"], usage: "/media N", summary: "Download and open the media of #N", category: "Messages" },
+    CommandInfo { names: &["send-media", "sendmedia"], usage: "/send-media <path> [caption]", summary: "Send a file", category: "Messages" },
+    CommandInfo { names: &["compose"], usage: "/compose", summary: "Write the message in $EDITOR", category: "Messages" },
+    CommandInfo { names: &["unsend"], usage: "/unsend", summary: "Take back the message you just sent (off unless undo_send_secs is set)", category: "Messages" },
+    CommandInfo { names: &["from"], usage: "/from [number|off]", summary: "Send from a linked identity", category: "Messages" },
+    CommandInfo { names: &["search", "s"], usage: "/search <query>", summary: "Search this chat", category: "Find" },
+    CommandInfo { names: &["searchall", "sa"], usage: "/searchall <query>", summary: "Search every chat", category: "Find" },
+    CommandInfo { names: &["open"], usage: "/open N", summary: "Open /searchall result N", category: "Find" },
+    CommandInfo { names: &["goto"], usage: "/goto N", summary: "Scroll to message #N", category: "Find" },
+    CommandInfo { names: &["date"], usage: "/date YYYY-MM-DD", summary: "Jump to a day", category: "Find" },
+    CommandInfo { names: &["filter"], usage: "/filter <what> | off", summary: "Filter by sender, media, link or time", category: "Find" },
+    CommandInfo { names: &["unread"], usage: "/unread", summary: "Show only unread messages", category: "View" },
+    CommandInfo { names: &["list"], usage: "/list", summary: "One line per message", category: "View" },
+    CommandInfo { names: &["presence"], usage: "/presence", summary: "Show/hide online status", category: "View" },
+    CommandInfo { names: &["receipts"], usage: "/receipts", summary: "Show/hide delivery and read marks", category: "View" },
+    CommandInfo { names: &["statusline"], usage: "/statusline", summary: "Show/hide the status line", category: "View" },
+    CommandInfo { names: &["activity"], usage: "/activity", summary: "Show recent notifications", category: "View" },
+    CommandInfo { names: &["alias"], usage: "/alias N name", summary: "Name the sender of #N", category: "View" },
+    CommandInfo { names: &["unalias"], usage: "/unalias N", summary: "Remove the sender's alias", category: "View" },
+    CommandInfo { names: &["color", "colour"], usage: "/color N <color>|off", summary: "Pin a color for the sender of #N", category: "View" },
+    CommandInfo { names: &["new"], usage: "/new [@user]", summary: "Start a chat; without a user, step by step", category: "Chats" },
+    CommandInfo { names: &["self", "me"], usage: "/self", summary: "Open your message-yourself chat", category: "Chats" },
+    CommandInfo { names: &["whoami"], usage: "/whoami", summary: "Show the logged-in account", category: "Chats" },
+    CommandInfo { names: &["reconnect"], usage: "/reconnect", summary: "Check the login and restart sync", category: "Chats" },
+    CommandInfo { names: &["newgroup"], usage: "/newgroup <name>", summary: "Create a group", category: "Chats" },
+    CommandInfo { names: &["add"], usage: "/add @user", summary: "Add a group member", category: "Chats" },
+    CommandInfo { names: &["kick", "remove"], usage: "/kick @user", summary: "Remove a group member", category: "Chats" },
+    CommandInfo { names: &["leave"], usage: "/leave", summary: "Leave this group", category: "Chats" },
+    CommandInfo { names: &["members"], usage: "/members", summary: "List group members", category: "Chats" },
+    CommandInfo { names: &["mute"], usage: "/mute", summary: "Silence notifications", category: "Chats" },
+    CommandInfo { names: &["unmute"], usage: "/unmute", summary: "Notify again", category: "Chats" },
+    CommandInfo { names: &["dnd"], usage: "/dnd [on|off|HH:MM-HH:MM|hours off]", summary: "Do not disturb, now or on a daily schedule", category: "Chats" },
+    CommandInfo { names: &["archive"], usage: "/archive", summary: "Hide from the chat list", category: "Chats" },
+    CommandInfo { names: &["unarchive"], usage: "/unarchive", summary: "Bring back to the chat list", category: "Chats" },
+    CommandInfo { names: &["export"], usage: "/export [txt|md] [path]", summary: "Save the chat to a file", category: "Chats" },
+    CommandInfo { names: &["reload"], usage: "/reload [config]", summary: "Reload config, keymap and theme", category: "Settings" },
+    CommandInfo { names: &["diag"], usage: "/diag [raw] [path]", summary: "Write a diagnostics file for bug reports", category: "Settings" },
+    CommandInfo { names: &["layout"], usage: "/layout save|load|delete <name> | list", summary: "Named pane layouts", category: "Settings" },
+    CommandInfo { names: &["set"], usage: "/set <setting> <value>", summary: "Change a timing setting", category: "Settings" },
+];
+
+/// The command called `name`, by its name or an alias
+fn find_command(name: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|c| c.names.contains(&name))
+}
+
+/// Names starting with `prefix` (typed without the slash) for Tab completion, in table
+/// order: each command once, by its first matching name. One- and two-letter aliases only
+/// count when typed in full.
+pub fn command_candidates(prefix: &str) -> Vec<&'static str> {
+    COMMANDS
+        .iter()
+        .filter_map(|c| {
+            c.names
+                .iter()
+                .find(|n| **n == prefix || (n.len() > 2 && n.starts_with(prefix)))
+                .copied()
+        })
+        .collect()
+}
+
+/// What the status bar shows for a command being typed: its usage and summary
+pub fn command_hint(name: &str) -> Option<String> {
+    find_command(name).map(|c| format!("{} - {}", c.usage, c.summary))
+}
+
+/// The hint while Tab steps through `candidates` at `idx`: the current command's usage,
+/// then the others in the order further Tabs reach them
+pub fn command_cycle_hint(candidates: &[&str], idx: usize) -> String {
+    let hint = candidates.get(idx).and_then(|name| command_hint(name)).unwrap_or_default();
+    if candidates.len() < 2 {
+        return hint;
+    }
+    let others: Vec<String> = (1..candidates.len())
+        .map(|step| format!("/{}", candidates[(idx + step) % candidates.len()]))
+        .collect();
+    format!("{}  |  Tab: {}", hint, others.join(" "))
+}
+
+/// Whether `/name` does nothing useful without arguments, so accepting its completion
+/// leaves room to type them instead of running it
+pub fn needs_arguments(name: &str) -> bool {
+    find_command(name).is_some_and(|c| {
+        c.usage.split_whitespace().nth(1).is_some_and(|arg| !arg.starts_with('['))
+    })
+}
+
+/// A destructive command held until it is confirmed with `y` (see `App::pending_confirm`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmAction {
+    /// /delete: our message `msg_id`, shown as #`msg_num` in the pane
+    DeleteMessage { pane_idx: usize, chat_id: String, msg_id: String, msg_num: usize },
+    /// /kick: `member` out of the group `chat_id`
+    RemoveMember { pane_idx: usize, chat_id: String, member: String },
+    /// /leave
+    LeaveGroup { chat_id: String, chat_name: String },
+}
+
 pub struct CommandHandler;
 
 impl CommandHandler {
@@ -40,20 +450,24 @@ impl CommandHandler {
             None => return Ok(false),
         };
 
-        match cmd.name.as_str() {
-            "reply" | "r" => {
+        let Some(info) = find_command(&cmd.name) else {
+            return Ok(false);
+        };
+
+        match info.names[0] {
+            "reply" => {
                 Self::handle_reply(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
-            "media" | "m" => {
+            "media" => {
                 Self::handle_media(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
-            "edit" | "e" => {
+            "edit" => {
                 Self::handle_edit(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
-            "delete" | "del" | "d" => {
+            "delete" => {
                 Self::handle_delete(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
@@ -65,14 +479,142 @@ impl CommandHandler {
                 Self::handle_unalias(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
+            "color" => {
+                Self::handle_color(app, &cmd, pane_idx);
+                Ok(true)
+            }
             "filter" => {
                 Self::handle_filter(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
-            "search" | "s" => {
+            "search" => {
                 Self::handle_search(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
+            "presence" => {
+                app.toggle_presence();
+                Ok(true)
+            }
+            "receipts" => {
+                app.toggle_receipts();
+                Ok(true)
+            }
+            "statusline" => {
+                app.toggle_status_line();
+                Ok(true)
+            }
+            "copy" => {
+                Self::handle_copy(app, &cmd, pane_idx).await;
+                Ok(true)
+            }
+            "locate" => {
+                Self::handle_locate(app, &cmd, pane_idx);
+                Ok(true)
+            }
+            "save-contact" => {
+                Self::handle_save_contact(app, &cmd, pane_idx);
+                Ok(true)
+            }
+            "activity" => {
+                app.show_activity();
+                Ok(true)
+            }
+            "list" => {
+                Self::handle_list_mode(app, pane_idx);
+                Ok(true)
+            }
+            "from" => {
+                Self::handle_from(app, &cmd, pane_idx);
+                Ok(true)
+            }
+            "unsend" => {
+                app.unsend_last();
+                Ok(true)
+            }
+            "compose" => {
+                app.request_external_editor();
+                Ok(true)
+            }
+            "react" => {
+                Self::handle_react(app, &cmd, pane_idx).await;
+                Ok(true)
+            }
+            "self" => {
+                Self::handle_self(app, pane_idx).await;
+                Ok(true)
+            }
+            "reload" => {
+                if cmd.args.iter().all(|a| a.eq_ignore_ascii_case("config")) {
+                    app.reload_config();
+                } else {
+                    app.notify("Usage: /reload [config]");
+                }
+                Ok(true)
+            }
+            "diag" => {
+                Self::handle_diag(app, &cmd).await;
+                Ok(true)
+            }
+            "whoami" => {
+                Self::handle_whoami(app).await;
+                Ok(true)
+            }
+            "reconnect" => {
+                app.reconnect().await;
+                Ok(true)
+            }
+            "unread" => {
+                Self::handle_unread(app, pane_idx);
+                Ok(true)
+            }
+            "set" => {
+                Self::handle_set(app, &cmd);
+                Ok(true)
+            }
+            "mute" => {
+                Self::handle_mute(app, pane_idx, true);
+                Ok(true)
+            }
+            "unmute" => {
+                Self::handle_mute(app, pane_idx, false);
+                Ok(true)
+            }
+            "dnd" => {
+                Self::handle_dnd(app, &cmd);
+                Ok(true)
+            }
+            "archive" => {
+                Self::handle_archive(app, pane_idx, true);
+                Ok(true)
+            }
+            "unarchive" => {
+                Self::handle_archive(app, pane_idx, false);
+                Ok(true)
+            }
+            "layout" => {
+                Self::handle_layout(app, &cmd).await?;
+                Ok(true)
+            }
+            "searchall" => {
+                Self::handle_search_all(app, &cmd, pane_idx).await?;
+                Ok(true)
+            }
+            "open" => {
+                Self::handle_open_result(app, &cmd, pane_idx).await?;
+                Ok(true)
+            }
+            "goto" => {
+                Self::handle_goto(app, &cmd, pane_idx);
+                Ok(true)
+            }
+            "send-media" => {
+                Self::handle_send_media(app, &cmd, pane_idx).await?;
+                Ok(true)
+            }
+            "date" => {
+                Self::handle_date(app, &cmd, pane_idx).await?;
+                Ok(true)
+            }
             "new" => {
                 Self::handle_new_chat(app, &cmd, pane_idx).await?;
                 Ok(true)
@@ -85,7 +627,7 @@ impl CommandHandler {
                 Self::handle_add_member(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
-            "kick" | "remove" => {
+            "kick" => {
                 Self::handle_remove_member(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
@@ -93,10 +635,26 @@ impl CommandHandler {
                 Self::handle_members(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
-            "forward" | "fwd" | "f" => {
+            "msginfo" => {
+                Self::handle_msginfo(app, &cmd, pane_idx).await;
+                Ok(true)
+            }
+            "leave" => {
+                Self::handle_leave(app, pane_idx);
+                Ok(true)
+            }
+            "forward" => {
                 Self::handle_forward(app, &cmd, pane_idx).await?;
                 Ok(true)
             }
+            "forward-to-current" => {
+                Self::handle_forward_to_current(app, &cmd, pane_idx).await;
+                Ok(true)
+            }
+            "export" => {
+                Self::handle_export(app, &cmd, pane_idx).await;
+                Ok(true)
+            }
             _ => Ok(false),
         }
     }
@@ -122,9 +680,13 @@ impl CommandHandler {
                 if let Some(ref chat_id) = pane.chat_id {
                     // Get actual message ID from msg_data
                     if let Some(msg_data) = pane.msg_data.get((msg_num - 1) as usize) {
+                        let quoted = QuotedMessage {
+                            sender_name: msg_data.sender_name.clone(),
+                            text: msg_data.text.clone(),
+                        };
                         match app
                             .whatsapp
-                            .reply_to_message(chat_id, &msg_data.msg_id, &text)
+                            .reply_to_message(chat_id, &msg_data.msg_id, &text, Some(&quoted), pane.send_as.as_deref())
                             .await
                         {
                             Ok(_) => pane.add_message(format!("✓ Replied to #{}", msg_num)),
@@ -142,12 +704,7 @@ impl CommandHandler {
                     
                     // Get first line of message for preview (max 60 chars)
                     let first_line = msg_data.text.lines().next().unwrap_or(&msg_data.text);
-                    let preview_text = if first_line.chars().count() > 60 {
-                        let truncate_at = first_line.char_indices().nth(60).map(|(i, _)| i).unwrap_or(first_line.len());
-                        format!("{}...", &first_line[..truncate_at])
-                    } else {
-                        first_line.to_string()
-                    };
+                    let preview_text = truncate_chars(first_line, 60);
                     
                     pane.show_reply_preview(format!("Reply to #{}: {}", msg_num, preview_text));
                     app.notify(&format!("Replying to message #{}. Type your reply.", msg_num));
@@ -213,14 +770,7 @@ impl CommandHandler {
                 .await
             {
                 Ok(path) => {
-                    #[cfg(target_os = "macos")]
-                    {
-                        let _ = std::process::Command::new("open").arg(&path).spawn();
-                    }
-                    #[cfg(target_os = "linux")]
-                    {
-                        let _ = std::process::Command::new("xdg-open").arg(&path).spawn();
-                    }
+                    let _ = crate::utils::open_external(&path);
                     app.notify_with_duration(
                         &format!(
                             "✓ {}",
@@ -241,84 +791,192 @@ impl CommandHandler {
         Ok(())
     }
 
+    /// /edit N text - edit one of your own recent messages. Without an `edit` command in
+    /// whatsapp-cli, and only with `edit_resend` on, the message is deleted and sent again.
     async fn handle_edit(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
-        if cmd.args.len() < 2 {
-            app.notify("Usage: /edit N new_text");
-            return Ok(());
-        }
-
-        let msg_num: i32 = match cmd.args[0].trim_start_matches('#').parse() {
-            Ok(n) => n,
-            Err(_) => {
-                app.notify("Usage: /edit N new_text");
+        let usage = "Usage: /edit N new_text";
+        let msg_num = match cmd.args.first().map(|a| a.trim_start_matches('#').parse::<usize>()) {
+            Some(Ok(n)) if n > 0 && cmd.args.len() >= 2 => n,
+            _ => {
+                app.notify(usage);
                 return Ok(());
             }
         };
-
         let new_text = cmd.args[1..].join(" ");
 
-        if let Some(pane) = app.panes.get_mut(pane_idx) {
-            if let Some(ref chat_id) = pane.chat_id {
-                // Get actual message ID from msg_data
-                if let Some(msg_data) = pane.msg_data.get((msg_num - 1) as usize) {
-                    match app
-                        .whatsapp
-                        .edit_message(chat_id, &msg_data.msg_id, &new_text)
-                        .await
-                    {
-                        Ok(_) => {
-                            pane.add_message(format!("✓ Edited message #{}", msg_num));
-                            app.notify("Message edited");
-                        }
-                        Err(e) => {
-                            pane.add_message(format!("✗ Edit failed: {}", e));
-                            app.notify(&format!("Edit failed: {}", e));
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return Ok(());
+        };
+        let Some(chat_id) = pane.chat_id.clone() else {
+            app.notify("Select a chat first");
+            return Ok(());
+        };
+        let Some(msg) = pane.msg_data.get(msg_num - 1).cloned() else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return Ok(());
+        };
+        if !msg.is_outgoing {
+            app.notify("You can only edit your own messages");
+            return Ok(());
+        }
+        if msg.msg_id.is_empty() {
+            app.notify(&format!("Message #{} is still being sent", msg_num));
+            return Ok(());
+        }
+        if chrono::Utc::now().timestamp() - msg.timestamp > EDIT_WINDOW_SECS {
+            app.notify(&format!(
+                "Message #{} is too old to edit (WhatsApp allows {} minutes)",
+                msg_num,
+                EDIT_WINDOW_SECS / 60
+            ));
+            return Ok(());
+        }
+
+        if app.whatsapp.edit_supported() {
+            match app.whatsapp.edit_message(&chat_id, &msg.msg_id, &new_text).await {
+                Ok(()) => {
+                    if let Some(pane) = app.panes.get_mut(pane_idx) {
+                        if let Some(m) = pane.msg_data.iter_mut().find(|m| m.msg_id == msg.msg_id) {
+                            m.text = new_text;
                         }
+                        pane.format_cache.clear();
                     }
-                } else {
-                    pane.add_message(format!("✗ Message #{} not found", msg_num));
+                    app.notify(&format!("✓ Edited message #{}", msg_num));
+                    return Ok(());
+                }
+                Err(e) if app.whatsapp.edit_supported() => {
+                    crate::warn_log!("/edit {} failed: {}", msg.msg_id, e);
+                    app.notify(&format!("✗ Edit failed: {}", e));
+                    return Ok(());
                 }
+                Err(_) => {}
+            }
+        }
+
+        if !app.edit_resend {
+            app.notify("This whatsapp-cli can't edit messages (set edit_resend to delete and resend instead)");
+            return Ok(());
+        }
+        if let Err(e) = app.whatsapp.delete_message(&chat_id, &msg.msg_id).await {
+            crate::warn_log!("/edit {}: delete before resend failed: {}", msg.msg_id, e);
+            app.notify(&format!("✗ Edit failed: couldn't delete the original: {}", e));
+            return Ok(());
+        }
+        let send_as = app.panes.get(pane_idx).and_then(|p| p.send_as.clone());
+        if let Err(e) = app.whatsapp.send_message(&chat_id, &new_text, send_as.as_deref()).await {
+            crate::warn_log!("/edit {}: resend failed: {}", msg.msg_id, e);
+            app.notify(&format!("✗ Original #{} deleted but resending failed: {}", msg_num, e));
+            if let Some(pane) = app.panes.get_mut(pane_idx) {
+                pane.msg_data.retain(|m| m.msg_id != msg.msg_id);
+                pane.format_cache.clear();
             }
+            return Ok(());
         }
 
+        // The resent copy is a new message at the bottom; it replaces this echo once synced
+        if let Some(pane) = app.panes.get_mut(pane_idx) {
+            pane.msg_data.retain(|m| m.msg_id != msg.msg_id);
+            pane.msg_data.push(MessageData {
+                msg_id: String::new(),
+                text: new_text,
+                timestamp: chrono::Utc::now().timestamp(),
+                reactions: HashMap::new(),
+                ..msg
+            });
+            pane.format_cache.clear();
+        }
+        app.notify(&format!(
+            "⚠ Not a true edit: #{} was deleted and the new text sent as a new message",
+            msg_num
+        ));
         Ok(())
     }
 
-    async fn handle_delete(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
-        if cmd.args.is_empty() {
-            app.notify("Usage: /delete N");
-            return Ok(());
+    /// Answer the pending confirmation: `true` runs the held command, `false` drops it
+    pub async fn answer_confirm(app: &mut App, confirmed: bool) {
+        let Some((_, action)) = app.pending_confirm.take() else {
+            return;
+        };
+        app.needs_redraw = true;
+        if !confirmed {
+            app.notify("Cancelled");
+            return;
+        }
+        match action {
+            ConfirmAction::DeleteMessage { pane_idx, chat_id, msg_id, msg_num } => {
+                Self::delete_message(app, pane_idx, &chat_id, &msg_id, msg_num).await;
+            }
+            ConfirmAction::RemoveMember { pane_idx, chat_id, member } => {
+                Self::remove_member(app, pane_idx, &chat_id, &member).await;
+            }
+            ConfirmAction::LeaveGroup { chat_id, chat_name } => {
+                Self::leave_group(app, &chat_id, &chat_name).await;
+            }
         }
+    }
 
-        let msg_num: i32 = match cmd.args[0].trim_start_matches('#').parse() {
-            Ok(n) => n,
-            Err(_) => {
+    /// /delete N - delete our message #N for everyone. It stays in the pane, marked as
+    /// deleted, so the numbers of the messages after it don't change.
+    async fn handle_delete(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        let msg_num = match cmd.args.first().map(|a| a.trim_start_matches('#').parse::<usize>()) {
+            Some(Ok(n)) if n > 0 => n,
+            _ => {
                 app.notify("Usage: /delete N");
                 return Ok(());
             }
         };
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return Ok(());
+        };
+        let Some(chat_id) = pane.chat_id.clone() else {
+            app.notify("Select a chat first");
+            return Ok(());
+        };
+        let Some(msg) = pane.msg_data.get(msg_num - 1).cloned() else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return Ok(());
+        };
+        if !msg.is_outgoing {
+            app.notify("You can only delete your own messages");
+            return Ok(());
+        }
+        if msg.msg_id.is_empty() {
+            app.notify(&format!("Message #{} is still being sent", msg_num));
+            return Ok(());
+        }
+        if chrono::Utc::now().timestamp() - msg.timestamp > DELETE_WINDOW_SECS {
+            app.notify(&format!(
+                "Message #{} is too old to delete for everyone (WhatsApp allows 2 days and 12 hours)",
+                msg_num
+            ));
+            return Ok(());
+        }
 
-        if let Some(pane) = app.panes.get_mut(pane_idx) {
-            if let Some(ref chat_id) = pane.chat_id {
-                // Get actual message ID from msg_data
-                if let Some(msg_data) = pane.msg_data.get((msg_num - 1) as usize) {
-                    match app.whatsapp.delete_message(chat_id, &msg_data.msg_id).await {
-                        Ok(_) => {
-                            pane.add_message(format!("✓ Deleted message #{}", msg_num));
-                            app.notify("Message deleted");
-                        }
-                        Err(e) => {
-                            pane.add_message(format!("✗ Delete failed: {}", e));
-                            app.notify(&format!("Delete failed: {}", e));
-                        }
+        let action = ConfirmAction::DeleteMessage { pane_idx, chat_id, msg_id: msg.msg_id, msg_num };
+        app.ask_confirm(&format!("Delete message #{} for everyone?", msg_num), action);
+        Ok(())
+    }
+
+    /// The confirmed half of /delete
+    async fn delete_message(app: &mut App, pane_idx: usize, chat_id: &str, msg_id: &str, msg_num: usize) {
+        match app.whatsapp.delete_message(chat_id, msg_id).await {
+            Ok(()) => {
+                if let Some(pane) = app.panes.get_mut(pane_idx) {
+                    if let Some(m) = pane.msg_data.iter_mut().find(|m| m.msg_id == msg_id) {
+                        m.text = DELETED_TEXT.to_string();
+                        m.media_type = None;
+                        m.media_label = None;
+                        m.reactions.clear();
                     }
-                } else {
-                    pane.add_message(format!("✗ Message #{} not found", msg_num));
+                    pane.format_cache.clear();
                 }
+                app.notify(&format!("✓ Deleted message #{}", msg_num));
+            }
+            Err(e) => {
+                crate::warn_log!("/delete {} failed: {}", msg_id, e);
+                app.notify(&format!("✗ Delete failed: {}", e));
             }
         }
-
-        Ok(())
     }
 
     async fn handle_alias(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
@@ -391,20 +1049,57 @@ impl CommandHandler {
         Ok(())
     }
 
+    /// /color N <color>|off - pin a group chat color for the sender of #N, or go back to
+    /// their palette color
+    fn handle_color(app: &mut App, cmd: &Command, pane_idx: usize) {
+        const USAGE: &str = "Usage: /color N <color>|off";
+        if cmd.args.len() != 2 {
+            app.notify(USAGE);
+            return;
+        }
+        let Ok(msg_num) = cmd.args[0].trim_start_matches('#').parse::<usize>() else {
+            app.notify(USAGE);
+            return;
+        };
+        let Some(sender) = app
+            .panes
+            .get(pane_idx)
+            .and_then(|p| p.msg_data.get(msg_num.wrapping_sub(1)))
+            .map(|m| (m.sender_id.clone(), m.sender_name.clone()))
+        else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return;
+        };
+        let (sender_id, sender_name) = sender;
+        let name = app.aliases.map.get(&sender_id).cloned().unwrap_or(sender_name);
+
+        if cmd.args[1].eq_ignore_ascii_case("off") {
+            if app.user_colors.remove(&sender_id).is_some() {
+                app.notify(&format!("{} uses the palette color again", name));
+            } else {
+                app.notify(&format!("No color pinned for {}", name));
+            }
+        } else {
+            match crate::theme::parse_color(&cmd.args[1]) {
+                Ok(color) => {
+                    app.user_colors.insert(sender_id, color);
+                    app.notify(&format!("Color for {}: {}", name, color));
+                }
+                Err(e) => app.notify(&format!("Color: {}", e)),
+            }
+        }
+    }
+
     async fn handle_filter(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        const USAGE: &str = "Usage: /filter off | clear-all | [add] photo | video | audio | doc | link | since|before <date> | between <a> <b> | <name> | remove <sender|media|link|time>";
+
         if cmd.args.is_empty() {
             if let Some(pane) = app.panes.get(pane_idx) {
-                if pane.filter_type.is_some() {
-                    let ft = match &pane.filter_type {
-                        Some(FilterType::Sender) => "sender",
-                        Some(FilterType::Media) => "media",
-                        Some(FilterType::Link) => "link",
-                        None => "",
-                    };
-                    let fv = pane.filter_value.as_deref().unwrap_or("");
-                    app.notify(&format!("Current filter: {}={}", ft, fv));
+                if pane.filters.is_empty() {
+                    app.notify(USAGE);
                 } else {
-                    app.notify("Usage: /filter off | photo | video | audio | doc | link | <name>");
+                    let label = filters_label(&pane.filters);
+                    app.notify(&format!("Current filter: {}", label));
                 }
             }
             return Ok(());
@@ -414,58 +1109,62 @@ impl CommandHandler {
 
         if filter_arg == "off" {
             if let Some(pane) = app.panes.get_mut(pane_idx) {
-                pane.filter_type = None;
-                pane.filter_value = None;
-                pane.format_cache.clear();
+                pane.clear_filter();
             }
             app.notify("Filter disabled");
             return Ok(());
         }
 
-        // Media type filters
-        let media_types: &[(&str, &str)] = &[
-            ("photo", "photo"),
-            ("photos", "photo"),
-            ("video", "video"),
-            ("videos", "video"),
-            ("audio", "audio"),
-            ("voice", "voice"),
-            ("doc", "document"),
-            ("document", "document"),
-            ("documents", "document"),
-            ("file", "document"),
-            ("files", "document"),
-            ("link", "link"),
-            ("links", "link"),
-            ("url", "link"),
-            ("sticker", "sticker"),
-            ("stickers", "sticker"),
-            ("gif", "gif"),
-            ("gifs", "gif"),
-        ];
-
-        let notify_msg;
-        if let Some((_, media_type)) = media_types.iter().find(|(k, _)| *k == filter_arg) {
-            if let Some(pane) = app.panes.get_mut(pane_idx) {
-                if *media_type == "link" {
-                    pane.filter_type = Some(FilterType::Link);
-                } else {
-                    pane.filter_type = Some(FilterType::Media);
-                }
-                pane.filter_value = Some(media_type.to_string());
-                pane.format_cache.clear();
+        if filter_arg == "clear-all" {
+            let mut cleared = 0;
+            for pane in app.panes.iter_mut().filter(|p| !p.filters.is_empty()) {
+                pane.clear_filter();
+                cleared += 1;
             }
-            notify_msg = format!("Filtering: {} only", media_type);
-        } else {
-            let filter_val = cmd.args.join(" ");
-            notify_msg = format!("Filtering: messages from '{}'", filter_val);
-            if let Some(pane) = app.panes.get_mut(pane_idx) {
-                pane.filter_type = Some(FilterType::Sender);
-                pane.filter_value = Some(filter_val);
-                pane.format_cache.clear();
+            app.notify(&format!("Filters cleared in {} pane(s)", cleared));
+            return Ok(());
+        }
+
+        if filter_arg == "remove" {
+            let Some(kind) = cmd.args.get(1).map(|k| k.to_lowercase()) else {
+                app.notify("Usage: /filter remove <sender|media|link|time>");
+                return Ok(());
+            };
+            let Some(pane) = app.panes.get_mut(pane_idx) else {
+                return Ok(());
+            };
+            if !pane.remove_filter(&kind) {
+                app.notify(&format!("No {} filter active", kind));
+            } else if pane.filters.is_empty() {
+                app.notify("Filter disabled");
+            } else {
+                let label = filters_label(&pane.filters);
+                app.notify(&format!("Filtering: {}", label));
             }
+            return Ok(());
+        }
+
+        // `/filter add <spec>` combines with the active filters, a bare spec replaces them
+        let (adding, spec) = if filter_arg == "add" {
+            (true, &cmd.args[1..])
+        } else {
+            (false, &cmd.args[..])
+        };
+        let Some(filter) = parse_filter_spec(spec) else {
+            app.notify(USAGE);
+            return Ok(());
+        };
+
+        let Some(pane) = app.panes.get_mut(pane_idx) else {
+            return Ok(());
+        };
+        if adding {
+            pane.add_filter(filter);
+        } else {
+            pane.set_filter(filter);
         }
-        app.notify(&notify_msg);
+        let label = filters_label(&pane.filters);
+        app.notify(&format!("Filtering: {}", label));
 
         Ok(())
     }
@@ -499,22 +1198,8 @@ impl CommandHandler {
                         let msg_data: Vec<crate::widgets::MessageData> = results
                             .iter()
                             .map(|(msg_id, sender_id, sender_name, text, reply_to_id, reactions)| {
-                                let reply_to_msg_id = reply_to_id.clone();
-                                
-                                crate::widgets::MessageData {
-                                    msg_id: msg_id.clone(),
-                                    sender_id: sender_id.clone(),
-                                    sender_name: sender_name.clone(),
-                                    text: text.clone(),
-                                    is_outgoing: sender_id == &app.my_user_jid,
-                                    timestamp: chrono::Utc::now().timestamp(),
-                                    media_type: None,
-                                    media_label: None,
-                                    reactions: reactions.clone(),
-                                    reply_to_msg_id,
-                                    reply_sender: None,
-                                    reply_text: None,
-                                }
+                                let raw = (msg_id.clone(), sender_id.clone(), sender_name.clone(), text.clone(), reply_to_id.clone(), None, reactions.clone(), chrono::Utc::now().timestamp());
+                                crate::widgets::MessageData::from_raw(&raw, &app.my_user_jid)
                             })
                             .collect();
 
@@ -541,9 +1226,648 @@ impl CommandHandler {
         Ok(())
     }
 
+    /// /searchall <query> - search every chat and list hits grouped by chat in a results pane
+    async fn handle_search_all(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        if cmd.args.is_empty() {
+            app.notify("Usage: /searchall <query> or /sa <query>");
+            return Ok(());
+        }
+
+        let query = cmd.args.join(" ");
+        app.notify(&format!("Searching all chats for '{}'...", query));
+
+        let hits = match app.whatsapp.search_all_messages(&query, SEARCH_ALL_LIMIT).await {
+            Ok(hits) => hits,
+            Err(e) => {
+                app.notify(&format!("Search failed: {}", e));
+                return Ok(());
+            }
+        };
+        if hits.is_empty() {
+            app.notify("No results found");
+            return Ok(());
+        }
+
+        let chat_names: HashMap<String, String> =
+            app.chats.iter().map(|c| (c.id.clone(), c.name.clone())).collect();
+        let (lines, hits) = format_search_results(&query, hits, &chat_names);
+        let count = hits.len();
+
+        // Reuse the pane if it already shows results, otherwise split off a new one
+        let results_idx = if app.panes.get(pane_idx).is_some_and(|p| !p.search_hits.is_empty()) {
+            pane_idx
+        } else {
+            app.focused_pane_idx = pane_idx;
+            app.split_vertical();
+            app.focused_pane_idx
+        };
+        if let Some(pane) = app.panes.get_mut(results_idx) {
+            pane.chat_id = None;
+            pane.chat_name = format!("Search all: '{}' ({} results)", query, count);
+            pane.msg_data.clear();
+            pane.messages = lines;
+            pane.search_hits = hits;
+            pane.scroll_offset = 0;
+        }
+
+        if count >= SEARCH_ALL_LIMIT {
+            app.notify(&format!("Showing the first {} results - refine the query to see more", count));
+        } else {
+            app.notify(&format!("Found {} results - /open N to jump to one", count));
+        }
+        Ok(())
+    }
+
+    /// /open N - open the chat of /searchall result N and scroll to that message
+    async fn handle_open_result(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        let hit_num: usize = match cmd.args.first().map(|a| a.trim_start_matches('#').parse()) {
+            Some(Ok(n)) => n,
+            _ => {
+                app.notify("Usage: /open N");
+                return Ok(());
+            }
+        };
+
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return Ok(());
+        };
+        if pane.search_hits.is_empty() {
+            app.notify("/open works in a /searchall results pane");
+            return Ok(());
+        }
+        let Some(hit) = hit_num.checked_sub(1).and_then(|i| pane.search_hits.get(i)).cloned() else {
+            app.notify(&format!("No result #{} (have {})", hit_num, pane.search_hits.len()));
+            return Ok(());
+        };
+
+        let chat_name = app
+            .chats
+            .iter()
+            .find(|c| c.id == hit.chat_jid)
+            .map(|c| c.name.clone())
+            .or(hit.chat_name)
+            .unwrap_or_else(|| hit.chat_jid.clone());
+        app.open_chat_in_pane(pane_idx, hit.chat_jid, &chat_name).await;
+
+        let msg_idx = app
+            .panes
+            .get(pane_idx)
+            .and_then(|p| p.msg_data.iter().position(|m| m.msg_id == hit.msg_id));
+        match msg_idx {
+            Some(idx) => {
+                app.scroll_message_to_top(pane_idx, idx);
+            }
+            None => app.notify("That message is older than the loaded history"),
+        }
+        Ok(())
+    }
+
+    /// /unread - toggle showing only the unread messages (plus a little context)
+    fn handle_unread(app: &mut App, pane_idx: usize) {
+        let Some(pane) = app.panes.get_mut(pane_idx) else {
+            return;
+        };
+        let was_on = pane.unread_only;
+        if pane.toggle_unread_only() {
+            let count = pane.unread_count_at_load;
+            app.notify(&format!("Showing {} unread messages", count));
+        } else if was_on {
+            // Leaving the unread view means the reader has caught up
+            pane.mark_caught_up();
+            app.notify("Showing all messages");
+        } else {
+            app.notify("No unread messages");
+        }
+    }
+
+    /// /set <setting> <value> - change a timing setting at runtime; saved with the config
+    fn handle_set(app: &mut App, cmd: &Command) {
+        let usage = "Usage: /set chat_refresh_secs <1-3600> | event_poll_ms <50-10000> | undo_send_secs <0-60, 0 = off> | autosave_secs <0-3600>";
+        let [key, value] = cmd.args.as_slice() else {
+            app.notify(&format!(
+                "chat_refresh_secs={} event_poll_ms={} undo_send_secs={} autosave_secs={} ({})",
+                app.chat_refresh_secs, app.event_poll_ms, app.undo_send_secs, app.autosave_secs, usage
+            ));
+            return;
+        };
+        let Ok(value) = value.parse::<u64>() else {
+            app.notify(usage);
+            return;
+        };
+
+        let applied = match key.as_str() {
+            "chat_refresh_secs" => {
+                app.chat_refresh_secs = crate::config::clamp_chat_refresh_secs(value);
+                app.chat_refresh_secs
+            }
+            "event_poll_ms" => {
+                app.event_poll_ms = crate::config::clamp_event_poll_ms(value);
+                app.event_poll_ms
+            }
+            "undo_send_secs" => {
+                app.undo_send_secs = crate::config::clamp_undo_send_secs(value);
+                app.undo_send_secs
+            }
+            "autosave_secs" => {
+                app.autosave_secs = crate::config::clamp_autosave_secs(value);
+                app.autosave_secs
+            }
+            _ => {
+                app.notify(usage);
+                return;
+            }
+        };
+        if let Err(e) = app.save_state() {
+            crate::warn_log!("handle_set: failed to save settings: {}", e);
+        }
+        app.notify(&format!("{} = {}", key, applied));
+    }
+
+    /// /mute, /unmute - toggle notifications for the pane's chat
+    fn handle_mute(app: &mut App, pane_idx: usize, mute: bool) {
+        let Some(chat_id) = app.panes.get(pane_idx).and_then(|p| p.chat_id.clone()) else {
+            app.notify("Select a chat first");
+            return;
+        };
+        let chat_name = app
+            .chats
+            .iter()
+            .find(|c| c.id == chat_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| chat_id.clone());
+
+        if mute {
+            app.muted_chats.insert(chat_id);
+            app.notify(&format!("Muted {}", chat_name));
+        } else if app.muted_chats.remove(&chat_id) {
+            app.notify(&format!("Unmuted {}", chat_name));
+        } else {
+            app.notify(&format!("{} is not muted", chat_name));
+        }
+    }
+
+    /// /dnd - turn do not disturb on or off, or set or clear the daily quiet hours
+    fn handle_dnd(app: &mut App, cmd: &Command) {
+        let usage = "Usage: /dnd [on|off|HH:MM-HH:MM|hours off]";
+        let args: Vec<&str> = cmd.args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            [] => {
+                let hours = match app.quiet_hours {
+                    Some(q) => format!("quiet hours {}", q),
+                    None => "no quiet hours".to_string(),
+                };
+                let state = if app.dnd { "ON" } else { "OFF" };
+                app.notify(&format!("Do not disturb: {}, {}", state, hours));
+            }
+            ["on"] => {
+                app.dnd = true;
+                app.notify("Do not disturb: ON");
+            }
+            ["off"] => {
+                app.dnd = false;
+                app.notify("Do not disturb: OFF");
+            }
+            ["hours", "off"] => {
+                app.quiet_hours = None;
+                app.notify("Quiet hours cleared");
+            }
+            [spec] => match QuietHours::parse(spec) {
+                Some(hours) => {
+                    app.quiet_hours = Some(hours);
+                    app.notify(&format!("Quiet hours: {}", hours));
+                }
+                None => app.notify(usage),
+            },
+            _ => app.notify(usage),
+        }
+    }
+
+    /// /archive, /unarchive - hide the pane's chat from the chat list or bring it back
+    fn handle_archive(app: &mut App, pane_idx: usize, archive: bool) {
+        let Some(chat_id) = app.panes.get(pane_idx).and_then(|p| p.chat_id.clone()) else {
+            app.notify("Select a chat first");
+            return;
+        };
+        let chat_name = app
+            .chats
+            .iter()
+            .find(|c| c.id == chat_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| chat_id.clone());
+
+        if archive {
+            app.archived_chats.insert(chat_id);
+            app.notify(&format!("Archived {} (Ctrl+A shows archived chats)", chat_name));
+        } else if app.archived_chats.remove(&chat_id) {
+            app.notify(&format!("Unarchived {}", chat_name));
+        } else {
+            app.notify(&format!("{} is not archived", chat_name));
+        }
+    }
+
+    /// /layout save|load|delete <name>, /layout list
+    async fn handle_layout(app: &mut App, cmd: &Command) -> Result<()> {
+        let usage = "Usage: /layout save|load|delete <name> | /layout list";
+        let action = match cmd.args.first() {
+            Some(a) => a.to_lowercase(),
+            None => {
+                app.notify(usage);
+                return Ok(());
+            }
+        };
+        let name = cmd.args[1..].join(" ");
+
+        let mut layouts = match NamedLayouts::load(&app.config) {
+            Ok(l) => l,
+            Err(e) => {
+                crate::error_log!("handle_layout: failed to read saved layouts: {}", e);
+                app.notify(&format!("Failed to read saved layouts: {}", e));
+                return Ok(());
+            }
+        };
+
+        match action.as_str() {
+            "list" => {
+                if layouts.map.is_empty() {
+                    app.notify("No saved layouts");
+                } else {
+                    let mut names: Vec<&str> = layouts.map.keys().map(|k| k.as_str()).collect();
+                    names.sort_unstable();
+                    app.notify(&format!("Layouts: {}", names.join(", ")));
+                }
+            }
+            "save" | "load" | "delete" if name.is_empty() => app.notify(usage),
+            "save" => {
+                layouts.map.insert(name.clone(), app.current_layout());
+                layouts.save(&app.config)?;
+                app.notify(&format!("Layout '{}' saved", name));
+            }
+            "load" => match layouts.map.remove(&name) {
+                Some(layout) => {
+                    app.apply_layout(layout).await;
+                    app.notify(&format!("Layout '{}' loaded", name));
+                }
+                None => app.notify(&format!("No layout named '{}'", name)),
+            },
+            "delete" => {
+                if layouts.map.remove(&name).is_some() {
+                    layouts.save(&app.config)?;
+                    app.notify(&format!("Layout '{}' deleted", name));
+                } else {
+                    app.notify(&format!("No layout named '{}'", name));
+                }
+            }
+            _ => app.notify(usage),
+        }
+
+        Ok(())
+    }
+
+    /// /list - toggle one line per message with an expandable selection
+    fn handle_list_mode(app: &mut App, pane_idx: usize) {
+        let Some(pane) = app.panes.get_mut(pane_idx) else {
+            return;
+        };
+        if pane.toggle_list_mode() {
+            app.notify("List mode: ON (Up/Down to select, Enter to expand)");
+        } else {
+            app.notify("List mode: OFF");
+        }
+    }
+
+    /// /from [number|off] - send this pane's messages from another linked identity
+    fn handle_from(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let Some(pane) = app.panes.get_mut(pane_idx) else {
+            return;
+        };
+        let status = match cmd.args.first().map(|a| a.as_str()) {
+            None => match pane.send_as {
+                Some(ref from) => format!("Sending as {} (/from off for your own number)", from),
+                None => "Sending as your own number (usage: /from <number|JID> | off)".to_string(),
+            },
+            Some("off") => {
+                pane.send_as = None;
+                "Sending as your own number".to_string()
+            }
+            Some(from) => {
+                pane.send_as = Some(from.to_string());
+                format!("Sending as {} (needs a whatsapp-cli with send --from)", from)
+            }
+        };
+        app.notify(&status);
+    }
+
+    /// /react N [emoji] - react to message #N, or pick the emoji from a quick-picker
+    async fn handle_react(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let usage = "Usage: /react N [emoji]";
+        let msg_num = match cmd.args.first().map(|a| a.trim_start_matches('#').parse::<usize>()) {
+            Some(Ok(n)) if n > 0 => n,
+            _ => {
+                app.notify(usage);
+                return;
+            }
+        };
+        match cmd.args.get(1) {
+            Some(emoji) => app.react_to_message(pane_idx, msg_num - 1, emoji).await,
+            None => app.open_reaction_picker(pane_idx, msg_num - 1),
+        }
+    }
+
+    /// /self - open the "message yourself" chat in the pane. A self-chat that doesn't
+    /// exist yet is added to the chat list; sending to it creates it on WhatsApp.
+    async fn handle_self(app: &mut App, pane_idx: usize) {
+        if !app.is_self_chat(&app.my_user_jid) {
+            app.notify("Your own number isn't known yet - send any message from this account first");
+            return;
+        }
+        let jid = app.my_user_jid.clone();
+        if !app.chats.iter().any(|c| c.id == jid) {
+            app.chats.push(ChatInfo {
+                id: jid.clone(),
+                name: "You".to_string(),
+                username: None,
+                unread: 0,
+                is_channel: false,
+                is_group: false,
+                last_message_ts: 0,
+            });
+        }
+        let name = app
+            .chats
+            .iter()
+            .find(|c| c.id == jid)
+            .map(|c| c.name.clone())
+            .unwrap_or_default();
+        app.open_chat_in_pane(pane_idx, jid, &name).await;
+        app.notify("Message yourself: notes sent here go to your own number");
+    }
+
+    /// /whoami - the account this client is logged in as, the connection state and the
+    /// whatsapp-cli version, or how to log in when the store isn't authenticated
+    async fn handle_whoami(app: &mut App) {
+        let status = app.whatsapp.connection_status().await;
+        if !status.authenticated {
+            let message = format!(
+                "Not logged in. Run: {} and scan the QR code with your phone",
+                app.whatsapp.auth_command()
+            );
+            app.notify_with_duration(&message, 15);
+            return;
+        }
+        let account = if app.my_user_jid == crate::whatsapp::UNKNOWN_JID {
+            "an account whose number isn't known yet (send any message to pick it up)".to_string()
+        } else {
+            format!(
+                "{} ({})",
+                crate::whatsapp::format_phone_number(&app.my_user_jid),
+                app.my_user_jid
+            )
+        };
+        let cli = app.whatsapp.cli_version().unwrap_or("whatsapp-cli version unknown");
+        app.notify_with_duration(&format!("Logged in as {}, {} ({})", account, status.state.label(), cli), 10);
+    }
+
+    /// /goto N - scroll so message #N is at the top of the pane
+    fn handle_goto(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let msg_num: usize = match cmd.args.first().map(|a| a.trim_start_matches('#').parse()) {
+            Some(Ok(n)) => n,
+            _ => {
+                app.notify("Usage: /goto N");
+                return;
+            }
+        };
+
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return;
+        };
+        let msg_count = pane.msg_data.len();
+        if msg_num == 0 || msg_num > msg_count {
+            app.notify(&format!("No message #{} (have {})", msg_num, msg_count));
+            return;
+        }
+
+        if !app.scroll_message_to_top(pane_idx, msg_num - 1) {
+            app.notify(&format!("Message #{} has nothing to show", msg_num));
+        }
+    }
+
+    async fn handle_send_media(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        let args_text = cmd.full_text.split_once(char::is_whitespace).map_or("", |(_, rest)| rest);
+        let Some((path_arg, caption)) = split_path_and_caption(args_text) else {
+            app.notify("Usage: /send-media <path> [caption]  (quote paths with spaces)");
+            return Ok(());
+        };
+        let Some(chat_id) = app.panes.get(pane_idx).and_then(|p| p.chat_id.clone()) else {
+            app.notify("Select a chat first");
+            return Ok(());
+        };
+
+        let path = expand_user_path(&path_arg);
+        if !path.is_file() {
+            app.notify(&format!("File not found: {}", path.display()));
+            return Ok(());
+        }
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        // Show the message right away; it is replaced once the send syncs back
+        let timestamp = chrono::Utc::now().timestamp();
+        if let Some(pane) = app.panes.get_mut(pane_idx) {
+            pane.msg_data.push(MessageData {
+                media_type: Some(media_type_for_path(&path).to_string()),
+                send_status: crate::widgets::SendStatus::Sent,
+                ..MessageData::echo(&app.my_user_jid, caption.as_deref().unwrap_or_default(), timestamp)
+            });
+            pane.mark_caught_up();
+        }
+        app.notify(&format!("Sending {}...", file_name));
+
+        match app.whatsapp.send_media(&chat_id, &path, caption.as_deref()).await {
+            Ok(()) => app.notify(&format!("✓ Sent {}", file_name)),
+            Err(e) => {
+                crate::warn_log!("/send-media {} failed: {}", path.display(), e);
+                if let Some(pane) = app.panes.get_mut(pane_idx) {
+                    pane.msg_data
+                        .retain(|m| !(m.msg_id.is_empty() && m.is_outgoing && m.timestamp == timestamp && m.media_type.is_some()));
+                    pane.format_cache.clear();
+                }
+                app.notify(&e.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_date(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        let date_arg = cmd.args.first().map(String::as_str).unwrap_or("");
+        let bounds = chrono::NaiveDate::parse_from_str(date_arg, "%Y-%m-%d")
+            .ok()
+            .and_then(|_| Some((parse_time_bound(date_arg, false)?, parse_time_bound(date_arg, true)?)));
+        let Some((day_start, day_end)) = bounds else {
+            app.notify("Usage: /date YYYY-MM-DD");
+            return Ok(());
+        };
+
+        if app.panes.get(pane_idx).is_none_or(|p| p.chat_id.is_none()) {
+            app.notify("Select a chat first");
+            return Ok(());
+        }
+
+        if let Err(e) = app.load_history_before(pane_idx, day_start).await {
+            crate::warn_log!("/date: loading older messages failed: {}", e);
+        }
+
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return Ok(());
+        };
+        let found = find_message_on_date(&pane.msg_data, day_start, day_end);
+        let Some((msg_idx, exact)) = found else {
+            app.notify(&format!("No messages on or before {}", date_arg));
+            return Ok(());
+        };
+        let msg_time = format_timestamp(pane.msg_data[msg_idx].timestamp);
+
+        if !app.scroll_message_to_top(pane_idx, msg_idx) {
+            app.notify(&format!("Message #{} has nothing to show", msg_idx + 1));
+        } else if exact {
+            app.notify(&format!("{} (#{})", date_arg, msg_idx + 1));
+        } else {
+            app.notify(&format!("No messages on {}, showing the last one before it from {} (#{})", date_arg, msg_time, msg_idx + 1));
+        }
+        Ok(())
+    }
+
+    async fn handle_export(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let mut args = cmd.args.as_slice();
+        let markdown = match args.first().map(|a| a.to_lowercase()) {
+            Some(format) if format == "md" || format == "markdown" => {
+                args = &args[1..];
+                true
+            }
+            Some(format) if format == "txt" || format == "text" => {
+                args = &args[1..];
+                false
+            }
+            _ => false,
+        };
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return;
+        };
+        let Some(chat_id) = pane.chat_id.clone() else {
+            app.notify("Select a chat first");
+            return;
+        };
+        let chat_name = pane.chat_name.clone();
+        let (path, numbered) = if args.is_empty() {
+            (default_export_path(&chat_name, if markdown { "md" } else { "txt" }), true)
+        } else {
+            (expand_user_path(&args.join(" ")), false)
+        };
+
+        // The pane only holds the latest messages; ask the store for the rest of the history
+        let mut messages: Vec<MessageData> = match app.whatsapp.get_messages(&chat_id, EXPORT_LIMIT).await {
+            Ok(raw) => raw.iter().map(|raw| MessageData::from_raw(raw, &app.my_user_jid)).collect(),
+            Err(e) => {
+                crate::warn_log!("/export: loading history for {} failed: {}", chat_id, e);
+                Vec::new()
+            }
+        };
+        if messages.len() < pane.msg_data.len() {
+            messages = pane.msg_data.clone();
+        }
+        messages.sort_by_key(|m| m.timestamp);
+        messages.retain(|m| !m.pending);
+        if messages.is_empty() {
+            app.notify("Nothing to export");
+            return;
+        }
+
+        let transcript = format_transcript(&messages, &app.aliases.map, &chat_name, markdown);
+        match write_new_file(&path, numbered, transcript.as_bytes()) {
+            Ok(saved) => app.notify(&format!("Exported {} messages to {}", messages.len(), saved.display())),
+            Err(e) => {
+                crate::warn_log!("/export to {} failed: {}", path.display(), e);
+                app.notify(&format!("Export failed: {}", write_failed_notice(&path, &e)));
+            }
+        }
+    }
+
+    /// /diag [raw] [path] - write versions, settings, connection state, store table sizes and
+    /// the log tail to a file for bug reports. JIDs, numbers and the home directory are
+    /// redacted unless `raw` is given
+    async fn handle_diag(app: &mut App, cmd: &Command) {
+        let mut args = cmd.args.as_slice();
+        let raw = args.first().is_some_and(|a| a.eq_ignore_ascii_case("raw"));
+        if raw {
+            args = &args[1..];
+        }
+        let (path, numbered) = if args.is_empty() {
+            (default_diag_path(), true)
+        } else {
+            (expand_user_path(&args.join(" ")), false)
+        };
+
+        let status = app.whatsapp.connection_status().await;
+        let client = format!(
+            "version: {}\nplatform: {} {}",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+        let connection = format!(
+            "authenticated: {}\nsync: {}\nown JID: {}\nchats: {}\npanes: {}\nqueued messages: {}",
+            status.authenticated,
+            status.state.label(),
+            app.my_user_jid,
+            app.chats.len(),
+            app.panes.len(),
+            app.outbox.messages.len()
+        );
+        let cli = format!(
+            "path: {}\nstore: {}\nversion: {}\ncommands run this session: {}",
+            app.config.whatsapp_cli_path.display(),
+            app.config.store_path().display(),
+            app.whatsapp.cli_version().unwrap_or("unknown"),
+            app.whatsapp.cli_spawn_count()
+        );
+        let config = serde_json::to_string_pretty(&app.config).unwrap_or_else(|e| e.to_string());
+        let tables = app.whatsapp.store_table_counts().await;
+        let store = if tables.is_empty() {
+            "no databases found".to_string()
+        } else {
+            tables
+                .iter()
+                .map(|(file, table, rows)| format!("{} {}: {}", file, table, rows))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let log = crate::utils::log_tail(crate::diagnostics::LOG_TAIL_LINES)
+            .unwrap_or_else(|| "log not available".to_string());
+
+        let mut report = crate::diagnostics::format_report(&[
+            ("Client", client),
+            ("Connection", connection),
+            ("whatsapp-cli", cli),
+            ("Config", config),
+            ("Store", store),
+            ("Log", log),
+        ]);
+        if !raw {
+            report = crate::diagnostics::redact(&report);
+        }
+        match write_new_file(&path, numbered, report.as_bytes()) {
+            Ok(saved) => app.notify_with_duration(
+                &format!("Diagnostics written to {} - check it before sharing", saved.display()),
+                15,
+            ),
+            Err(e) => {
+                crate::warn_log!("/diag to {} failed: {}", path.display(), e);
+                app.notify(&format!("Diagnostics failed: {}", write_failed_notice(&path, &e)));
+            }
+        }
+    }
+
     async fn handle_new_chat(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
         if cmd.args.is_empty() {
-            app.notify("Usage: /new @username");
+            app.open_new_chat_wizard();
             return Ok(());
         }
 
@@ -612,6 +1936,8 @@ impl CommandHandler {
 
         match app.whatsapp.add_member(&chat_id, username).await {
             Ok(_) => {
+                // Mention completion fetches the new member list next time
+                app.group_members.remove(&chat_id);
                 if let Some(pane) = app.panes.get_mut(pane_idx) {
                     pane.add_message(format!("✓ Added {} to group", username));
                 }
@@ -648,21 +1974,57 @@ impl CommandHandler {
             return Ok(());
         };
 
-        app.notify(&format!("Removing {}...", username));
+        let chat_name = app.panes[pane_idx].chat_name.clone();
+        let action = ConfirmAction::RemoveMember { pane_idx, chat_id, member: username.clone() };
+        app.ask_confirm(&format!("Remove {} from {}?", username, chat_name), action);
+        Ok(())
+    }
+
+    /// The confirmed half of /kick
+    async fn remove_member(app: &mut App, pane_idx: usize, chat_id: &str, member: &str) {
+        app.notify(&format!("Removing {}...", member));
 
-        match app.whatsapp.remove_member(&chat_id, username).await {
+        match app.whatsapp.remove_member(chat_id, member).await {
             Ok(_) => {
+                // Mention completion fetches the new member list next time
+                app.group_members.remove(chat_id);
                 if let Some(pane) = app.panes.get_mut(pane_idx) {
-                    pane.add_message(format!("✓ Removed {} from group", username));
+                    pane.add_message(format!("✓ Removed {} from group", member));
                 }
-                app.notify(&format!("{} removed from group", username));
+                app.notify(&format!("{} removed from group", member));
             }
             Err(e) => {
-                app.notify(&format!("Failed to remove {}: {}", username, e));
+                app.notify(&format!("Failed to remove {}: {}", member, e));
             }
         }
+    }
+
+    /// /leave - leave the pane's group chat, once confirmed
+    fn handle_leave(app: &mut App, pane_idx: usize) {
+        let Some(chat_id) = app.panes.get(pane_idx).and_then(|p| p.chat_id.clone()) else {
+            app.notify("Open a group chat first");
+            return;
+        };
+        if !chat_id.ends_with("@g.us") {
+            app.notify("/leave only works in a group chat");
+            return;
+        }
+        let chat_name = app.panes[pane_idx].chat_name.clone();
+        app.ask_confirm(&format!("Leave {}?", chat_name), ConfirmAction::LeaveGroup { chat_id, chat_name: chat_name.clone() });
+    }
 
-        Ok(())
+    /// The confirmed half of /leave
+    async fn leave_group(app: &mut App, chat_id: &str, chat_name: &str) {
+        match app.whatsapp.leave_group(chat_id).await {
+            Ok(()) => {
+                app.forget_left_group(chat_id);
+                app.notify(&format!("Left {}", chat_name));
+            }
+            Err(e) => {
+                crate::warn_log!("/leave {} failed: {}", chat_id, e);
+                app.notify(&format!("Failed to leave {}: {}", chat_name, e));
+            }
+        }
     }
 
     async fn handle_members(app: &mut App, _cmd: &Command, pane_idx: usize) -> Result<()> {
@@ -682,6 +2044,8 @@ impl CommandHandler {
 
         match app.whatsapp.get_members(&chat_id).await {
             Ok(members) => {
+                let mentionable = members.iter().map(|(id, name, _)| (id.clone(), name.clone())).collect();
+                app.group_members.insert(chat_id.clone(), mentionable);
                 if let Some(pane) = app.panes.get_mut(pane_idx) {
                     pane.add_message(format!("--- Members ({}) ---", members.len()));
                     for (id, name, role) in &members {
@@ -699,68 +2063,1032 @@ impl CommandHandler {
         Ok(())
     }
 
-    async fn handle_forward(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
-        if cmd.args.len() < 2 {
-            app.notify("Usage: /forward N @username or /fwd N @username");
-            return Ok(());
+    /// /msginfo [N] - delivery and read receipts of our message #N, or of the message under
+    /// the /list cursor
+    async fn handle_msginfo(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let Some(pane) = app.panes.get(pane_idx) else {
+            return;
+        };
+        let msg_num = match cmd.args.first() {
+            Some(arg) => arg.trim_start_matches('#').parse::<usize>().ok().filter(|&n| n > 0),
+            None => pane.selected_msg.map(|idx| idx + 1),
+        };
+        let Some(msg_num) = msg_num else {
+            app.notify("Usage: /msginfo N");
+            return;
+        };
+        let Some(chat_id) = pane.chat_id.clone() else {
+            app.notify("Select a chat first");
+            return;
+        };
+        let Some(msg) = pane.msg_data.get(msg_num - 1) else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return;
+        };
+        if !msg.is_outgoing {
+            app.notify("Message info is only kept for your own messages");
+            return;
         }
+        if msg.msg_id.is_empty() {
+            app.notify(&format!("Message #{} is still being sent", msg_num));
+            return;
+        }
+        let msg_id = msg.msg_id.clone();
 
-        let msg_num: i32 = match cmd.args[0].trim_start_matches('#').parse() {
-            Ok(n) => n,
-            Err(_) => {
-                app.notify("Usage: /forward N @username");
-                return Ok(());
+        match app.whatsapp.get_message_info(&chat_id, &msg_id).await {
+            Ok(info) => {
+                let lines = message_info_lines(msg_num, &info, chat_id.ends_with("@g.us"));
+                if let Some(pane) = app.panes.get_mut(pane_idx) {
+                    if let Some(m) = pane.msg_data.iter_mut().find(|m| m.msg_id == msg_id) {
+                        m.receipts = Some(info.totals);
+                    }
+                    for line in lines {
+                        pane.add_message(line);
+                    }
+                }
             }
+            Err(e) => app.notify(&format!("Failed to load message info: {}", e)),
+        }
+    }
+
+    /// /copy N [link] - put the text of message #N, or only its first link, on the clipboard
+    async fn handle_copy(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let usage = "Usage: /copy N [link]";
+        let msg_num = match cmd.args.first().map(|a| a.trim_start_matches('#').parse::<usize>()) {
+            Some(Ok(n)) if n > 0 => n,
+            _ => {
+                app.notify(usage);
+                return;
+            }
+        };
+        let link = match cmd.args.get(1) {
+            None => false,
+            Some(arg) if arg.eq_ignore_ascii_case("link") => true,
+            Some(_) => {
+                app.notify(usage);
+                return;
+            }
+        };
+        let Some(msg) = app.panes.get(pane_idx).and_then(|p| p.msg_data.get(msg_num - 1)) else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return;
+        };
+        let Some(text) = copy_text(msg, link).map(str::to_string) else {
+            let what = if link { "link" } else { "text" };
+            app.notify(&format!("Message #{} has no {} to copy", msg_num, what));
+            return;
         };
 
-        let target = &cmd.args[1];
+        match crate::utils::write_clipboard(&text).await {
+            Ok(()) if link => app.notify(&format!("Copied link from #{}", msg_num)),
+            Ok(()) => app.notify(&format!("Copied #{} to the clipboard", msg_num)),
+            Err(e) => {
+                crate::warn_log!("/copy {} failed: {}", msg_num, e);
+                app.notify(&format!("Couldn't copy: {}", e));
+            }
+        }
+    }
 
-        let (from_chat_id, message_id) = if let Some(pane) = app.panes.get(pane_idx) {
-            let from_id = match &pane.chat_id {
-                Some(id) => id.clone(),
-                None => {
-                    app.notify("No chat selected");
-                    return Ok(());
-                }
-            };
-            // Get actual WhatsApp message ID from msg_data
-            let msg_id = match pane.msg_data.get((msg_num - 1) as usize) {
-                Some(msg) => msg.msg_id.clone(),
-                None => {
-                    app.notify(&format!("Message #{} not found", msg_num));
-                    return Ok(());
-                }
-            };
-            (from_id, msg_id)
+    /// /locate N - open the coordinates of location message #N on OpenStreetMap
+    fn handle_locate(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let Some(msg_num) = cmd.args.first().and_then(|a| a.trim_start_matches('#').parse::<usize>().ok()).filter(|&n| n > 0)
+        else {
+            app.notify("Usage: /locate N");
+            return;
+        };
+        let Some(msg) = app.panes.get(pane_idx).and_then(|p| p.msg_data.get(msg_num - 1)) else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return;
+        };
+        let Some((lat, lon)) = msg.location else {
+            if msg.media_type.as_deref() == Some("location") {
+                app.notify(&format!("Location #{} came without coordinates", msg_num));
+            } else {
+                app.notify(&format!("Message #{} isn't a location", msg_num));
+            }
+            return;
+        };
+
+        match crate::utils::open_external(&map_url(lat, lon)) {
+            Ok(()) => app.notify(&format!("Opening #{} on the map ({:.5}, {:.5})", msg_num, lat, lon)),
+            Err(e) => {
+                crate::warn_log!("/locate {} failed: {}", msg_num, e);
+                app.notify(&format!("Couldn't open the map: {}", e));
+            }
+        }
+    }
+
+    /// /save-contact N [path] - write the vCards shared in message #N to a .vcf file
+    fn handle_save_contact(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let Some(msg_num) = cmd.args.first().and_then(|a| a.trim_start_matches('#').parse::<usize>().ok()).filter(|&n| n > 0)
+        else {
+            app.notify("Usage: /save-contact N [path]");
+            return;
+        };
+        let Some(msg) = app.panes.get(pane_idx).and_then(|p| p.msg_data.get(msg_num - 1)) else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return;
+        };
+        if msg.contacts.is_empty() {
+            app.notify(&format!("Message #{} doesn't share a contact", msg_num));
+            return;
+        }
+        let contacts = msg.contacts.clone();
+        let vcf: String = contacts.iter().map(|c| format!("{}\n", c.vcard)).collect();
+        let (path, numbered) = if cmd.args.len() > 1 {
+            (expand_user_path(&cmd.args[1..].join(" ")), false)
         } else {
+            (default_vcf_path(&contacts), true)
+        };
+        match write_new_file(&path, numbered, vcf.as_bytes()) {
+            Ok(saved) if contacts.len() == 1 => {
+                app.notify_with_duration(&format!("Saved {} to {}", contacts[0].name, saved.display()), 5);
+            }
+            Ok(saved) => app.notify_with_duration(&format!("Saved {} contacts to {}", contacts.len(), saved.display()), 5),
+            Err(e) => {
+                crate::warn_log!("/save-contact to {} failed: {}", path.display(), e);
+                app.notify(&write_failed_notice(&path, &e));
+            }
+        }
+    }
+
+    async fn handle_forward(app: &mut App, cmd: &Command, pane_idx: usize) -> Result<()> {
+        let usage = "Usage: /forward N @user [@user ...]";
+        let Some(msg_num) = cmd.args.first().and_then(|n| n.trim_start_matches('#').parse::<usize>().ok()) else {
+            app.notify(usage);
             return Ok(());
         };
+        if cmd.args.len() < 2 {
+            app.notify(usage);
+            return Ok(());
+        }
 
-        app.notify(&format!("Forwarding #{} to {}...", msg_num, target));
+        // Unresolved targets are reported and skipped, the rest still get the message
+        let mut targets: Vec<(String, String)> = Vec::new();
+        let mut unresolved: Vec<String> = Vec::new();
+        for target in &cmd.args[1..] {
+            match crate::whatsapp::resolve_chat_target(&app.chats, target) {
+                Some((jid, name, _)) if !targets.iter().any(|(j, _)| *j == jid) => targets.push((jid, name)),
+                Some(_) => {}
+                None => unresolved.push(target.clone()),
+            }
+        }
+        Self::forward_to_chats(app, pane_idx, msg_num, pane_idx, targets, unresolved).await;
+        Ok(())
+    }
 
-        // Resolve target
-        match app.whatsapp.resolve_username(target).await {
-            Ok(Some((to_chat_id, _name, _is_group))) => {
-                match app.whatsapp.forward_message(&from_chat_id, &message_id, &to_chat_id).await {
-                    Ok(_) => {
-                        if let Some(pane) = app.panes.get_mut(pane_idx) {
-                            pane.add_message(format!("✓ Forwarded #{} to {}", msg_num, target));
-                        }
-                        app.notify(&format!("Forwarded to {}", target));
-                    }
-                    Err(e) => {
-                        app.notify(&format!("Forward failed: {}", e));
-                    }
+    /// /forward-to-current P N: forward message #N of pane P into the focused pane's chat
+    async fn handle_forward_to_current(app: &mut App, cmd: &Command, pane_idx: usize) {
+        let usage = "Usage: /forward-to-current P N (message #N of pane P)";
+        let numbers: Vec<usize> = cmd.args.iter().filter_map(|a| a.trim_start_matches('#').parse().ok()).collect();
+        let [pane_number, msg_num] = numbers[..] else {
+            app.notify(usage);
+            return;
+        };
+        if cmd.args.len() != 2 {
+            app.notify(usage);
+            return;
+        }
+        let all_panes = app.pane_tree.get_pane_indices();
+        let Some(&from_pane) = pane_number.checked_sub(1).and_then(|pos| all_panes.get(pos)) else {
+            app.notify(&format!("No pane {} (have {})", pane_number, all_panes.len()));
+            return;
+        };
+        let Some(chat_id) = app.panes.get(pane_idx).and_then(|p| p.chat_id.clone()) else {
+            app.notify("No chat selected");
+            return;
+        };
+        let name = app.panes[pane_idx].chat_name.clone();
+        Self::forward_to_chats(app, from_pane, msg_num, pane_idx, vec![(chat_id, name)], Vec::new()).await;
+    }
+
+    /// Forward message #`msg_num` of pane `from_pane` to each of `targets` (JID, name) in
+    /// turn, then sum up what worked in one notification and a line in `report_pane`
+    async fn forward_to_chats(
+        app: &mut App,
+        from_pane: usize,
+        msg_num: usize,
+        report_pane: usize,
+        targets: Vec<(String, String)>,
+        unresolved: Vec<String>,
+    ) {
+        let Some(pane) = app.panes.get(from_pane) else {
+            return;
+        };
+        let Some(from_chat_id) = pane.chat_id.clone() else {
+            app.notify("No chat selected");
+            return;
+        };
+        let Some(msg) = msg_num.checked_sub(1).and_then(|i| pane.msg_data.get(i)) else {
+            app.notify(&format!("Message #{} not found", msg_num));
+            return;
+        };
+        if msg.msg_id.is_empty() {
+            app.notify(&format!("Message #{} hasn't been sent yet", msg_num));
+            return;
+        }
+        let message_id = msg.msg_id.clone();
+        let text = (msg.media_type.is_none()).then(|| msg.text.clone());
+
+        let mut forwarded = Vec::new();
+        let mut failed = Vec::new();
+        if !targets.is_empty() {
+            app.notify(&format!("Forwarding #{} to {} chat(s)...", msg_num, targets.len()));
+        }
+        for (to_chat_id, name) in targets {
+            match app
+                .whatsapp
+                .forward_message(&from_chat_id, &message_id, &to_chat_id, text.as_deref())
+                .await
+            {
+                Ok(()) => forwarded.push(name),
+                Err(e) => {
+                    crate::warn_log!("/forward #{} to {} failed: {}", msg_num, to_chat_id, e);
+                    failed.push(format!("{} ({})", name, e));
                 }
             }
-            Ok(None) => {
-                app.notify(&format!("User '{}' not found", target));
+        }
+
+        if !forwarded.is_empty() {
+            if let Some(pane) = app.panes.get_mut(report_pane) {
+                pane.add_message(format!("✓ Forwarded #{} to {}", msg_num, forwarded.join(", ")));
             }
-            Err(e) => {
-                app.notify(&format!("Lookup failed: {}", e));
+        }
+        app.notify(&forward_summary(msg_num, &forwarded, &failed, &unresolved));
+    }
+}
+
+/// One-line outcome of a forward to several chats
+fn forward_summary(msg_num: usize, forwarded: &[String], failed: &[String], unresolved: &[String]) -> String {
+    let mut parts = Vec::new();
+    if !forwarded.is_empty() {
+        parts.push(format!("Forwarded #{} to {}", msg_num, forwarded.join(", ")));
+    }
+    if !failed.is_empty() {
+        parts.push(format!("failed: {}", failed.join(", ")));
+    }
+    if !unresolved.is_empty() {
+        parts.push(format!("not found: {}", unresolved.join(", ")));
+    }
+    if parts.is_empty() {
+        return format!("Nothing to forward #{} to", msg_num);
+    }
+    parts.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::widgets::ChatPane;
+    use ratatui::style::Color;
+
+    fn msg(id: &str, text: &str) -> MessageData {
+        MessageData {
+            msg_id: id.to_string(),
+            sender_id: "bob@s.whatsapp.net".to_string(),
+            sender_name: "Bob".to_string(),
+            text: text.to_string(),
+            is_outgoing: false,
+            timestamp: 0,
+            media_type: None,
+            media_label: None,
+            location: None,
+            contacts: Vec::new(),
+            reactions: HashMap::new(),
+            reply_to_msg_id: None,
+            reply_sender: None,
+            reply_text: None,
+            pending: false,
+            sending: false,
+            send_status: crate::widgets::SendStatus::Sent,
+            receipts: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_picks_the_text_or_first_link() {
+        let with_link = msg("1", "  see https://example.com/a?b=1 and https://other.org  ");
+        assert_eq!(copy_text(&with_link, false), Some("see https://example.com/a?b=1 and https://other.org"));
+        assert_eq!(copy_text(&with_link, true), Some("https://example.com/a?b=1"));
+        assert_eq!(copy_text(&msg("2", "no links here"), true), None);
+        assert_eq!(copy_text(&msg("3", ""), false), None);
+
+        let mut app = App::new_for_test();
+        app.panes[0].msg_data = vec![msg("1", "plain"), msg("2", "")];
+        CommandHandler::handle(&mut app, "/copy", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Usage: /copy N [link]"));
+        CommandHandler::handle(&mut app, "/copy 1 url", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Usage: /copy N [link]"));
+        CommandHandler::handle(&mut app, "/copy 5", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #5 not found"));
+        CommandHandler::handle(&mut app, "/copy 1 link", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #1 has no link to copy"));
+        CommandHandler::handle(&mut app, "/copy #2", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #2 has no text to copy"));
+    }
+
+    #[tokio::test]
+    async fn test_locate_needs_a_location() {
+        assert_eq!(map_url(59.3293, -18.5), "https://www.openstreetmap.org/?mlat=59.3293&mlon=-18.5#map=16/59.3293/-18.5");
+
+        let mut app = App::new_for_test();
+        let no_coordinates = MessageData { media_type: Some("location".to_string()), ..msg("2", "") };
+        app.panes[0].msg_data = vec![msg("1", "meet at 5"), no_coordinates];
+        CommandHandler::handle(&mut app, "/locate", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Usage: /locate N"));
+        CommandHandler::handle(&mut app, "/locate 1", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #1 isn't a location"));
+        CommandHandler::handle(&mut app, "/map #2", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Location #2 came without coordinates"));
+        CommandHandler::handle(&mut app, "/locate 3", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #3 not found"));
+    }
+
+    #[tokio::test]
+    async fn test_save_contact_writes_the_vcards() {
+        let anna = "BEGIN:VCARD\nVERSION:3.0\nFN:Anna Berg\nTEL:+46701234567\nEND:VCARD";
+        let bob = "BEGIN:VCARD\nVERSION:3.0\nFN:Bob\nEND:VCARD";
+        let share = |id: &str, content: &str| {
+            MessageData::from_raw(
+                &(id.to_string(), "bob@s.whatsapp.net".to_string(), "Bob".to_string(), content.to_string(), None, Some("contact".to_string()), HashMap::new(), 0),
+                "me@s.whatsapp.net",
+            )
+        };
+        let mut app = App::new_for_test();
+        app.panes[0].msg_data = vec![msg("1", "hi"), share("2", anna), share("3", &format!("{}\n{}", anna, bob))];
+        assert_eq!(default_vcf_path(&app.panes[0].msg_data[1].contacts).file_name().unwrap(), "Anna_Berg.vcf");
+        assert_eq!(default_vcf_path(&app.panes[0].msg_data[2].contacts).file_name().unwrap(), "Anna_Berg_and_1_more.vcf");
+
+        CommandHandler::handle(&mut app, "/save-contact 1", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #1 doesn't share a contact"));
+
+        let dir = std::env::temp_dir().join(format!("whatsapp_vcf_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let one = dir.join("anna.vcf");
+        CommandHandler::handle(&mut app, &format!("/save-contact 2 {}", one.display()), 0).await.unwrap();
+        assert_eq!(app.status_message, Some(format!("Saved Anna Berg to {}", one.display())));
+        assert_eq!(std::fs::read_to_string(&one).unwrap(), format!("{}\n", anna));
+
+        let both = dir.join("both.vcf");
+        CommandHandler::handle(&mut app, &format!("/vcf #3 {}", both.display()), 0).await.unwrap();
+        assert_eq!(app.status_message, Some(format!("Saved 2 contacts to {}", both.display())));
+        assert_eq!(std::fs::read_to_string(&both).unwrap(), format!("{}\n{}\n", anna, bob));
+
+        // An existing file is never replaced
+        CommandHandler::handle(&mut app, &format!("/vcf 2 {}", both.display()), 0).await.unwrap();
+        assert_eq!(app.status_message, Some(format!("{} already exists, give another path", both.display())));
+        assert_eq!(std::fs::read_to_string(&both).unwrap(), format!("{}\n{}\n", anna, bob));
+        let (second, _) = create_numbered(&one).unwrap();
+        assert_eq!(second, dir.join("anna_2.vcf"));
+        assert_eq!(create_numbered(&one).unwrap().0, dir.join("anna_3.vcf"));
+        assert_eq!(std::fs::read_to_string(&one).unwrap(), format!("{}\n", anna));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_diag_writes_redacted_report() {
+        let mut app = App::new_for_test();
+        app.my_user_jid = "46760789806@s.whatsapp.net".to_string();
+        app.muted_chats.insert("120363025@g.us".to_string());
+        app.config.settings.muted_chats = app.muted_chats.clone();
+        let path = std::env::temp_dir().join(format!("whatsapp_diag_test_{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let command = format!("/diag {}", path.display());
+        assert!(CommandHandler::handle(&mut app, &command, 0).await.unwrap());
+        let report = std::fs::read_to_string(&path).unwrap();
+        // The report isn't written over an existing file
+        assert!(CommandHandler::handle(&mut app, &command, 0).await.unwrap());
+        let kept = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(kept, report);
+        assert_eq!(
+            app.status_message,
+            Some(format!("Diagnostics failed: {} already exists, give another path", path.display()))
+        );
+        for section in ["== Client ==", "== Connection ==", "== whatsapp-cli ==", "== Config ==", "== Store ==", "== Log =="] {
+            assert!(report.contains(section), "missing {}", section);
+        }
+        assert!(report.contains("own JID: <jid-1>@s.whatsapp.net"), "{}", report);
+        assert!(report.contains("<jid-2>@g.us"), "{}", report);
+        assert!(!report.contains("46760789806"));
+        assert!(!report.contains("120363025"));
+    }
+
+    #[tokio::test]
+    async fn test_whoami_shows_login_instructions_when_not_authenticated() {
+        let mut app = App::new_for_test();
+        assert!(CommandHandler::handle(&mut app, "/whoami", 0).await.unwrap());
+        let status = app.status_message.clone().unwrap();
+        assert!(status.starts_with("Not logged in"), "{}", status);
+        assert!(status.contains(&app.whatsapp.auth_command()), "{}", status);
+    }
+
+    #[tokio::test]
+    async fn test_color_pins_and_unpins_sender_color() {
+        let mut app = App::new_for_test();
+        app.panes[0].msg_data = vec![msg("1", "hi")];
+        let palette_color = app.sender_color("bob@s.whatsapp.net");
+
+        CommandHandler::handle(&mut app, "/color 1 #ff8800", 0).await.unwrap();
+        assert_eq!(app.sender_color("bob@s.whatsapp.net"), Color::Rgb(0xff, 0x88, 0x00));
+
+        CommandHandler::handle(&mut app, "/color 1 nope", 0).await.unwrap();
+        assert_eq!(app.sender_color("bob@s.whatsapp.net"), Color::Rgb(0xff, 0x88, 0x00));
+
+        CommandHandler::handle(&mut app, "/color 1 off", 0).await.unwrap();
+        assert_eq!(app.sender_color("bob@s.whatsapp.net"), palette_color);
+    }
+
+    #[tokio::test]
+    async fn test_goto_scrolls_message_to_top() {
+        let mut app = App::new_for_test();
+        app.compact_mode = false;
+        app.show_timestamps = false;
+        app.show_line_numbers = false;
+        app.panes[0].msg_data = (1..=5).map(|i| msg(&i.to_string(), "hi")).collect();
+        app.panes[0].last_message_width.set(40);
+
+        // Each message is one line plus a blank separator, so #3 starts on line 4
+        assert!(CommandHandler::handle(&mut app, "/goto 3", 0).await.unwrap());
+        assert_eq!(app.panes[0].scroll_offset, 5);
+
+        CommandHandler::handle(&mut app, "/goto 5", 0).await.unwrap();
+        assert_eq!(app.panes[0].scroll_offset, 0);
+
+        app.panes[0].scroll_offset = 7;
+        CommandHandler::handle(&mut app, "/goto 9", 0).await.unwrap();
+        assert_eq!(app.panes[0].scroll_offset, 7);
+        assert_eq!(app.status_message.as_deref(), Some("No message #9 (have 5)"));
+    }
+
+    #[tokio::test]
+    async fn test_date_loads_older_history_and_falls_back_to_earlier_day() {
+        let group = "team@g.us";
+        // One message a day at noon UTC through January and February, skipping January 10th
+        let stamps: Vec<(String, String)> = (0..60)
+            .filter(|&day| day != 9)
+            .map(|day| {
+                let date = chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + chrono::Days::new(day);
+                (format!("m{}", day), format!("{}T12:00:00Z", date))
+            })
+            .collect();
+        let rows: Vec<(&str, &str, &str, &str, &str, bool)> = stamps
+            .iter()
+            .map(|(id, ts)| (id.as_str(), group, "a@s.whatsapp.net", "hi", ts.as_str(), false))
+            .collect();
+
+        let mut app = App::new_for_test();
+        app.whatsapp = crate::whatsapp::WhatsAppClient::new_for_test_with_messages("date", &rows);
+        app.panes[0].chat_id = Some(group.to_string());
+        let recent = app.whatsapp.get_messages(group, 10).await.unwrap();
+        app.panes[0].msg_data = recent.iter().map(|raw| MessageData::from_raw(raw, &app.my_user_jid)).collect();
+        app.panes[0].last_message_width.set(40);
+
+        CommandHandler::handle(&mut app, "/date 2024-01-05", 0).await.unwrap();
+        assert_eq!(app.panes[0].msg_data.first().map(|m| m.msg_id.as_str()), Some("m0"));
+        assert_eq!(app.status_message.as_deref(), Some("2024-01-05 (#5)"));
+        assert!(app.panes[0].scroll_offset > 0);
+
+        CommandHandler::handle(&mut app, "/date 2024-01-10", 0).await.unwrap();
+        let status = app.status_message.clone().unwrap();
+        assert!(status.starts_with("No messages on 2024-01-10, showing the last one before it from 2024-01-09"), "{}", status);
+
+        CommandHandler::handle(&mut app, "/date 2023-12-31", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("No messages on or before 2023-12-31"));
+        CommandHandler::handle(&mut app, "/date 7d", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Usage: /date YYYY-MM-DD"));
+    }
+
+    #[tokio::test]
+    async fn test_export_writes_full_history() {
+        let group = "export@g.us";
+        let rows: Vec<(String, String)> = (0..60)
+            .map(|i| (format!("m{}", i), format!("2024-01-01T10:{:02}:00Z", i % 60)))
+            .collect();
+        let rows: Vec<(&str, &str, &str, &str, &str, bool)> = rows
+            .iter()
+            .map(|(id, ts)| (id.as_str(), group, "a@s.whatsapp.net", id.as_str(), ts.as_str(), false))
+            .collect();
+        let mut app = App::new_for_test();
+        app.whatsapp = crate::whatsapp::WhatsAppClient::new_for_test_with_messages("export", &rows);
+        app.panes[0].chat_id = Some(group.to_string());
+        app.panes[0].chat_name = "Export Test".to_string();
+        app.panes[0].msg_data = vec![msg("m59", "m59")];
+
+        let path = std::env::temp_dir().join(format!("whatsapp_export_test_{}.md", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        CommandHandler::handle(&mut app, &format!("/export md {}", path.display()), 0).await.unwrap();
+        assert_eq!(
+            app.status_message,
+            Some(format!("Exported 60 messages to {}", path.display()))
+        );
+        let written = std::fs::read_to_string(&path).unwrap();
+        // A second export doesn't replace the first
+        CommandHandler::handle(&mut app, &format!("/export {}", path.display()), 0).await.unwrap();
+        let again = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            app.status_message,
+            Some(format!("Export failed: {} already exists, give another path", path.display()))
+        );
+        assert_eq!(again, written);
+        assert!(written.starts_with("# Export Test\n"));
+        assert!(written.contains("\nm0\n") && written.contains("\nm59\n"));
+
+        assert!(default_export_path("Family / Friends", "txt")
+            .to_string_lossy()
+            .contains("whatsapp_export_Family___Friends_"));
+    }
+
+    #[test]
+    fn test_send_media_arguments() {
+        assert_eq!(split_path_and_caption("pic.jpg"), Some(("pic.jpg".to_string(), None)));
+        assert_eq!(
+            split_path_and_caption("~/pic.jpg look at this"),
+            Some(("~/pic.jpg".to_string(), Some("look at this".to_string())))
+        );
+        assert_eq!(
+            split_path_and_caption("\"My Photos/a b.png\" hi"),
+            Some(("My Photos/a b.png".to_string(), Some("hi".to_string())))
+        );
+        assert_eq!(split_path_and_caption("  "), None);
+        assert_eq!(split_path_and_caption("\"unterminated"), None);
+
+        if let Some(home) = dirs::home_dir() {
+            assert_eq!(expand_user_path("~/a.png"), home.join("a.png"));
+        }
+        assert_eq!(expand_user_path("a.png"), std::env::current_dir().unwrap().join("a.png"));
+        assert_eq!(expand_user_path("/tmp/a.png"), PathBuf::from("/tmp/a.png"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_only_own_recent_messages_and_resend_is_opt_in() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("bob@s.whatsapp.net".to_string());
+        let now = chrono::Utc::now().timestamp();
+        let mine = |id: &str, timestamp: i64| MessageData {
+            is_outgoing: true,
+            timestamp,
+            ..msg(id, "teh typo")
+        };
+        app.panes[0].msg_data = vec![msg("1", "hi"), mine("2", now - 20 * 60), mine("3", now - 60)];
+
+        CommandHandler::handle(&mut app, "/edit 1 hello", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("You can only edit your own messages"));
+        CommandHandler::handle(&mut app, "/edit 2 the typo", 0).await.unwrap();
+        assert!(app.status_message.as_deref().unwrap().contains("too old to edit"));
+
+        // No edit command and no opt-in: nothing is deleted
+        app.whatsapp.set_edit_supported(false);
+        CommandHandler::handle(&mut app, "/edit 3 the typo", 0).await.unwrap();
+        assert!(app.status_message.as_deref().unwrap().contains("set edit_resend"));
+        assert_eq!(app.panes[0].msg_data[2].text, "teh typo");
+
+        // Opted in, a failed delete leaves the message as it was
+        app.edit_resend = true;
+        CommandHandler::handle(&mut app, "/edit 3 the typo", 0).await.unwrap();
+        assert!(app.status_message.as_deref().unwrap().contains("couldn't delete the original"));
+        assert_eq!(app.panes[0].msg_data.len(), 3);
+        assert_eq!(app.panes[0].msg_data[2].msg_id, "3");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_delete_marks_own_message_and_explains_too_old() {
+        // WhatsApp refuses message "OLD" as past the window, whatever the local clock says
+        let (_dir, whatsapp) = crate::whatsapp::tests::fake_cli(
+            "#!/bin/sh\ncase \"$*\" in\n*OLD*) echo '{\"success\":false,\"error\":\"revoke: message too old\"}'; exit 1;;\n*) echo '{\"success\":true}';;\nesac\n",
+        );
+        let mut app = App::new_for_test();
+        app.whatsapp = whatsapp;
+        app.panes[0].chat_id = Some("bob@s.whatsapp.net".to_string());
+        let now = chrono::Utc::now().timestamp();
+        let mine = |id: &str, timestamp: i64| MessageData { is_outgoing: true, timestamp, ..msg(id, "oops") };
+        app.panes[0].msg_data = vec![msg("1", "hi"), mine("2", now - 3 * 24 * 3600), mine("OLD", now - 60), mine("4", now - 60)];
+
+        CommandHandler::handle(&mut app, "/delete 1", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("You can only delete your own messages"));
+        CommandHandler::handle(&mut app, "/delete 2", 0).await.unwrap();
+        assert!(app.status_message.as_deref().unwrap().contains("too old to delete"));
+        CommandHandler::handle(&mut app, "/delete 3", 0).await.unwrap();
+        CommandHandler::answer_confirm(&mut app, true).await;
+        assert_eq!(app.status_message.as_deref(), Some(format!("✗ Delete failed: {}", crate::whatsapp::DELETE_TOO_OLD).as_str()));
+        assert_eq!(app.panes[0].msg_data[2].text, "oops");
+
+        CommandHandler::handle(&mut app, "/delete #4", 0).await.unwrap();
+        CommandHandler::answer_confirm(&mut app, true).await;
+        assert_eq!(app.status_message.as_deref(), Some("✓ Deleted message #4"));
+        // Still there, so later message numbers stay the same
+        assert_eq!(app.panes[0].msg_data.len(), 4);
+        assert_eq!(app.panes[0].msg_data[3].text, DELETED_TEXT);
+    }
+
+    #[tokio::test]
+    async fn test_from_sets_pane_identity() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_name = "Shop".to_string();
+        CommandHandler::handle(&mut app, "/from +46700000001", 0).await.unwrap();
+        assert_eq!(app.panes[0].send_as.as_deref(), Some("+46700000001"));
+        assert_eq!(app.panes[0].header_text(), "Shop [as +46700000001]");
+        assert_eq!(app.current_layout().panes[0].send_as.as_deref(), Some("+46700000001"));
+
+        CommandHandler::handle(&mut app, "/from off", 0).await.unwrap();
+        assert!(app.panes[0].send_as.is_none());
+        assert_eq!(app.panes[0].header_text(), "Shop");
+    }
+
+    #[test]
+    fn test_command_candidates_complete_names_and_long_aliases() {
+        assert_eq!(command_candidates("re"), vec!["reply", "react", "receipts", "reconnect", "remove", "reload"]);
+        assert_eq!(command_candidates("reply"), vec!["reply"]);
+        // Short aliases only when typed in full; each command once
+        assert_eq!(command_candidates("d"), vec!["delete", "date", "dnd", "diag"]);
+        assert_eq!(command_candidates("sa"), vec!["save-contact", "sa"]);
+        assert_eq!(command_candidates("fwd"), vec!["fwd", "fwdhere"]);
+        assert!(command_candidates("zz").is_empty());
+
+        assert_eq!(command_hint("r").as_deref(), Some("/reply N [text] - Reply to message #N"));
+        assert_eq!(
+            command_cycle_hint(&["reply", "react", "remove"], 1),
+            "/react N [emoji] - React to message #N  |  Tab: /remove /reply"
+        );
+        assert!(needs_arguments("reply"));
+        assert!(needs_arguments("kick"));
+        assert!(!needs_arguments("leave"));
+        assert!(!needs_arguments("new"));
+        assert!(!needs_arguments("unread"));
+    }
+
+    #[tokio::test]
+    async fn test_every_listed_command_is_dispatched() {
+        let mut names = std::collections::HashSet::new();
+        for info in COMMANDS {
+            for name in info.names {
+                assert!(names.insert(*name), "/{} listed twice", name);
+                let mut app = App::new_for_test();
+                assert!(
+                    CommandHandler::handle(&mut app, &format!("/{}", name), 0).await.unwrap(),
+                    "/{} is listed but not handled",
+                    name
+                );
             }
         }
+        let mut app = App::new_for_test();
+        assert!(!CommandHandler::handle(&mut app, "/nosuchcommand", 0).await.unwrap());
+    }
 
-        Ok(())
+    #[tokio::test]
+    async fn test_self_opens_message_yourself_chat() {
+        let mut app = App::new_for_test();
+        app.my_user_jid = crate::whatsapp::UNKNOWN_JID.to_string();
+        CommandHandler::handle(&mut app, "/self", 0).await.unwrap();
+        assert!(app.panes[0].chat_id.is_none());
+        assert!(app.status_message.as_deref().unwrap().contains("isn't known yet"));
+
+        app.my_user_jid = "46700000000@s.whatsapp.net".to_string();
+        CommandHandler::handle(&mut app, "/self", 0).await.unwrap();
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("46700000000@s.whatsapp.net"));
+        assert_eq!(app.panes[0].chat_name, "You");
+        let listed: Vec<&ChatInfo> = app.chats.iter().filter(|c| app.is_self_chat(&c.id)).collect();
+        assert_eq!(listed.len(), 1);
+
+        // Running it again reuses the listed chat
+        CommandHandler::handle(&mut app, "/me", 0).await.unwrap();
+        assert_eq!(app.chats.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_send_media_checks_file_and_drops_echo_on_failure() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+
+        CommandHandler::handle(&mut app, "/send-media /nonexistent/pic.jpg", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("File not found: /nonexistent/pic.jpg"));
+        assert!(app.panes[0].msg_data.is_empty());
+
+        // The test client has no whatsapp-cli, so the send itself fails
+        let file = std::env::temp_dir().join(format!("send_media_{}.png", std::process::id()));
+        std::fs::write(&file, b"png").unwrap();
+        let command = format!("/send-media {} caption", file.display());
+        CommandHandler::handle(&mut app, &command, 0).await.unwrap();
+        assert!(app.panes[0].msg_data.is_empty());
+        assert!(!app.status_message.as_deref().unwrap_or_default().starts_with('✓'));
+        let _ = std::fs::remove_file(file);
+    }
+
+    #[tokio::test]
+    async fn test_filter_add_and_remove_combine_filters() {
+        let mut app = App::new_for_test();
+
+        CommandHandler::handle(&mut app, "/filter photo", 0).await.unwrap();
+        CommandHandler::handle(&mut app, "/filter add bob", 0).await.unwrap();
+        CommandHandler::handle(&mut app, "/filter add since 2024-03-10", 0).await.unwrap();
+        let kinds: Vec<&str> = app.panes[0].filters.iter().map(|f| f.kind.name()).collect();
+        assert_eq!(kinds, vec!["media", "sender", "time"]);
+
+        // Adding a filter of a kind already present replaces it
+        CommandHandler::handle(&mut app, "/filter add alice", 0).await.unwrap();
+        assert_eq!(app.panes[0].filters.len(), 3);
+        assert_eq!(app.panes[0].filters[2], Filter::new(FilterType::Sender, "alice"));
+
+        CommandHandler::handle(&mut app, "/filter remove time", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Filtering: media=photo AND sender=alice"));
+        CommandHandler::handle(&mut app, "/filter remove time", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("No time filter active"));
+
+        CommandHandler::handle(&mut app, "/filter off", 0).await.unwrap();
+        assert!(app.panes[0].filters.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_filter_clear_all_clears_every_pane() {
+        let mut app = App::new_for_test();
+        app.panes.push(ChatPane::new());
+        app.panes.push(ChatPane::new());
+
+        CommandHandler::handle(&mut app, "/filter photo", 0).await.unwrap();
+        CommandHandler::handle(&mut app, "/filter link", 2).await.unwrap();
+        CommandHandler::handle(&mut app, "/filter clear-all", 1).await.unwrap();
+
+        assert!(app.panes.iter().all(|p| p.filters.is_empty()));
+        assert_eq!(app.status_message.as_deref(), Some("Filters cleared in 2 pane(s)"));
+        // The toggle key can still bring a pane's filter back
+        assert!(app.panes[2].toggle_filter());
+        assert_eq!(app.panes[2].filters, vec![Filter::new(FilterType::Link, "link")]);
+    }
+
+    #[tokio::test]
+    async fn test_unread_view_toggles_and_clears_once_caught_up() {
+        let mut app = App::new_for_test();
+        app.panes[0].msg_data = (1..=10).map(|i| msg(&i.to_string(), "hi")).collect();
+
+        CommandHandler::handle(&mut app, "/unread", 0).await.unwrap();
+        assert!(!app.panes[0].unread_only);
+        assert_eq!(app.status_message.as_deref(), Some("No unread messages"));
+
+        app.panes[0].unread_count_at_load = 4;
+        CommandHandler::handle(&mut app, "/unread", 0).await.unwrap();
+        assert!(app.panes[0].unread_only);
+
+        // Messages arriving while catching up count as unread too
+        app.panes[0].merge_new_messages(vec![msg("11", "more")]);
+        assert_eq!(app.panes[0].unread_count_at_load, 5);
+
+        CommandHandler::handle(&mut app, "/unread", 0).await.unwrap();
+        assert!(!app.panes[0].unread_only);
+        assert_eq!(app.panes[0].unread_count_at_load, 0);
+    }
+
+    #[tokio::test]
+    async fn test_set_clamps_intervals() {
+        let mut app = App::new_for_test();
+        app.config.config_dir = std::env::temp_dir().join(format!("whatsapp_set_test_{}", std::process::id()));
+        std::fs::create_dir_all(&app.config.config_dir).unwrap();
+
+        CommandHandler::handle(&mut app, "/set chat_refresh_secs 15", 0).await.unwrap();
+        assert_eq!(app.chat_refresh_secs, 15);
+        CommandHandler::handle(&mut app, "/set chat_refresh_secs 0", 0).await.unwrap();
+        assert_eq!(app.chat_refresh_secs, 1);
+        CommandHandler::handle(&mut app, "/set event_poll_ms 5", 0).await.unwrap();
+        assert_eq!(app.event_poll_ms, 50);
+        assert_eq!(app.status_message.as_deref(), Some("event_poll_ms = 50"));
+
+        CommandHandler::handle(&mut app, "/set event_poll_ms soon", 0).await.unwrap();
+        assert_eq!(app.event_poll_ms, 50);
+
+        let saved = std::fs::read_to_string(app.config.config_dir.join("whatsapp_config.json")).unwrap();
+        assert!(saved.contains("\"event_poll_ms\": 50"));
+        let _ = std::fs::remove_dir_all(&app.config.config_dir);
+    }
+
+    #[tokio::test]
+    async fn test_layout_save_and_load_round_trip() {
+        let mut app = App::new_for_test();
+        app.config.config_dir = std::env::temp_dir().join(format!("whatsapp_layouts_test_{}", std::process::id()));
+        std::fs::create_dir_all(&app.config.config_dir).unwrap();
+        app.chats = vec![crate::app::ChatInfo {
+            id: "111@s.whatsapp.net".to_string(),
+            name: "Alice".to_string(),
+            username: None,
+            unread: 0,
+            is_channel: false,
+            is_group: false,
+            last_message_ts: 0,
+        }];
+
+        app.split_vertical();
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        app.panes[1].chat_id = Some("gone@s.whatsapp.net".to_string());
+        app.focused_pane_idx = 1;
+        CommandHandler::handle(&mut app, "/layout save work", 0).await.unwrap();
+
+        app.close_pane();
+        assert_eq!(app.pane_tree.count_panes(), 1);
+
+        CommandHandler::handle(&mut app, "/layout load work", 0).await.unwrap();
+        assert_eq!(app.pane_tree.count_panes(), 2);
+        assert_eq!(app.focused_pane_idx, 1);
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("111@s.whatsapp.net"));
+        assert_eq!(app.panes[0].chat_name, "Alice");
+        // The chat that no longer exists leaves its pane empty
+        assert!(app.panes[1].chat_id.is_none());
+
+        CommandHandler::handle(&mut app, "/layout delete work", 0).await.unwrap();
+        CommandHandler::handle(&mut app, "/layout list", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("No saved layouts"));
+
+        let _ = std::fs::remove_dir_all(&app.config.config_dir);
+    }
+
+    fn hit(chat_jid: &str, msg_id: &str, text: &str) -> SearchHit {
+        SearchHit {
+            chat_jid: chat_jid.to_string(),
+            chat_name: None,
+            msg_id: msg_id.to_string(),
+            sender_id: "bob@s.whatsapp.net".to_string(),
+            sender_name: "Bob".to_string(),
+            text: text.to_string(),
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_search_results_grouped_by_chat() {
+        let hits = vec![
+            hit("a@g.us", "1", "lunch?"),
+            hit("b@s.whatsapp.net", "2", "lunch at noon"),
+            hit("a@g.us", "3", "lunch\nsecond line"),
+        ];
+        let names = HashMap::from([("a@g.us".to_string(), "Team".to_string())]);
+        let (lines, ordered) = format_search_results("lunch", hits, &names);
+
+        let ids: Vec<&str> = ordered.iter().map(|h| h.msg_id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "3", "2"]);
+        assert_eq!(lines[0], "3 results for 'lunch' in 2 chats");
+        assert_eq!(lines[2], "── Team (2) ──");
+        assert!(lines[3].starts_with("  #1 ") && lines[3].ends_with("Bob: lunch?"));
+        assert!(lines[4].starts_with("  #2 ") && lines[4].ends_with("Bob: lunch"));
+        assert_eq!(lines[6], "── b@s.whatsapp.net (1) ──");
+        assert!(lines[7].starts_with("  #3 "));
+    }
+
+    #[tokio::test]
+    async fn test_msginfo_lists_recipients_of_own_messages() {
+        let info = MessageInfo {
+            totals: crate::widgets::Receipts { delivered: 2, read: 1, recipients: Some(5) },
+            recipients: vec![
+                ("1@s.whatsapp.net".to_string(), "Alice".to_string(), ReceiptStatus::Read, None),
+                ("2@s.whatsapp.net".to_string(), "Bob".to_string(), ReceiptStatus::Delivered, None),
+            ],
+        };
+        assert_eq!(
+            message_info_lines(3, &info, true),
+            vec![
+                "--- Message info #3 ---",
+                "  Read by 1 of 5, delivered to 2",
+                &format!("  {} Alice", READ_MARKER),
+                "  ✓✓ Bob",
+                "  3 more not delivered yet",
+                "---",
+            ]
+        );
+        assert_eq!(message_info_lines(1, &MessageInfo::default(), false)[1], "  ✓ Sent, not delivered yet");
+
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("team@g.us".to_string());
+        app.panes[0].msg_data = vec![msg("1", "hi")];
+        CommandHandler::handle(&mut app, "/msginfo 1", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message info is only kept for your own messages"));
+        CommandHandler::handle(&mut app, "/msginfo", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Usage: /msginfo N"));
+    }
+
+    #[tokio::test]
+    async fn test_open_needs_a_results_pane() {
+        let mut app = App::new_for_test();
+        CommandHandler::handle(&mut app, "/open 1", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("/open works in a /searchall results pane"));
+
+        app.panes[0].search_hits = vec![hit("a@g.us", "1", "hi")];
+        CommandHandler::handle(&mut app, "/open 1", 0).await.unwrap();
+        assert_eq!(app.panes[0].chat_id.as_deref(), Some("a@g.us"));
+        assert!(app.panes[0].search_hits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_forward_skips_unknown_targets_and_reports_each() {
+        let group = "team@g.us";
+        let rows = [("m1", group, "a@s.whatsapp.net", "hi", "2024-01-01T12:00:00Z", false)];
+        let mut app = App::new_for_test();
+        app.whatsapp = crate::whatsapp::WhatsAppClient::new_for_test_with_messages("forward", &rows);
+        app.chats = vec![crate::app::ChatInfo {
+            id: "111@s.whatsapp.net".to_string(),
+            name: "Alice".to_string(),
+            username: None,
+            unread: 0,
+            is_channel: false,
+            is_group: false,
+            last_message_ts: 0,
+        }];
+        app.panes[0].chat_id = Some(group.to_string());
+        let recent = app.whatsapp.get_messages(group, 10).await.unwrap();
+        app.panes[0].msg_data = recent.iter().map(|raw| MessageData::from_raw(raw, &app.my_user_jid)).collect();
+
+        CommandHandler::handle(&mut app, "/forward 5 alice", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Message #5 not found"));
+
+        // The test CLI doesn't exist, so every resolved target fails; "alice" and the
+        // chat's JID are the same chat and only tried once
+        CommandHandler::handle(&mut app, "/forward 1 alice @nobody 111@s.whatsapp.net @4670123456", 0).await.unwrap();
+        let status = app.status_message.clone().unwrap();
+        assert!(status.starts_with("failed: Alice ("), "{}", status);
+        assert!(status.contains("), 4670123456 ("), "{}", status);
+        assert!(status.ends_with(" | not found: @nobody"), "{}", status);
+        assert!(!app.panes[0].msg_data.is_empty());
+
+        CommandHandler::handle(&mut app, "/forward-to-current 2 1", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("No pane 2 (have 1)"));
+
+        assert_eq!(
+            forward_summary(3, &["Alice".to_string(), "Team".to_string()], &[], &[]),
+            "Forwarded #3 to Alice, Team"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_destructive_commands_wait_for_confirmation() {
+        let mut app = App::new_for_test();
+        app.panes[0].chat_id = Some("team@g.us".to_string());
+        app.panes[0].chat_name = "Team".to_string();
+        let mine = MessageData { is_outgoing: true, timestamp: chrono::Utc::now().timestamp(), ..msg("M1", "oops") };
+        app.panes[0].msg_data = vec![mine];
+
+        // Nothing happens until the answer; no CLI runs either (the test CLI doesn't exist)
+        CommandHandler::handle(&mut app, "/delete 1", 0).await.unwrap();
+        let (prompt, action) = app.pending_confirm.clone().unwrap();
+        assert_eq!(prompt, "Delete message #1 for everyone?");
+        assert_eq!(
+            action,
+            ConfirmAction::DeleteMessage {
+                pane_idx: 0,
+                chat_id: "team@g.us".to_string(),
+                msg_id: "M1".to_string(),
+                msg_num: 1
+            }
+        );
+        CommandHandler::answer_confirm(&mut app, false).await;
+        assert!(app.pending_confirm.is_none());
+        assert_eq!(app.status_message.as_deref(), Some("Cancelled"));
+        assert_eq!(app.panes[0].msg_data[0].text, "oops");
+
+        CommandHandler::handle(&mut app, "/kick 4670", 0).await.unwrap();
+        assert_eq!(app.pending_confirm.as_ref().unwrap().0, "Remove 4670 from Team?");
+        CommandHandler::answer_confirm(&mut app, false).await;
+        assert_eq!(app.panes[0].msg_data.len(), 1);
+        assert_eq!(app.status_message.as_deref(), Some("Cancelled"));
+
+        // A stray answer with nothing pending does nothing
+        CommandHandler::answer_confirm(&mut app, true).await;
+        assert_eq!(app.status_message.as_deref(), Some("Cancelled"));
+    }
+
+    #[tokio::test]
+    async fn test_leave_needs_confirmation_and_a_group() {
+        let mut app = App::new_for_test();
+        CommandHandler::handle(&mut app, "/leave", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("Open a group chat first"));
+
+        app.panes[0].chat_id = Some("111@s.whatsapp.net".to_string());
+        CommandHandler::handle(&mut app, "/leave", 0).await.unwrap();
+        assert_eq!(app.status_message.as_deref(), Some("/leave only works in a group chat"));
+
+        let group = crate::app::ChatInfo {
+            id: "team@g.us".to_string(),
+            name: "Team".to_string(),
+            username: None,
+            unread: 0,
+            is_channel: false,
+            is_group: true,
+            last_message_ts: 0,
+        };
+        app.chats = vec![group];
+        app.panes.push(ChatPane::new());
+        for pane in &mut app.panes {
+            pane.chat_id = Some("team@g.us".to_string());
+            pane.chat_name = "Team".to_string();
+        }
+        CommandHandler::handle(&mut app, "/leave", 0).await.unwrap();
+        assert_eq!(app.pending_confirm.as_ref().unwrap().0, "Leave Team?");
+
+        // The test CLI doesn't exist, so leaving fails and nothing changes
+        CommandHandler::answer_confirm(&mut app, true).await;
+        assert!(app.status_message.as_deref().unwrap().starts_with("Failed to leave Team: "));
+        assert_eq!(app.chats.len(), 1);
+
+        app.forget_left_group("team@g.us");
+        assert!(app.chats.is_empty());
+        assert!(app.panes.iter().all(|p| p.chat_id.is_none()));
+        assert!(app.left_groups.contains("team@g.us"));
     }
 }
@@ -1,17 +1,55 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::formatting::{ReactionStyle, TimestampStyle, UnreadCountStyle, WrapStyle};
+use crate::keymap::Keymap;
+use crate::theme::Theme;
+
+/// Schema version written to `whatsapp_config.json`. Bump it together with a step in
+/// `migrate_config` when a setting is renamed or changes type.
+pub const CONFIG_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    // Schema version of the file; 0 for files written before versions were recorded
+    #[serde(default)]
+    pub version: u32,
+
     pub whatsapp_cli_path: PathBuf,
+
+    // whatsapp-cli store directory, passed to it as --store. Unset means `store` in the
+    // config directory; see `store_path()`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store_path: Option<PathBuf>,
     
     #[serde(default)]
     pub settings: Settings,
     
     #[serde(skip)]
     pub config_dir: PathBuf,
+
+    // Loaded from its own file, see `keymap_path`
+    #[serde(skip)]
+    pub keymap: Keymap,
+
+    // Why the keymap file was (partly) ignored, shown once at startup
+    #[serde(skip)]
+    pub keymap_note: Option<String>,
+
+    // Loaded from its own file, see `theme_path`
+    #[serde(skip)]
+    pub theme: Theme,
+
+    // Theme fields that were ignored, shown once at startup
+    #[serde(skip)]
+    pub theme_note: Option<String>,
+
+    // Settings that were reset while migrating the file, shown once at startup
+    #[serde(skip)]
+    pub settings_note: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +80,136 @@ pub struct Settings {
 
     #[serde(default = "default_true")]
     pub show_chat_list: bool,
+
+    // Upper bound on concurrently running whatsapp-cli processes
+    #[serde(default = "default_max_concurrent_cli")]
+    pub max_concurrent_cli: usize,
+
+    // Chats (by JID) that never raise notifications, managed with /mute and /unmute
+    #[serde(default)]
+    pub muted_chats: HashSet<String>,
+
+    // Notify about group messages that mention you even when the group is muted
+    #[serde(default = "default_true")]
+    pub notify_on_mention: bool,
+
+    // Do not disturb: new messages raise no desktop notification or status bar popup (/dnd)
+    #[serde(default)]
+    pub dnd: bool,
+
+    // Daily window that behaves like do not disturb, e.g. "22:00-07:00"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quiet_hours: Option<QuietHours>,
+
+    // Chats (by JID) hidden from the chat list, managed with /archive and /unarchive
+    #[serde(default)]
+    pub archived_chats: HashSet<String>,
+
+    // Unarchive an archived chat when a new message arrives in it
+    #[serde(default = "default_true")]
+    pub auto_unarchive: bool,
+
+    // Continuation-line indent of wrapped messages: "aligned", "hanging" or "flush"
+    #[serde(default)]
+    pub wrap_style: WrapStyle,
+
+    // How message times are written: "clock" (14:05, dated before today), "relative"
+    // (2m, 3h, yesterday) or "full" (always date and time)
+    #[serde(default)]
+    pub timestamp_style: TimestampStyle,
+
+    // Reactions after a message: "full" lists every emoji, "summary" shows the total and
+    // the most used one, in full on the message under the list-mode cursor
+    #[serde(default)]
+    pub reaction_style: ReactionStyle,
+
+    // Where a chat reopens when messages arrived since it was last left: "saved" keeps
+    // its old scroll position, "unread" jumps to the unread divider
+    #[serde(default)]
+    pub reopen_scroll: ReopenScroll,
+
+    // Enter in a pane with nothing typed: "nothing", "list" focuses the chat list, "recent"
+    // also opens the most recently active other chat in the pane
+    #[serde(default)]
+    pub enter_on_empty: EnterOnEmpty,
+
+    // Seconds between chat list refreshes (each one runs `whatsapp-cli chats list`)
+    #[serde(default = "default_chat_refresh_secs")]
+    pub chat_refresh_secs: u64,
+
+    // Messages loaded when a chat is opened, and per older batch when scrolling up past the top
+    #[serde(default = "default_initial_message_limit")]
+    pub initial_message_limit: usize,
+
+    // Milliseconds between checks for incoming WhatsApp events
+    #[serde(default = "default_event_poll_ms")]
+    pub event_poll_ms: u64,
+
+    // Show "online" / "last seen" for the focused individual chat
+    #[serde(default = "default_true")]
+    pub show_presence: bool,
+
+    // Show ✓ / ✓✓ delivery and read marks on your messages in the focused chat
+    #[serde(default = "default_true")]
+    pub show_receipts: bool,
+
+    // Show the sender's name once for a run of their messages at most 5 minutes apart
+    #[serde(default)]
+    pub group_consecutive: bool,
+
+    // Footer with the connection, total unread, focused chat and its filter
+    #[serde(default = "default_true")]
+    pub show_status_line: bool,
+
+    // Let contacts see when you're typing (off by default for privacy)
+    #[serde(default)]
+    pub send_typing: bool,
+
+    // Tell the sender's phone that messages were read when a chat is opened or focused
+    #[serde(default = "default_true")]
+    pub send_read_receipts: bool,
+
+    // Let /edit fall back to deleting the message and sending the new text when
+    // whatsapp-cli can't edit (off by default: recipients see a deleted message)
+    #[serde(default)]
+    pub edit_resend: bool,
+
+    // Seconds a sent message is held back so /unsend can take it back (off by default:
+    // 0 sends at once)
+    #[serde(default)]
+    pub undo_send_secs: u64,
+
+    // Seconds between autosaves of layout, aliases and settings (0 turns autosave off)
+    #[serde(default = "default_autosave_secs")]
+    pub autosave_secs: u64,
+
+    // Ask for a second Enter before a message goes to a group chat
+    #[serde(default)]
+    pub confirm_group_send: bool,
+
+    // With confirm_group_send, only ask for groups with at least this many members (0: every group)
+    #[serde(default)]
+    pub confirm_group_min_members: usize,
+
+    // Emoji most recently sent as reactions, newest first; offered first by the picker
+    #[serde(default)]
+    pub recent_reactions: Vec<String>,
+
+    // Country code the new chat wizard puts in front of numbers typed without `+`, e.g. "46"
+    #[serde(default)]
+    pub default_country_code: String,
+
+    // Marker appended to chat names cut short in the chat list
+    #[serde(default = "default_chat_list_ellipsis")]
+    pub chat_list_ellipsis: String,
+
+    // Placement of chat list unread counts: "inline", "badge" or "right"
+    #[serde(default)]
+    pub chat_list_unread: UnreadCountStyle,
+
+    // Colors pinned to senders (by JID) with /color, in theme color syntax
+    #[serde(default)]
+    pub user_colors: HashMap<String, String>,
 }
 
 impl Default for Settings {
@@ -56,6 +224,37 @@ impl Default for Settings {
             show_user_colors: true,
             show_borders: true,
             show_chat_list: true,
+            max_concurrent_cli: default_max_concurrent_cli(),
+            muted_chats: HashSet::new(),
+            notify_on_mention: true,
+            dnd: false,
+            quiet_hours: None,
+            archived_chats: HashSet::new(),
+            auto_unarchive: true,
+            wrap_style: WrapStyle::default(),
+            reopen_scroll: ReopenScroll::default(),
+            enter_on_empty: EnterOnEmpty::default(),
+            timestamp_style: TimestampStyle::default(),
+            reaction_style: ReactionStyle::default(),
+            chat_refresh_secs: default_chat_refresh_secs(),
+            event_poll_ms: default_event_poll_ms(),
+            initial_message_limit: default_initial_message_limit(),
+            show_presence: true,
+            show_receipts: true,
+            group_consecutive: false,
+            show_status_line: true,
+            send_typing: false,
+            send_read_receipts: true,
+            edit_resend: false,
+            undo_send_secs: 0,
+            autosave_secs: default_autosave_secs(),
+            confirm_group_send: false,
+            confirm_group_min_members: 0,
+            recent_reactions: Vec::new(),
+            default_country_code: String::new(),
+            chat_list_ellipsis: default_chat_list_ellipsis(),
+            chat_list_unread: UnreadCountStyle::default(),
+            user_colors: HashMap::new(),
         }
     }
 }
@@ -64,40 +263,268 @@ fn default_true() -> bool {
     true
 }
 
+fn default_max_concurrent_cli() -> usize {
+    4
+}
+
+fn default_chat_refresh_secs() -> u64 {
+    5
+}
+
+fn default_event_poll_ms() -> u64 {
+    500
+}
+
+fn default_initial_message_limit() -> usize {
+    50
+}
+
+fn default_chat_list_ellipsis() -> String {
+    "…".to_string()
+}
+
+/// Keep the chat list refresh interval within 1s..1h
+pub fn clamp_chat_refresh_secs(secs: u64) -> u64 {
+    secs.clamp(1, 3600)
+}
+
+/// Keep the undo-send window within 0..60s
+pub fn clamp_undo_send_secs(secs: u64) -> u64 {
+    secs.min(60)
+}
+
+fn default_autosave_secs() -> u64 {
+    30
+}
+
+/// Keep the autosave interval within 0 (off)..1h
+pub fn clamp_autosave_secs(secs: u64) -> u64 {
+    secs.min(3600)
+}
+
+/// Keep the event poll interval within 50ms..10s
+pub fn clamp_event_poll_ms(ms: u64) -> u64 {
+    ms.clamp(50, 10_000)
+}
+
+/// Keep the per-fetch message count within 10..500
+pub fn clamp_message_limit(limit: usize) -> usize {
+    limit.clamp(10, 500)
+}
+
+/// Where a pane scrolls to when it switches back to a chat with new messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReopenScroll {
+    /// Where the chat was left
+    #[default]
+    Saved,
+    /// The unread divider, with the first new message below it
+    Unread,
+}
+
+/// What Enter does in a pane whose input is empty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnterOnEmpty {
+    #[default]
+    Nothing,
+    /// Move focus to the chat list
+    List,
+    /// Open the most recently active other chat in the pane, with the chat list focused
+    Recent,
+}
+
+/// A daily time window written "HH:MM-HH:MM". It may wrap past midnight ("22:00-07:00");
+/// the start is inside the window and the end isn't, so equal times make an empty window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    pub start: chrono::NaiveTime,
+    pub end: chrono::NaiveTime,
+}
+
+impl QuietHours {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (start, end) = spec.split_once('-')?;
+        let time = |t: &str| chrono::NaiveTime::parse_from_str(t.trim(), "%H:%M").ok();
+        Some(Self { start: time(start)?, end: time(end)? })
+    }
+
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(spec: String) -> std::result::Result<Self, String> {
+        Self::parse(&spec).ok_or_else(|| format!("\"{}\" is not HH:MM-HH:MM", spec))
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(hours: QuietHours) -> String {
+        hours.to_string()
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+/// What `migrate_config` did to a config file
+#[derive(Debug, Default)]
+struct ConfigMigration {
+    from_version: u32,
+    // Settings whose value couldn't be used and were removed, so they take their default
+    reset: Vec<String>,
+}
+
+impl ConfigMigration {
+    /// Files from a newer version are left alone, so going back to an older build
+    /// doesn't strip settings it doesn't know
+    fn needs_rewrite(&self) -> bool {
+        self.from_version <= CONFIG_VERSION
+            && (self.from_version < CONFIG_VERSION || !self.reset.is_empty())
+    }
+}
+
+/// Upgrade a parsed config file to `CONFIG_VERSION` and make its settings readable: values
+/// quoted as strings (`"15"`, `"true"`) are converted, and values that still don't fit the
+/// setting are removed instead of failing the whole load.
+fn migrate_config(value: &mut serde_json::Value) -> ConfigMigration {
+    let from_version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    // Version 1 only added the version field; later upgrade steps go here, oldest first
+
+    let mut reset = Vec::new();
+    if let Some(file) = value.as_object_mut() {
+        match file.get_mut("settings") {
+            Some(serde_json::Value::Object(settings)) => reset = repair_settings(settings),
+            Some(_) => {
+                file.remove("settings");
+                reset.push("settings".to_string());
+            }
+            None => {}
+        }
+        if from_version < CONFIG_VERSION {
+            file.insert("version".to_string(), CONFIG_VERSION.into());
+        }
+    }
+    ConfigMigration { from_version, reset }
+}
+
+/// Convert or remove each setting that `Settings` can't read, returning the removed names
+fn repair_settings(settings: &mut serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+    let Ok(serde_json::Value::Object(defaults)) = serde_json::to_value(Settings::default()) else {
+        return Vec::new();
+    };
+    // Try each value on its own against the defaults, so one bad setting doesn't hide another
+    let fits = |key: &str, value: &serde_json::Value| {
+        let mut probe = defaults.clone();
+        probe.insert(key.to_string(), value.clone());
+        serde_json::from_value::<Settings>(serde_json::Value::Object(probe)).is_ok()
+    };
+
+    let mut reset = Vec::new();
+    settings.retain(|key, value| {
+        if fits(key, value) {
+            return true;
+        }
+        let unquoted = value.as_str().and_then(|s| serde_json::from_str::<serde_json::Value>(s.trim()).ok());
+        if let Some(unquoted) = unquoted.filter(|v| fits(key, v)) {
+            *value = unquoted;
+            return true;
+        }
+        reset.push(key.clone());
+        false
+    });
+    reset.sort();
+    reset
+}
+
 impl Config {
     pub fn load() -> Result<Self> {
         let config_dir = Self::get_config_dir();
         let config_path = config_dir.join("whatsapp_config.json");
 
         if config_path.exists() {
-            let content = fs::read_to_string(&config_path)?;
-            let mut config: Config = serde_json::from_str(&content)?;
-            config.config_dir = config_dir;
-            
-            // Expand relative paths to absolute
-            if config.whatsapp_cli_path.is_relative() {
-                if let Ok(absolute) = config.whatsapp_cli_path.canonicalize() {
-                    config.whatsapp_cli_path = absolute;
-                } else {
-                    // Try to find it in common locations
-                    if let Some(found) = Self::find_whatsapp_cli() {
-                        config.whatsapp_cli_path = found.canonicalize().unwrap_or(found);
-                    }
-                }
-            }
-            
-            Ok(config)
+            Self::read(config_dir)
         } else {
             // Create new config
-            let config = Self::create_new(config_dir)?;
+            let mut config = Self::create_new(config_dir)?;
+            config.load_keymap();
+            config.load_theme();
             Ok(config)
         }
     }
 
+    /// Read the config file, keymap and theme in `config_dir`. A file from an older
+    /// version is migrated and written back, keeping the original as a `.bak` file.
+    fn read(config_dir: PathBuf) -> Result<Self> {
+        let config_path = config_dir.join("whatsapp_config.json");
+        let content = fs::read_to_string(&config_path)?;
+        let mut value: serde_json::Value = serde_json::from_str(&content)?;
+        let migration = migrate_config(&mut value);
+        let mut config: Config = serde_json::from_value(value)?;
+        config.config_dir = config_dir;
+        if migration.needs_rewrite() {
+            fs::write(config_path.with_extension("json.bak"), &content)?;
+            config.save()?;
+        }
+        if !migration.reset.is_empty() {
+            config.settings_note = Some(format!(
+                "Config: invalid {} reset to default (old file kept as whatsapp_config.json.bak)",
+                migration.reset.join(", ")
+            ));
+        }
+        config.load_keymap();
+        config.load_theme();
+
+        // Expand relative paths to absolute
+        if config.whatsapp_cli_path.is_relative() {
+            if let Ok(absolute) = config.whatsapp_cli_path.canonicalize() {
+                config.whatsapp_cli_path = absolute;
+            } else {
+                // Try to find it in common locations
+                if let Some(found) = Self::find_whatsapp_cli() {
+                    config.whatsapp_cli_path = found.canonicalize().unwrap_or(found);
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Read the config, keymap and theme files again from this config's directory
+    pub fn reload(&self) -> Result<Self> {
+        Self::read(self.config_dir.clone())
+    }
+
+    /// Settings that differ in `new` but only take effect after a restart, because the
+    /// WhatsApp client was built with the old ones
+    pub fn restart_required_changes(&self, new: &Config) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.store_path() != new.store_path() {
+            changed.push("store_path");
+        }
+        if self.settings.max_concurrent_cli != new.settings.max_concurrent_cli {
+            changed.push("max_concurrent_cli");
+        }
+        changed
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = self.config_dir.join("whatsapp_config.json");
         let content = serde_json::to_string_pretty(&self)?;
-        fs::write(config_path, content)?;
+        crate::persistence::write_atomic(&config_path, &content)?;
         Ok(())
     }
 
@@ -115,16 +542,16 @@ impl Config {
         ];
         
         for candidate in candidates {
-            let path = if candidate.starts_with("~/") {
+            let path = if let Some(rest) = candidate.strip_prefix("~/") {
                 if let Some(home) = dirs::home_dir() {
-                    home.join(&candidate[2..])
+                    home.join(rest)
                 } else {
                     continue
                 }
-            } else if candidate.starts_with("./") {
+            } else if let Some(rest) = candidate.strip_prefix("./") {
                 std::env::current_dir()
                     .unwrap_or_else(|_| PathBuf::from("."))
-                    .join(&candidate[2..])
+                    .join(rest)
             } else {
                 // Try to find in PATH using std::env::var("PATH")
                 let path_var = std::env::var("PATH").unwrap_or_default();
@@ -185,9 +612,9 @@ impl Config {
             }
         } else {
             // Expand ~ in user input
-            let expanded = if cli_path_str.starts_with("~/") {
+            let expanded = if let Some(rest) = cli_path_str.strip_prefix("~/") {
                 if let Some(home) = dirs::home_dir() {
-                    home.join(&cli_path_str[2..])
+                    home.join(rest)
                 } else {
                     PathBuf::from(cli_path_str)
                 }
@@ -212,9 +639,16 @@ impl Config {
         }
 
         let config = Config {
+            version: CONFIG_VERSION,
             whatsapp_cli_path,
+            store_path: None,
             settings: Settings::default(),
             config_dir,
+            keymap: Keymap::default(),
+            keymap_note: None,
+            theme: Theme::default(),
+            theme_note: None,
+            settings_note: None,
         };
 
         config.save()?;
@@ -240,8 +674,33 @@ impl Config {
         }
     }
 
+    /// The whatsapp-cli store: `store_path` from the config file, with `~` expanded and
+    /// relative paths taken from the config directory, or `store` in the config directory
     pub fn store_path(&self) -> PathBuf {
-        self.config_dir.join("store")
+        let Some(path) = &self.store_path else {
+            return self.config_dir.join("store");
+        };
+        let path = match (path.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => home.join(rest),
+            _ => path.clone(),
+        };
+        if path.is_relative() {
+            self.config_dir.join(path)
+        } else {
+            path
+        }
+    }
+
+    /// `store_path()`, created if it doesn't exist yet
+    pub fn prepare_store(&self) -> Result<PathBuf> {
+        let store_path = self.store_path();
+        if store_path.exists() && !store_path.is_dir() {
+            anyhow::bail!("store_path {} is not a directory", store_path.display());
+        }
+        fs::create_dir_all(&store_path).map_err(|e| {
+            anyhow::anyhow!("Can't create the whatsapp-cli store at {}: {}", store_path.display(), e)
+        })?;
+        Ok(store_path)
     }
 
     pub fn layout_path(&self) -> PathBuf {
@@ -251,4 +710,154 @@ impl Config {
     pub fn aliases_path(&self) -> PathBuf {
         self.config_dir.join("whatsapp_aliases.json")
     }
+
+    pub fn named_layouts_path(&self) -> PathBuf {
+        self.config_dir.join("whatsapp_layouts.json")
+    }
+
+    pub fn outbox_path(&self) -> PathBuf {
+        self.config_dir.join("whatsapp_outbox.json")
+    }
+
+    pub fn keymap_path(&self) -> PathBuf {
+        self.config_dir.join("whatsapp_keymap.json")
+    }
+
+    fn load_keymap(&mut self) {
+        let (keymap, note) = Keymap::load(&self.keymap_path());
+        self.keymap = keymap;
+        self.keymap_note = note;
+    }
+
+    pub fn theme_path(&self) -> PathBuf {
+        self.config_dir.join("whatsapp_theme.json")
+    }
+
+    fn load_theme(&mut self) {
+        let (theme, note) = Theme::load(&self.theme_path());
+        self.theme = theme;
+        self.theme_note = note;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_unversioned_config_is_migrated_and_rewritten() {
+        let dir = config_dir("config_v0");
+        let old = r#"{
+            "whatsapp_cli_path": "/usr/bin/mdtest",
+            "settings": {
+                "compact_mode": "true",
+                "chat_refresh_secs": "15",
+                "muted_chats": ["team@g.us"],
+                "wrap_style": "sideways",
+                "event_poll_ms": -5
+            }
+        }"#;
+        fs::write(dir.join("whatsapp_config.json"), old).unwrap();
+
+        let config = Config::read(dir.clone()).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.settings.compact_mode);
+        assert_eq!(config.settings.chat_refresh_secs, 15);
+        assert!(config.settings.muted_chats.contains("team@g.us"));
+        assert_eq!(config.settings.wrap_style, WrapStyle::default());
+        assert_eq!(config.settings.event_poll_ms, default_event_poll_ms());
+        assert_eq!(config.settings.undo_send_secs, 0, "holding messages back is opt-in");
+        assert!(config.settings_note.as_deref().unwrap().contains("event_poll_ms, wrap_style"));
+
+        // The old file is kept and the new one reads back without changes
+        assert_eq!(fs::read_to_string(dir.join("whatsapp_config.json.bak")).unwrap(), old);
+        let rewritten = fs::read_to_string(dir.join("whatsapp_config.json")).unwrap();
+        assert!(rewritten.contains(r#""chat_refresh_secs": 15"#), "{}", rewritten);
+        let again = Config::read(dir.clone()).unwrap();
+        assert!(again.settings_note.is_none());
+        assert_eq!(fs::read_to_string(dir.join("whatsapp_config.json")).unwrap(), rewritten);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_newer_config_is_read_but_not_rewritten() {
+        let dir = config_dir("config_newer");
+        let newer = r#"{"version": 99, "whatsapp_cli_path": "/usr/bin/mdtest", "settings": {"show_emojis": false, "future": 1}}"#;
+        fs::write(dir.join("whatsapp_config.json"), newer).unwrap();
+
+        let config = Config::read(dir.clone()).unwrap();
+        assert!(!config.settings.show_emojis);
+        assert_eq!(fs::read_to_string(dir.join("whatsapp_config.json")).unwrap(), newer);
+        assert!(!dir.join("whatsapp_config.json.bak").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_store_path_default_relative_and_invalid() {
+        let dir = config_dir("store_path");
+        let mut config = Config {
+            version: CONFIG_VERSION,
+            whatsapp_cli_path: PathBuf::from("/usr/bin/mdtest"),
+            store_path: None,
+            settings: Settings::default(),
+            config_dir: dir.clone(),
+            keymap: Keymap::default(),
+            keymap_note: None,
+            theme: Theme::default(),
+            theme_note: None,
+            settings_note: None,
+        };
+        assert_eq!(config.store_path(), dir.join("store"));
+
+        config.store_path = Some(PathBuf::from("data/wa"));
+        assert_eq!(config.prepare_store().unwrap(), dir.join("data/wa"));
+        assert!(dir.join("data/wa").is_dir());
+        if let Some(home) = dirs::home_dir() {
+            config.store_path = Some(PathBuf::from("~/wa-store"));
+            assert_eq!(config.store_path(), home.join("wa-store"));
+        }
+
+        fs::write(dir.join("file"), "").unwrap();
+        config.store_path = Some(dir.join("file"));
+        assert!(config.prepare_store().unwrap_err().to_string().contains("is not a directory"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_quiet_hours_boundaries_and_wrap_around() {
+        let at = |t: &str| chrono::NaiveTime::parse_from_str(t, "%H:%M").unwrap();
+        let night = QuietHours::parse("22:00-07:00").unwrap();
+        assert!(night.contains(at("22:00")));
+        assert!(night.contains(at("23:59")));
+        assert!(night.contains(at("00:00")));
+        assert!(night.contains(at("06:59")));
+        assert!(!night.contains(at("07:00")));
+        assert!(!night.contains(at("21:59")));
+        assert!(!night.contains(at("12:00")));
+
+        let lunch = QuietHours::parse("12:00 - 13:30").unwrap();
+        assert!(lunch.contains(at("12:00")));
+        assert!(lunch.contains(at("13:29")));
+        assert!(!lunch.contains(at("13:30")));
+        assert!(!lunch.contains(at("11:59")));
+
+        assert!(!QuietHours::parse("09:00-09:00").unwrap().contains(at("09:00")));
+        assert_eq!(QuietHours::parse("25:00-07:00"), None);
+        assert_eq!(QuietHours::parse("22:00"), None);
+
+        let json = serde_json::to_string(&night).unwrap();
+        assert_eq!(json, r#""22:00-07:00""#);
+        assert_eq!(serde_json::from_str::<QuietHours>(&json).unwrap(), night);
+        assert!(serde_json::from_str::<QuietHours>(r#""late""#).is_err());
+    }
 }
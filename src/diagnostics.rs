@@ -0,0 +1,66 @@
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// How many log lines go into a diagnostics report
+pub const LOG_TAIL_LINES: usize = 200;
+
+/// Hide personal details in diagnostics text. Each distinct JID becomes `<jid-N>@server`,
+/// numbered in order of appearance so a report still shows which lines are about the same
+/// chat; phone numbers become `<number>` and the home directory becomes `~`.
+pub fn redact(text: &str) -> String {
+    let jid = Regex::new(
+        r"[0-9A-Za-z._+-]+(?::\d+)?@(s\.whatsapp\.net|g\.us|lid|c\.us|broadcast|newsletter)\b",
+    )
+    .unwrap();
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let text = jid.replace_all(text, |caps: &Captures| {
+        // Device suffixes ("46...:12@") belong to the same account
+        let whole = &caps[0];
+        let user = whole.split(['@', ':']).next().unwrap_or(whole).to_string();
+        let next = seen.len() + 1;
+        let n = *seen.entry(user).or_insert(next);
+        format!("<jid-{}>@{}", n, &caps[1])
+    });
+
+    let phone = Regex::new(r"\+\d[\d -]{5,}\d|\b\d{11,15}\b").unwrap();
+    let mut text = phone.replace_all(&text, "<number>").into_owned();
+
+    if let Some(home) = dirs::home_dir().and_then(|h| h.to_str().map(str::to_string)) {
+        if home.len() > 1 {
+            text = text.replace(&home, "~");
+        }
+    }
+    text
+}
+
+/// The report as plain text: a header, then one `== title ==` block per section
+pub fn format_report(sections: &[(&str, String)]) -> String {
+    let mut out = format!(
+        "whatsapp_client_rs diagnostics, {}\n",
+        chrono::Local::now().format("%Y-%m-%d %H:%M:%S %z")
+    );
+    for (title, body) in sections {
+        out.push_str(&format!("\n== {} ==\n{}\n", title, body.trim_end()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_jids_numbers_and_home() {
+        let text = "sync 46760789806:12@s.whatsapp.net in 120363025@g.us, then 46760789806@s.whatsapp.net \
+                    and 4670111@lid; call +46 70 123 45 67 or 46760789806; ts 1700000000";
+        assert_eq!(
+            redact(text),
+            "sync <jid-1>@s.whatsapp.net in <jid-2>@g.us, then <jid-1>@s.whatsapp.net \
+             and <jid-3>@lid; call <number> or <number>; ts 1700000000"
+        );
+
+        if let Some(home) = dirs::home_dir().filter(|h| h.as_os_str().len() > 1) {
+            assert_eq!(redact(&format!("{}/debug.log", home.display())), "~/debug.log");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+/// Shortcodes (without the colons) and the emoji they stand for: the common ones, named as
+/// on GitHub and Slack. Completion offers them in this order.
+const SHORTCODES: &[(&str, &str)] = &[
+    // Faces
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("joy", "😂"),
+    ("rofl", "🤣"),
+    ("laughing", "😆"),
+    ("sweat_smile", "😅"),
+    ("slightly_smiling_face", "🙂"),
+    ("upside_down_face", "🙃"),
+    ("wink", "😉"),
+    ("blush", "😊"),
+    ("innocent", "😇"),
+    ("heart_eyes", "😍"),
+    ("star_struck", "🤩"),
+    ("kissing_heart", "😘"),
+    ("yum", "😋"),
+    ("stuck_out_tongue", "😛"),
+    ("stuck_out_tongue_winking_eye", "😜"),
+    ("hugs", "🤗"),
+    ("thinking", "🤔"),
+    ("shushing_face", "🤫"),
+    ("neutral_face", "😐"),
+    ("expressionless", "😑"),
+    ("no_mouth", "😶"),
+    ("smirk", "😏"),
+    ("unamused", "😒"),
+    ("roll_eyes", "🙄"),
+    ("grimacing", "😬"),
+    ("relieved", "😌"),
+    ("pensive", "😔"),
+    ("sleepy", "😪"),
+    ("sleeping", "😴"),
+    ("mask", "😷"),
+    ("nerd_face", "🤓"),
+    ("sunglasses", "😎"),
+    ("confused", "😕"),
+    ("worried", "😟"),
+    ("open_mouth", "😮"),
+    ("astonished", "😲"),
+    ("flushed", "😳"),
+    ("pleading_face", "🥺"),
+    ("cry", "😢"),
+    ("sob", "😭"),
+    ("scream", "😱"),
+    ("weary", "😩"),
+    ("angry", "😠"),
+    ("rage", "😡"),
+    ("exploding_head", "🤯"),
+    ("partying_face", "🥳"),
+    ("skull", "💀"),
+    ("poop", "💩"),
+    ("clown_face", "🤡"),
+    ("see_no_evil", "🙈"),
+    // Hands and people
+    ("thumbsup", "👍"),
+    ("+1", "👍"),
+    ("thumbsdown", "👎"),
+    ("-1", "👎"),
+    ("ok_hand", "👌"),
+    ("v", "✌️"),
+    ("crossed_fingers", "🤞"),
+    ("wave", "👋"),
+    ("clap", "👏"),
+    ("raised_hands", "🙌"),
+    ("pray", "🙏"),
+    ("handshake", "🤝"),
+    ("muscle", "💪"),
+    ("point_up", "☝️"),
+    ("point_right", "👉"),
+    ("eyes", "👀"),
+    ("facepalm", "🤦"),
+    ("shrug", "🤷"),
+    // Hearts and symbols
+    ("heart", "❤️"),
+    ("orange_heart", "🧡"),
+    ("yellow_heart", "💛"),
+    ("green_heart", "💚"),
+    ("blue_heart", "💙"),
+    ("purple_heart", "💜"),
+    ("black_heart", "🖤"),
+    ("broken_heart", "💔"),
+    ("two_hearts", "💕"),
+    ("sparkling_heart", "💖"),
+    ("100", "💯"),
+    ("fire", "🔥"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("zap", "⚡"),
+    ("boom", "💥"),
+    ("tada", "🎉"),
+    ("gift", "🎁"),
+    ("balloon", "🎈"),
+    ("trophy", "🏆"),
+    ("check", "✔️"),
+    ("white_check_mark", "✅"),
+    ("x", "❌"),
+    ("warning", "⚠️"),
+    ("question", "❓"),
+    ("exclamation", "❗"),
+    ("zzz", "💤"),
+    // Nature, food and things
+    ("sunny", "☀️"),
+    ("rainbow", "🌈"),
+    ("snowflake", "❄️"),
+    ("rose", "🌹"),
+    ("dog", "🐶"),
+    ("cat", "🐱"),
+    ("coffee", "☕"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("wine_glass", "🍷"),
+    ("champagne", "🍾"),
+    ("pizza", "🍕"),
+    ("cake", "🍰"),
+    ("birthday", "🎂"),
+    ("rocket", "🚀"),
+    ("car", "🚗"),
+    ("house", "🏠"),
+    ("phone", "📱"),
+    ("computer", "💻"),
+    ("camera", "📷"),
+    ("bulb", "💡"),
+    ("moneybag", "💰"),
+    ("calendar", "📅"),
+    ("pushpin", "📌"),
+    ("lock", "🔒"),
+    ("bell", "🔔"),
+    ("musical_note", "🎵"),
+    ("soccer", "⚽"),
+];
+
+/// Most shortcodes a completion hint lists
+const MAX_HINTS: usize = 6;
+
+/// The emoji for `code` (without colons)
+pub fn lookup(code: &str) -> Option<&'static str> {
+    SHORTCODES.iter().find(|(c, _)| *c == code).map(|(_, emoji)| *emoji)
+}
+
+/// Shortcodes starting with `prefix`, in table order
+fn matching(prefix: &str) -> Vec<(&'static str, &'static str)> {
+    SHORTCODES.iter().copied().filter(|(c, _)| c.starts_with(prefix)).collect()
+}
+
+/// Whether `c` can be part of a shortcode
+fn is_code_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')
+}
+
+/// Replace every complete `:shortcode:` in `text` with its emoji. Unknown codes, and colons
+/// in times or URLs, are left alone.
+pub fn expand_shortcodes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let code_len = after.find(|c: char| !is_code_char(c)).unwrap_or(after.len());
+        let emoji = after[code_len..]
+            .starts_with(':')
+            .then(|| lookup(&after[..code_len]))
+            .flatten();
+        match emoji {
+            Some(emoji) => {
+                result.push_str(emoji);
+                rest = &after[code_len + 1..];
+            }
+            None => {
+                // The closing colon may open the next code, as in ":x::heart:"
+                result.push(':');
+                rest = after;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Tab completion for a `:code` (or `:code:`) at the end of `text`: the text with the code
+/// replaced when one shortcode fits, or a hint listing the candidates when several do
+pub fn complete_shortcode(text: &str) -> (Option<String>, Option<String>) {
+    let word_start = text.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+    let word = &text[word_start..];
+    let Some(code) = word.strip_prefix(':') else {
+        return (None, None);
+    };
+    let code = code.strip_suffix(':').unwrap_or(code);
+    if code.is_empty() || !code.chars().all(is_code_char) {
+        return (None, None);
+    }
+
+    let candidates = matching(code);
+    let emoji = lookup(code).or(match candidates.as_slice() {
+        [(_, emoji)] => Some(*emoji),
+        _ => None,
+    });
+    if let Some(emoji) = emoji {
+        return (Some(format!("{}{}", &text[..word_start], emoji)), None);
+    }
+    if candidates.is_empty() {
+        return (None, None);
+    }
+    let mut hint: Vec<String> = candidates
+        .iter()
+        .take(MAX_HINTS)
+        .map(|(c, emoji)| format!("{} :{}:", emoji, c))
+        .collect();
+    if candidates.len() > MAX_HINTS {
+        hint.push(format!("+{} more", candidates.len() - MAX_HINTS));
+    }
+    (None, Some(hint.join("  ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_shortcodes() {
+        assert_eq!(expand_shortcodes(":heart:"), "❤️");
+        assert_eq!(expand_shortcodes("great :thumbsup: see you"), "great 👍 see you");
+        assert_eq!(expand_shortcodes(":x::heart:"), "❌❤️");
+        // Unknown codes and other colons stay as typed
+        assert_eq!(expand_shortcodes("what :xyz: is"), "what :xyz: is");
+        assert_eq!(expand_shortcodes("at 12:30:45, see https://a.b/c:d"), "at 12:30:45, see https://a.b/c:d");
+        assert_eq!(expand_shortcodes("ratio 1:1 :"), "ratio 1:1 :");
+    }
+
+    #[test]
+    fn test_complete_shortcode() {
+        assert_eq!(complete_shortcode("love :heart"), (Some("love ❤️".to_string()), None));
+        assert_eq!(complete_shortcode("love :heart:"), (Some("love ❤️".to_string()), None));
+        // One shortcode starts with it
+        assert_eq!(complete_shortcode(":rock"), (Some("🚀".to_string()), None));
+        // Several do: list them instead
+        let (completed, hint) = complete_shortcode("hi :sm");
+        assert_eq!(completed, None);
+        assert_eq!(hint.as_deref(), Some("😄 :smile:  😃 :smiley:  😏 :smirk:"));
+        assert_eq!(complete_shortcode("hi :xyz"), (None, None));
+        assert_eq!(complete_shortcode("hi :"), (None, None));
+        assert_eq!(complete_shortcode("no code"), (None, None));
+    }
+}
@@ -1,8 +1,46 @@
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, Datelike, Local, NaiveDate, TimeZone};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::widgets::MessageData;
+use crate::whatsapp::SharedContact;
+use crate::widgets::{ChatPane, Filter, FilterType, MessageData, SendStatus};
+
+/// How continuation lines of a wrapped message are indented
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WrapStyle {
+    /// Align under the message text, after the sender-name prefix
+    #[default]
+    Aligned,
+    /// Small fixed indent
+    Hanging,
+    /// No indent
+    Flush,
+}
+
+/// Continuation indent used by `WrapStyle::Hanging`
+const HANGING_INDENT: usize = 2;
+/// Below this many text columns per line, any style falls back to flush
+const MIN_WRAP_TEXT_WIDTH: usize = 12;
+
+impl WrapStyle {
+    /// Indent of continuation lines for a message whose first line starts with a
+    /// `prefix_len`-column prefix, in a pane `width` columns wide
+    pub fn continuation_indent(self, prefix_len: usize, width: usize) -> usize {
+        let indent = match self {
+            WrapStyle::Aligned => prefix_len,
+            WrapStyle::Hanging => HANGING_INDENT.min(prefix_len),
+            WrapStyle::Flush => 0,
+        };
+        if width.saturating_sub(indent) < MIN_WRAP_TEXT_WIDTH {
+            0
+        } else {
+            indent
+        }
+    }
+}
 
 /// Extract YouTube video ID from a URL
 #[cfg(test)]
@@ -46,6 +84,37 @@ pub fn format_reactions(reactions: &HashMap<String, u32>) -> String {
     parts.join(" ")
 }
 
+/// How reactions are shown after a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReactionStyle {
+    /// Every emoji with its count
+    #[default]
+    Full,
+    /// The total and the most used emoji, in full on the message under the list-mode cursor
+    Summary,
+}
+
+/// Reactions collapsed to their total and the most used emoji, e.g. "8 👍…" where "…"
+/// means other emoji were used too
+pub fn summarize_reactions(reactions: &HashMap<String, u32>) -> String {
+    let total: u32 = reactions.values().sum();
+    // Ties go to the first emoji in sort order, so the summary doesn't flicker
+    let Some((top, _)) = reactions.iter().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0))) else {
+        return String::new();
+    };
+    let more = if reactions.len() > 1 { "…" } else { "" };
+    format!("{} {}{}", total, top, more)
+}
+
+/// Put a message number inside a media label: "[IMG]" becomes "[IMG #5]"
+pub fn number_media_label(label: &str, number: usize) -> String {
+    match label.strip_suffix(']') {
+        Some(inner) => format!("{} #{}]", inner, number),
+        None => format!("{} #{}", label, number),
+    }
+}
+
 /// Get media label for different types - matching Python's colored output
 pub fn get_media_label(media_type: &str, title: Option<&str>) -> String {
     match media_type {
@@ -71,8 +140,27 @@ pub fn get_media_label(media_type: &str, title: Option<&str>) -> String {
         "sticker" => "[STICKER]".to_string(),
         "gif" => "[GIF]".to_string(),
         "document" => "[FILE]".to_string(),
-        "contact" => "[CONTACT]".to_string(),
-        "location" => "[LOCATION]".to_string(),
+        "contact" => {
+            if let Some(contact) = title {
+                format!("[CONTACT: {}]", contact)
+            } else {
+                "[CONTACT]".to_string()
+            }
+        }
+        "contacts" | "contact_array" => {
+            if let Some(contacts) = title {
+                format!("[CONTACTS: {}]", contacts)
+            } else {
+                "[CONTACTS]".to_string()
+            }
+        }
+        "location" => {
+            if let Some(coordinates) = title {
+                format!("[LOCATION: {}]", coordinates)
+            } else {
+                "[LOCATION]".to_string()
+            }
+        }
         "poll" => "[POLL]".to_string(),
         "dice" => "[DICE]".to_string(),
         "game" => "[GAME]".to_string(),
@@ -80,20 +168,40 @@ pub fn get_media_label(media_type: &str, title: Option<&str>) -> String {
     }
 }
 
+/// Whether `text` mentions the account `my_jid`: its number as a word of its own (`@4670…`,
+/// `+4670…` or bare), or an `@me` token
+pub fn mentions_me(text: &str, my_jid: &str) -> bool {
+    let number = my_jid.split(['@', ':']).next().unwrap_or_default();
+    let known = number.len() >= 7 && number.chars().all(|c| c.is_ascii_digit());
+    text.split(|c: char| !(c.is_alphanumeric() || c == '@' || c == '+'))
+        .any(|word| word.eq_ignore_ascii_case("@me") || (known && word.trim_start_matches(['@', '+']) == number))
+}
+
+/// What counts as a link in message text
+const URL_PATTERN: &str = r"https?://[^\s]+";
+
+/// The first link in `text`
+pub fn first_url(text: &str) -> Option<&str> {
+    Regex::new(URL_PATTERN).unwrap().find(text).map(|m| m.as_str())
+}
+
+/// `text` cut to its first `max_chars` characters with "..." appended, or unchanged if it fits
+pub fn truncate_chars(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
 /// Shorten long URLs in text by truncating
 pub fn shorten_urls(text: &str, max_len: usize) -> String {
-    let url_regex = Regex::new(r"https?://[^\s]+").unwrap();
+    let url_regex = Regex::new(URL_PATTERN).unwrap();
 
     let mut result = text.to_string();
     for cap in url_regex.find_iter(text) {
         let url = cap.as_str();
-        if url.chars().count() > max_len {
-            let truncate_at = url
-                .char_indices()
-                .nth(max_len)
-                .map(|(i, _)| i)
-                .unwrap_or(url.len());
-            let shortened = format!("{}...", &url[..truncate_at]);
+        let shortened = truncate_chars(url, max_len);
+        if shortened != url {
             result = result.replace(url, &shortened);
         }
     }
@@ -109,126 +217,296 @@ pub fn strip_emojis(text: &str) -> String {
     emoji_regex.replace_all(text, "").to_string()
 }
 
-/// Wrap text to fit within a given width, with indent for continuation lines
-pub fn wrap_text(text: &str, indent: usize, width: usize) -> String {
-    if width <= indent {
-        return text.to_string();
+/// Terminal columns `text` occupies (wide CJK/emoji count as 2)
+pub fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Split `word` after at most `max_width` columns, always taking at least one character
+fn split_at_width(word: &str, max_width: usize) -> (&str, &str) {
+    let mut used = 0;
+    for (i, c) in word.char_indices() {
+        let w = UnicodeWidthChar::width(c).unwrap_or(0);
+        if used + w > max_width && i > 0 {
+            return word.split_at(i);
+        }
+        used += w;
     }
-    let content_width = width - indent;
-    let pad: String = " ".repeat(indent);
-    let mut result_lines: Vec<String> = Vec::new();
+    (word, "")
+}
 
-    for (i, paragraph) in text.split('\n').enumerate() {
-        if paragraph.is_empty() {
-            result_lines.push(if i > 0 { pad.clone() } else { String::new() });
-            continue;
+/// How message times are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampStyle {
+    /// "14:05" today, with the date before that
+    #[default]
+    Clock,
+    /// "2m", "3h", "yesterday", "4d", then the date
+    Relative,
+    /// Always the date and time
+    Full,
+}
+
+/// A message time in `style`, relative to `now`
+pub fn format_timestamp_as(timestamp: i64, style: TimestampStyle, now: DateTime<Local>) -> String {
+    let datetime = Local.timestamp_opt(timestamp, 0).single().unwrap_or(now);
+    match style {
+        TimestampStyle::Clock if datetime.date_naive() == now.date_naive() => datetime.format("%H:%M").to_string(),
+        TimestampStyle::Clock | TimestampStyle::Full => datetime.format("%Y-%m-%d %H:%M").to_string(),
+        TimestampStyle::Relative => format_relative_timestamp(datetime, now),
+    }
+}
+
+/// "just now", "2m", "3h" (earlier today), "yesterday", "4d" (this week), then the date.
+/// Times in the future, e.g. from a clock that's ahead, count as now.
+fn format_relative_timestamp(datetime: DateTime<Local>, now: DateTime<Local>) -> String {
+    let datetime = datetime.min(now);
+    let secs = (now - datetime).num_seconds();
+    let days = (now.date_naive() - datetime.date_naive()).num_days();
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m", secs / 60),
+        _ if days == 0 => format!("{}h", secs / 3600),
+        _ if days == 1 => "yesterday".to_string(),
+        _ if days < 7 => format!("{}d", days),
+        _ => datetime.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// The local calendar day of a message time
+pub fn local_date(timestamp: i64) -> Option<NaiveDate> {
+    Local.timestamp_opt(timestamp, 0).single().map(|datetime| datetime.date_naive())
+}
+
+/// How a day separator line starts, which keeps it apart from message lines
+pub const DAY_SEPARATOR: &str = "──";
+
+/// Marks where a new day starts in the message view: "── Monday, Jan 6 ──", with the
+/// year when it isn't this one, centred in `width` columns
+pub fn format_day_separator(date: NaiveDate, today: NaiveDate, width: usize) -> String {
+    let label = if date.year() == today.year() {
+        date.format("%A, %b %-d").to_string()
+    } else {
+        date.format("%A, %b %-d %Y").to_string()
+    };
+    let side = width.saturating_sub(display_width(&label) + 2) / 2;
+    let rule = "─".repeat(side.max(2));
+    format!("{} {} {}", rule, label, rule)
+}
+
+/// The loaded message that `data` replies to
+pub fn reply_target<'a>(msg_data: &'a [MessageData], data: &MessageData) -> Option<&'a MessageData> {
+    let reply_to_id = data.reply_to_msg_id.as_ref()?;
+    msg_data.iter().find(|m| &m.msg_id == reply_to_id)
+}
+
+/// Shown above the oldest message once scrolling up has found nothing older
+pub const BEGINNING_OF_CHAT: &str = "— beginning of chat —";
+
+/// Shown above the oldest message when whatsapp-cli can't reach further back
+pub const OLDER_HISTORY_UNAVAILABLE: &str = "— older history unavailable —";
+
+/// `BEGINNING_OF_CHAT` or `OLDER_HISTORY_UNAVAILABLE` centred in `width` columns
+pub fn format_history_start(label: &str, width: usize) -> String {
+    let pad = width.saturating_sub(display_width(label)) / 2;
+    format!("{}{}", " ".repeat(pad), label)
+}
+
+/// Format timestamp for display
+pub fn format_timestamp(timestamp: i64) -> String {
+    format_timestamp_as(timestamp, TimestampStyle::Clock, Local::now())
+}
+
+/// "name=value" description of a pane filter, with time ranges shown as local dates
+pub fn filter_label(filter_type: &FilterType, filter_value: Option<&str>) -> String {
+    let fmt = |t: i64| {
+        Local
+            .timestamp_opt(t, 0)
+            .single()
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| t.to_string())
+    };
+    match filter_type {
+        FilterType::TimeRange { since, before } => {
+            let range = match (since, before) {
+                (Some(s), Some(b)) => format!("{} to {}", fmt(*s), fmt(*b)),
+                (Some(s), None) => format!("since {}", fmt(*s)),
+                (None, Some(b)) => format!("before {}", fmt(*b)),
+                (None, None) => "all".to_string(),
+            };
+            format!("time={}", range)
         }
+        other => format!("{}={}", other.name(), filter_value.unwrap_or("")),
+    }
+}
 
-        let words: Vec<&str> = paragraph.split(' ').collect();
-        let mut current_line = String::new();
-        let first_line_of_para = i == 0;
-
-        for word in &words {
-            // Handle very long words - use char_count instead of byte len
-            let word_char_count = word.chars().count();
-            if word_char_count > content_width {
-                if !current_line.is_empty() {
-                    if first_line_of_para && result_lines.is_empty() {
-                        result_lines.push(current_line.clone());
-                    } else {
-                        result_lines.push(format!("{}{}", pad, current_line));
-                    }
-                    current_line.clear();
-                }
-                // Split word by character boundaries
-                let char_indices: Vec<(usize, char)> = word.char_indices().collect();
-                let mut char_pos = 0;
-                while char_pos < char_indices.len() {
-                    let chunk_end = (char_pos + content_width).min(char_indices.len());
-                    let byte_start = char_indices[char_pos].0;
-                    let byte_end = if chunk_end < char_indices.len() {
-                        char_indices[chunk_end].0
-                    } else {
-                        word.len()
-                    };
-                    let chunk = &word[byte_start..byte_end];
-                    if first_line_of_para && result_lines.is_empty() {
-                        result_lines.push(chunk.to_string());
-                    } else {
-                        result_lines.push(format!("{}{}", pad, chunk));
-                    }
-                    char_pos = chunk_end;
-                }
-                continue;
-            }
+/// All of a pane's filters joined with AND, e.g. "media=photo AND sender=bob"
+pub fn filters_label(filters: &[Filter]) -> String {
+    filters
+        .iter()
+        .map(|f| filter_label(&f.kind, Some(&f.value)))
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+/// Parse a time filter bound: `YYYY-MM-DD` (local midnight, or the following midnight when
+/// `end_of_day` is set) or a relative age like `30m`, `12h`, `7d`, `2w`
+pub fn parse_time_bound(spec: &str, end_of_day: bool) -> Option<i64> {
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(spec, "%Y-%m-%d") {
+        let date = if end_of_day { date.succ_opt()? } else { date };
+        let midnight = date.and_hms_opt(0, 0, 0)?;
+        return Local.from_local_datetime(&midnight).earliest().map(|dt| dt.timestamp());
+    }
+
+    let unit_secs = match spec.chars().last()? {
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86_400,
+        'w' => 7 * 86_400,
+        _ => return None,
+    };
+    // An age typed by the user: negative or too large to be a time is no bound
+    let amount: i64 = spec[..spec.len() - 1].parse().ok().filter(|&n: &i64| n >= 0)?;
+    amount.checked_mul(unit_secs).and_then(|secs| Local::now().timestamp().checked_sub(secs))
+}
 
-            let test_line = if current_line.is_empty() {
-                word.to_string()
+/// Index of the first message in `[day_start, day_end)`, or failing that the last one before
+/// `day_start`. The flag tells whether the message is on the day itself.
+pub fn find_message_on_date(msg_data: &[MessageData], day_start: i64, day_end: i64) -> Option<(usize, bool)> {
+    if let Some(idx) = msg_data
+        .iter()
+        .position(|m| m.timestamp >= day_start && m.timestamp < day_end)
+    {
+        return Some((idx, true));
+    }
+    msg_data
+        .iter()
+        .rposition(|m| m.timestamp < day_start)
+        .map(|idx| (idx, false))
+}
+
+/// Whole chat as a plain-text or markdown transcript for /export: full timestamps, alias-resolved
+/// senders, the message each reply answers, and `[media_type]` placeholders for attachments
+pub fn format_transcript(
+    msg_data: &[MessageData],
+    aliases: &HashMap<String, String>,
+    chat_name: &str,
+    markdown: bool,
+) -> String {
+    let name_of = |data: &MessageData| {
+        aliases
+            .get(&data.sender_id)
+            .cloned()
+            .unwrap_or_else(|| data.sender_name.clone())
+    };
+    let full_time = |ts: i64| {
+        Local
+            .timestamp_opt(ts, 0)
+            .single()
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    };
+
+    let mut out = if markdown {
+        format!("# {}\n\n", chat_name)
+    } else {
+        format!("Chat: {}\n\n", chat_name)
+    };
+    for data in msg_data {
+        let mut body = data.text.clone();
+        if let Some(media_type) = &data.media_type {
+            body = if body.is_empty() {
+                format!("[{}]", media_type)
             } else {
-                format!("{} {}", current_line, word)
+                format!("[{}] {}", media_type, body)
             };
+        }
+        if body.is_empty() {
+            continue;
+        }
 
-            if test_line.chars().count() <= content_width {
-                current_line = test_line;
-            } else {
-                if !current_line.is_empty() {
-                    if first_line_of_para && result_lines.is_empty() {
-                        result_lines.push(current_line.clone());
-                    } else {
-                        result_lines.push(format!("{}{}", pad, current_line));
-                    }
-                }
-                current_line = word.to_string();
+        let reply = data.reply_to_msg_id.as_ref().and_then(|id| {
+            match msg_data.iter().find(|m| &m.msg_id == id) {
+                Some(original) => Some((name_of(original), original.text.clone())),
+                None => data.reply_sender.clone().zip(data.reply_text.clone()),
             }
-        }
+        });
+        let sender = name_of(data);
+        let time = full_time(data.timestamp);
 
-        if !current_line.is_empty() {
-            if first_line_of_para && result_lines.is_empty() {
-                result_lines.push(current_line);
-            } else {
-                result_lines.push(format!("{}{}", pad, current_line));
+        if markdown {
+            out.push_str(&format!("**{}** · {}\n\n", sender, time));
+            if let Some((reply_sender, reply_text)) = reply {
+                out.push_str(&format!("> **{}:**\n", reply_sender));
+                for line in reply_text.lines() {
+                    out.push_str(&format!("> {}\n", line));
+                }
+                out.push('\n');
             }
+            out.push_str(&body);
+            out.push_str("\n\n");
+        } else {
+            let context = reply
+                .map(|(reply_sender, reply_text)| {
+                    let first_line = reply_text.lines().next().unwrap_or_default().to_string();
+                    format!(" (replying to {}: \"{}\")", reply_sender, first_line)
+                })
+                .unwrap_or_default();
+            out.push_str(&format!("[{}] {}{}: {}\n", time, sender, context, body));
         }
     }
-
-    result_lines.join("\n")
+    out
 }
 
-/// Format timestamp for display
-pub fn format_timestamp(timestamp: i64) -> String {
-    let datetime: DateTime<Local> = Local
-        .timestamp_opt(timestamp, 0)
-        .single()
-        .unwrap_or_else(Local::now);
+/// Read messages kept above the unread divider in the unread-only view
+pub const UNREAD_CONTEXT: usize = 3;
 
-    let now = Local::now();
-    if datetime.date_naive() == now.date_naive() {
-        datetime.format("%H:%M").to_string()
-    } else {
-        datetime.format("%Y-%m-%d %H:%M").to_string()
-    }
+/// Longest pause, in seconds, between two messages that `group_consecutive` shows as one
+pub const GROUP_GAP_SECS: i64 = 5 * 60;
+
+/// The display toggles `format_messages_for_display` applies
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DisplayOptions {
+    pub compact_mode: bool,
+    pub show_emojis: bool,
+    pub reactions: Option<ReactionStyle>,   // None hides them
+    pub timestamps: Option<TimestampStyle>, // None hides them
+    pub show_line_numbers: bool,
+    pub show_receipts: bool,
+    pub group_consecutive: bool,
 }
 
-/// Format all messages for a pane display - matching Python's _format_messages
+/// Format all messages for a pane display - matching Python's _format_messages.
+/// Messages are shown if they pass the pane's filters, and the pane's unread divider and
+/// unread-only view are applied. Also returns, for each entry of `pane.msg_data`, the index
+/// of its first output line (`None` for messages that produce no output).
 pub fn format_messages_for_display(
-    msg_data: &[MessageData],
+    pane: &ChatPane,
     width: usize,
-    compact_mode: bool,
-    show_emojis: bool,
-    show_reactions: bool,
-    show_timestamps: bool,
-    show_line_numbers: bool,
-    filter_type: Option<&str>,
-    filter_value: Option<&str>,
-    unread_count: u32,
+    options: DisplayOptions,
     aliases: &HashMap<String, String>,
-) -> Vec<String> {
+) -> (Vec<String>, Vec<Option<usize>>) {
+    let DisplayOptions {
+        compact_mode,
+        show_emojis,
+        reactions,
+        timestamps,
+        show_line_numbers,
+        show_receipts,
+        group_consecutive,
+    } = options;
+    let msg_data = &pane.msg_data;
+    let filters = &pane.filters;
+    let now = Local::now();
+    let unread_count = pane.unread_count_at_load;
+    let unread_only = pane.unread_only;
     let mut lines: Vec<String> = Vec::new();
+    let mut starts: Vec<Option<usize>> = vec![None; msg_data.len()];
 
     // Show filter indicator if active
-    if let Some(ft) = filter_type {
-        let fv = filter_value.unwrap_or("");
-        lines.push(format!("Filter: {}={} (use /filter off to disable)", ft, fv));
+    if !filters.is_empty() {
+        lines.push(format!("Filter: {} (use /filter off to disable)", filters_label(filters)));
         lines.push(String::new());
     }
 
@@ -238,53 +516,100 @@ pub fn format_messages_for_display(
         usize::MAX
     };
 
-    for (idx, data) in msg_data.iter().enumerate() {
+    // Unread-only view: start a few messages before the unread divider
+    let first_shown = if unread_only && unread_count > 0 {
+        lines.push(format!("Unread only: {} new (use /unread to show all)", unread_count));
+        lines.push(String::new());
+        unread_marker_idx.saturating_sub(UNREAD_CONTEXT)
+    } else {
+        0
+    };
+    if first_shown == 0 && pane.history_exhausted {
+        lines.push(format_history_start(BEGINNING_OF_CHAT, width));
+    } else if first_shown == 0 && pane.history_unavailable {
+        lines.push(format_history_start(OLDER_HISTORY_UNAVAILABLE, width));
+    }
+
+    // The last message shown, which a continuation must follow closely
+    let mut previous: Option<&MessageData> = None;
+    let mut previous_day: Option<NaiveDate> = None;
+
+    for (idx, data) in msg_data.iter().enumerate().skip(first_shown) {
         // Show unread marker
         if idx == unread_marker_idx && unread_count > 0 {
             let marker = "-".repeat(width / 2);
             lines.push(format!("{} {} unread {}", marker, unread_count, marker));
+            previous = None;
         }
 
         let media_label = if let Some(ref media_type) = data.media_type {
-            get_media_label(media_type, None)
+            // Locations show where they point (/locate opens the map), contact shares who
+            // they share (/save-contact writes the vCard)
+            let title = match (data.location, data.contacts.as_slice()) {
+                (Some((lat, lon)), _) => Some(format!("{:.5}, {:.5}", lat, lon)),
+                (None, []) => None,
+                (None, contacts) => Some(contacts.iter().map(SharedContact::summary).collect::<Vec<_>>().join(", ")),
+            };
+            let media_type = if data.contacts.len() > 1 { "contacts" } else { media_type.as_str() };
+            let label = get_media_label(media_type, title.as_deref());
+            // Without line numbers the label carries the number /media needs
+            if show_line_numbers {
+                label
+            } else {
+                number_media_label(&label, idx + 1)
+            }
         } else {
             data.media_label.as_deref().unwrap_or("").to_string()
         };
-        let mut text = data.text.clone();
+        // A contact share's text is the vCard the label sums up
+        let mut text = if data.contacts.is_empty() { data.text.clone() } else { String::new() };
 
         if text.is_empty() && media_label.is_empty() {
             continue;
         }
 
+        if !pane.message_matches_filter(data) {
+            continue;
+        }
+
+        let day = local_date(data.timestamp);
+        if previous_day.is_some() && day != previous_day {
+            if let Some(day) = day {
+                lines.push(format_day_separator(day, now.date_naive(), width));
+                previous = None;
+            }
+        }
+        previous_day = day;
+        starts[idx] = Some(lines.len());
+
         // Resolve sender name (use alias if available)
         let sender_name = aliases
             .get(&data.sender_id)
             .cloned()
             .unwrap_or_else(|| data.sender_name.clone());
 
-        let timestamp = format_timestamp(data.timestamp);
-        let num_str = format!("#{}", idx + 1);
+        // A continuation is drawn without the name (see `DisplayLine::Continuation`), its
+        // text lined up under the message before it. Replies keep the name, since their quote
+        // sits in between.
+        let continues = group_consecutive
+            && data.reply_to_msg_id.is_none()
+            && previous.is_some_and(|prev| {
+                prev.sender_id == data.sender_id
+                    && prev.is_outgoing == data.is_outgoing
+                    && (data.timestamp - prev.timestamp).abs() <= GROUP_GAP_SECS
+            });
+        previous = Some(data);
 
-        // Calculate prefix length for wrapping
-        let mut prefix_len = sender_name.len() + 2; // "name: "
-        if show_line_numbers {
-            prefix_len += num_str.len() + 1; // "#N "
-        }
-        if show_timestamps {
-            prefix_len += timestamp.len() + 1; // "HH:MM "
-        }
+        let num_str = format!("#{}", idx + 1);
 
-        // Process text
+        // Process text; wrapping happens at render time (see `DisplayLine::wrap`)
         if !text.is_empty() {
             text = shorten_urls(&text, 60);
             if !show_emojis {
                 text = strip_emojis(&text);
             }
-            let wrapped = wrap_text(&text, prefix_len, width);
             if !media_label.is_empty() {
-                text = format!("{} {}", media_label, wrapped);
-            } else {
-                text = wrapped;
+                text = format!("{} {}", media_label, text);
             }
         } else {
             text = media_label.to_string();
@@ -294,7 +619,7 @@ pub fn format_messages_for_display(
         // Look up the actual message being replied to in msg_data
         if let Some(ref reply_to_id) = data.reply_to_msg_id {
             // Try to find the message being replied to in our loaded messages
-            if let Some(original_msg) = msg_data.iter().find(|m| &m.msg_id == reply_to_id) {
+            if let Some(original_msg) = reply_target(msg_data, data) {
                 let reply_sender = aliases
                     .get(&original_msg.sender_id)
                     .cloned()
@@ -306,12 +631,7 @@ pub fn format_messages_for_display(
                 }
                 // Get first line only and truncate if needed
                 let first_line = rt.lines().next().unwrap_or(&rt);
-                let display_text = if first_line.chars().count() > 50 {
-                    let truncate_at = first_line.char_indices().nth(50).map(|(i, _)| i).unwrap_or(first_line.len());
-                    format!("{}...", &first_line[..truncate_at])
-                } else {
-                    first_line.to_string()
-                };
+                let display_text = truncate_chars(first_line, 50);
                 // Add marker if replying to my own message
                 let reply_marker = if original_msg.is_outgoing {
                     "[REPLY_TO_ME] "
@@ -328,12 +648,7 @@ pub fn format_messages_for_display(
                         rt = strip_emojis(&rt);
                     }
                     let first_line = rt.lines().next().unwrap_or(&rt);
-                    let display_text = if first_line.chars().count() > 50 {
-                        let truncate_at = first_line.char_indices().nth(50).map(|(i, _)| i).unwrap_or(first_line.len());
-                        format!("{}...", &first_line[..truncate_at])
-                    } else {
-                        first_line.to_string()
-                    };
+                    let display_text = truncate_chars(first_line, 50);
                     lines.push(format!("  ↳ Reply to {}: {}", reply_sender, display_text));
                 } else {
                     // No info available, just show message ID
@@ -343,11 +658,13 @@ pub fn format_messages_for_display(
         }
 
         // Get reactions
-        let reactions_suffix = if show_reactions && !data.reactions.is_empty() {
-            let r = format_reactions(&data.reactions);
-            format!(" [{}]", r)
-        } else {
-            String::new()
+        let reactions_suffix = match reactions {
+            _ if data.reactions.is_empty() => String::new(),
+            Some(ReactionStyle::Summary) if pane.selected_msg != Some(idx) => {
+                format!(" [{}]", summarize_reactions(&data.reactions))
+            }
+            Some(_) => format!(" [{}]", format_reactions(&data.reactions)),
+            None => String::new(),
         };
 
         // Build message line
@@ -356,8 +673,8 @@ pub fn format_messages_for_display(
         if show_line_numbers {
             parts.push(num_str);
         }
-        if show_timestamps {
-            parts.push(timestamp);
+        if let Some(style) = timestamps {
+            parts.push(format_timestamp_as(data.timestamp, style, now));
         }
 
         // Reply arrow if this was a reply
@@ -365,14 +682,29 @@ pub fn format_messages_for_display(
             parts.push("^".to_string());
         }
 
+        if data.pending {
+            parts.push("[queued]".to_string());
+        } else if data.sending || data.send_status == SendStatus::Pending {
+            parts.push("[sending]".to_string());
+        }
+        if data.send_status == SendStatus::Failed {
+            parts.push("✗".to_string());
+        } else if let Some(receipts) = data.receipts.filter(|_| {
+            show_receipts && data.is_outgoing && data.send_status == SendStatus::Sent && !data.pending && !data.sending
+        }) {
+            parts.push(receipts.marker());
+        }
+
         // Add sender name and message
         // We use internal markers that will be parsed in app.rs for coloring
-        // Format: [OUT|IN]:sender_id:sender_name:message
-        let formatted_msg = if data.is_outgoing {
-            format!("[OUT]:{}:{}:{}", data.sender_id, sender_name, text)
-        } else {
-            format!("[IN]:{}:{}:{}", data.sender_id, sender_name, text)
+        // Format: [OUT|IN]:sender_id:sender_name:message, with [OUT+|IN+] for a continuation
+        let marker = match (data.is_outgoing, continues) {
+            (true, false) => "[OUT]",
+            (false, false) => "[IN]",
+            (true, true) => "[OUT+]",
+            (false, true) => "[IN+]",
         };
+        let formatted_msg = format!("{}:{}:{}:{}", marker, data.sender_id, sender_name, text);
         parts.push(formatted_msg);
 
         let mut msg_line = parts.join(" ");
@@ -386,12 +718,434 @@ pub fn format_messages_for_display(
         }
     }
 
+    (lines, starts)
+}
+
+/// Word-wrap plain text to `max_width` columns, hard-splitting words that don't fit
+pub fn wrap_plain_text(text: &str, max_width: usize) -> Vec<String> {
+    wrap_lines(text, max_width, max_width)
+}
+
+/// Word-wrap `text` with the first line `first_width` columns wide and the rest `rest_width`.
+///
+/// This is the one wrapping routine every display path goes through. Widths are terminal
+/// columns (see `display_width`). Each `\n`-separated paragraph starts a new line, empty
+/// paragraphs are kept as empty lines, runs of spaces collapse, and words wider than a whole
+/// line are hard-split. A zero width disables wrapping.
+pub fn wrap_lines(text: &str, first_width: usize, rest_width: usize) -> Vec<String> {
+    if first_width == 0 || rest_width == 0 {
+        return text.split('\n').map(str::to_string).collect();
+    }
+
+    let mut lines: Vec<String> = Vec::new();
+    for paragraph in text.split('\n') {
+        let mut current_line = String::new();
+        let mut current_width = 0;
+        // Width available to the line currently being filled
+        let line_width = |lines: &Vec<String>| if lines.is_empty() { first_width } else { rest_width };
+
+        for word in paragraph.split_whitespace() {
+            let word_width = display_width(word);
+            let sep = usize::from(!current_line.is_empty());
+            if current_width + sep + word_width <= line_width(&lines) {
+                if sep == 1 {
+                    current_line.push(' ');
+                }
+                current_line.push_str(word);
+                current_width += sep + word_width;
+                continue;
+            }
+
+            if !current_line.is_empty() {
+                lines.push(std::mem::take(&mut current_line));
+            }
+            // Hard-split words wider than a whole line
+            let mut rest = word;
+            while display_width(rest) > line_width(&lines) {
+                let (head, tail) = split_at_width(rest, line_width(&lines));
+                lines.push(head.to_string());
+                rest = tail;
+            }
+            current_line = rest.to_string();
+            current_width = display_width(rest);
+        }
+        lines.push(current_line);
+    }
     lines
 }
 
+/// Wrap "prefix sender: text", indenting continuation lines according to `style`
+pub fn wrap_message_with_indent(
+    prefix: &str,
+    sender_name: &str,
+    message_text: &str,
+    max_width: usize,
+    style: WrapStyle,
+) -> Vec<String> {
+    wrap_with_header(&format!("{}{}: ", prefix, sender_name), message_text, max_width, style)
+}
+
+/// Wrap `header` followed by `message_text`, indenting continuation lines according to `style`
+fn wrap_with_header(header: &str, message_text: &str, max_width: usize, style: WrapStyle) -> Vec<String> {
+    let header_len = display_width(header);
+
+    if header_len >= max_width {
+        return wrap_plain_text(&format!("{}{}", header, message_text), max_width);
+    }
+
+    let indent_len = style.continuation_indent(header_len, max_width);
+    let wrapped = wrap_lines(message_text, max_width - header_len, max_width - indent_len);
+
+    let indent = " ".repeat(indent_len);
+    let mut lines = Vec::with_capacity(wrapped.len());
+    for (i, line) in wrapped.iter().enumerate() {
+        if i == 0 {
+            let first = format!("{}{}", header, line);
+            lines.push(if line.is_empty() { first.trim_end().to_string() } else { first });
+        } else if line.is_empty() {
+            lines.push(String::new());
+        } else {
+            lines.push(format!("{}{}", indent, line));
+        }
+    }
+    lines
+}
+
+/// One line of `format_messages_for_display` output, split on its rendering marker
+pub enum DisplayLine<'a> {
+    Blank,
+    ReplyToMe(String),
+    ReplyContext(&'a str),
+    DaySeparator(&'a str),
+    Message {
+        is_outgoing: bool,
+        prefix: &'a str,
+        sender_id: &'a str,
+        sender_name: &'a str,
+        text: &'a str,
+    },
+    /// A grouped message following one from the same sender: drawn without the name, its
+    /// text lined up under the text of the message before it
+    Continuation {
+        is_outgoing: bool,
+        prefix: &'a str,
+        sender_name: &'a str,
+        text: &'a str,
+    },
+    Plain(&'a str),
+}
+
+impl<'a> DisplayLine<'a> {
+    pub fn parse(msg: &'a str) -> Self {
+        if msg.is_empty() {
+            return DisplayLine::Blank;
+        }
+        if msg.starts_with("[REPLY_TO_ME]") {
+            return DisplayLine::ReplyToMe(msg.replace("[REPLY_TO_ME]", "").trim_start().to_string());
+        }
+        if msg.starts_with("  ↳ Reply to") {
+            return DisplayLine::ReplyContext(msg);
+        }
+        if msg.starts_with(DAY_SEPARATOR) || [BEGINNING_OF_CHAT, OLDER_HISTORY_UNAVAILABLE].contains(&msg.trim_start()) {
+            return DisplayLine::DaySeparator(msg);
+        }
+
+        // The first marker is the line's own; the text after it may hold anything
+        let found = [("[OUT]:", true, false), ("[IN]:", false, false), ("[OUT+]:", true, true), ("[IN+]:", false, true)]
+            .into_iter()
+            .filter_map(|(marker, is_outgoing, continues)| msg.find(marker).map(|pos| (pos, marker, is_outgoing, continues)))
+            .min_by_key(|(pos, ..)| *pos);
+        if let Some((marker_pos, marker, is_outgoing, continues)) = found {
+            let after_marker = &msg[marker_pos + marker.len()..];
+            if let Some((sender_id, after_id)) = after_marker.split_once(':') {
+                if let Some((sender_name, text)) = after_id.split_once(':') {
+                    let prefix = &msg[..marker_pos];
+                    return if continues {
+                        DisplayLine::Continuation { is_outgoing, prefix, sender_name, text }
+                    } else {
+                        DisplayLine::Message { is_outgoing, prefix, sender_id, sender_name, text }
+                    };
+                }
+            }
+        }
+        DisplayLine::Plain(msg)
+    }
+
+    /// What goes before a message's text: "prefix sender: ", or for a continuation the
+    /// prefix and as many spaces
+    fn header(&self) -> Option<String> {
+        match self {
+            DisplayLine::Message { prefix, sender_name, .. } => Some(format!("{}{}: ", prefix, sender_name)),
+            DisplayLine::Continuation { prefix, sender_name, .. } => {
+                Some(format!("{}{}", prefix, " ".repeat(display_width(sender_name) + 2)))
+            }
+            _ => None,
+        }
+    }
+
+    /// The screen lines this line occupies at `width` columns
+    pub fn wrap(&self, width: usize, style: WrapStyle) -> Vec<String> {
+        match self {
+            DisplayLine::Blank => vec![String::new()],
+            DisplayLine::ReplyToMe(text) => wrap_plain_text(text, width),
+            DisplayLine::ReplyContext(text) | DisplayLine::DaySeparator(text) | DisplayLine::Plain(text) => {
+                wrap_plain_text(text, width)
+            }
+            DisplayLine::Message {
+                prefix,
+                sender_name,
+                text,
+                ..
+            } => wrap_message_with_indent(prefix, sender_name, text, width, style),
+            DisplayLine::Continuation { text, .. } => {
+                wrap_with_header(&self.header().unwrap_or_default(), text, width, style)
+            }
+        }
+    }
+}
+
+/// How a pane turns display lines into screen lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineLayout {
+    pub width: usize,
+    pub wrap_style: WrapStyle,
+    /// One truncated line per message instead of wrapping, like an email list
+    pub list_mode: bool,
+    /// In list mode, the message shown in full
+    pub expanded: Option<usize>,
+}
+
+impl LineLayout {
+    pub fn new(width: usize, wrap_style: WrapStyle) -> Self {
+        Self {
+            width,
+            wrap_style,
+            ..Self::default()
+        }
+    }
+
+    /// The screen lines for a display line belonging to message `owner`
+    pub fn screen_lines(&self, line: &DisplayLine, owner: Option<usize>) -> Vec<String> {
+        if !self.list_mode || (owner.is_some() && owner == self.expanded) {
+            return line.wrap(self.width, self.wrap_style);
+        }
+        match line {
+            // Scanning a list: no separators or quoted context
+            DisplayLine::Blank | DisplayLine::ReplyContext(_) => Vec::new(),
+            DisplayLine::ReplyToMe(text) => vec![truncate_to_width(text, self.width)],
+            DisplayLine::DaySeparator(text) | DisplayLine::Plain(text) => vec![truncate_to_width(text, self.width)],
+            DisplayLine::Message { text, .. } | DisplayLine::Continuation { text, .. } => {
+                let full = format!("{}{}", line.header().unwrap_or_default(), text.replace('\n', " "));
+                vec![truncate_to_width(&full, self.width)]
+            }
+        }
+    }
+}
+
+/// Cut `text` to `max_width` columns, ending in "…" when anything was cut. A zero width
+/// leaves it whole.
+pub fn truncate_to_width(text: &str, max_width: usize) -> String {
+    if max_width == 0 || display_width(text) <= max_width {
+        return text.to_string();
+    }
+    let (head, _) = split_at_width(text, max_width - 1);
+    format!("{}…", head.trim_end())
+}
+
+/// Where a chat list entry shows its unread count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnreadCountStyle {
+    /// "(3) " in front of the name
+    #[default]
+    Inline,
+    /// Fixed-width badge in front of the name, so names line up in a column
+    Badge,
+    /// " (3)" against the right edge of the list
+    Right,
+}
+
+/// Columns of the `UnreadCountStyle::Badge` count; larger counts show as "999+"
+const UNREAD_BADGE_WIDTH: usize = 4;
+
+/// One chat list entry cut to fit its row. Rendered in order: marker, prefix, name, suffix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatListEntry {
+    pub marker: String,
+    pub prefix: String,
+    pub name: String,
+    pub suffix: String,
+    pub truncated: bool,
+}
+
+/// Lay out a chat list entry in `width` columns. Only the name is shortened, ending in
+/// `ellipsis` when cut; the marker and unread count always stay whole.
+pub fn layout_chat_list_entry(
+    unread: u32,
+    name: &str,
+    width: usize,
+    style: UnreadCountStyle,
+    ellipsis: &str,
+) -> ChatListEntry {
+    let marker = match (unread > 0, style) {
+        (true, _) => "▶ ",
+        // Keep badge names in their column when there is nothing unread
+        (false, UnreadCountStyle::Badge) => "  ",
+        (false, _) => "",
+    }
+    .to_string();
+    let (prefix, suffix) = match style {
+        UnreadCountStyle::Inline if unread > 0 => (format!("({}) ", unread), String::new()),
+        UnreadCountStyle::Inline => (String::new(), String::new()),
+        UnreadCountStyle::Badge => {
+            let count = match unread {
+                0 => String::new(),
+                1..=999 => unread.to_string(),
+                _ => "999+".to_string(),
+            };
+            (format!("{:>w$} ", count, w = UNREAD_BADGE_WIDTH), String::new())
+        }
+        UnreadCountStyle::Right if unread > 0 => (String::new(), format!(" ({})", unread)),
+        UnreadCountStyle::Right => (String::new(), String::new()),
+    };
+
+    let available = width
+        .saturating_sub(display_width(&marker) + display_width(&prefix) + display_width(&suffix));
+    let truncated = display_width(name) > available;
+    let mut name = if truncated {
+        let ellipsis = if display_width(ellipsis) <= available { ellipsis } else { "" };
+        let keep = available - display_width(ellipsis);
+        let head = if keep == 0 { "" } else { split_at_width(name, keep).0 };
+        // A wide character may not fit in the last column
+        let head = if display_width(head) > keep { "" } else { head };
+        format!("{}{}", head, ellipsis)
+    } else {
+        name.to_string()
+    };
+    if !suffix.is_empty() {
+        let pad = available.saturating_sub(display_width(&name));
+        name.push_str(&" ".repeat(pad));
+    }
+
+    ChatListEntry { marker, prefix, name, suffix, truncated }
+}
+
+/// The message each of `lines` belongs to, before wrapping. A message owns everything from
+/// its first line up to the next message's.
+pub fn line_owners(lines: &[String], starts: &[Option<usize>]) -> Vec<Option<usize>> {
+    let mut owners: Vec<Option<usize>> = vec![None; lines.len()];
+    let mut msg_starts: Vec<(usize, usize)> = starts
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, start)| start.map(|s| (s, idx)))
+        .collect();
+    msg_starts.sort_unstable();
+    for (n, &(start, idx)) in msg_starts.iter().enumerate() {
+        let end = msg_starts.get(n + 1).map_or(lines.len(), |&(next, _)| next);
+        for owner in &mut owners[start..end.min(lines.len())] {
+            *owner = Some(idx);
+        }
+    }
+    owners
+}
+
+/// For each screen line (as laid out by `layout`), the index into `msg_data` of the message
+/// drawn there. Blank lines, the unread divider and indicator lines map to `None`.
+pub fn line_to_msg(lines: &[String], starts: &[Option<usize>], layout: &LineLayout) -> Vec<Option<usize>> {
+    let mut mapping = Vec::with_capacity(lines.len());
+    for (line, owner) in lines.iter().zip(line_owners(lines, starts)) {
+        let parsed = DisplayLine::parse(line);
+        let count = layout.screen_lines(&parsed, owner).len();
+        let owner = match parsed {
+            DisplayLine::Blank | DisplayLine::DaySeparator(_) | DisplayLine::Plain(_) => None,
+            _ => owner,
+        };
+        mapping.extend(std::iter::repeat_n(owner, count));
+    }
+    mapping
+}
+
+/// Screen line (as laid out by `layout`) where the message at `msg_idx` starts
+pub fn message_display_offset(
+    lines: &[String],
+    starts: &[Option<usize>],
+    msg_idx: usize,
+    layout: &LineLayout,
+) -> Option<usize> {
+    starts.get(msg_idx)?.as_ref()?;
+    line_to_msg(lines, starts, layout)
+        .iter()
+        .position(|owner| *owner == Some(msg_idx))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::widgets::Receipts;
+
+    #[test]
+    fn test_format_transcript_resolves_aliases_replies_and_media() {
+        let message = |id: &str, sender: &str, text: &str| MessageData {
+            msg_id: id.to_string(),
+            sender_id: format!("{}@s.whatsapp.net", sender),
+            sender_name: sender.to_string(),
+            text: text.to_string(),
+            is_outgoing: false,
+            timestamp: 0,
+            media_type: None,
+            media_label: None,
+            location: None,
+            contacts: Vec::new(),
+            reactions: HashMap::new(),
+            reply_to_msg_id: None,
+            reply_sender: None,
+            reply_text: None,
+            pending: false,
+            sending: false,
+            send_status: SendStatus::Sent,
+            receipts: None,
+        };
+        let mut photo = message("2", "bob", "");
+        photo.media_type = Some("photo".to_string());
+        let mut reply = message("3", "bob", "sure");
+        reply.reply_to_msg_id = Some("1".to_string());
+        let msgs = vec![message("1", "alice", "lunch?\ntomorrow"), photo, reply];
+        let aliases = HashMap::from([("alice@s.whatsapp.net".to_string(), "Al".to_string())]);
+
+        let txt = format_transcript(&msgs, &aliases, "Team", false);
+        let lines: Vec<&str> = txt.lines().collect();
+        assert_eq!(lines[0], "Chat: Team");
+        assert!(lines[2].ends_with("] Al: lunch?"), "{}", lines[2]);
+        assert!(lines[4].ends_with("] bob: [photo]"), "{}", lines[4]);
+        assert!(lines[5].ends_with("] bob (replying to Al: \"lunch?\"): sure"), "{}", lines[5]);
+
+        let md = format_transcript(&msgs, &aliases, "Team", true);
+        assert!(md.starts_with("# Team\n\n**Al** · "));
+        assert!(md.contains("\n\n[photo]\n\n"));
+        assert!(md.contains("> **Al:**\n> lunch?\n> tomorrow\n\nsure\n"));
+    }
+
+    #[test]
+    fn test_mentions_me() {
+        let me = "46700000000:12@s.whatsapp.net";
+        assert!(mentions_me("hey @46700000000, lunch?", me));
+        assert!(mentions_me("call +46700000000", me));
+        assert!(mentions_me("46700000000", me));
+        assert!(mentions_me("ask @ME", me));
+        assert!(mentions_me("ask @me", crate::whatsapp::UNKNOWN_JID));
+        assert!(!mentions_me("@467000000001 is someone else", me));
+        assert!(!mentions_me("see 1467000000000", me));
+        assert!(!mentions_me("mail @meg or come@me", me));
+        assert!(!mentions_me("unknown is not a number", crate::whatsapp::UNKNOWN_JID));
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("hello", 5), "hello");
+        assert_eq!(truncate_chars("hello!", 5), "hello...");
+        assert_eq!(truncate_chars("héllo wörld", 7), "héllo w...");
+        assert_eq!(truncate_chars("", 0), "");
+    }
 
     #[test]
     fn test_shorten_urls() {
@@ -429,19 +1183,140 @@ mod tests {
     }
 
     #[test]
-    fn test_wrap_text() {
-        let text = "This is a longer text that should be wrapped at word boundaries properly";
-        let result = wrap_text(text, 10, 40);
-        // Each line should not exceed 40 chars (first line) or 30 chars (continuation)
-        for (i, line) in result.split('\n').enumerate() {
-            if i == 0 {
-                assert!(line.len() <= 40, "First line too long: {}", line);
-            } else {
-                assert!(line.len() <= 40, "Continuation line too long: {}", line);
+    fn test_consecutive_messages_share_one_name_header() {
+        let at = |id: &str, ts: i64| MessageData { timestamp: ts, ..msg(id, &format!("message {}", id)) };
+        let mut alice = at("5", 700);
+        alice.sender_id = "alice@s.whatsapp.net".to_string();
+        alice.sender_name = "Alice".to_string();
+        let data = vec![at("1", 0), at("2", 60), at("3", 120), at("4", 120 + GROUP_GAP_SECS + 1), alice];
+        let pane = pane_of(&data, &[], 0, false);
+        let format = |group| {
+            let options = DisplayOptions { show_line_numbers: true, group_consecutive: group, ..compact() };
+            format_messages_for_display(&pane, 80, options, &HashMap::new()).0
+        };
+
+        let grouped = format(true);
+        let headers = |lines: &[String]| {
+            lines.iter().filter(|l| matches!(DisplayLine::parse(l), DisplayLine::Message { sender_name: "Bob", .. })).count()
+        };
+        assert_eq!(headers(&grouped[..3]), 1);
+        // Line numbers stay, so /reply 2 still finds the continuation
+        assert_eq!(grouped[1], "#2 [IN+]:bob@s.whatsapp.net:Bob:message 2");
+        assert!(matches!(DisplayLine::parse(&grouped[1]), DisplayLine::Continuation { sender_name: "Bob", .. }));
+        // The gap and the change of sender both start a new header
+        assert_eq!(headers(&grouped), 2);
+        assert!(grouped[4].contains("Alice:"));
+        assert_eq!(headers(&format(false)), 4);
+
+        // The continuation's text lines up under the first message's
+        let layout = LineLayout::new(80, WrapStyle::Aligned);
+        let first = layout.screen_lines(&DisplayLine::parse(&grouped[0]), Some(0));
+        let second = layout.screen_lines(&DisplayLine::parse(&grouped[1]), Some(1));
+        assert_eq!(first, vec!["#1 Bob: message 1"]);
+        assert_eq!(second, vec!["#2      message 2"]);
+    }
+
+    #[test]
+    fn test_day_separator_between_messages_from_different_days() {
+        let at = |id: &str, hour: u32, day: u32| MessageData {
+            timestamp: Local.with_ymd_and_hms(2026, 1, day, hour, 0, 0).unwrap().timestamp(),
+            ..msg(id, "hi")
+        };
+        let data = vec![at("1", 9, 5), at("2", 23, 5), at("3", 8, 6), at("4", 9, 6)];
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 40, compact(), &HashMap::new(),
+        );
+
+        let separators: Vec<usize> = (0..lines.len())
+            .filter(|&i| matches!(DisplayLine::parse(&lines[i]), DisplayLine::DaySeparator(_)))
+            .collect();
+        assert_eq!(separators, vec![2]);
+        assert_eq!(starts, vec![Some(0), Some(1), Some(3), Some(4)]);
+        let today = NaiveDate::from_ymd_opt(2026, 10, 17).unwrap();
+        let separator = format_day_separator(NaiveDate::from_ymd_opt(2026, 1, 6).unwrap(), today, 40);
+        assert_eq!(separator, format!("{0} Tuesday, Jan 6 {0}", "─".repeat(12)));
+        assert_eq!(display_width(&separator), 40);
+        // Not a message line, so it is never colored or selected
+        let layout = LineLayout::new(40, WrapStyle::Aligned);
+        assert_eq!(line_to_msg(&lines, &starts, &layout)[2], None);
+        assert!(format_day_separator(NaiveDate::from_ymd_opt(2025, 1, 6).unwrap(), today, 0).contains("Jan 6 2025"));
+    }
+
+    #[test]
+    fn test_reaction_summary_expands_on_the_selected_message() {
+        let mut reactions = HashMap::new();
+        reactions.insert("👍".to_string(), 5);
+        reactions.insert("❤️".to_string(), 2);
+        reactions.insert("😂".to_string(), 1);
+        assert_eq!(summarize_reactions(&reactions), "8 👍…");
+        assert_eq!(summarize_reactions(&HashMap::from([("🔥".to_string(), 3)])), "3 🔥");
+        assert_eq!(summarize_reactions(&HashMap::new()), "");
+        // Equal counts always pick the same emoji
+        let tie = HashMap::from([("b".to_string(), 2), ("a".to_string(), 2)]);
+        assert_eq!(summarize_reactions(&tie), "4 a…");
+
+        let mut liked = msg("1", "party tonight");
+        liked.reactions = reactions;
+        let data = vec![liked, msg("2", "ok")];
+        let mut pane = pane_of(&data, &[], 0, false);
+        let format = |pane: &ChatPane, style| {
+            format_messages_for_display(pane, 80, DisplayOptions { reactions: style, ..compact() }, &HashMap::new()).0
+        };
+        assert!(format(&pane, Some(ReactionStyle::Summary))[0].ends_with("party tonight [8 👍…]"));
+        assert!(format(&pane, Some(ReactionStyle::Full))[0].contains("5x👍"));
+        assert!(!format(&pane, None)[0].contains('👍'));
+
+        pane.selected_msg = Some(0);
+        let lines = format(&pane, Some(ReactionStyle::Summary));
+        assert!(lines[0].contains("5x👍") && lines[0].contains("2x❤️"), "{}", lines[0]);
+    }
+
+    #[test]
+    fn test_wrap_lines_matrix() {
+        let cases: &[(&str, usize, usize, &[&str])] = &[
+            // Fits on one line
+            ("short text", 20, 20, &["short text"]),
+            // Word boundaries, narrower first line
+            ("one two three four", 7, 10, &["one two", "three four"]),
+            // Long words are hard-split, then wrapping continues after them
+            ("abcdefghijkl xy", 5, 5, &["abcde", "fghij", "kl xy"]),
+            ("ab abcdefghij", 6, 4, &["ab", "abcd", "efgh", "ij"]),
+            // Paragraphs start new lines, blank ones are kept
+            ("first line\n\nsecond paragraph here", 12, 12, &["first line", "", "second", "paragraph", "here"]),
+            ("a\nb", 80, 80, &["a", "b"]),
+            // Runs of spaces collapse
+            ("a    b", 10, 10, &["a b"]),
+            // Wide characters count two columns
+            ("日本語 日本語", 6, 6, &["日本語", "日本語"]),
+            ("🎉🎉🎉", 4, 4, &["🎉🎉", "🎉"]),
+            ("café naïve", 4, 5, &["café", "naïve"]),
+            // Zero width disables wrapping but still honours paragraphs
+            ("no wrap\nhere", 0, 0, &["no wrap", "here"]),
+            ("", 10, 10, &[""]),
+        ];
+        for (text, first, rest, expected) in cases {
+            let lines = wrap_lines(text, *first, *rest);
+            assert_eq!(&lines, expected, "wrap_lines({:?}, {}, {})", text, first, rest);
+            if *first > 0 {
+                assert!(display_width(&lines[0]) <= *first);
+                assert!(lines[1..].iter().all(|l| display_width(l) <= *rest));
             }
         }
     }
 
+    #[test]
+    fn test_message_wrap_keeps_paragraphs_and_indent() {
+        let lines = wrap_message_with_indent("", "Bob", "first\n\nsecond one", 40, WrapStyle::Aligned);
+        assert_eq!(lines, vec!["Bob: first", "", "     second one"]);
+
+        // Empty text leaves just the header
+        assert_eq!(wrap_message_with_indent("10:30 ", "Bob", "", 40, WrapStyle::Aligned), vec!["10:30 Bob:"]);
+
+        // Header wider than the pane wraps as plain text
+        let narrow = wrap_message_with_indent("", "Bartholomew", "hi there", 8, WrapStyle::Aligned);
+        assert!(narrow.iter().all(|l| display_width(l) <= 8));
+    }
+
     #[test]
     fn test_strip_emojis() {
         let text = "Hello 👋 World 🌍";
@@ -451,4 +1326,437 @@ mod tests {
         assert!(result.contains("Hello"));
         assert!(result.contains("World"));
     }
+
+    fn msg(id: &str, text: &str) -> MessageData {
+        MessageData {
+            msg_id: id.to_string(),
+            sender_id: "bob@s.whatsapp.net".to_string(),
+            sender_name: "Bob".to_string(),
+            text: text.to_string(),
+            is_outgoing: false,
+            timestamp: 0,
+            media_type: None,
+            media_label: None,
+            location: None,
+            contacts: Vec::new(),
+            reactions: HashMap::new(),
+            reply_to_msg_id: None,
+            reply_sender: None,
+            reply_text: None,
+            pending: false,
+            sending: false,
+            send_status: SendStatus::Sent,
+            receipts: None,
+        }
+    }
+
+    fn pane_of(data: &[MessageData], filters: &[Filter], unread: u32, unread_only: bool) -> ChatPane {
+        let mut pane = ChatPane::new();
+        pane.msg_data = data.to_vec();
+        pane.filters = filters.to_vec();
+        pane.unread_count_at_load = unread;
+        pane.unread_only = unread_only;
+        pane
+    }
+
+    /// Compact, with emojis and nothing else
+    fn compact() -> DisplayOptions {
+        DisplayOptions { compact_mode: true, show_emojis: true, ..DisplayOptions::default() }
+    }
+
+    #[test]
+    fn test_message_display_offset_counts_wrapped_lines() {
+        let mut reply = msg("3", "ok");
+        reply.reply_to_msg_id = Some("1".to_string());
+        let data = vec![
+            msg("1", "one two three four five six seven eight nine ten"),
+            msg("2", ""),
+            reply,
+        ];
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 20, compact(), &HashMap::new(),
+        );
+
+        assert_eq!(starts, vec![Some(0), None, Some(1)]);
+        assert_eq!(message_display_offset(&lines, &starts, 0, &LineLayout::new(20, WrapStyle::Aligned)), Some(0));
+        // "Bob: " leaves 15 columns, so the first message wraps onto 4 lines
+        assert_eq!(message_display_offset(&lines, &starts, 2, &LineLayout::new(20, WrapStyle::Aligned)), Some(4));
+        assert_eq!(message_display_offset(&lines, &starts, 2, &LineLayout::new(0, WrapStyle::Aligned)), Some(1));
+        assert_eq!(message_display_offset(&lines, &starts, 1, &LineLayout::new(20, WrapStyle::Aligned)), None);
+    }
+
+    #[test]
+    fn test_find_message_on_date_falls_back_to_earlier_message() {
+        let data: Vec<MessageData> = [100, 200, 210, 400]
+            .iter()
+            .enumerate()
+            .map(|(i, &ts)| {
+                let mut m = msg(&i.to_string(), "hi");
+                m.timestamp = ts;
+                m
+            })
+            .collect();
+
+        assert_eq!(find_message_on_date(&data, 200, 300), Some((1, true)));
+        assert_eq!(find_message_on_date(&data, 300, 400), Some((2, false)));
+        assert_eq!(find_message_on_date(&data, 500, 600), Some((3, false)));
+        assert_eq!(find_message_on_date(&data, 0, 50), None);
+    }
+
+    #[test]
+    fn test_parse_time_bound() {
+        let day = parse_time_bound("2024-03-10", false).unwrap();
+        let next = parse_time_bound("2024-03-10", true).unwrap();
+        assert_eq!(Local.timestamp_opt(day, 0).unwrap().format("%Y-%m-%d %H:%M").to_string(), "2024-03-10 00:00");
+        assert_eq!(Local.timestamp_opt(next, 0).unwrap().format("%Y-%m-%d %H:%M").to_string(), "2024-03-11 00:00");
+
+        let week_ago = parse_time_bound("7d", false).unwrap();
+        assert!((Local::now().timestamp() - 7 * 86_400 - week_ago).abs() <= 1);
+        assert!(parse_time_bound("7x", false).is_none());
+        assert!(parse_time_bound("yesterday", false).is_none());
+        assert!(parse_time_bound("-7d", false).is_none());
+        assert!(parse_time_bound("200000000000000d", false).is_none());
+        assert!(parse_time_bound(&format!("{}w", i64::MAX), false).is_none());
+    }
+
+    #[test]
+    fn test_time_range_filter_hides_messages_outside_range() {
+        let mut data: Vec<MessageData> = (1..=4).map(|i| msg(&i.to_string(), &format!("m{}", i))).collect();
+        for (i, m) in data.iter_mut().enumerate() {
+            m.timestamp = 1_000 * (i as i64 + 1);
+        }
+        let filter = FilterType::TimeRange { since: Some(2_000), before: Some(4_000) };
+        let value = FilterType::time_range_value(Some(2_000), Some(4_000));
+        let filters = [Filter::new(filter.clone(), value.clone())];
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &filters, 0, false), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+
+        assert!(lines[0].starts_with("Filter: time="));
+        assert_eq!(starts, vec![None, Some(2), Some(3), None]);
+        // Numbering still follows msg_data so /reply N keeps working
+        assert!(lines[2].starts_with("#2 "));
+        assert_eq!(FilterType::from_saved("time", Some(&value)), Some(filter));
+        assert_eq!(
+            FilterType::from_saved("time", Some("..4000")),
+            Some(FilterType::TimeRange { since: None, before: Some(4_000) })
+        );
+    }
+
+    #[test]
+    fn test_filters_combine_with_and() {
+        let mut data: Vec<MessageData> = (1..=4).map(|i| msg(&i.to_string(), &format!("m{}", i))).collect();
+        data[0].media_type = Some("photo".to_string());
+        data[0].sender_name = "Alice".to_string();
+        data[1].media_type = Some("photo".to_string());
+        let filters = [
+            Filter::new(FilterType::Media, "photo"),
+            Filter::new(FilterType::Sender, "bob"),
+        ];
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &filters, 0, false), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+
+        assert_eq!(lines[0], "Filter: media=photo AND sender=bob (use /filter off to disable)");
+        assert_eq!(starts, vec![None, Some(2), None, None]);
+    }
+
+    #[test]
+    fn test_pane_filters_match_sender_media_and_links() {
+        let matches = |filter: Filter, data: &MessageData| {
+            pane_of(&[], &[filter], 0, false).message_matches_filter(data)
+        };
+
+        // Sender: case-insensitive substring
+        let mut from_alice = msg("1", "hi");
+        from_alice.sender_name = "Alice Smith".to_string();
+        assert!(matches(Filter::new(FilterType::Sender, "smi"), &from_alice));
+        assert!(matches(Filter::new(FilterType::Sender, "ALICE"), &from_alice));
+        assert!(!matches(Filter::new(FilterType::Sender, "bob"), &from_alice));
+
+        // Media: each subtype matches only itself, anything else matches any media
+        let subtypes = ["photo", "video", "audio", "voice", "document", "sticker", "gif"];
+        for media_type in subtypes {
+            let mut data = msg("2", "");
+            data.media_type = Some(media_type.to_string());
+            for wanted in subtypes {
+                assert_eq!(matches(Filter::new(FilterType::Media, wanted), &data), wanted == media_type);
+            }
+            assert!(matches(Filter::new(FilterType::Media, "media"), &data));
+        }
+        assert!(!matches(Filter::new(FilterType::Media, "media"), &msg("3", "text only")));
+
+        // Link: http and https URLs anywhere in the text
+        assert!(matches(Filter::new(FilterType::Link, "link"), &msg("4", "see https://example.com")));
+        assert!(matches(Filter::new(FilterType::Link, "link"), &msg("5", "http://a.b/c please")));
+        assert!(!matches(Filter::new(FilterType::Link, "link"), &msg("6", "no links here")));
+
+        // The formatter shows exactly the matching messages
+        let data = vec![msg("1", "plain"), msg("2", "https://example.com"), msg("3", "also plain")];
+        let filters = [Filter::new(FilterType::Link, "link")];
+        let (_, starts) = format_messages_for_display(
+            &pane_of(&data, &filters, 0, false), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+        assert_eq!(starts, vec![None, Some(2), None]);
+    }
+
+    #[test]
+    fn test_unread_only_view_starts_before_divider() {
+        let data: Vec<MessageData> = (1..=10).map(|i| msg(&i.to_string(), &format!("m{}", i))).collect();
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &[], 2, true), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+
+        assert_eq!(lines[0], "Unread only: 2 new (use /unread to show all)");
+        // Messages 6-8 are context, 9 and 10 are unread
+        assert!(starts[..5].iter().all(Option::is_none));
+        assert!(starts[5..].iter().all(Option::is_some));
+        assert!(lines.iter().any(|l| l.contains(" 2 unread ")));
+        assert!(lines[starts[5].unwrap()].starts_with("#6 "));
+
+        // Without unread messages the view shows everything
+        let (_, starts) = format_messages_for_display(
+            &pane_of(&data, &[], 0, true), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+        assert!(starts.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn test_timestamp_styles_across_offsets() {
+        let now = Local.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+        let at = |secs_ago: i64| now.timestamp() - secs_ago;
+        let relative = |secs_ago| format_timestamp_as(at(secs_ago), TimestampStyle::Relative, now);
+        assert_eq!(relative(0), "just now");
+        assert_eq!(relative(59), "just now");
+        assert_eq!(relative(60), "1m");
+        assert_eq!(relative(2 * 60 + 30), "2m");
+        assert_eq!(relative(3599), "59m");
+        assert_eq!(relative(3600), "1h");
+        assert_eq!(relative(11 * 3600), "11h");
+        // 13:00 the day before is under 24 hours ago but not today
+        assert_eq!(relative(23 * 3600), "yesterday");
+        assert_eq!(relative(3 * 86400), "3d");
+        assert_eq!(relative(6 * 86400), "6d");
+        assert_eq!(relative(8 * 86400), "2024-05-07");
+        // A sender's clock running ahead doesn't give times in the future
+        assert_eq!(relative(-600), "just now");
+
+        let clock = |secs_ago| format_timestamp_as(at(secs_ago), TimestampStyle::Clock, now);
+        assert_eq!(clock(90 * 60), "10:30");
+        assert_eq!(clock(23 * 3600), "2024-05-14 13:00");
+        let full = |secs_ago| format_timestamp_as(at(secs_ago), TimestampStyle::Full, now);
+        assert_eq!(full(90 * 60), "2024-05-15 10:30");
+        assert_eq!(full(-600), "2024-05-15 12:10");
+
+        let mut recent = msg("1", "hi");
+        recent.timestamp = Local::now().timestamp() - 120;
+        let data = vec![recent];
+        let (lines, _) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 80, DisplayOptions { timestamps: Some(TimestampStyle::Relative), ..compact() }, &HashMap::new(),
+        );
+        assert!(lines[0].starts_with("2m "), "{}", lines[0]);
+    }
+
+    #[test]
+    fn test_media_label_carries_message_number_without_line_numbers() {
+        let mut photo = msg("2", "");
+        photo.media_type = Some("photo".to_string());
+        let data = vec![msg("1", "hi"), photo];
+
+        let (lines, _) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 80, compact(), &HashMap::new(),
+        );
+        assert!(lines[1].ends_with(":Bob:[IMG #2]"));
+
+        let (lines, _) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+        assert!(lines[1].starts_with("#2 ") && lines[1].ends_with(":Bob:[IMG]"));
+
+        let mut pin = msg("3", "");
+        pin.media_type = Some("location".to_string());
+        pin.location = Some((59.3293, 18.0686));
+        let data = vec![pin];
+        let (lines, _) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 80, compact(), &HashMap::new(),
+        );
+        assert!(lines[0].ends_with(":Bob:[LOCATION: 59.32930, 18.06860 #1]"), "{}", lines[0]);
+
+        let card = |name: &str, phone: Option<&str>| SharedContact {
+            name: name.to_string(),
+            phone: phone.map(str::to_string),
+            vcard: String::new(),
+        };
+        let mut share = msg("4", "BEGIN:VCARD\nFN:Anna\nEND:VCARD");
+        share.media_type = Some("contact".to_string());
+        share.contacts = vec![card("Anna", Some("+46 70 123 45 67"))];
+        let mut several = share.clone();
+        several.contacts.push(card("Bob", None));
+        let data = vec![share, several];
+        let (lines, _) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 80, DisplayOptions { show_line_numbers: true, ..compact() }, &HashMap::new(),
+        );
+        assert!(lines[0].ends_with(":Bob:[CONTACT: Anna (+46 70 123 45 67)]"), "{}", lines[0]);
+        assert!(lines[1].ends_with(":Bob:[CONTACTS: Anna (+46 70 123 45 67), Bob]"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_receipt_marks_only_on_sent_outgoing_messages() {
+        let mut mine = msg("1", "hi");
+        mine.is_outgoing = true;
+        mine.receipts = Some(Receipts { delivered: 3, read: 1, recipients: Some(3) });
+        let mut queued = msg("2", "later");
+        queued.is_outgoing = true;
+        queued.pending = true;
+        let mut unknown = msg("4", "no word yet");
+        unknown.is_outgoing = true;
+        let data = vec![mine, queued, msg("3", "hello"), unknown];
+        let format = |show_receipts| {
+            format_messages_for_display(
+                &pane_of(&data, &[], 0, false), 80, DisplayOptions { show_receipts, ..compact() }, &HashMap::new(),
+            )
+            .0
+        };
+
+        let lines = format(true);
+        assert!(lines[0].starts_with("✓✓ 1/3 [OUT]:"), "{}", lines[0]);
+        assert!(lines[1].starts_with("[queued] [OUT]:"), "{}", lines[1]);
+        assert!(lines[2].starts_with("[IN]:"), "{}", lines[2]);
+        assert!(lines[3].starts_with("[OUT]:"), "{}", lines[3]);
+        assert!(format(false)[0].starts_with("[OUT]:"));
+
+        assert_eq!(Receipts::default().marker(), "✓");
+        assert_eq!(Receipts { delivered: 1, read: 0, recipients: Some(1) }.marker(), "✓✓");
+        assert_eq!(Receipts { delivered: 1, read: 1, recipients: Some(1) }.marker(), crate::widgets::READ_MARKER);
+        assert_eq!(Receipts { delivered: 4, read: 2, recipients: None }.marker(), "✓✓ 2 read");
+    }
+
+    #[test]
+    fn test_line_to_msg_maps_wrapped_lines_to_messages() {
+        let mut reply = msg("3", "ok");
+        reply.reply_to_msg_id = Some("1".to_string());
+        let data = vec![
+            msg("1", "one two three four five six seven eight nine ten"),
+            msg("2", ""),
+            reply,
+        ];
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 20, DisplayOptions { show_emojis: true, ..DisplayOptions::default() }, &HashMap::new(),
+        );
+
+        // Message 1 wraps onto 4 lines, then a blank separator. The reply's quoted context
+        // (4 wrapped lines) belongs to the reply. Message 2 is empty and never drawn.
+        let mut expected = vec![Some(0); 4];
+        expected.push(None);
+        expected.extend([Some(2); 5]);
+        expected.push(None);
+        assert_eq!(line_to_msg(&lines, &starts, &LineLayout::new(20, WrapStyle::Aligned)), expected);
+    }
+
+    #[test]
+    fn test_list_mode_shows_one_truncated_line_per_message() {
+        let mut reply = msg("3", "ok");
+        reply.reply_to_msg_id = Some("1".to_string());
+        let data = vec![msg("1", "one two three four five six seven eight nine ten"), msg("2", "short"), reply];
+        let (lines, starts) = format_messages_for_display(
+            &pane_of(&data, &[], 0, false), 20, DisplayOptions { show_emojis: true, ..DisplayOptions::default() }, &HashMap::new(),
+        );
+
+        let list = LineLayout { list_mode: true, ..LineLayout::new(20, WrapStyle::Aligned) };
+        let owners = line_owners(&lines, &starts);
+        let screen: Vec<String> = lines
+            .iter()
+            .zip(&owners)
+            .flat_map(|(line, owner)| list.screen_lines(&DisplayLine::parse(line), *owner))
+            .collect();
+        assert_eq!(screen, vec!["Bob: one two three…", "Bob: short", "^ Bob: ok"]);
+        assert!(screen.iter().all(|l| display_width(l) <= 20));
+        assert_eq!(line_to_msg(&lines, &starts, &list), vec![Some(0), Some(1), Some(2)]);
+
+        // The expanded message wraps in full, followed by its blank separator
+        let expanded = LineLayout { expanded: Some(0), ..list };
+        let mut expected = vec![Some(0); 4];
+        expected.extend([None, Some(1), Some(2)]);
+        assert_eq!(line_to_msg(&lines, &starts, &expanded), expected);
+        assert_eq!(message_display_offset(&lines, &starts, 2, &expanded), Some(6));
+
+        assert_eq!(truncate_to_width("日本語日本語", 7), "日本語…");
+        assert_eq!(truncate_to_width("fits", 4), "fits");
+    }
+
+    #[test]
+    fn test_wrap_styles_indent_continuation_lines() {
+        let text = "one two three four five six seven eight nine ten eleven twelve";
+        let wrap = |style, width| wrap_message_with_indent("10:30 ", "Alice", text, width, style);
+
+        // "10:30 Alice: " is 13 columns
+        let aligned = wrap(WrapStyle::Aligned, 40);
+        assert_eq!(aligned[0], "10:30 Alice: one two three four five six");
+        assert!(aligned[1..].iter().all(|l| l.starts_with(&" ".repeat(13)) && !l.starts_with(&" ".repeat(14))));
+
+        let hanging = wrap(WrapStyle::Hanging, 40);
+        assert_eq!(hanging[0], aligned[0]);
+        assert_eq!(hanging[1], "  seven eight nine ten eleven twelve");
+
+        let flush = wrap(WrapStyle::Flush, 40);
+        assert_eq!(flush[1], "seven eight nine ten eleven twelve");
+        assert!(flush.len() <= hanging.len() && hanging.len() <= aligned.len());
+
+        // Too narrow to keep the aligned indent: falls back to flush
+        let narrow = wrap(WrapStyle::Aligned, 24);
+        assert!(narrow[1..].iter().all(|l| !l.starts_with(' ')));
+        assert!(narrow.iter().all(|l| l.chars().count() <= 24));
+    }
+
+    #[test]
+    fn test_wrapping_uses_display_width_for_cjk_and_emoji() {
+        // Each CJK character is 3 bytes but 2 columns wide
+        let cjk = "日本語 日本語 日本語";
+        assert_eq!(display_width(cjk), 20);
+        assert_eq!(wrap_plain_text(cjk, 14), vec!["日本語 日本語", "日本語"]);
+        assert_eq!(wrap_plain_text(cjk, 20), vec![cjk]);
+
+        // A word wider than the line is split without overflowing
+        let long = "漢字漢字漢字漢字";
+        let lines = wrap_plain_text(long, 5);
+        assert_eq!(lines, vec!["漢字", "漢字", "漢字", "漢字"]);
+
+        let emoji = "🎉🎉 party 🎉🎉 time";
+        for line in wrap_message_with_indent("", "Bob", emoji, 12, WrapStyle::Flush) {
+            assert!(display_width(&line) <= 12, "{:?} overflows", line);
+        }
+
+        let wrapped = wrap_message_with_indent("", "Al", "日本語 日本語 日本語", 18, WrapStyle::Aligned);
+        assert_eq!(wrapped, vec!["Al: 日本語 日本語", "    日本語"]);
+    }
+
+    #[test]
+    fn test_chat_list_entry_truncation() {
+        let entry = |unread, name, width, style, ellipsis| {
+            let e = layout_chat_list_entry(unread, name, width, style, ellipsis);
+            (format!("{}{}{}{}", e.marker, e.prefix, e.name, e.suffix), e.truncated)
+        };
+
+        // Inline keeps the current look
+        assert_eq!(entry(0, "Alice", 10, UnreadCountStyle::Inline, "…"), ("Alice".to_string(), false));
+        assert_eq!(entry(3, "Alice Smith", 12, UnreadCountStyle::Inline, "…"), ("▶ (3) Alice…".to_string(), true));
+        assert_eq!(entry(3, "Alice Smith", 12, UnreadCountStyle::Inline, "..."), ("▶ (3) Ali...".to_string(), true));
+        assert_eq!(entry(3, "Alice Smith", 12, UnreadCountStyle::Inline, ""), ("▶ (3) Alice ".to_string(), true));
+
+        // Badges put every name in the same column
+        assert_eq!(entry(0, "Bob", 12, UnreadCountStyle::Badge, "…"), ("       Bob".to_string(), false));
+        assert_eq!(entry(12, "Bob", 12, UnreadCountStyle::Badge, "…"), ("▶   12 Bob".to_string(), false));
+        assert_eq!(entry(5000, "Carol Jones", 12, UnreadCountStyle::Badge, "…"), ("▶ 999+ Caro…".to_string(), true));
+
+        // Right-aligned counts sit against the edge, whatever the name length
+        assert_eq!(entry(2, "Bob", 12, UnreadCountStyle::Right, "…"), ("▶ Bob    (2)".to_string(), false));
+        assert_eq!(entry(2, "Robert Brown", 12, UnreadCountStyle::Right, "…"), ("▶ Rober… (2)".to_string(), true));
+
+        // Widths are columns: wide characters never overflow the row
+        let (text, truncated) = entry(0, "日本語のチャット", 6, UnreadCountStyle::Inline, "…");
+        assert!(truncated);
+        assert!(display_width(&text) <= 6);
+        // No room for the name at all
+        assert_eq!(entry(3, "Alice", 6, UnreadCountStyle::Inline, "…"), ("▶ (3) ".to_string(), true));
+    }
 }
@@ -0,0 +1,458 @@
+use anyhow::Result;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Something a key binding can do. Text editing keys (arrows, Tab, Enter, Backspace, ...)
+/// are not remappable and stay in `run_app`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Quit,
+    RefreshChats,
+    ToggleArchived,
+    SplitVertical,
+    SplitHorizontal,
+    ToggleSplitDirection,
+    ClosePane,
+    ToggleChatList,
+    ClearPane,
+    ToggleReactions,
+    ToggleNotifications,
+    ToggleCompact,
+    ToggleEmojis,
+    ToggleLineNumbers,
+    ToggleTimestamps,
+    ToggleUserColors,
+    ToggleFilter,
+    ToggleBorders,
+    PasteClipboard,
+    PastePrimary,
+    React,
+    Unsend,
+    ToggleChatListFocus,
+    NextUnread,
+    RecentChats,
+    Compose,
+    NewChat,
+    ShowActivity,
+    FocusPrevPane,
+    FocusNextPane,
+    ScrollTop,
+    ScrollBottom,
+    PageUp,
+    PageDown,
+    ShowHelp,
+    ReloadConfig,
+    Reconnect,
+}
+
+/// Every action with its keymap file name, default key and help text
+const ACTIONS: &[(KeyAction, &str, &str, &str)] = &[
+    (KeyAction::Quit, "quit", "ctrl+q", "Quit"),
+    (KeyAction::RefreshChats, "refresh_chats", "ctrl+r", "Refresh the chat list"),
+    (KeyAction::ToggleArchived, "toggle_archived", "ctrl+a", "Show/hide archived chats"),
+    (KeyAction::SplitVertical, "split_vertical", "ctrl+v", "Split the pane side by side"),
+    (KeyAction::SplitHorizontal, "split_horizontal", "ctrl+b", "Split the pane top and bottom"),
+    (KeyAction::ToggleSplitDirection, "toggle_split_direction", "ctrl+k", "Toggle the split direction"),
+    (KeyAction::ClosePane, "close_pane", "ctrl+w", "Close the pane"),
+    (KeyAction::ToggleChatList, "toggle_chat_list", "ctrl+s", "Show/hide the chat list"),
+    (KeyAction::ClearPane, "clear_pane", "ctrl+l", "Clear the pane"),
+    (KeyAction::ToggleReactions, "toggle_reactions", "ctrl+e", "Show/hide reactions"),
+    (KeyAction::ToggleNotifications, "toggle_notifications", "ctrl+n", "Toggle notifications"),
+    (KeyAction::ToggleCompact, "toggle_compact", "ctrl+d", "Toggle compact mode"),
+    (KeyAction::ToggleEmojis, "toggle_emojis", "ctrl+o", "Toggle emojis"),
+    (KeyAction::ToggleLineNumbers, "toggle_line_numbers", "ctrl+g", "Toggle message numbers"),
+    (KeyAction::ToggleTimestamps, "toggle_timestamps", "ctrl+t", "Toggle timestamps"),
+    (KeyAction::ToggleUserColors, "toggle_user_colors", "ctrl+u", "Toggle sender colors"),
+    (KeyAction::ToggleFilter, "toggle_filter", "ctrl+f", "Toggle the last-used filter"),
+    (KeyAction::ToggleBorders, "toggle_borders", "ctrl+y", "Toggle borders"),
+    (KeyAction::PasteClipboard, "paste_clipboard", "alt+v", "Paste the clipboard into the input"),
+    (KeyAction::PastePrimary, "paste_primary", "alt+p", "Paste the primary selection into the input"),
+    (KeyAction::React, "react", "alt+r", "React to the selected or newest message"),
+    (KeyAction::Unsend, "unsend", "alt+u", "Take back the message you just sent (off unless undo_send_secs is set)"),
+    (KeyAction::ToggleChatListFocus, "toggle_chat_list_focus", "alt+c", "Jump between chat list and pane"),
+    (KeyAction::NextUnread, "next_unread", "alt+n", "Open the next chat with unread messages"),
+    (KeyAction::RecentChats, "recent_chats", "ctrl+p", "Cycle through recently opened chats"),
+    (KeyAction::Compose, "compose", "ctrl+x", "Write the message in $EDITOR"),
+    (KeyAction::NewChat, "new_chat", "alt+s", "Start a chat with a phone number"),
+    (KeyAction::ShowActivity, "show_activity", "alt+a", "Show recent notifications"),
+    (KeyAction::FocusPrevPane, "focus_prev_pane", "alt+left", "Focus the previous pane"),
+    (KeyAction::FocusNextPane, "focus_next_pane", "alt+right", "Focus the next pane"),
+    (KeyAction::ScrollTop, "scroll_top", "ctrl+home", "Scroll to the oldest loaded message"),
+    (KeyAction::ScrollBottom, "scroll_bottom", "ctrl+end", "Scroll to the newest message"),
+    (KeyAction::PageUp, "page_up", "pageup", "Scroll up a page"),
+    (KeyAction::PageDown, "page_down", "pagedown", "Scroll down a page"),
+    (KeyAction::ShowHelp, "show_help", "f1", "Show this help"),
+    (KeyAction::ReloadConfig, "reload_config", "f5", "Reload config, keymap and theme"),
+    (KeyAction::Reconnect, "reconnect", "f6", "Check the login and restart sync"),
+];
+
+impl KeyAction {
+    pub fn name(self) -> &'static str {
+        ACTIONS.iter().find(|(a, ..)| *a == self).map_or("", |(_, name, ..)| name)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ACTIONS.iter().find(|(_, n, ..)| *n == name).map(|(a, ..)| *a)
+    }
+
+    /// What the action does, for the help overlay
+    pub fn description(self) -> &'static str {
+        ACTIONS.iter().find(|(a, ..)| *a == self).map_or("", |(.., description)| description)
+    }
+}
+
+/// One key press with its modifiers, e.g. `ctrl+shift+k`. Letters are stored lowercase
+/// with SHIFT, so `K` and `shift+k` are the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeySpec {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeySpec {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let mut modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Char(c) if c.is_uppercase() => {
+                modifiers |= KeyModifiers::SHIFT;
+                KeyCode::Char(c.to_lowercase().next().unwrap_or(c))
+            }
+            // Shift+Tab arrives as BackTab, with or without SHIFT depending on the terminal
+            KeyCode::BackTab => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Parse a single key such as `ctrl+shift+k`, `alt+left`, `pagedown` or `f5`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        // The key itself may be "+", as in "ctrl++"
+        let (mods, key) = match spec.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None => spec.rsplit_once('+').unwrap_or(("", spec)),
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for part in mods.split('+').filter(|p| !p.is_empty()) {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => anyhow::bail!("unknown modifier \"{}\" in \"{}\"", part, spec),
+            };
+        }
+
+        // Letters keep their case: "K" means shift+k
+        let code = match key.to_lowercase().as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    (Some('f' | 'F'), Some(_)) => match key[1..].parse::<u8>() {
+                        Ok(n @ 1..=12) => KeyCode::F(n),
+                        _ => anyhow::bail!("unknown key \"{}\" in \"{}\"", key, spec),
+                    },
+                    (None, _) => anyhow::bail!("missing key in \"{}\"", spec),
+                    _ => anyhow::bail!("unknown key \"{}\" in \"{}\"", key, spec),
+                }
+            }
+        };
+        Ok(Self::new(code, modifiers))
+    }
+
+    /// Whether the key types a character into the input rather than doing something
+    fn is_typed(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && !self.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl From<KeyEvent> for KeySpec {
+    fn from(key: KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+}
+
+impl fmt::Display for KeySpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (KeyModifiers::CONTROL, "ctrl+"),
+            (KeyModifiers::ALT, "alt+"),
+            (KeyModifiers::SHIFT, "shift+"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            KeyCode::BackTab => f.write_str("backtab"),
+            code => f.write_str(&format!("{:?}", code).to_lowercase()),
+        }
+    }
+}
+
+/// A key or a sequence of keys pressed one after another, e.g. `ctrl+s s`
+fn parse_sequence(spec: &str) -> Result<Vec<KeySpec>> {
+    let keys = spec.split_whitespace().map(KeySpec::parse).collect::<Result<Vec<_>>>()?;
+    if keys.is_empty() {
+        anyhow::bail!("empty key binding");
+    }
+    Ok(keys)
+}
+
+fn sequence_label(keys: &[KeySpec]) -> String {
+    keys.iter().map(|k| k.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+/// What a key press means once it has been looked up
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyResolution {
+    Action(KeyAction),
+    /// The start of a longer sequence; wait for the next key
+    Pending,
+    /// Not bound; handle the key as usual
+    Unbound,
+}
+
+/// Key bindings for `KeyAction`s: the defaults, overridden per action by the keymap file
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeySpec>, KeyAction)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: ACTIONS
+                .iter()
+                .map(|(action, _, default, _)| (parse_sequence(default).expect("valid default key"), *action))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    /// Load the keymap file. A missing file means the defaults. A malformed one also falls
+    /// back to the defaults, with the problem returned for the user to see.
+    pub fn load(path: &Path) -> (Self, Option<String>) {
+        if !path.exists() {
+            return (Self::default(), None);
+        }
+        let parsed = fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(serde_json::from_str::<BTreeMap<String, String>>(&content)?))
+            .and_then(|overrides| Self::with_overrides(&overrides));
+        match parsed {
+            Ok((keymap, unbound)) => {
+                let note = (!unbound.is_empty()).then(|| format!("Keymap: {}", unbound.join("; ")));
+                (keymap, note)
+            }
+            Err(e) => {
+                crate::warn_log!("Keymap {}: {}", path.display(), e);
+                (Self::default(), Some(format!("Keymap error, using default keys: {}", e)))
+            }
+        }
+    }
+
+    /// The defaults with `overrides` (action name -> key sequence) applied. A default binding
+    /// that clashes with an override is dropped; the notes returned say which. A sequence may
+    /// not start with a plain character, which would fire while typing a message.
+    pub fn with_overrides(overrides: &BTreeMap<String, String>) -> Result<(Self, Vec<String>)> {
+        let mut custom = Vec::new();
+        for (name, spec) in overrides {
+            let action = KeyAction::from_name(name).ok_or_else(|| anyhow::anyhow!("unknown action \"{}\"", name))?;
+            let keys = parse_sequence(spec).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+            if keys[0].is_typed() {
+                anyhow::bail!("{}: \"{}\" is typed into messages, start it with ctrl+ or alt+", name, keys[0]);
+            }
+            custom.push((keys, action));
+        }
+        for (i, (keys, action)) in custom.iter().enumerate() {
+            if let Some((other_keys, other)) = custom[..i].iter().find(|(other, _)| clashes(keys, other)) {
+                anyhow::bail!(
+                    "{} ({}) clashes with {} ({})",
+                    action.name(),
+                    sequence_label(keys),
+                    other.name(),
+                    sequence_label(other_keys)
+                );
+            }
+        }
+
+        let mut notes = Vec::new();
+        let mut bindings = Vec::new();
+        for (keys, action) in Self::default().bindings {
+            if custom.iter().any(|(_, a)| *a == action) {
+                continue;
+            }
+            match custom.iter().find(|(other, _)| clashes(&keys, other)) {
+                Some((_, by)) => notes.push(format!(
+                    "{} unbound, {} is used by {}",
+                    action.name(),
+                    sequence_label(&keys),
+                    by.name()
+                )),
+                None => bindings.push((keys, action)),
+            }
+        }
+        bindings.extend(custom);
+        Ok((Self { bindings }, notes))
+    }
+
+    /// Every action with the keys bound to it (empty if unbound), in the default order
+    pub fn describe(&self) -> Vec<(KeyAction, String)> {
+        ACTIONS
+            .iter()
+            .map(|(action, ..)| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, a)| a == action)
+                    .map(|(keys, _)| sequence_label(keys))
+                    .collect();
+                (*action, keys.join(", "))
+            })
+            .collect()
+    }
+
+    /// Look up `key`, following on from the keys already `pending` in a sequence
+    pub fn resolve(&self, pending: &mut Vec<KeySpec>, key: KeySpec) -> KeyResolution {
+        pending.push(key);
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| keys == pending) {
+            pending.clear();
+            return KeyResolution::Action(*action);
+        }
+        if self.bindings.iter().any(|(keys, _)| keys.starts_with(pending)) {
+            return KeyResolution::Pending;
+        }
+        // A key that breaks off a sequence counts on its own
+        let restart = pending.len() > 1;
+        pending.clear();
+        if restart {
+            return self.resolve(pending, key);
+        }
+        KeyResolution::Unbound
+    }
+}
+
+/// Whether two sequences can't both be bound: one is the other or starts it
+fn clashes(a: &[KeySpec], b: &[KeySpec]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_spec_parsing() {
+        let ctrl_shift_k = KeySpec::parse("ctrl+shift+k").unwrap();
+        assert_eq!(ctrl_shift_k.code, KeyCode::Char('k'));
+        assert_eq!(ctrl_shift_k.modifiers, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+        assert_eq!(KeySpec::parse("Ctrl+Shift+K").unwrap(), ctrl_shift_k);
+        assert_eq!(KeySpec::parse("ctrl+K").unwrap(), ctrl_shift_k);
+        assert_eq!(ctrl_shift_k.to_string(), "ctrl+shift+k");
+        // What a terminal sends for Ctrl+Shift+K
+        let event = KeyEvent::new(KeyCode::Char('K'), KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+        assert_eq!(KeySpec::from(event), ctrl_shift_k);
+
+        assert_eq!(
+            KeySpec::parse("alt+left").unwrap(),
+            KeySpec { code: KeyCode::Left, modifiers: KeyModifiers::ALT }
+        );
+        assert_eq!(KeySpec::parse("f5").unwrap().code, KeyCode::F(5));
+        assert_eq!(KeySpec::parse("ctrl++").unwrap().code, KeyCode::Char('+'));
+        assert_eq!(KeySpec::parse("shift+tab").unwrap(), KeySpec::from(KeyEvent::from(KeyCode::BackTab)));
+
+        assert!(KeySpec::parse("hyper+k").is_err());
+        assert!(KeySpec::parse("ctrl+").is_err());
+        assert!(KeySpec::parse("ctrl+foo").is_err());
+        assert!(KeySpec::parse("f13").is_err());
+        assert!(parse_sequence("  ").is_err());
+        assert_eq!(parse_sequence("ctrl+s s").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_keymap_overrides_and_sequences() {
+        let key = |spec: &str| KeySpec::parse(spec).unwrap();
+        let defaults = Keymap::default();
+        assert_eq!(defaults.bindings.len(), ACTIONS.len());
+        let mut pending = Vec::new();
+        assert_eq!(defaults.resolve(&mut pending, key("ctrl+v")), KeyResolution::Action(KeyAction::SplitVertical));
+        assert_eq!(defaults.resolve(&mut pending, key("x")), KeyResolution::Unbound);
+
+        let overrides = BTreeMap::from([("split_vertical".to_string(), "ctrl+s s".to_string())]);
+        let (keymap, notes) = Keymap::with_overrides(&overrides).unwrap();
+        assert_eq!(notes, vec!["toggle_chat_list unbound, ctrl+s is used by split_vertical"]);
+        assert_eq!(keymap.resolve(&mut pending, key("ctrl+v")), KeyResolution::Unbound);
+        assert_eq!(keymap.resolve(&mut pending, key("ctrl+s")), KeyResolution::Pending);
+        assert_eq!(keymap.resolve(&mut pending, key("s")), KeyResolution::Action(KeyAction::SplitVertical));
+        assert!(pending.is_empty());
+
+        // A key that doesn't continue the sequence is looked up on its own
+        assert_eq!(keymap.resolve(&mut pending, key("ctrl+s")), KeyResolution::Pending);
+        assert_eq!(keymap.resolve(&mut pending, key("ctrl+q")), KeyResolution::Action(KeyAction::Quit));
+
+        let bad = |name: &str, spec: &str| {
+            Keymap::with_overrides(&BTreeMap::from([(name.to_string(), spec.to_string())])).is_err()
+        };
+        assert!(bad("split_sideways", "ctrl+v"));
+        assert!(bad("split_vertical", "ctrl+bogus"));
+        assert!(bad("quit", "q"));
+        assert!(bad("quit", "Q"));
+        assert!(bad("quit", "space q"));
+        assert!(!bad("quit", "f2"));
+        let clash = BTreeMap::from([
+            ("split_vertical".to_string(), "ctrl+s".to_string()),
+            ("split_horizontal".to_string(), "ctrl+s h".to_string()),
+        ]);
+        assert!(Keymap::with_overrides(&clash).is_err());
+    }
+
+    #[test]
+    fn test_keymap_load_falls_back_to_defaults() {
+        let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_keymap_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("whatsapp_keymap.json");
+
+        let _ = std::fs::remove_file(&path);
+        let (_, note) = Keymap::load(&path);
+        assert!(note.is_none());
+
+        std::fs::write(&path, r#"{"split_vertical": "ctrl+nope"}"#).unwrap();
+        let (keymap, note) = Keymap::load(&path);
+        assert!(note.unwrap().starts_with("Keymap error, using default keys"));
+        let mut pending = Vec::new();
+        assert_eq!(
+            keymap.resolve(&mut pending, KeySpec::parse("ctrl+v").unwrap()),
+            KeyResolution::Action(KeyAction::SplitVertical)
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,24 +1,35 @@
 use anyhow::Result;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 mod app;
 mod commands;
 mod config;
+mod diagnostics;
+mod emoji;
 mod formatting;
+mod keymap;
 mod persistence;
+mod qr;
 mod split_view;
+mod theme;
 mod whatsapp;
 mod utils;
 mod widgets;
 
 
 use app::App;
+use keymap::{KeyAction, KeyResolution};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -38,17 +49,20 @@ async fn main() -> Result<()> {
     crate::info_log!("=== WhatsApp Client Starting ===");
     
     // Create app BEFORE entering TUI mode (so authentication can work)
+    let started = std::time::Instant::now();
     let mut app = App::new().await?;
+    crate::info_log!("Ready for the first frame after {:?}", started.elapsed());
 
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, EnableBracketedPaste)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Run app
-    let _res = run_app(&mut terminal, &mut app).await;
+    let shutdown = listen_for_shutdown_signals();
+    let _res = run_app(&mut terminal, &mut app, &shutdown).await;
     
     // Save state before exiting (even if there was an error)
     let _ = app.save_state();
@@ -58,118 +72,315 @@ async fn main() -> Result<()> {
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
     Ok(())
 }
 
+/// Flag raised by SIGINT, SIGTERM or SIGHUP (the terminal closing), so `run_app` can quit
+/// through the same path as Ctrl+Q instead of the process dying with the terminal in raw mode
+fn listen_for_shutdown_signals() -> Arc<AtomicBool> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let flag = shutdown.clone();
+    tokio::spawn(async move {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let (Ok(mut int), Ok(mut term), Ok(mut hup)) = (
+                signal(SignalKind::interrupt()),
+                signal(SignalKind::terminate()),
+                signal(SignalKind::hangup()),
+            ) else {
+                crate::warn_log!("Could not install signal handlers");
+                return;
+            };
+            let name = tokio::select! {
+                _ = int.recv() => "SIGINT",
+                _ = term.recv() => "SIGTERM",
+                _ = hup.recv() => "SIGHUP",
+            };
+            crate::info_log!("Received {}, shutting down", name);
+        }
+        #[cfg(not(unix))]
+        {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            crate::info_log!("Received Ctrl+C, shutting down");
+        }
+        flag.store(true, Ordering::Relaxed);
+    });
+    shutdown
+}
+
+/// What quitting does, by key or by signal: finish or keep unsent messages and save state
+async fn shut_down(app: &mut App) -> Result<()> {
+    app.drain_sends().await;
+    app.save_state()
+}
+
+/// Run a remappable key binding. Returns true to quit.
+async fn run_key_action(app: &mut App, action: KeyAction) -> Result<bool> {
+    match action {
+        KeyAction::Quit => {
+            shut_down(app).await?;
+            return Ok(true);
+        }
+        KeyAction::RefreshChats => app.refresh_chats().await?,
+        KeyAction::ToggleArchived => app.toggle_archived(),
+        KeyAction::SplitVertical => app.split_vertical(),
+        KeyAction::SplitHorizontal => app.split_horizontal(),
+        KeyAction::ToggleSplitDirection => app.toggle_split_direction(),
+        KeyAction::ClosePane => app.close_pane(),
+        KeyAction::ToggleChatList => app.toggle_chat_list(),
+        KeyAction::ClearPane => app.clear_pane(),
+        KeyAction::ToggleReactions => app.toggle_reactions(),
+        KeyAction::ToggleNotifications => app.toggle_notifications(),
+        KeyAction::ToggleCompact => app.toggle_compact(),
+        KeyAction::ToggleEmojis => app.toggle_emojis(),
+        KeyAction::ToggleLineNumbers => app.toggle_line_numbers(),
+        KeyAction::ToggleTimestamps => app.toggle_timestamps(),
+        KeyAction::ToggleUserColors => app.toggle_user_colors(),
+        KeyAction::ToggleFilter => app.toggle_filter(),
+        KeyAction::ToggleBorders => app.toggle_borders(),
+        // Paste into the input, so not while the chat list has focus
+        KeyAction::PasteClipboard | KeyAction::PastePrimary if !app.focus_on_chat_list => {
+            let selection = if action == KeyAction::PasteClipboard {
+                utils::Selection::Clipboard
+            } else {
+                utils::Selection::Primary
+            };
+            let text = utils::read_clipboard(selection).await;
+            app.handle_clipboard_paste(text);
+        }
+        KeyAction::PasteClipboard | KeyAction::PastePrimary => {}
+        KeyAction::React => app.open_reaction_picker_for_selection(),
+        KeyAction::Unsend => app.unsend_last(),
+        KeyAction::ToggleChatListFocus => app.toggle_chat_list_focus(),
+        KeyAction::NextUnread => app.open_next_unread().await,
+        KeyAction::RecentChats => app.cycle_recent_chats(),
+        KeyAction::Compose => app.request_external_editor(),
+        KeyAction::NewChat => app.open_new_chat_wizard(),
+        KeyAction::ShowActivity => app.show_activity(),
+        KeyAction::FocusPrevPane => app.focus_prev_pane(),
+        KeyAction::FocusNextPane => app.focus_next_pane(),
+        KeyAction::ScrollTop => app.handle_scroll_top(),
+        KeyAction::ScrollBottom => app.handle_scroll_bottom(),
+        KeyAction::PageUp => app.handle_page_up(),
+        KeyAction::PageDown => app.handle_page_down(),
+        KeyAction::ShowHelp => app.show_help(),
+        KeyAction::ReloadConfig => app.reload_config(),
+        KeyAction::Reconnect => app.reconnect().await,
+    }
+    Ok(false)
+}
+
 async fn run_app<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
     app: &mut App,
+    shutdown: &AtomicBool,
 ) -> Result<()> {
     let mut last_whatsapp_check = std::time::Instant::now();
     let mut last_chat_list_refresh = std::time::Instant::now();
+    // Keys typed so far of a multi-key binding such as "ctrl+s s"
+    let mut pending_keys = Vec::new();
+    let mut last_minute = chrono::Local::now().timestamp() / 60;
 
     loop {
+        if shutdown.load(Ordering::Relaxed) {
+            shut_down(app).await?;
+            break;
+        }
+
         // Only redraw when something changed
         if app.needs_redraw {
             terminal.draw(|f| app.draw(f))?;
             app.needs_redraw = false;
         }
+        if app.startup_pending && app.finish_startup().await {
+            app.needs_redraw = true;
+        }
+        // The switcher opens its chat once its key has been let go of for a moment
+        if app.chat_switch_due() {
+            app.finish_chat_switch().await;
+            app.needs_redraw = true;
+        }
+        if app.editor_requested {
+            app.open_external_editor().await;
+            // The editor drew over the screen ratatui thinks is there
+            terminal.clear()?;
+            continue;
+        }
+        // A pane scrolled to its first line gets the batch of messages before it
+        if let Some(pane_idx) = app.older_history_requested.take() {
+            if let Err(e) = app.load_older_messages(pane_idx).await {
+                app.notify(&format!("Couldn't load older messages: {}", e));
+            }
+            app.needs_redraw = true;
+        }
+
+        // Relative timestamps ("2m") age once a minute without anything else changing
+        let minute = chrono::Local::now().timestamp() / 60;
+        if minute != last_minute {
+            last_minute = minute;
+            if app.show_timestamps && app.timestamp_style == formatting::TimestampStyle::Relative {
+                app.needs_redraw = true;
+            }
+        }
+
+        // Intervals are read every iteration so /set takes effect immediately
+        let chat_refresh = std::time::Duration::from_secs(app.chat_refresh_secs);
+        let event_poll = std::time::Duration::from_millis(app.event_poll_ms);
 
-        // Refresh chat list every 5 seconds to get latest messages
-        if last_chat_list_refresh.elapsed() >= std::time::Duration::from_secs(5) {
+        // Refresh chat list periodically to get latest messages
+        if last_chat_list_refresh.elapsed() >= chat_refresh {
             let _ = app.refresh_chat_list().await;
             last_chat_list_refresh = std::time::Instant::now();
             app.needs_redraw = true;
         }
 
-        // Process WhatsApp events every 500ms
-        if last_whatsapp_check.elapsed() >= std::time::Duration::from_millis(500) {
+        // Process WhatsApp events
+        if last_whatsapp_check.elapsed() >= event_poll {
             let had_updates = app.process_whatsapp_events().await?;
+            let presence_changed = app.update_presence().await;
+            app.update_group_members().await;
+            let receipts_changed = app.update_receipts().await;
+            app.update_typing();
+            app.send_read_receipts().await;
+            let outbox_sent = app.flush_outbox();
+            let held_sent = app.finish_held_sends();
+            let typing_expired = app.expire_typing_indicators();
+            let auth_changed = app.check_auth().await;
+            let sync_changed = app.update_group_sync().await;
+            app.autosave();
             last_whatsapp_check = std::time::Instant::now();
-            if had_updates {
+            if had_updates || presence_changed || receipts_changed || outbox_sent || held_sent || typing_expired || auth_changed || sync_changed {
                 app.needs_redraw = true;
             }
         }
 
-        // Sleep until next check (or cap at 500ms)
-        let poll_timeout = std::time::Duration::from_millis(500)
+        // Sleep until next check (or cap at the poll interval), waking often enough to
+        // notice the switcher key being let go of
+        let mut poll_timeout = event_poll
             .saturating_sub(last_whatsapp_check.elapsed())
             .max(std::time::Duration::from_millis(16));
+        if app.chat_switcher.is_some() {
+            poll_timeout = poll_timeout.min(std::time::Duration::from_millis(50));
+        }
 
         if event::poll(poll_timeout)? {
             let event = event::read()?;
             match event {
                 Event::Key(key) => {
                     app.needs_redraw = true;
+                    // The login overlay takes every key until it closes
+                    if app.auth.is_some() {
+                        match key.code {
+                            KeyCode::Esc => app.close_auth(),
+                            KeyCode::Enter => app.retry_auth(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    // A destructive command waits for y/n: y runs it, any other key cancels it
+                    if app.pending_confirm.is_some() {
+                        let confirmed = matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y'));
+                        commands::CommandHandler::answer_confirm(app, confirmed).await;
+                        continue;
+                    }
+                    // The help overlay scrolls with the arrow and page keys; any other key closes it
+                    if app.help_scroll.is_some() {
+                        match key.code {
+                            KeyCode::Up => app.scroll_help(-1),
+                            KeyCode::Down => app.scroll_help(1),
+                            KeyCode::PageUp => app.scroll_help(-10),
+                            KeyCode::PageDown => app.scroll_help(10),
+                            KeyCode::Home => app.scroll_help(-i32::from(u16::MAX)),
+                            KeyCode::End => app.scroll_help(i32::from(u16::MAX)),
+                            _ => app.close_help(),
+                        }
+                        continue;
+                    }
+                    // The recent chats switcher: its own key moves down, arrows move freely,
+                    // Esc closes it and any other key opens the highlighted chat
+                    if app.chat_switcher.is_some() {
+                        match app.config.keymap.resolve(&mut pending_keys, key.into()) {
+                            KeyResolution::Action(KeyAction::RecentChats) => {
+                                app.cycle_recent_chats();
+                                continue;
+                            }
+                            KeyResolution::Pending => continue,
+                            _ => {}
+                        }
+                        match key.code {
+                            KeyCode::Esc => app.close_chat_switcher(),
+                            KeyCode::Up | KeyCode::BackTab => app.move_chat_switcher(-1),
+                            KeyCode::Down | KeyCode::Tab => app.move_chat_switcher(1),
+                            _ => app.finish_chat_switch().await,
+                        }
+                        continue;
+                    }
+                    // The new chat wizard takes every key until it closes
+                    if app.new_chat_wizard.is_some() {
+                        match key.code {
+                            KeyCode::Esc => app.new_chat_wizard_back(),
+                            KeyCode::Enter => app.new_chat_wizard_enter().await,
+                            KeyCode::Backspace => app.new_chat_wizard_backspace(),
+                            KeyCode::Char(c)
+                                if !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                            {
+                                app.new_chat_wizard_char(c);
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    // An open reaction picker takes every key until it closes
+                    if app.reaction_picker.is_some() {
+                        match key.code {
+                            KeyCode::Esc => app.close_reaction_picker(),
+                            KeyCode::Left | KeyCode::Up | KeyCode::BackTab => app.move_reaction_picker(-1),
+                            KeyCode::Right | KeyCode::Down | KeyCode::Tab => app.move_reaction_picker(1),
+                            KeyCode::Enter => app.pick_reaction(None).await,
+                            KeyCode::Char(c @ '1'..='9') => {
+                                app.pick_reaction(Some(c as usize - '1' as usize)).await;
+                            }
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    let typing = !app.focus_on_chat_list
+                        && app
+                            .panes
+                            .get(app.focused_pane_idx)
+                            .is_some_and(|p| !p.input_buffer.is_empty());
+                    // Ctrl+W in a pane deletes the previous word, whatever close_pane is bound to;
+                    // with nothing typed it does nothing rather than close the pane
+                    if !app.focus_on_chat_list && key.code == KeyCode::Char('w') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                        app.handle_delete_word_back();
+                        continue;
+                    }
+                    // Remappable bindings (whatsapp_keymap.json) come before the fixed editing keys
+                    match app.config.keymap.resolve(&mut pending_keys, key.into()) {
+                        KeyResolution::Action(action) => {
+                            if run_key_action(app, action).await? {
+                                break;
+                            }
+                            continue;
+                        }
+                        KeyResolution::Pending => continue,
+                        KeyResolution::Unbound => {}
+                    }
                     match key.code {
-                    // Ctrl+Q: Quit
-                    KeyCode::Char('q') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.save_state()?;
-                        break;
-                    }
-                    // Ctrl+R: Refresh chats
-                    KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.refresh_chats().await?;
-                    }
-                    // Ctrl+V: Split vertical
-                    KeyCode::Char('v') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.split_vertical();
-                    }
-                    // Ctrl+B: Split horizontal
-                    KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.split_horizontal();
-                    }
-                    // Ctrl+K: Toggle split direction
-                    KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_split_direction();
-                    }
-                    // Ctrl+W: Close pane
-                    KeyCode::Char('w') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.close_pane();
-                    }                    // Ctrl+S: Toggle chat list (Sidebar)
-                    KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_chat_list();
-                    }                    // Ctrl+L: Clear pane
-                    KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.clear_pane();
-                    }
-                    // Ctrl+E: Toggle reactions
-                    KeyCode::Char('e') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_reactions();
-                    }
-                    // Ctrl+N: Toggle notifications
-                    KeyCode::Char('n') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_notifications();
-                    }
-                    // Ctrl+D: Toggle compact mode
-                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_compact();
-                    }
-                    // Ctrl+O: Toggle emojis
-                    KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_emojis();
-                    }
-                    // Ctrl+G: Toggle line numbers
-                    KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_line_numbers();
-                    }
-                    // Ctrl+T: Toggle timestamps
-                    KeyCode::Char('t') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_timestamps();
-                    }
-                    // Ctrl+U: Toggle user colors
-                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_user_colors();
-                    }
-                    // Ctrl+Y: Toggle borders
-                    KeyCode::Char('y') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                        app.toggle_borders();
+                    // ?: Help, unless it is being typed
+                    KeyCode::Char('?') if !typing => {
+                        app.show_help();
                     }
                     // Esc: Cancel reply mode
+                    KeyCode::Esc if app.cancel_group_send() => {}
                     KeyCode::Esc => {
                         if let Some(pane) = app.panes.get_mut(app.focused_pane_idx) {
                             if pane.reply_to_message.is_some() {
@@ -183,7 +394,7 @@ async fn run_app<B: ratatui::backend::Backend>(
                         let input_empty = app
                             .panes
                             .get(app.focused_pane_idx)
-                            .map_or(true, |p| p.input_buffer.is_empty());
+                            .is_none_or(|p| p.input_buffer.is_empty());
                         if app.focus_on_chat_list || input_empty {
                             app.cycle_focus_reverse();
                         }
@@ -192,12 +403,16 @@ async fn run_app<B: ratatui::backend::Backend>(
                     KeyCode::Tab => {
                         app.handle_tab();
                     }
-                    // Alt+Left/Right: Focus previous/next pane
-                    KeyCode::Left if key.modifiers.contains(KeyModifiers::ALT) => {
-                        app.focus_prev_pane();
+                    // Ctrl+Left/Right: Move cursor by word
+                    KeyCode::Left
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.focus_on_chat_list =>
+                    {
+                        app.handle_word_left();
                     }
-                    KeyCode::Right if key.modifiers.contains(KeyModifiers::ALT) => {
-                        app.focus_next_pane();
+                    KeyCode::Right
+                        if key.modifiers.contains(KeyModifiers::CONTROL) && !app.focus_on_chat_list =>
+                    {
+                        app.handle_word_right();
                     }
                     // Arrow keys
                     KeyCode::Up => {
@@ -206,61 +421,83 @@ async fn run_app<B: ratatui::backend::Backend>(
                     KeyCode::Down => {
                         app.handle_down();
                     }
-                    KeyCode::Left => {
-                        if !app.focus_on_chat_list {
-                            app.handle_input_left();
-                        }
+                    KeyCode::Left if !app.focus_on_chat_list => {
+                        app.handle_input_left();
                     }
-                    KeyCode::Right => {
-                        if !app.focus_on_chat_list {
-                            app.handle_input_right();
-                        }
+                    KeyCode::Right if !app.focus_on_chat_list => {
+                        app.handle_input_right();
                     }
                     // Home/End: Move cursor to start/end
-                    KeyCode::Home => {
-                        if !app.focus_on_chat_list {
-                            app.handle_home();
-                        }
-                    }
-                    KeyCode::End => {
-                        if !app.focus_on_chat_list {
-                            app.handle_end();
-                        }
-                    }
-                    // PageUp/PageDown: Scroll messages
-                    KeyCode::PageUp => {
-                        app.handle_page_up();
+                    KeyCode::Home if !app.focus_on_chat_list => {
+                        app.handle_home();
                     }
-                    KeyCode::PageDown => {
-                        app.handle_page_down();
+                    KeyCode::End if !app.focus_on_chat_list => {
+                        app.handle_end();
                     }
                     // Enter: Submit
                     KeyCode::Enter => {
                         app.handle_enter().await?;
                     }
                     // Character input (only when not on chat list)
-                    KeyCode::Char(c) => {
-                        if !app.focus_on_chat_list {
-                            app.handle_char(c);
-                        }
+                    // 1-9 on the chat list: choose which pane receives the next opened chat
+                    KeyCode::Char(c @ '1'..='9') if app.focus_on_chat_list => {
+                        app.select_target_pane(c as usize - '0' as usize);
+                    }
+                    KeyCode::Char(c) if !app.focus_on_chat_list => {
+                        app.handle_char(c);
+                    }
+                    // Alt+Backspace: Delete previous word
+                    KeyCode::Backspace
+                        if key.modifiers.contains(KeyModifiers::ALT) && !app.focus_on_chat_list =>
+                    {
+                        app.handle_delete_word_back();
                     }
                     // Backspace
-                    KeyCode::Backspace => {
-                        if !app.focus_on_chat_list {
-                            app.handle_backspace();
-                        }
+                    KeyCode::Backspace if !app.focus_on_chat_list => {
+                        app.handle_backspace();
                     }
                     // Delete
-                    KeyCode::Delete => {
-                        if !app.focus_on_chat_list {
-                            app.handle_delete();
-                        }
+                    KeyCode::Delete if !app.focus_on_chat_list => {
+                        app.handle_delete();
                     }
                     _ => {}
                     }
                 }
                 Event::Mouse(mouse) => {
+                    // Movement only redraws when the hovered chat's tooltip changes
+                    if let event::MouseEventKind::Moved = mouse.kind {
+                        if app.handle_chat_list_hover(mouse.column, mouse.row) {
+                            app.needs_redraw = true;
+                        }
+                        continue;
+                    }
+                    // Releases and drags change nothing on screen
+                    if !matches!(
+                        mouse.kind,
+                        event::MouseEventKind::Down(_)
+                            | event::MouseEventKind::ScrollUp
+                            | event::MouseEventKind::ScrollDown
+                    ) {
+                        continue;
+                    }
+                    if app.auth.is_some() {
+                        continue;
+                    }
                     app.needs_redraw = true;
+                    if app.help_scroll.is_some() {
+                        match mouse.kind {
+                            event::MouseEventKind::ScrollUp => app.scroll_help(-3),
+                            event::MouseEventKind::ScrollDown => app.scroll_help(3),
+                            event::MouseEventKind::Down(_) => app.close_help(),
+                            _ => {}
+                        }
+                        continue;
+                    }
+                    match mouse.kind {
+                        event::MouseEventKind::ScrollUp => app.handle_mouse_scroll(mouse.column, mouse.row, true),
+                        event::MouseEventKind::ScrollDown => app.handle_mouse_scroll(mouse.column, mouse.row, false),
+                        _ => {}
+                    }
                     if let event::MouseEventKind::Down(event::MouseButton::Left) = mouse.kind {
                         // Check if clicking on chat list first
                         if let Some(area) = app.chat_list_area {
@@ -272,10 +509,20 @@ async fn run_app<B: ratatui::backend::Backend>(
                         }
                         // Check if clicking on a pane
                         app.handle_mouse_click(mouse.column, mouse.row);
+                        // Clicking a media message opens it
+                        app.handle_message_click(mouse.column, mouse.row).await?;
                         // Load messages for focused pane if needed
                         app.load_pane_messages_if_needed(app.focused_pane_idx).await;
                     }
                 }
+                // Bracketed paste: the whole clipboard arrives at once, so newlines
+                // land in the input buffer instead of submitting line by line
+                Event::Paste(text) => {
+                    app.needs_redraw = true;
+                    if !app.focus_on_chat_list {
+                        app.handle_paste(&text);
+                    }
+                }
                 Event::Resize(_, _) => {
                     app.needs_redraw = true;
                 }
@@ -286,3 +533,26 @@ async fn run_app<B: ratatui::backend::Backend>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_signal_saves_state_and_leaves_the_loop() {
+        let mut app = App::new_for_test();
+        let dir = crate::whatsapp::tests::TempDirGuard::new("shutdown");
+        app.config.config_dir = dir.path().to_path_buf();
+        app.aliases.insert("111@s.whatsapp.net".to_string(), "Al".to_string());
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(80, 24)).unwrap();
+
+        // As if the signal listener had fired
+        let shutdown = AtomicBool::new(true);
+        run_app(&mut terminal, &mut app, &shutdown).await.unwrap();
+
+        assert!(app.config.layout_path().exists());
+        assert!(dir.path().join("whatsapp_config.json").exists());
+        let aliases = std::fs::read_to_string(app.config.aliases_path()).unwrap();
+        assert!(aliases.contains("\"Al\""), "{}", aliases);
+    }
+}
@@ -2,16 +2,47 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::io::Write;
+use std::path::Path;
 
 use crate::config::Config;
 use crate::split_view::PaneNode;
+use crate::widgets::{Filter, FilterType};
+
+/// Replace `path` with `contents` without ever leaving it half-written: the data goes to a
+/// `.tmp` file next to it, which is flushed to disk and then renamed over `path`. A crash
+/// mid-save leaves the previous file in place.
+pub fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp_path, path)
+}
+
+/// Schema version written to `whatsapp_layout.json` and each named layout. Bump it
+/// together with a step in `migrate_layout` when the layout format changes.
+pub const LAYOUT_VERSION: u32 = 1;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LayoutData {
+    // Schema version; 0 for layouts saved before versions were recorded
+    #[serde(default)]
+    pub version: u32,
     pub panes: Vec<PaneState>,
     pub focused_pane: usize,
     #[serde(default)]
     pub pane_tree: Option<PaneNode>,
+    // Scroll offset of chats not open in a pane, restored when one is opened again
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub chat_scroll: HashMap<String, usize>,
+    // Chats most recently opened in a pane, newest first, for the recent chats switcher
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recent_chats: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,23 +51,67 @@ pub struct PaneState {
     pub chat_name: String,
     pub scroll_offset: usize,
     #[serde(default)]
-    pub filter_type: Option<String>,
+    pub filters: Vec<Filter>,
     #[serde(default)]
-    pub filter_value: Option<String>,
+    pub list_mode: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_as: Option<String>,
+}
+
+/// Upgrade a parsed layout to `LAYOUT_VERSION`. Returns whether anything changed.
+fn migrate_layout(value: &mut serde_json::Value) -> bool {
+    let Some(layout) = value.as_object_mut() else {
+        return false;
+    };
+    let from_version = layout.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if from_version >= LAYOUT_VERSION {
+        return false;
+    }
+
+    // Version 1: a pane's single `filter_type`/`filter_value` became the `filters` list
+    let panes = layout.get_mut("panes").and_then(|p| p.as_array_mut()).into_iter().flatten();
+    for pane in panes.filter_map(|p| p.as_object_mut()) {
+        let name = pane.remove("filter_type");
+        let value = pane.remove("filter_value");
+        let has_filters = pane.get("filters").and_then(|f| f.as_array()).is_some_and(|f| !f.is_empty());
+        if has_filters {
+            continue;
+        }
+        let value = value.as_ref().and_then(|v| v.as_str());
+        let filter = name
+            .as_ref()
+            .and_then(|n| n.as_str())
+            .and_then(|name| FilterType::from_saved(name, value))
+            .map(|kind| Filter::new(kind, value.unwrap_or_default()));
+        if let Some(filter) = filter.and_then(|f| serde_json::to_value(vec![f]).ok()) {
+            pane.insert("filters".to_string(), filter);
+        }
+    }
+
+    // A pane tree this version can't read falls back to side-by-side panes
+    if layout.get("pane_tree").is_some_and(|t| serde_json::from_value::<PaneNode>(t.clone()).is_err()) {
+        layout.remove("pane_tree");
+    }
+    layout.insert("version".to_string(), LAYOUT_VERSION.into());
+    true
 }
 
 impl LayoutData {
     pub fn new() -> Self {
         Self {
+            version: LAYOUT_VERSION,
             panes: vec![PaneState {
                 chat_id: None,
                 chat_name: "No chat selected".to_string(),
                 scroll_offset: 0,
-                filter_type: None,
-                filter_value: None,
+                filters: Vec::new(),
+                list_mode: false,
+                send_as: None,
             }],
             focused_pane: 0,
             pane_tree: None,
+            chat_scroll: HashMap::new(),
+            recent_chats: Vec::new(),
         }
     }
 
@@ -44,7 +119,12 @@ impl LayoutData {
         let path = config.layout_path();
         if path.exists() {
             let content = fs::read_to_string(path)?;
-            let layout: LayoutData = serde_json::from_str(&content)?;
+            let mut value: serde_json::Value = serde_json::from_str(&content)?;
+            let migrated = migrate_layout(&mut value);
+            let layout: LayoutData = serde_json::from_value(value)?;
+            if migrated {
+                layout.save(config)?;
+            }
             Ok(layout)
         } else {
             Ok(Self::new())
@@ -54,7 +134,7 @@ impl LayoutData {
     pub fn save(&self, config: &Config) -> Result<()> {
         let path = config.layout_path();
         let content = serde_json::to_string_pretty(self)?;
-        fs::write(path, content)?;
+        write_atomic(&path, &content)?;
         Ok(())
     }
 }
@@ -92,7 +172,7 @@ impl Aliases {
     pub fn save(&self, config: &Config) -> Result<()> {
         let path = config.aliases_path();
         let content = serde_json::to_string_pretty(self)?;
-        fs::write(path, content)?;
+        write_atomic(&path, &content)?;
         Ok(())
     }
 
@@ -115,6 +195,80 @@ impl Default for Aliases {
     }
 }
 
+/// Layouts saved by name with /layout save
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NamedLayouts {
+    #[serde(flatten)]
+    pub map: HashMap<String, LayoutData>, // name -> layout
+}
+
+impl NamedLayouts {
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = config.named_layouts_path();
+        if path.exists() {
+            let content = fs::read_to_string(path)?;
+            let mut value: serde_json::Value = serde_json::from_str(&content)?;
+            let mut migrated = false;
+            for layout in value.as_object_mut().into_iter().flat_map(|m| m.values_mut()) {
+                migrated |= migrate_layout(layout);
+            }
+            let layouts: NamedLayouts = serde_json::from_value(value)?;
+            if migrated {
+                layouts.save(config)?;
+            }
+            Ok(layouts)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let path = config.named_layouts_path();
+        let content = serde_json::to_string_pretty(self)?;
+        write_atomic(&path, &content)?;
+        Ok(())
+    }
+}
+
+/// A message written while offline, waiting in the outbox
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    pub chat_id: String,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_as: Option<String>,
+    pub queued_at: i64,
+}
+
+/// Messages queued while offline, oldest first. Saved on every change so a
+/// restart doesn't lose them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    pub messages: Vec<QueuedMessage>,
+}
+
+impl Outbox {
+    pub fn load(config: &Config) -> Result<Self> {
+        let path = config.outbox_path();
+        if path.exists() {
+            let content = fs::read_to_string(path)?;
+            let outbox: Outbox = serde_json::from_str(&content)?;
+            Ok(outbox)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let path = config.outbox_path();
+        let content = serde_json::to_string_pretty(self)?;
+        write_atomic(&path, &content)?;
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppState {
     pub settings: AppSettings,
@@ -198,3 +352,90 @@ impl AppState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config(name: &str) -> Config {
+        let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Config {
+            version: crate::config::CONFIG_VERSION,
+            whatsapp_cli_path: std::path::PathBuf::from("/nonexistent/whatsapp-cli"),
+            store_path: None,
+            settings: crate::config::Settings::default(),
+            config_dir: dir,
+            keymap: crate::keymap::Keymap::default(),
+            keymap_note: None,
+            theme: crate::theme::Theme::default(),
+            theme_note: None,
+            settings_note: None,
+        }
+    }
+
+    #[test]
+    fn test_legacy_layout_filters_are_migrated() {
+        let config = test_config("layout_v0");
+        let old = r#"{
+            "panes": [
+                {"chat_id": "team@g.us", "chat_name": "Team", "scroll_offset": 3,
+                 "filter_type": "time", "filter_value": "100..200"},
+                {"chat_id": null, "chat_name": "Empty", "scroll_offset": 0, "filter_type": "bogus"}
+            ],
+            "focused_pane": 1,
+            "pane_tree": {"NoLongerAShape": 7}
+        }"#;
+        fs::write(config.layout_path(), old).unwrap();
+
+        let layout = LayoutData::load(&config).unwrap();
+        assert_eq!(layout.version, LAYOUT_VERSION);
+        assert_eq!(
+            layout.panes[0].filters,
+            vec![Filter::new(FilterType::TimeRange { since: Some(100), before: Some(200) }, "100..200")]
+        );
+        assert!(layout.panes[1].filters.is_empty());
+        assert_eq!(layout.focused_pane, 1);
+        assert!(layout.pane_tree.is_none());
+
+        let rewritten = fs::read_to_string(config.layout_path()).unwrap();
+        assert!(!rewritten.contains("filter_type"), "{}", rewritten);
+        assert_eq!(LayoutData::load(&config).unwrap().panes[0].filters, layout.panes[0].filters);
+
+        let named = r#"{"work": {"panes": [{"chat_id": "a@g.us", "chat_name": "A", "scroll_offset": 0,
+            "filter_type": "sender", "filter_value": "bob"}], "focused_pane": 0}}"#;
+        fs::write(config.named_layouts_path(), named).unwrap();
+        let layouts = NamedLayouts::load(&config).unwrap();
+        assert_eq!(layouts.map["work"].panes[0].filters, vec![Filter::new(FilterType::Sender, "bob")]);
+        assert!(fs::read_to_string(config.named_layouts_path()).unwrap().contains(r#""version": 1"#));
+
+        let _ = fs::remove_dir_all(&config.config_dir);
+    }
+
+    #[test]
+    fn test_failed_save_keeps_previous_file() {
+        let config = test_config("atomic_save");
+        let mut aliases = Aliases::new();
+        aliases.insert("111@s.whatsapp.net".to_string(), "Al".to_string());
+        aliases.save(&config).unwrap();
+        let good = fs::read_to_string(config.aliases_path()).unwrap();
+
+        // A save that dies before the rename, here because its temp file can't be created
+        let tmp = config.config_dir.join("whatsapp_aliases.json.tmp");
+        fs::create_dir(&tmp).unwrap();
+        aliases.insert("222@s.whatsapp.net".to_string(), "Bo".to_string());
+        assert!(aliases.save(&config).is_err());
+        assert_eq!(fs::read_to_string(config.aliases_path()).unwrap(), good);
+
+        // A temp file left half-written by a crash doesn't matter to the next save
+        fs::remove_dir(&tmp).unwrap();
+        fs::write(&tmp, "{\"111@s.wha").unwrap();
+        assert_eq!(Aliases::load(&config).unwrap().map.len(), 1);
+        aliases.save(&config).unwrap();
+        assert_eq!(Aliases::load(&config).unwrap().map.len(), 2);
+        assert!(!tmp.exists());
+
+        let _ = fs::remove_dir_all(&config.config_dir);
+    }
+}
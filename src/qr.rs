@@ -0,0 +1,81 @@
+use qrcodegen::{QrCode, QrCodeEcc};
+use regex::Regex;
+
+/// Light modules around the code; scanners need some margin to find it
+const QUIET_ZONE: i32 = 2;
+
+/// The code to scan in a line of `whatsapp-cli auth` output: a JSON field such as
+/// `{"qr": "2@..."}`, or a bare `2@ref,key,key,secret` payload anywhere in the line
+pub fn extract_payload(line: &str) -> Option<String> {
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(line.trim()) {
+        let event = value.get("data").filter(|d| d.is_object()).unwrap_or(&value);
+        return ["qr", "qr_code", "code"]
+            .iter()
+            .find_map(|k| event.get(*k).and_then(|v| v.as_str()))
+            .filter(|code| !code.is_empty())
+            .map(str::to_string);
+    }
+    let payload = Regex::new(r"\b\d@[A-Za-z0-9+/=]+(?:,[A-Za-z0-9+/=]+){2,}").unwrap();
+    payload.find(line).map(|m| m.as_str().to_string())
+}
+
+/// Lines the CLI drew its own QR code with, left out when we draw the code ourselves
+pub fn is_qr_art(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && line.chars().all(|c| matches!(c, '█' | '▀' | '▄' | ' '))
+}
+
+/// `payload` as a QR code, two modules per character row with half blocks. Light modules
+/// are the drawn ones, so it must be shown light on dark (white on black).
+pub fn render(payload: &str) -> Option<Vec<String>> {
+    let code = QrCode::encode_text(payload, QrCodeEcc::Low).ok()?;
+    // Outside the code get_module is false (dark), so the quiet zone is flipped below
+    let light = |x: i32, y: i32| {
+        let inside = (0..code.size()).contains(&x) && (0..code.size()).contains(&y);
+        !inside || !code.get_module(x, y)
+    };
+    let range = -QUIET_ZONE..code.size() + QUIET_ZONE;
+    let lines = range
+        .clone()
+        .step_by(2)
+        .map(|y| {
+            range
+                .clone()
+                .map(|x| match (light(x, y), y + 1 < range.end && light(x, y + 1)) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' ',
+                })
+                .collect()
+        })
+        .collect();
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_extraction_and_rendering() {
+        let payload = "2@AbC+/=,KeY1=,KeY2=,SeCrEt=";
+        assert_eq!(extract_payload(&format!("QR code: {}", payload)).as_deref(), Some(payload));
+        assert_eq!(extract_payload(&format!(r#"{{"event":"qr","data":{{"code":"{}"}}}}"#, payload)).as_deref(), Some(payload));
+        assert_eq!(extract_payload("Waiting for scan, 2@ is not a code"), None);
+        assert_eq!(extract_payload(r#"{"event":"connected"}"#), None);
+
+        assert!(is_qr_art("█▀▀▀▀▀█ ▄▄ █▀▀▀▀▀█"));
+        assert!(!is_qr_art("Scan the code █"));
+        assert!(!is_qr_art("   "));
+
+        let lines = render(payload).unwrap();
+        // Version 2 at this length: 25 modules plus the quiet zone, two rows per line
+        let width = 25 + 2 * QUIET_ZONE as usize;
+        assert!(lines.iter().all(|l| l.chars().count() == width));
+        assert_eq!(lines.len(), width.div_ceil(2));
+        // The quiet zone is light all round, then the finder pattern's dark border starts
+        assert!(lines[0].chars().all(|c| c == '█'));
+        assert!(lines[1].starts_with("██ ▄▄▄▄▄ "));
+    }
+}
@@ -17,7 +17,7 @@ pub enum PaneNode {
     Single(usize),  // Index into App.panes
     Split {
         direction: SplitDirection,
-        children: Vec<Box<PaneNode>>,
+        children: Vec<PaneNode>,
     },
 }
 
@@ -30,7 +30,7 @@ impl PaneNode {
         let old_node = std::mem::replace(self, PaneNode::Single(0));
         *self = PaneNode::Split {
             direction,
-            children: vec![Box::new(old_node), Box::new(PaneNode::Single(new_pane_idx))],
+            children: vec![old_node, PaneNode::Single(new_pane_idx)],
         };
     }
 
@@ -58,7 +58,7 @@ impl PaneNode {
             PaneNode::Split { children, .. } => {
                 // Check if any child IS the pane we want to remove
                 if let Some(pos) = children.iter().position(|child| {
-                    matches!(**child, PaneNode::Single(idx) if idx == pane_idx)
+                    matches!(*child, PaneNode::Single(idx) if idx == pane_idx)
                 }) {
                     // Remove this direct child
                     children.remove(pos);
@@ -66,7 +66,7 @@ impl PaneNode {
                     // If only one child remains, collapse the split
                     if children.len() == 1 {
                         let child = children.remove(0);
-                        *self = *child;
+                        *self = child;
                     }
                     return true;
                 }
@@ -89,7 +89,7 @@ impl PaneNode {
         area: Rect,
         panes: &[ChatPane],
         focused_idx: usize,
-        render_fn: &impl Fn(&mut Frame, Rect, &ChatPane, bool),
+        render_fn: &impl Fn(&mut Frame, Rect, usize, &ChatPane, bool),
         pane_areas: &mut std::collections::HashMap<usize, Rect>,
     ) {
         match self {
@@ -97,7 +97,7 @@ impl PaneNode {
                 if let Some(pane) = panes.get(*pane_idx) {
                     let is_focused = *pane_idx == focused_idx;
                     pane_areas.insert(*pane_idx, area);
-                    render_fn(f, area, pane, is_focused);
+                    render_fn(f, area, *pane_idx, pane, is_focused);
                 }
             }
             PaneNode::Split { direction, children } => {
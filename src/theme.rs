@@ -0,0 +1,243 @@
+use anyhow::Result;
+use ratatui::style::Color;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Colors used to draw the UI. The defaults are the built-in look; the theme file can
+/// override any of them by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub outgoing: Color,       // Your name on your messages
+    pub incoming: Color,       // Other senders in one-to-one chats
+    pub reply_to_me: Color,    // Reply line above a reply to one of your messages
+    pub reply_context: Color,  // Reply line above other replies
+    pub mention: Color,        // @mentions of you in group messages
+    pub reply_preview: Color,  // "Replying to ..." bar above the input
+    pub status: Color,         // Status bar
+    pub border_focused: Color, // Focused chat list, help and reaction picker borders
+    pub header: Color,         // Pane header
+    pub header_focused: Color, // Header of the pane you are typing in
+    pub header_target: Color,  // Header of the pane the chat list opens chats in
+    pub dimmed: Color,         // Headers of other panes while picking from the chat list
+    pub chat_active: Color,    // Chat list entry open in the focused pane
+    pub chat_marker: Color,    // Chat list unread badge
+    pub section_header: Color, // Chat list section titles
+    pub help_heading: Color,   // Help overlay headings
+    pub receipt_read: Color,   // Read marks on your messages
+    pub connected: Color,      // Status line dot while synced with WhatsApp
+    pub disconnected: Color,   // Status line dot while offline or the sync process is down
+    pub user_palette: Vec<Color>, // Sender colors in group chats
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            outgoing: Color::Green,
+            incoming: Color::Cyan,
+            reply_to_me: Color::Red,
+            reply_context: Color::DarkGray,
+            mention: Color::LightRed,
+            reply_preview: Color::Magenta,
+            status: Color::Yellow,
+            border_focused: Color::Green,
+            header: Color::Cyan,
+            header_focused: Color::Green,
+            header_target: Color::Yellow,
+            dimmed: Color::DarkGray,
+            chat_active: Color::Yellow,
+            chat_marker: Color::Red,
+            section_header: Color::DarkGray,
+            help_heading: Color::Cyan,
+            receipt_read: Color::LightBlue,
+            connected: Color::Green,
+            disconnected: Color::Red,
+            user_palette: vec![
+                Color::Cyan, Color::Yellow, Color::Magenta, Color::Blue,
+                Color::Red, Color::Green, Color::White, Color::LightCyan,
+                Color::LightYellow, Color::LightMagenta, Color::LightBlue,
+                Color::LightRed, Color::LightGreen, Color::DarkGray,
+                Color::Rgb(192, 192, 192),
+                Color::Rgb(255, 165, 0),
+                Color::Rgb(255, 192, 203),
+                Color::Rgb(128, 0, 128),
+                Color::Rgb(0, 255, 255),
+                Color::Rgb(255, 20, 147),
+            ],
+        }
+    }
+}
+
+/// A color from the theme file: `#rrggbb`, or a name such as `green` or `lightcyan`
+pub fn parse_color(value: &str) -> Result<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("\"{}\" is not a #rrggbb color", value);
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+        return Ok(Color::Rgb(channel(0), channel(2), channel(4)));
+    }
+    value
+        .parse::<Color>()
+        .map_err(|_| anyhow::anyhow!("unknown color \"{}\"", value))
+}
+
+/// Palette slot for a sender. Depends only on the JID, so a sender keeps the same color
+/// across restarts and whichever messages happen to be loaded.
+pub fn palette_index(sender_id: &str, palette_len: usize) -> usize {
+    let mut hash: u64 = 0;
+    for byte in sender_id.bytes() {
+        hash = hash.wrapping_mul(31).wrapping_add(byte as u64);
+    }
+    hash = hash.wrapping_mul(2654435761);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85ebca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2ae35);
+    hash ^= hash >> 16;
+    (hash as usize) % palette_len.max(1)
+}
+
+impl Theme {
+    /// Palette color for a sender in group chats
+    pub fn user_color(&self, sender_id: &str) -> Color {
+        self.user_palette
+            .get(palette_index(sender_id, self.user_palette.len()))
+            .copied()
+            .unwrap_or(self.incoming)
+    }
+
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        Some(match name {
+            "outgoing" => &mut self.outgoing,
+            "incoming" => &mut self.incoming,
+            "reply_to_me" => &mut self.reply_to_me,
+            "reply_context" => &mut self.reply_context,
+            "mention" => &mut self.mention,
+            "reply_preview" => &mut self.reply_preview,
+            "status" => &mut self.status,
+            "border_focused" => &mut self.border_focused,
+            "header" => &mut self.header,
+            "header_focused" => &mut self.header_focused,
+            "header_target" => &mut self.header_target,
+            "dimmed" => &mut self.dimmed,
+            "chat_active" => &mut self.chat_active,
+            "chat_marker" => &mut self.chat_marker,
+            "section_header" => &mut self.section_header,
+            "help_heading" => &mut self.help_heading,
+            "receipt_read" => &mut self.receipt_read,
+            "connected" => &mut self.connected,
+            "disconnected" => &mut self.disconnected,
+            _ => return None,
+        })
+    }
+
+    /// Load the theme file. A missing file means the default theme. Fields that can't be
+    /// used keep their default, and the problems are returned for the user to see.
+    pub fn load(path: &Path) -> (Self, Option<String>) {
+        if !path.exists() {
+            return (Self::default(), None);
+        }
+        let parsed = fs::read_to_string(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Self::from_json(&content));
+        match parsed {
+            Ok((theme, problems)) => {
+                let note = (!problems.is_empty()).then(|| format!("Theme: {}", problems.join("; ")));
+                (theme, note)
+            }
+            Err(e) => {
+                crate::warn_log!("Theme {}: {}", path.display(), e);
+                (Self::default(), Some(format!("Theme error, using default colors: {}", e)))
+            }
+        }
+    }
+
+    /// The default theme with the colors in `content` (field name -> color) applied, field
+    /// by field. Returns the fields that were skipped and why.
+    pub fn from_json(content: &str) -> Result<(Self, Vec<String>)> {
+        let fields: BTreeMap<String, serde_json::Value> = serde_json::from_str(content)?;
+        let mut theme = Self::default();
+        let mut problems = Vec::new();
+        for (name, value) in &fields {
+            if name == "user_palette" {
+                let entries = value.as_array().cloned().unwrap_or_default();
+                let palette: Vec<Color> = entries
+                    .iter()
+                    .filter_map(|entry| match entry.as_str().map(parse_color) {
+                        Some(Ok(color)) => Some(color),
+                        Some(Err(e)) => {
+                            problems.push(format!("user_palette: {}", e));
+                            None
+                        }
+                        None => {
+                            problems.push(format!("user_palette: {} is not a string", entry));
+                            None
+                        }
+                    })
+                    .collect();
+                if palette.is_empty() {
+                    problems.push("user_palette has no usable colors".to_string());
+                } else {
+                    theme.user_palette = palette;
+                }
+                continue;
+            }
+
+            let Some(slot) = theme.color_mut(name) else {
+                problems.push(format!("unknown color \"{}\"", name));
+                continue;
+            };
+            match value.as_str().map(parse_color) {
+                Some(Ok(color)) => *slot = color,
+                Some(Err(e)) => problems.push(format!("{}: {}", name, e)),
+                None => problems.push(format!("{}: {} is not a string", name, value)),
+            }
+        }
+        Ok((theme, problems))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_fields_fall_back_one_by_one() {
+        let (theme, problems) = Theme::from_json(
+            r##"{
+                "outgoing": "#ff8800",
+                "incoming": "lightblue",
+                "status": "#12345",
+                "reply_preview": 7,
+                "bogus": "red",
+                "user_palette": ["#000000", "nope"]
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(theme.outgoing, Color::Rgb(0xff, 0x88, 0x00));
+        assert_eq!(theme.incoming, Color::LightBlue);
+        assert_eq!(theme.status, Theme::default().status);
+        assert_eq!(theme.reply_preview, Theme::default().reply_preview);
+        assert_eq!(theme.user_palette, vec![Color::Rgb(0, 0, 0)]);
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems.iter().any(|p| p.starts_with("status: ")));
+
+        assert!(Theme::from_json("[1, 2]").is_err());
+        assert_eq!(Theme::default().user_palette.len(), 20);
+    }
+
+    #[test]
+    fn test_sender_palette_index_is_stable() {
+        let alice = "4915112345678@s.whatsapp.net";
+        // A fixed value: changing the hash would give every sender a new color
+        assert_eq!(palette_index(alice, 20), 11);
+        assert_ne!(palette_index("bob@s.whatsapp.net", 20), 11);
+        assert_eq!(palette_index(alice, 20), 11);
+        assert_eq!(palette_index(alice, 0), 0);
+
+        let theme = Theme::default();
+        assert_eq!(theme.user_color(alice), theme.user_palette[11]);
+    }
+}
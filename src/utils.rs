@@ -36,25 +36,318 @@ pub fn init_logging(log_file_path: &str) -> Result<(), Box<dyn std::error::Error
     Ok(())
 }
 
+/// The last `max_lines` lines of the log file, oldest first
+pub fn log_tail(max_lines: usize) -> Option<String> {
+    let path = LOG_FILE.lock().ok()?.clone()?;
+    let content = std::fs::read_to_string(path).ok()?;
+    let lines: Vec<&str> = content.lines().collect();
+    Some(lines[lines.len().saturating_sub(max_lines)..].join("\n"))
+}
+
 pub fn send_desktop_notification(_title: &str, _message: &str) {
     // TODO: Implement desktop notifications
     // For now, just log it
     crate::debug_log!("Notification: {} - {}", _title, _message);
 }
 
-pub fn try_autocomplete(text: &str) -> (Option<String>, Option<String>) {
-    // Simple autocomplete for commands
-    let commands = vec!["/reply", "/media", "/edit", "/delete", "/alias", "/search", "/forward"];
-    
-    if text.starts_with('/') {
-        for cmd in commands {
-            if cmd.starts_with(text) {
-                return (Some(cmd.to_string()), None);
+/// Which system selection `read_clipboard` reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// The regular copy/paste clipboard
+    Clipboard,
+    /// The X11/Wayland primary selection (last highlighted text); other platforms use the clipboard
+    Primary,
+}
+
+/// Command-line tools that print the selection, in the order they are tried
+fn clipboard_commands(selection: Selection) -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbpaste", vec![])]
+    } else if cfg!(windows) {
+        vec![("powershell", vec!["-NoProfile", "-Command", "Get-Clipboard -Raw"])]
+    } else {
+        match selection {
+            Selection::Clipboard => vec![
+                ("wl-paste", vec!["--no-newline"]),
+                ("xclip", vec!["-o", "-selection", "clipboard"]),
+                ("xsel", vec!["--output", "--clipboard"]),
+            ],
+            Selection::Primary => vec![
+                ("wl-paste", vec!["--no-newline", "--primary"]),
+                ("xclip", vec!["-o", "-selection", "primary"]),
+                ("xsel", vec!["--output", "--primary"]),
+            ],
+        }
+    }
+}
+
+/// Read text from the system clipboard with the platform's clipboard tools. Errors when no
+/// tool is installed or none of them can reach a clipboard (e.g. no display).
+pub async fn read_clipboard(selection: Selection) -> anyhow::Result<String> {
+    use tokio::process::Command;
+
+    let mut last_error = None;
+    for (program, args) in clipboard_commands(selection) {
+        // A selection owner that never answers would otherwise hang the UI
+        let output = Command::new(program).args(&args).kill_on_drop(true).output();
+        match tokio::time::timeout(std::time::Duration::from_secs(2), output).await {
+            Ok(Ok(output)) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+            }
+            Ok(Ok(output)) => {
+                crate::debug_log!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim());
+                last_error = Some(format!("{} failed", program));
+            }
+            Ok(Err(e)) => {
+                crate::debug_log!("{} unavailable: {}", program, e);
+            }
+            Err(_) => {
+                last_error = Some(format!("{} timed out", program));
+            }
+        }
+    }
+    Err(anyhow::anyhow!(last_error.unwrap_or_else(|| "no clipboard tool found".to_string())))
+}
+
+/// Command-line tools that take new clipboard contents on stdin, in the order they are tried
+fn clipboard_write_commands() -> Vec<(&'static str, Vec<&'static str>)> {
+    if cfg!(target_os = "macos") {
+        vec![("pbcopy", vec![])]
+    } else if cfg!(windows) {
+        vec![("clip", vec![])]
+    } else {
+        vec![
+            ("wl-copy", vec![]),
+            ("xclip", vec!["-i", "-selection", "clipboard"]),
+            ("xsel", vec!["--input", "--clipboard"]),
+        ]
+    }
+}
+
+/// Put `text` on the system clipboard with the platform's clipboard tools. Errors like
+/// `read_clipboard` when no tool is installed or none of them reaches a clipboard.
+pub async fn write_clipboard(text: &str) -> anyhow::Result<()> {
+    use std::process::Stdio;
+    use tokio::io::AsyncWriteExt;
+    use tokio::process::Command;
+
+    let mut last_error = None;
+    for (program, args) in clipboard_write_commands() {
+        let child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::piped())
+            // xclip and xsel fork a process that serves the selection; piped output would
+            // stay open with it and never reach EOF
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                crate::debug_log!("{} unavailable: {}", program, e);
+                continue;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            // Closing stdin tells the tool the text is complete
+            if let Err(e) = stdin.write_all(text.as_bytes()).await {
+                crate::debug_log!("{} didn't take the text: {}", program, e);
+            }
+        }
+        // A tool that never exits must not hang the UI
+        match tokio::time::timeout(std::time::Duration::from_secs(2), child.wait()).await {
+            Ok(Ok(status)) if status.success() => return Ok(()),
+            Ok(Ok(status)) => {
+                crate::debug_log!("{} failed: {}", program, status);
+                last_error = Some(format!("{} failed", program));
             }
+            Ok(Err(e)) => {
+                crate::debug_log!("{} failed: {}", program, e);
+                last_error = Some(format!("{} failed", program));
+            }
+            Err(_) => {
+                last_error = Some(format!("{} timed out", program));
+            }
+        }
+    }
+    Err(anyhow::anyhow!(last_error.unwrap_or_else(|| "no clipboard tool found".to_string())))
+}
+
+/// Open a file or URL with the desktop's default application (`open` on macOS, `start` on
+/// Windows, `xdg-open` elsewhere) without waiting for it
+pub fn open_external(target: &str) -> anyhow::Result<()> {
+    use std::process::{Command, Stdio};
+
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    // Browsers print to the terminal otherwise, over the UI
+    command.arg(target).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+    command.spawn().map(|_| ()).map_err(|e| anyhow::anyhow!("couldn't start {:?}: {}", command.get_program(), e))
+}
+
+/// The user's editor, split into program and arguments: `$VISUAL`, then `$EDITOR`, then vi
+pub fn editor_command() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .map(|editor| split_shell_words(&editor))
+        .find(|words| !words.is_empty())
+        .unwrap_or_else(|| vec!["vi".to_string()])
+}
+
+/// Split a command line the way a POSIX shell splits words: 'single' and "double" quotes
+/// group words, and a backslash escapes the next character (inside double quotes only
+/// before `"`, `\`, `$` and `` ` ``). An unclosed quote runs to the end.
+pub fn split_shell_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next());
+            }
+            '\'' => {
+                in_word = true;
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(next @ ('"' | '\\' | '$' | '`')) => word.push(next),
+                            Some(next) => {
+                                word.push('\\');
+                                word.push(next);
+                            }
+                            None => word.push('\\'),
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// What came back from `run_editor`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditorOutcome {
+    Saved(String),
+    Unchanged,
+    /// The file was gone when the editor exited
+    Deleted,
+}
+
+/// A new file in the temp directory holding `seed`, readable only by us. The name is
+/// never reused, so another user can't have put a file or link there first.
+fn create_compose_file(seed: &str) -> std::io::Result<std::path::PathBuf> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut attempt = 0;
+    loop {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let path = std::env::temp_dir()
+            .join(format!("whatsapp_compose_{}_{}_{}.txt", std::process::id(), nanos, attempt));
+        match options.open(&path) {
+            Ok(mut file) => {
+                file.write_all(seed.as_bytes())?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Write `seed` to a temp file, open it in `editor` (program and arguments; the file is
+/// appended) and wait for it to exit. The trailing newline editors add is dropped.
+pub async fn run_editor(editor: &[String], seed: &str) -> anyhow::Result<EditorOutcome> {
+    let Some((program, args)) = editor.split_first() else {
+        anyhow::bail!("no editor set");
+    };
+    let path = create_compose_file(seed)?;
+
+    let status = tokio::process::Command::new(program).args(args).arg(&path).status().await;
+    let content = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => anyhow::bail!("{} exited with {}", program, status),
+        Err(e) => anyhow::bail!("couldn't start {}: {}", program, e),
+    }
+
+    let Ok(content) = content else {
+        return Ok(EditorOutcome::Deleted);
+    };
+    let content = content.replace("\r\n", "\n");
+    let content = content.trim_end_matches('\n');
+    if content == seed {
+        Ok(EditorOutcome::Unchanged)
+    } else {
+        Ok(EditorOutcome::Saved(content.to_string()))
+    }
+}
+
+/// Tab completion for the input: a command name being typed becomes the first matching
+/// command, with the others as a hint; past the name, the hint is the command's usage.
+/// Otherwise an emoji shortcode being typed is completed.
+pub fn try_autocomplete(text: &str) -> (Option<String>, Option<String>) {
+    use crate::commands::{command_candidates, command_cycle_hint, command_hint};
+
+    if let Some(rest) = text.strip_prefix('/') {
+        let Some((name, _)) = rest.split_once(char::is_whitespace) else {
+            let candidates = command_candidates(rest);
+            return match candidates.first() {
+                Some(first) => (Some(format!("/{}", first)), Some(command_cycle_hint(&candidates, 0))),
+                None => (None, None),
+            };
+        };
+        let emoji = crate::emoji::complete_shortcode(text);
+        if emoji != (None, None) {
+            return emoji;
         }
+        return (None, command_hint(name));
     }
     
-    (None, None)
+    // Emoji shortcode being typed, e.g. "thanks :pr" -> "thanks 🙏"
+    crate::emoji::complete_shortcode(text)
 }
 
 pub fn log_debug(message: &str) {
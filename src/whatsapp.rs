@@ -1,29 +1,276 @@
 use anyhow::Result;
 use serde::Deserialize;
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex, Semaphore, SemaphorePermit};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command as TokioCommand;
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OptionalExtension, params};
 
-fn format_phone_number(jid: &str) -> String {
+/// Country calling codes with how their national numbers are grouped. Digits left over
+/// after the last group become one more group.
+const PHONE_GROUPS: &[(&str, &[usize])] = &[
+    ("1", &[3, 3, 4]),        // US, Canada: +1 415 555 0132
+    ("7", &[3, 3, 2, 2]),     // Russia, Kazakhstan: +7 912 345 67 89
+    ("31", &[1, 4, 4]),       // Netherlands: +31 6 1234 5678
+    ("33", &[1, 2, 2, 2, 2]), // France: +33 6 12 34 56 78
+    ("34", &[3, 3, 3]),       // Spain: +34 612 345 678
+    ("39", &[3, 3, 4]),       // Italy: +39 312 345 6789
+    ("44", &[4, 6]),          // UK: +44 7911 123456
+    ("45", &[2, 2, 2, 2]),    // Denmark: +45 20 12 34 56
+    ("46", &[2, 3, 2, 2]),    // Sweden: +46 76 078 98 06
+    ("47", &[3, 2, 3]),       // Norway: +47 412 34 567
+    ("49", &[3]),             // Germany: +49 151 23456789
+    ("55", &[2, 5, 4]),       // Brazil: +55 11 91234 5678
+    ("61", &[3, 3, 3]),       // Australia: +61 412 345 678
+    ("91", &[5, 5]),          // India: +91 98765 43210
+    ("358", &[2, 3, 4]),      // Finland: +358 40 123 4567
+];
+
+/// International number in groups for reading, e.g. "46760789806" -> "+46 76 078 98 06".
+/// Numbers from a country not in `PHONE_GROUPS` come back as "+" and the digits.
+pub fn group_phone_number(digits: &str) -> String {
+    let digits = digits.trim_start_matches('+');
+    let grouping = PHONE_GROUPS
+        .iter()
+        .filter(|(code, _)| digits.starts_with(code))
+        .max_by_key(|(code, _)| code.len());
+    let Some((code, sizes)) = grouping.filter(|_| {
+        (8..=15).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit())
+    }) else {
+        return format!("+{}", digits);
+    };
+
+    let mut rest = &digits[code.len()..];
+    let mut groups = vec![format!("+{}", code)];
+    for &size in sizes.iter() {
+        if rest.is_empty() {
+            break;
+        }
+        let (group, tail) = rest.split_at(size.min(rest.len()));
+        groups.push(group.to_string());
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        groups.push(rest.to_string());
+    }
+    groups.join(" ")
+}
+
+/// Display name for a JID without a contact name: the phone number, grouped for reading
+/// (`group_phone_number`). Linked-device IDs (`@lid`) aren't phone numbers and stay as they are.
+pub fn format_phone_number(jid: &str) -> String {
     // Extract phone number from JID (e.g., "46760789806@s.whatsapp.net" -> "46760789806")
-    if let Some(at_pos) = jid.find('@') {
-        let phone = &jid[..at_pos];
+    match jid.split_once('@') {
+        Some((phone, "s.whatsapp.net" | "c.us")) => {
+            group_phone_number(phone.split(':').next().unwrap_or(phone))
+        }
         // Format with + prefix if it doesn't have one
-        if phone.starts_with('+') {
-            phone.to_string()
-        } else {
-            format!("+{}", phone)
+        Some((id, _)) if id.starts_with('+') => id.to_string(),
+        Some((id, _)) => format!("+{}", id),
+        None => jid.to_string(),
+    }
+}
+
+/// Stand-in for our own JID until the real one is known
+pub const UNKNOWN_JID: &str = "unknown@s.whatsapp.net";
+
+/// Strip the device and agent parts from a JID: "4670:12@s.whatsapp.net" -> "4670@s.whatsapp.net"
+pub fn user_jid(jid: &str) -> String {
+    match jid.split_once('@') {
+        Some((user, server)) => {
+            let user = user.split([':', '.']).next().unwrap_or(user);
+            format!("{}@{}", user, server)
+        }
+        None => jid.to_string(),
+    }
+}
+
+/// Parse a CLI timestamp (unix seconds, RFC 3339 or "YYYY-MM-DD HH:MM:SS"), falling back to now
+fn parse_cli_timestamp(timestamp: &str) -> i64 {
+    try_parse_cli_timestamp(timestamp).unwrap_or_else(|| chrono::Utc::now().timestamp())
+}
+
+/// Parse a CLI timestamp without a fallback
+fn try_parse_cli_timestamp(timestamp: &str) -> Option<i64> {
+    timestamp
+        .parse::<i64>()
+        .or_else(|_| {
+            chrono::DateTime::parse_from_rfc3339(timestamp)
+                .map(|dt| dt.timestamp())
+                .or_else(|_| {
+                    chrono::NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S")
+                        .map(|dt| dt.and_utc().timestamp())
+                })
+        })
+        .ok()
+}
+
+/// A chat's last-message time from the `chats list` payload: unix seconds (or
+/// milliseconds) as a number, or any format `parse_cli_timestamp` reads
+fn chat_timestamp(value: &serde_json::Value) -> Option<i64> {
+    let ts = match value {
+        serde_json::Value::Number(n) => n.as_i64()?,
+        serde_json::Value::String(s) => try_parse_cli_timestamp(s)?,
+        _ => return None,
+    };
+    Some(if ts > 100_000_000_000 { ts / 1000 } else { ts })
+}
+
+/// Pick a chat-state event out of one line of `whatsapp-cli sync` output, returning
+/// `(chat_jid, sender_jid)` when someone started composing. Log lines and other events
+/// yield `None`; "paused" is left to the indicator's own expiry
+fn parse_typing_event(line: &str) -> Option<(String, String)> {
+    let value: serde_json::Value = serde_json::from_str(line.trim()).ok()?;
+    let event = value.get("data").filter(|d| d.is_object()).unwrap_or(&value);
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| event.get(*k).and_then(|v| v.as_str()))
+            .map(str::to_string)
+    };
+
+    let state = field(&["state", "chat_state", "presence"])?;
+    if !matches!(state.to_lowercase().as_str(), "composing" | "typing") {
+        return None;
+    }
+    let chat_jid = field(&["chat", "chat_jid", "jid"])?;
+    let sender_jid = field(&["sender", "sender_jid", "from"]).unwrap_or_else(|| chat_jid.clone());
+    Some((chat_jid, sender_jid))
+}
+
+/// Pick a connection change out of one line of `whatsapp-cli sync` output: a JSON
+/// event such as `{"event":"disconnected"}`, or a log line like `[Client INFO] Connected`.
+/// Message and chat-state events are ignored even when their fields look similar
+fn parse_connection_event(line: &str) -> Option<ConnectionState> {
+    let line = line.trim();
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(line) {
+        let event = value.get("data").filter(|d| d.is_object()).unwrap_or(&value);
+        if ["jid", "chat", "chat_jid", "from", "sender"].iter().any(|k| event.get(*k).is_some()) {
+            return None;
         }
+        let kind = ["event", "type", "status", "state"]
+            .iter()
+            .find_map(|k| event.get(*k).and_then(|v| v.as_str()))?
+            .to_lowercase()
+            .replace(['_', '-', ' '], "");
+        return match kind.as_str() {
+            "connected" | "keepaliverestored" => Some(ConnectionState::Connected),
+            "disconnected" | "keepalivetimeout" | "loggedout" | "streamreplaced" => {
+                Some(ConnectionState::Disconnected)
+            }
+            _ => None,
+        };
+    }
+
+    // Plain log lines, with any "[Module LEVEL]" prefix dropped
+    let text = match line.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        Some((_, rest)) => rest.trim_start(),
+        None => line,
+    }
+    .to_lowercase();
+    if text.starts_with("disconnected") || text.starts_with("connection lost") {
+        Some(ConnectionState::Disconnected)
+    } else if text.starts_with("connected") {
+        Some(ConnectionState::Connected)
     } else {
-        jid.to_string()
+        None
     }
 }
 
 use crate::app::ChatInfo;
 use crate::config::Config;
+use crate::persistence::QueuedMessage;
+use crate::widgets::Receipts;
+
+/// Whether the sync process is connected to WhatsApp, as far as its output tells us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting, // Started, nothing reported yet
+    Connected,
+    Disconnected,
+}
+
+impl ConnectionState {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Disconnected => "offline",
+        }
+    }
+}
+
+/// Whether the store is logged in, and what the sync process last reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionStatus {
+    pub authenticated: bool,
+    pub state: ConnectionState,
+}
+
+/// How far a group's forced sync has got: messages of the group found in the store so far,
+/// and whether it stopped (no more coming in, timeout, or sync failed to start)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupSyncProgress {
+    pub started: std::time::Instant,
+    pub found: usize,
+    pub done: bool,
+}
+
+impl GroupSyncProgress {
+    /// Pane header text while the sync runs
+    pub fn label(&self) -> String {
+        match self.found {
+            0 => format!("Syncing group… {}s", self.started.elapsed().as_secs()),
+            1 => "Syncing group: found 1 message…".to_string(),
+            n => format!("Syncing group: found {} messages…", n),
+        }
+    }
+}
+
+/// What a running `whatsapp-cli auth` has printed so far, the newest code to scan, and how it ended
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AuthProgress {
+    pub lines: Vec<String>,
+    pub qr: Option<String>, // Replaced each time the CLI prints a fresh code
+    pub finished: Option<Result<(), String>>,
+}
+
+/// A `whatsapp-cli auth` started by `reauthenticate`. Dropping it stops the process.
+pub struct AuthSession {
+    progress: Arc<std::sync::Mutex<AuthProgress>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl AuthSession {
+    pub fn progress(&self) -> AuthProgress {
+        self.progress.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// A session that already got as far as `progress`
+    #[cfg(test)]
+    pub fn for_test(progress: AuthProgress) -> Self {
+        Self {
+            progress: Arc::new(std::sync::Mutex::new(progress)),
+            task: tokio::spawn(async {}),
+        }
+    }
+}
+
+impl Drop for AuthSession {
+    fn drop(&mut self) {
+        // The task owns the child, which is killed on drop
+        self.task.abort();
+    }
+}
+
+/// A line of `whatsapp-cli auth` output without color codes, so a QR code the CLI draws
+/// with block characters shows up the same inside the TUI
+fn strip_ansi(line: &str) -> String {
+    let ansi = regex::Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap();
+    ansi.replace_all(line, "").trim_end().to_string()
+}
 
 /// Updates received from WhatsApp
 #[derive(Debug, Clone)]
@@ -34,21 +281,791 @@ pub enum WhatsAppUpdate {
         text: String,
         is_outgoing: bool,
     },
-    #[allow(dead_code)]
     UserTyping {
         chat_jid: String,
         user_name: String,
     },
+    ConnectionChanged(ConnectionState),
+    /// The send queue is done with a message: sent, or given up on with the last error
+    SendFinished {
+        message: QueuedMessage,
+        error: Option<String>,
+    },
+    /// Something the user should know that came up in the background, shown in the status bar
+    Notice(String),
+}
+
+/// A message row as returned by `get_messages`:
+/// (msg_id, sender_jid, sender_name, text, reply_to_id, media_type, reactions, timestamp)
+pub type RawMessage = (String, String, String, String, Option<String>, Option<String>, std::collections::HashMap<String, u32>, i64);
+
+/// A message found by /searchall, with the chat it belongs to
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub chat_jid: String,
+    pub chat_name: Option<String>,
+    pub msg_id: String,
+    pub sender_id: String,
+    pub sender_name: String,
+    pub text: String,
+    pub timestamp: i64,
+}
+
+/// WhatsApp media type ("photo", "video", ...) for a file, from its extension. Unknown
+/// extensions are sent as documents.
+pub fn media_type_for_path(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "jpg" | "jpeg" | "png" | "heic" | "heif" | "bmp" => "photo",
+        "gif" => "gif",
+        "webp" => "sticker",
+        "mp4" | "mov" | "m4v" | "mkv" | "webm" | "3gp" | "avi" => "video",
+        "mp3" | "m4a" | "aac" | "wav" | "flac" => "audio",
+        "ogg" | "opus" => "voice",
+        _ => "document",
+    }
+}
+
+/// Latitude and longitude in a location message's content: whatsapp-cli's JSON
+/// (`latitude`/`longitude`, `lat` with `lng` or `lon`, or whatsmeow's `degreesLatitude`),
+/// a `geo:` URI, a map link or a plain "59.3293, 18.0686" pair
+pub fn parse_location(content: &str) -> Option<(f64, f64)> {
+    let valid = |lat: f64, lon: f64| {
+        ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)).then_some((lat, lon))
+    };
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(content.trim()) {
+        let fields = json.get("location").unwrap_or(&json);
+        let number = |keys: &[&str]| {
+            keys.iter().find_map(|key| {
+                let value = fields.get(*key)?;
+                value.as_f64().or_else(|| value.as_str()?.trim().parse().ok())
+            })
+        };
+        let lat = number(&["latitude", "lat", "degreesLatitude"]);
+        let lon = number(&["longitude", "lng", "lon", "degreesLongitude"]);
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+            return valid(lat, lon);
+        }
+    }
+    // OpenStreetMap links name both; everything else writes "lat,lon" (geo:, ?q=, @)
+    let osm = regex::Regex::new(r"mlat=(-?\d+(?:\.\d+)?)&mlon=(-?\d+(?:\.\d+)?)").unwrap();
+    let pair = regex::Regex::new(r"(?:^|[^\d.])(-?\d{1,2}\.\d+)\s*,\s*(-?\d{1,3}\.\d+)").unwrap();
+    let caps = osm.captures(content).or_else(|| pair.captures(content))?;
+    valid(caps[1].parse().ok()?, caps[2].parse().ok()?)
+}
+
+/// Coordinates of a message when it is a location, for `MessageData::location`
+pub fn message_location(media_type: Option<&str>, content: &str) -> Option<(f64, f64)> {
+    (media_type == Some("location")).then(|| parse_location(content)).flatten()
+}
+
+/// A contact shared in a message: its vCard, with the name and first phone number in it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedContact {
+    pub name: String,
+    pub phone: Option<String>,
+    pub vcard: String,
+}
+
+impl SharedContact {
+    /// "Name (phone)", or the name alone when the card has no number
+    pub fn summary(&self) -> String {
+        match &self.phone {
+            Some(phone) if *phone != self.name => format!("{} ({})", self.name, phone),
+            _ => self.name.clone(),
+        }
+    }
+}
+
+/// Undo vCard escaping in a property value
+fn vcard_unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push(' '),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// The contact in one vCard: `FN` (or `N`) for the name, the first `TEL` for the number.
+/// `display_name` wins over the card's own name when given.
+fn parse_vcard(vcard: &str, display_name: Option<&str>) -> Option<SharedContact> {
+    // Folded lines continue on the next line after a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in vcard.lines() {
+        match lines.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => last.push_str(&line[1..]),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let (mut full_name, mut structured_name, mut phone) = (None, None, None);
+    for line in &lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // "item1.TEL;type=CELL;waid=4670..." is a TEL
+        let property = key.split(';').next().unwrap_or_default();
+        let property = property.rsplit('.').next().unwrap_or_default().to_ascii_uppercase();
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        match property.as_str() {
+            "FN" if full_name.is_none() => full_name = Some(vcard_unescape(value)),
+            "N" if structured_name.is_none() => {
+                // Family;Given;Middle;Prefix;Suffix
+                let parts: Vec<String> = value.split(';').map(vcard_unescape).collect();
+                let order = [1, 2, 0];
+                let name: Vec<&str> = order.iter().filter_map(|&i| parts.get(i)).map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+                if !name.is_empty() {
+                    structured_name = Some(name.join(" "));
+                }
+            }
+            "TEL" if phone.is_none() => phone = Some(value.trim_start_matches("tel:").to_string()),
+            _ => {}
+        }
+    }
+
+    let display_name = display_name.map(str::trim).filter(|n| !n.is_empty()).map(str::to_string);
+    let name = display_name.or(full_name).or(structured_name).or_else(|| phone.clone())?;
+    Some(SharedContact { name, phone, vcard: vcard.trim().to_string() })
+}
+
+/// The contacts shared in a contact message's content: whatsapp-cli's JSON (an object
+/// with `vcard` and `displayName`, a `contacts` list of them, or a plain list), or the
+/// vCards themselves, one or several
+pub fn parse_contacts(content: &str) -> Vec<SharedContact> {
+    if let Ok(json) = serde_json::from_str::<serde_json::Value>(content.trim()) {
+        let items = match json.get("contacts").unwrap_or(&json) {
+            serde_json::Value::Array(items) => items.clone(),
+            item => vec![item.clone()],
+        };
+        let text = |item: &serde_json::Value, keys: &[&str]| {
+            keys.iter().find_map(|key| item.get(*key)?.as_str().map(str::to_string))
+        };
+        return items
+            .iter()
+            .filter_map(|item| {
+                let vcard = text(item, &["vcard", "vCard"])?;
+                let display_name = text(item, &["displayName", "display_name", "name"]);
+                parse_vcard(&vcard, display_name.as_deref())
+            })
+            .collect();
+    }
+    let card = regex::Regex::new(r"(?is)BEGIN:VCARD.*?END:VCARD").unwrap();
+    card.find_iter(content).filter_map(|m| parse_vcard(m.as_str(), None)).collect()
+}
+
+/// Contacts of a message when it is a contact share, for `MessageData::contacts`
+pub fn message_contacts(media_type: Option<&str>, content: &str) -> Vec<SharedContact> {
+    match media_type {
+        Some("contact" | "contacts" | "contact_array") => parse_contacts(content),
+        _ => Vec::new(),
+    }
+}
+
+/// Turn whatsapp-cli's complaint about an upload into something readable
+fn describe_send_media_error(error: &str) -> String {
+    let lower = error.to_lowercase();
+    let detail = error.split_whitespace().collect::<Vec<_>>().join(" ");
+    if lower.contains("too large") || lower.contains("too big") || lower.contains("size") {
+        format!("File is too large for WhatsApp: {}", detail)
+    } else if lower.contains("unsupported") || lower.contains("mime") || lower.contains("file type") {
+        format!("File type not supported: {}", detail)
+    } else if is_unknown_flag_error(error) {
+        "This whatsapp-cli can't send files (no --file flag)".to_string()
+    } else if detail.is_empty() {
+        "Failed to send file".to_string()
+    } else {
+        format!("Failed to send file: {}", detail)
+    }
+}
+
+/// Arguments for a native reply through whatsapp-cli
+fn reply_args<'a>(chat_jid: &'a str, message_id: &'a str, text: &'a str, from: Option<&'a str>) -> Vec<&'a str> {
+    let mut args = send_args(chat_jid, text, from);
+    args.extend(["--reply-to", message_id]);
+    args
+}
+
+/// `send` arguments, sending from the linked identity `from` when given
+fn send_args<'a>(chat_jid: &'a str, text: &'a str, from: Option<&'a str>) -> Vec<&'a str> {
+    let mut args = vec!["send", "--to", chat_jid, "--message", text];
+    if let Some(from) = from {
+        args.extend(["--from", from]);
+    }
+    args
+}
+
+/// `revoke` arguments, deleting our message `message_id` for everyone in the chat
+fn revoke_args<'a>(chat_jid: &'a str, message_id: &'a str) -> Vec<&'a str> {
+    vec!["revoke", "--to", chat_jid, "--message-id", message_id]
+}
+
+/// Error for a revoke WhatsApp refused because the message is past the deletion window
+pub const DELETE_TOO_OLD: &str = "WhatsApp no longer lets this message be deleted for everyone";
+
+/// Whether a revoke failed because the message is too old to delete for everyone
+fn is_too_old_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    ["too old", "time limit", "expired", "window"].iter().any(|hint| error.contains(hint))
+}
+
+/// Error for group commands when this whatsapp-cli has no `group` command
+const GROUPS_UNSUPPORTED: &str = "This whatsapp-cli can't manage groups";
+const RECEIPTS_UNSUPPORTED: &str = "This whatsapp-cli doesn't report receipts";
+
+/// The JID for a member given as a phone number (`+46 70-123`, `@4670123`) or a JID
+fn participant_jid(phone: &str) -> Option<String> {
+    let phone = phone.trim().trim_start_matches('@');
+    if phone.contains('@') {
+        return Some(phone.to_string());
+    }
+    let digits = phone.trim_start_matches('+').replace(['-', ' ', '(', ')'], "");
+    (digits.len() >= 5 && digits.chars().all(|c| c.is_ascii_digit())).then(|| format!("{}@s.whatsapp.net", digits))
+}
+
+/// Whether `jid` is a chat nobody can send to from here: a WhatsApp channel (`@newsletter`)
+/// or the status broadcast. Broadcast lists of your own (`@broadcast`) do take messages.
+pub fn is_channel_jid(jid: &str) -> bool {
+    jid.ends_with("@newsletter") || jid == "status@broadcast"
+}
+
+/// JIDs mentioned in `text`: `@` and a phone number (7 to 15 digits) starting a word, the
+/// way WhatsApp writes mentions into the message text. Each JID once, in order.
+pub fn mention_jids(text: &str) -> Vec<String> {
+    let mut jids = Vec::new();
+    for word in text.split_whitespace() {
+        let Some(rest) = word.strip_prefix('@') else {
+            continue;
+        };
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        // "@4670123456," still counts; "@4670123456abc" is something else
+        let ends_word = rest[digits..].chars().next().is_none_or(|c| !c.is_alphanumeric());
+        let jid = format!("{}@s.whatsapp.net", &rest[..digits]);
+        if (7..=15).contains(&digits) && ends_word && !jids.contains(&jid) {
+            jids.push(jid);
+        }
+    }
+    jids
+}
+
+/// The `@partial` mention being typed at the end of `input`: the byte offset of its `@`
+/// and what follows it. None once a space ends the word, or for an `@` inside a word such
+/// as an email address.
+pub fn mention_query(input: &str) -> Option<(usize, &str)> {
+    let start = input
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let partial = input[start..].strip_prefix('@')?;
+    (!partial.contains('@')).then_some((start, partial))
+}
+
+/// `args` with a `--mention` flag for each of `mentions`
+fn with_mentions<'a>(mut args: Vec<&'a str>, mentions: &'a [String]) -> Vec<&'a str> {
+    for jid in mentions {
+        args.extend(["--mention", jid.as_str()]);
+    }
+    args
+}
+
+/// The JID for a phone number typed into the new chat wizard. Numbers starting with `+` or
+/// `00` are international; others get `country_code` in front, minus their leading zeros.
+pub fn phone_number_jid(input: &str, country_code: &str) -> Option<String> {
+    let digits = input.trim().replace(['-', ' ', '(', ')', '.'], "");
+    let number = match digits.strip_prefix('+').or_else(|| digits.strip_prefix("00")) {
+        Some(international) => international.to_string(),
+        None => format!("{}{}", country_code.trim().trim_start_matches('+'), digits.trim_start_matches('0')),
+    };
+    ((7..=15).contains(&number.len()) && number.chars().all(|c| c.is_ascii_digit()))
+        .then(|| format!("{}@s.whatsapp.net", number))
+}
+
+/// First string among `keys` in a JSON object; whatsapp-cli versions differ in casing
+fn json_str<'a>(item: &'a serde_json::Value, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|k| item.get(*k)?.as_str()).filter(|s| !s.is_empty())
+}
+
+/// Members from a `group info` response, as (jid, name, role) with role "superadmin",
+/// "admin" or "member", sorted by role and then name
+fn parse_group_members(
+    data: &serde_json::Value,
+    names: &std::collections::HashMap<String, String>,
+) -> Vec<(String, String, String)> {
+    let participants = ["participants", "Participants", "members"]
+        .iter()
+        .find_map(|k| data.get(*k))
+        .unwrap_or(data)
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let flag = |item: &serde_json::Value, keys: &[&str]| keys.iter().any(|k| item.get(*k).and_then(|v| v.as_bool()) == Some(true));
+
+    let mut members: Vec<(String, String, String)> = participants
+        .iter()
+        .filter_map(|item| {
+            let jid = json_str(item, &["jid", "JID", "id"])?.to_string();
+            let role = match json_str(item, &["role", "rank"]).map(str::to_lowercase).as_deref() {
+                Some("superadmin" | "owner" | "creator") => "superadmin",
+                Some("admin") => "admin",
+                Some(_) => "member",
+                None if flag(item, &["is_super_admin", "IsSuperAdmin"]) => "superadmin",
+                None if flag(item, &["is_admin", "IsAdmin"]) => "admin",
+                None => "member",
+            };
+            let name = names
+                .get(&jid)
+                .cloned()
+                .or_else(|| json_str(item, &["name", "display_name", "DisplayName", "push_name"]).map(str::to_string))
+                .unwrap_or_else(|| format_phone_number(&jid));
+            Some((jid, name, role.to_string()))
+        })
+        .collect();
+    let rank = |role: &str| match role {
+        "superadmin" => 0,
+        "admin" => 1,
+        _ => 2,
+    };
+    members.sort_by(|a, b| rank(&a.2).cmp(&rank(&b.2)).then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase())));
+    members
+}
+
+/// The new group's JID from a `group create` response
+fn parse_created_group(data: &serde_json::Value) -> Option<String> {
+    json_str(data, &["jid", "JID", "group_jid", "id"])
+        .filter(|jid| jid.ends_with("@g.us"))
+        .map(str::to_string)
+}
+
+/// The error code WhatsApp gave a participant change, e.g. 403 when the person's privacy
+/// settings only allow invites. `None` when every participant went through.
+fn participant_change_error(data: &serde_json::Value) -> Option<u64> {
+    let participants = ["participants", "Participants"].iter().find_map(|k| data.get(*k)).unwrap_or(data);
+    participants
+        .as_array()?
+        .iter()
+        .find_map(|p| ["error", "Error"].iter().find_map(|k| p.get(*k)?.as_u64()).filter(|&code| code != 0))
+}
+
+/// Text sent in place of a forward when whatsapp-cli can't forward natively
+fn forwarded_copy(text: &str) -> String {
+    format!("↪ Forwarded:\n{}", text)
+}
+
+/// Find the chat a user typed as a target: a phone number (`+46 70-123`, `@4670123`), a
+/// JID, or a chat name (case-insensitive, must match one chat). Numbers and JIDs that
+/// aren't in `chats` still resolve, as a chat that hasn't started yet.
+pub fn resolve_chat_target(chats: &[ChatInfo], target: &str) -> Option<(String, String, bool)> {
+    let target = target.trim().trim_start_matches('@');
+    let known = |jid: &str| chats.iter().find(|c| c.id == jid).map(|c| (c.id.clone(), c.name.clone(), c.is_group));
+
+    if let Some(jid) = participant_jid(target) {
+        let is_group = jid.ends_with("@g.us");
+        return known(&jid).or_else(|| Some((jid, target.to_string(), is_group)));
+    }
+
+    let mut by_name = chats.iter().filter(|c| !target.is_empty() && c.name.eq_ignore_ascii_case(target));
+    match (by_name.next(), by_name.next()) {
+        (Some(chat), None) => Some((chat.id.clone(), chat.name.clone(), chat.is_group)),
+        _ => None,
+    }
+}
+
+/// Collapse the entries of `chats list` that are one chat, keeping the first one's place:
+/// - the same JID listed twice is one chat
+/// - a linked-device chat (`@lid`) is the phone number chat (`@s.whatsapp.net`) with the same
+///   name, or else the only one whose name starts with its name or the other way around
+///   ("P" and "Patrik W"); failing that, an earlier `@lid` chat with the same name
+/// - an individual chat named like a group gives way to the group
+///
+/// Merged entries keep the highest unread count and the newest message time.
+pub fn dedupe_and_normalize_chats(chats: Vec<ChatInfo>) -> Vec<ChatInfo> {
+    fn merge(into: &mut ChatInfo, from: &ChatInfo) {
+        crate::debug_log!(
+            "dedupe_and_normalize_chats: {} '{}' is {} '{}'",
+            from.id,
+            from.name,
+            into.id,
+            into.name
+        );
+        into.unread = into.unread.max(from.unread);
+        into.last_message_ts = into.last_message_ts.max(from.last_message_ts);
+    }
+
+    let (lids, others): (Vec<ChatInfo>, Vec<ChatInfo>) =
+        chats.into_iter().partition(|c| c.id.ends_with("@lid"));
+    let mut deduped: Vec<ChatInfo> = Vec::new();
+    for chat in others {
+        match deduped.iter_mut().find(|c| c.id == chat.id) {
+            Some(existing) => merge(existing, &chat),
+            None => deduped.push(chat),
+        }
+    }
+
+    for chat in lids {
+        let is_phone = |c: &ChatInfo| c.id.ends_with("@s.whatsapp.net");
+        let similar = |c: &ChatInfo| {
+            !chat.name.is_empty()
+                && !c.name.is_empty()
+                && (c.name.starts_with(&chat.name) || chat.name.starts_with(&c.name))
+        };
+        let mut similar_phones = deduped
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| is_phone(c) && similar(c))
+            .map(|(idx, _)| idx);
+        let only_similar = match (similar_phones.next(), similar_phones.next()) {
+            (Some(idx), None) => Some(idx),
+            _ => None,
+        };
+        let target = deduped
+            .iter()
+            .position(|c| c.id == chat.id || (is_phone(c) && c.name == chat.name))
+            .or(only_similar)
+            .or_else(|| {
+                deduped
+                    .iter()
+                    .position(|c| c.id.ends_with("@lid") && c.name == chat.name)
+            });
+        match target {
+            Some(idx) => merge(&mut deduped[idx], &chat),
+            None => deduped.push(chat),
+        }
+    }
+
+    let group_names: std::collections::HashSet<String> = deduped
+        .iter()
+        .filter(|c| c.is_group)
+        .map(|c| c.name.clone())
+        .collect();
+    let (shadowed, mut kept): (Vec<ChatInfo>, Vec<ChatInfo>) = deduped
+        .into_iter()
+        .partition(|c| !c.is_group && group_names.contains(&c.name));
+    for chat in shadowed {
+        if let Some(group) = kept.iter_mut().find(|c| c.is_group && c.name == chat.name) {
+            merge(group, &chat);
+        }
+    }
+    kept
+}
+
+/// Whether whatsapp-cli rejected the flag `--name` as unknown
+fn is_unknown_flag(output: &str, name: &str) -> bool {
+    is_unknown_flag_error(output) && output.contains(&format!("-{}", name))
+}
+
+/// Whether whatsapp-cli rejected a flag it doesn't know (cobra and Go's flag package wording)
+fn is_unknown_flag_error(output: &str) -> bool {
+    output.contains("unknown flag") || output.contains("flag provided but not defined")
+}
+
+/// Whether whatsapp-cli rejected the command itself, or one of its flags
+fn is_unsupported_error(stderr: &str) -> bool {
+    stderr.contains("unknown command") || is_unknown_flag_error(stderr)
+}
+
+/// Outcome of a whatsapp-cli action that only reports success or an error. `unsupported`
+/// is the error when this whatsapp-cli doesn't know the command.
+fn action_result(output: &std::process::Output, unsupported: &str) -> Result<()> {
+    let response: Option<WhatsAppResponse> = serde_json::from_slice(&output.stdout).ok();
+    let error = match response {
+        Some(response) if output.status.success() && response.success => return Ok(()),
+        Some(response) => response.error.unwrap_or_default(),
+        None if output.status.success() => return Ok(()),
+        None => String::new(),
+    };
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if is_unsupported_error(&stderr) {
+        anyhow::bail!("{}", unsupported);
+    }
+    let detail = format!("{} {}", error, stderr).split_whitespace().collect::<Vec<_>>().join(" ");
+    anyhow::bail!("{}", if detail.is_empty() { "unknown error".to_string() } else { detail })
+}
+
+/// Whether the send response echoes the quoted message id back
+fn reply_quote_confirmed(data: Option<&serde_json::Value>, message_id: &str) -> bool {
+    data.is_some_and(|data| {
+        ["quoted_id", "reply_to", "quoted_message_id"]
+            .iter()
+            .any(|key| data.get(key).and_then(|v| v.as_str()) == Some(message_id))
+    })
+}
+
+/// Reply text prefixed with a "> Sender: first line" quote of the original message
+fn quote_inline(quoted: &QuotedMessage, text: &str) -> String {
+    let first_line = quoted.text.lines().next().unwrap_or("");
+    let excerpt = crate::formatting::truncate_chars(first_line, 80);
+    format!("> {}: {}\n{}", quoted.sender_name, excerpt, text)
 }
 
 #[derive(Clone)]
 pub struct WhatsAppClient {
-    cli_path: PathBuf,
+    cli_path: Arc<std::sync::RwLock<PathBuf>>, // Replaced in place when /reload changes whatsapp_cli_path
     store_path: PathBuf,
     pending_updates: Arc<Mutex<Vec<WhatsAppUpdate>>>,
     my_jid: Arc<Mutex<Option<String>>>,
     last_synced_message_id: Arc<Mutex<Option<String>>>,
     contact_cache: Arc<Mutex<std::collections::HashMap<String, String>>>, // JID -> name
+    cli_semaphore: Arc<Semaphore>, // Caps concurrent short-lived whatsapp-cli processes
+    reply_flag_supported: Arc<AtomicBool>, // Cleared once `send --reply-to` is rejected
+    presence_supported: Arc<AtomicBool>, // Cleared once `presence` is rejected as an unknown command
+    typing_supported: Arc<AtomicBool>, // Cleared once `typing` is rejected as an unknown command
+    read_receipts_supported: Arc<AtomicBool>, // Cleared once `mark-read` is rejected as an unknown command
+    edit_supported: Arc<AtomicBool>, // Cleared once `edit` is rejected as an unknown command
+    forward_supported: Arc<AtomicBool>, // Cleared once `forward` is rejected as an unknown command
+    delete_supported: Arc<AtomicBool>, // Cleared once `revoke` is rejected as an unknown command
+    reactions_supported: Arc<AtomicBool>, // Cleared once `react` is rejected as an unknown command
+    from_flag_supported: Arc<AtomicBool>, // Cleared once `send --from` is rejected
+    mention_flag_supported: Arc<AtomicBool>, // Cleared once `send --mention` is rejected
+    presence_cache: Arc<Mutex<std::collections::HashMap<String, (std::time::Instant, Presence)>>>, // JID -> last answer
+    receipts_supported: Arc<AtomicBool>, // Cleared once `receipts` is rejected as an unknown command
+    receipts_cache: Arc<Mutex<std::collections::HashMap<String, (std::time::Instant, ReceiptMap)>>>, // Chat JID -> last answer
+    dialogs_cache: Arc<Mutex<Option<CachedDialogs>>>, // Last `chats list` answer
+    cli_spawns: Arc<AtomicU64>, // Short-lived whatsapp-cli processes started, for /diag
+    connection: Arc<Mutex<ConnectionState>>,
+    sync_alive: Arc<AtomicBool>, // Set while the background `sync` process is running
+    sync_started: Arc<AtomicBool>, // Set once the background sync loop is running
+    sync_restart: Arc<AtomicBool>, // Asks the sync loop to restart its process, e.g. after logging in again
+    group_syncs: Arc<Mutex<std::collections::HashMap<String, GroupSyncProgress>>>, // Group JID -> forced sync progress
+    force_synced_groups: Arc<Mutex<std::collections::HashSet<String>>>, // Groups already synced for missing history this session
+    cli_version: Arc<std::sync::OnceLock<String>>, // First line of `whatsapp-cli --version`, read in the background at startup
+    send_queue: Arc<std::sync::OnceLock<mpsc::UnboundedSender<OutgoingSend>>>, // Started by the first `queue_send`
+}
+
+/// A message for the send queue, with the quote used when the CLI can't reply natively
+type OutgoingSend = (QueuedMessage, Option<QuotedMessage>);
+
+/// How many times the send queue tries a message before giving up on it
+const SEND_ATTEMPTS: u32 = 3;
+
+/// Wait before the send queue's first retry, doubled for each one after it
+const SEND_RETRY_BASE_MS: u64 = 500;
+
+/// How long a chat's presence is reused before whatsapp-cli is asked again
+const PRESENCE_CACHE_SECS: u64 = 10;
+
+/// How long a chat's receipts are reused before whatsapp-cli is asked again
+const RECEIPTS_CACHE_SECS: u64 = 10;
+
+/// How long the chat list is reused before whatsapp-cli is asked again
+const DIALOGS_CACHE_SECS: u64 = 2;
+
+/// A chat list and when whatsapp-cli returned it
+type CachedDialogs = (std::time::Instant, Vec<ChatInfo>);
+
+/// Most messages `get_messages_before` asks whatsapp-cli for while reaching back
+const MAX_CLI_HISTORY_FETCH: usize = 2000;
+
+/// Newest messages of a one-to-one chat fetched first when looking for what arrived since
+/// the last one shown
+const INCREMENTAL_CLI_FETCH: usize = 10;
+
+/// Receipts of our messages in one chat, by message ID
+pub type ReceiptMap = std::collections::HashMap<String, Receipts>;
+
+/// Unix seconds of a `timestamp` stored in messages.db, which holds RFC 3339 times as
+/// well as `2024-05-01 10:00:00.123+00:00`-style ones
+fn parse_db_timestamp(text: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(text)
+        .map(|dt| dt.timestamp())
+        .or_else(|_| chrono::DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%z").map(|dt| dt.timestamp()))
+        .or_else(|_| chrono::DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%z").map(|dt| dt.timestamp()))
+        .ok()
+}
+
+/// Parse the `data` of a `receipts` response: a list of our messages, each with an `id`
+/// (or `message_id`) and `delivered` / `read` as a count, a list of JIDs or a flag, or a
+/// single `status`. Groups may give the number of `recipients`; a one-to-one chat has one.
+fn parse_receipts(data: &serde_json::Value, is_group: bool) -> ReceiptMap {
+    let count = |item: &serde_json::Value, key: &str| -> u32 {
+        match item.get(key) {
+            Some(serde_json::Value::Number(n)) => n.as_u64().unwrap_or(0) as u32,
+            Some(serde_json::Value::Array(jids)) => jids.len() as u32,
+            Some(serde_json::Value::Bool(flag)) => *flag as u32,
+            _ => 0,
+        }
+    };
+    let items = data.as_array().map(Vec::as_slice).unwrap_or_default();
+    items
+        .iter()
+        .filter_map(|item| {
+            let id = ["id", "message_id"].iter().find_map(|k| item.get(*k)?.as_str())?;
+            let mut read = count(item, "read");
+            let mut delivered = count(item, "delivered").max(read);
+            match item.get("status").and_then(|v| v.as_str()) {
+                Some("read" | "played") => {
+                    read = read.max(1);
+                    delivered = delivered.max(1);
+                }
+                Some("delivered") => delivered = delivered.max(1),
+                _ => {}
+            }
+            let recipients = if is_group {
+                item.get("recipients").and_then(|v| v.as_u64()).map(|n| n as u32)
+            } else {
+                Some(1)
+            };
+            Some((id.to_string(), Receipts { delivered, read, recipients }))
+        })
+        .collect()
+}
+
+/// How far one of our messages got with one recipient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReceiptStatus {
+    Read,
+    Delivered,
+    Sent,
+}
+
+/// Who got and read one of our messages, from `get_message_info`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageInfo {
+    pub totals: Receipts,
+    /// (JID, name, status, Unix seconds when known), read first. A group only lists
+    /// members whatsapp-cli names; the rest are counted in `totals`.
+    pub recipients: Vec<(String, String, ReceiptStatus, Option<i64>)>,
+}
+
+/// Per-recipient receipts of message `message_id` in a `receipts` response. Members come
+/// from a `receipts` list of `{jid, status, timestamp}` or from `read` / `delivered` lists of
+/// JIDs (or `{jid, timestamp}`). A one-to-one chat without them has the chat itself as its
+/// recipient, with the message's overall status.
+fn parse_message_info(
+    data: &serde_json::Value,
+    chat_jid: &str,
+    message_id: &str,
+    names: &std::collections::HashMap<String, String>,
+) -> MessageInfo {
+    let is_group = chat_jid.ends_with("@g.us");
+    let Some(mut totals) = parse_receipts(data, is_group).remove(message_id) else {
+        return MessageInfo::default();
+    };
+    let item = data
+        .as_array()
+        .and_then(|items| items.iter().find(|item| json_str(item, &["id", "message_id"]) == Some(message_id)));
+
+    let mut found: Vec<(String, ReceiptStatus, Option<i64>)> = Vec::new();
+    let mut add = |jid: &str, status: ReceiptStatus, at: Option<i64>| {
+        match found.iter_mut().find(|(j, ..)| j == jid) {
+            // Keep the furthest status a member reached
+            Some(entry) if status < entry.1 => *entry = (jid.to_string(), status, at),
+            Some(_) => {}
+            None => found.push((jid.to_string(), status, at)),
+        }
+    };
+    let entry = |value: &serde_json::Value| -> Option<(String, Option<i64>)> {
+        match value {
+            serde_json::Value::String(jid) => Some((jid.clone(), None)),
+            _ => Some((
+                json_str(value, &["jid", "user", "participant"])?.to_string(),
+                value.get("timestamp").and_then(|t| t.as_i64()),
+            )),
+        }
+    };
+    if let Some(item) = item {
+        for receipt in item.get("receipts").and_then(|v| v.as_array()).into_iter().flatten() {
+            let status = match json_str(receipt, &["status", "type"]) {
+                Some("read" | "played") => ReceiptStatus::Read,
+                Some("delivered") => ReceiptStatus::Delivered,
+                _ => continue,
+            };
+            if let Some((jid, at)) = entry(receipt) {
+                add(&jid, status, at);
+            }
+        }
+        for (key, status) in [("read", ReceiptStatus::Read), ("delivered", ReceiptStatus::Delivered)] {
+            for value in item.get(key).and_then(|v| v.as_array()).into_iter().flatten() {
+                if let Some((jid, at)) = entry(value) {
+                    add(&jid, status, at);
+                }
+            }
+        }
+    }
+    // The counts whatsapp-cli sent may leave out members only named in `receipts`
+    let listed_read = found.iter().filter(|(_, status, _)| *status == ReceiptStatus::Read).count() as u32;
+    totals.read = totals.read.max(listed_read);
+    totals.delivered = totals.delivered.max(found.len() as u32).max(totals.read);
+    if found.is_empty() && !is_group {
+        let status = match totals {
+            Receipts { read: 1.., .. } => ReceiptStatus::Read,
+            Receipts { delivered: 1.., .. } => ReceiptStatus::Delivered,
+            _ => ReceiptStatus::Sent,
+        };
+        found.push((chat_jid.to_string(), status, None));
+    }
+
+    let mut recipients: Vec<(String, String, ReceiptStatus, Option<i64>)> = found
+        .into_iter()
+        .map(|(jid, status, at)| {
+            let name = names.get(&jid).cloned().unwrap_or_else(|| format_phone_number(&jid));
+            (jid, name, status, at)
+        })
+        .collect();
+    recipients.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.1.to_lowercase().cmp(&b.1.to_lowercase())));
+    MessageInfo { totals, recipients }
+}
+
+/// Whether a contact is online, as reported by whatsapp-cli
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Online,
+    LastSeen(i64), // Unix seconds
+    Unknown,
+}
+
+impl Presence {
+    /// Parse the `data` of a `presence` response: `available`/`online` and an optional
+    /// `last_seen` as Unix seconds or an RFC 3339 string
+    fn from_response(data: &serde_json::Value) -> Self {
+        let online = ["available", "online"]
+            .iter()
+            .find_map(|key| data.get(key).and_then(serde_json::Value::as_bool));
+        if online == Some(true) {
+            return Presence::Online;
+        }
+        let last_seen = data.get("last_seen").and_then(|v| {
+            v.as_i64().or_else(|| {
+                chrono::DateTime::parse_from_rfc3339(v.as_str()?)
+                    .ok()
+                    .map(|dt| dt.timestamp())
+            })
+        });
+        match last_seen {
+            Some(ts) if ts > 0 => Presence::LastSeen(ts),
+            _ => Presence::Unknown,
+        }
+    }
+
+    /// Short text for the pane header: "online", "last seen 14:32", or empty when unknown
+    pub fn label(&self) -> String {
+        match self {
+            Presence::Online => "online".to_string(),
+            Presence::LastSeen(ts) => format!("last seen {}", crate::formatting::format_timestamp(*ts)),
+            Presence::Unknown => String::new(),
+        }
+    }
+}
+
+/// The message a reply quotes, used to build an inline quote when the CLI can't reply natively
+#[derive(Debug, Clone)]
+pub struct QuotedMessage {
+    pub sender_name: String,
+    pub text: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -64,47 +1081,125 @@ struct ChatListItem {
     name: String,
     #[serde(default)]
     unread: u32,
+    #[serde(default, alias = "last_message_timestamp")]
+    last_message_time: Option<serde_json::Value>,
+    // Set by whatsapp-cli builds that know which chats take no messages
+    #[serde(default, alias = "readOnly")]
+    read_only: Option<bool>,
 }
 
+// The aliases are the camelCase names some whatsapp-cli builds use
 #[derive(Debug, Deserialize)]
 struct MessageItem {
+    #[serde(alias = "message_id")]
     id: String,
-    #[serde(rename = "chat_jid")]
+    #[serde(rename = "chat_jid", alias = "chatJid")]
     chat_jid: String,
-    #[serde(rename = "chat_name")]
+    #[serde(rename = "chat_name", alias = "chatName")]
     chat_name: Option<String>,
     sender: String,
-    #[serde(rename = "sender_name")]
+    #[serde(rename = "sender_name", alias = "senderName")]
     sender_name: Option<String>,
     content: String,
     timestamp: String,
-    #[serde(rename = "is_from_me")]
+    #[serde(rename = "is_from_me", alias = "isFromMe", alias = "from_me")]
     from_me: bool,
-    #[serde(rename = "media_type")]
+    #[serde(rename = "media_type", alias = "mediaType")]
     media_type: Option<String>,
+    // The vCard of a shared contact, when whatsapp-cli reports it apart from the content
+    #[serde(default, alias = "vCard")]
+    vcard: Option<String>,
+}
+
+/// Why the whatsapp-cli binary at `path` can't be run, and how to fix it
+pub fn check_cli(path: &Path) -> Result<()> {
+    let fix = "install it there, or set \"whatsapp_cli_path\" in whatsapp_config.json";
+    let meta = match std::fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            anyhow::bail!("whatsapp-cli not found at {}: {}", path.display(), fix)
+        }
+        Err(e) => anyhow::bail!("Can't read whatsapp-cli at {}: {}", path.display(), e),
+    };
+    if meta.is_dir() {
+        anyhow::bail!("{} is a directory, not the whatsapp-cli binary: {}", path.display(), fix);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if meta.permissions().mode() & 0o111 == 0 {
+            anyhow::bail!("whatsapp-cli at {} isn't executable: run chmod +x {}", path.display(), path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Major version of whatsapp-cli whose JSON output this client is written against
+const TESTED_CLI_MAJOR: u64 = 1;
+
+/// The `x.y[.z]` version number in `whatsapp-cli --version` output, e.g. "whatsapp-cli v1.4.2"
+fn parse_cli_version(text: &str) -> Option<(u64, u64, u64)> {
+    let re = regex::Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").unwrap();
+    let caps = re.captures(text)?;
+    let part = |i: usize| caps.get(i).and_then(|m| m.as_str().parse().ok()).unwrap_or(0);
+    Some((part(1), part(2), part(3)))
+}
+
+/// A warning for whatsapp-cli versions this client hasn't been tested with. Builds that
+/// don't report a version are let through, since most of them predate version numbers.
+pub fn cli_version_warning(version: Option<&str>) -> Option<String> {
+    let (major, minor, patch) = parse_cli_version(version?)?;
+    (major != TESTED_CLI_MAJOR).then(|| {
+        format!(
+            "whatsapp-cli {}.{}.{} is untested (expected {}.x): if chats or messages don't load, its output may have changed",
+            major, minor, patch, TESTED_CLI_MAJOR
+        )
+    })
 }
 
 impl WhatsAppClient {
     pub async fn new(config: &Config) -> Result<Self> {
         let cli_path = config.whatsapp_cli_path.clone();
-        let store_path = config.store_path();
-        
-        // Ensure store directory exists
-        std::fs::create_dir_all(&store_path)?;
+        check_cli(&cli_path)?;
+        let store_path = config.prepare_store()?;
         
         // Check if whatsapp-cli is authenticated
-        let client = Self {
-            cli_path: cli_path.clone(),
-            store_path: store_path.clone(),
-            pending_updates: Arc::new(Mutex::new(Vec::new())),
-            my_jid: Arc::new(Mutex::new(None)),
-            last_synced_message_id: Arc::new(Mutex::new(None)),
-            contact_cache: Arc::new(Mutex::new(std::collections::HashMap::new())),
-        };
+        let client = Self::from_parts(
+            cli_path.clone(),
+            store_path.clone(),
+            config.settings.max_concurrent_cli,
+        );
+        // Startup doesn't wait for it: an untested version is reported once it has answered
+        let background = client.clone();
+        tokio::spawn(async move { background.check_cli_version().await });
         
+        // Try to get account info to verify authentication
+        match client.get_me().await {
+            Ok(jid) => {
+                *client.my_jid.lock().await = Some(jid);
+                
+                // Names fill in while the UI starts, so the first frame doesn't wait on the chat list
+                let background = client.clone();
+                tokio::spawn(async move { background.prime_contact_cache().await });
+                
+                // Start sync in background
+                client.start_sync_background().await;
+            }
+            Err(e) => {
+                // The app opens the login overlay and starts sync once it succeeds
+                crate::warn_log!("Not logged in at startup: {}", e);
+            }
+        }
+        
+        Ok(client)
+    }
+    
+    /// Fill the contact cache with chat names, then with contact names from the store's
+    /// whatsapp.db in the background
+    pub async fn prime_contact_cache(&self) {
         // Pre-populate contact cache from chats
-        if let Ok(chats) = client.get_dialogs().await {
-            let mut cache = client.contact_cache.lock().await;
+        if let Ok(chats) = self.get_dialogs().await {
+            let mut cache = self.contact_cache.lock().await;
             for chat in chats {
                 // Cache all chats (both individual and groups) with their display names
                 cache.insert(chat.id.clone(), chat.name.clone());
@@ -112,10 +1207,10 @@ impl WhatsAppClient {
         }
         
         // Also load contacts from whatsapp.db database in background
-        let contacts_db_path = store_path.join("whatsapp.db");
+        let contacts_db_path = self.store_path.join("whatsapp.db");
         if contacts_db_path.exists() {
             let contacts_db_path_clone = contacts_db_path.clone();
-            let contact_cache = client.contact_cache.clone();
+            let contact_cache = self.contact_cache.clone();
             tokio::spawn(async move {
                 let contacts = tokio::task::spawn_blocking(move || {
                     let mut contacts_map = std::collections::HashMap::new();
@@ -150,58 +1245,124 @@ impl WhatsAppClient {
                 }
             });
         }
-        
-        // Try to get account info to verify authentication
-        match client.get_me().await {
-            Ok(jid) => {
-                *client.my_jid.lock().await = Some(jid);
-                
-                // Check if we have any chats
-                let chats = client.get_dialogs().await.unwrap_or_default();
-                if chats.is_empty() {
-                    println!();
-                    println!("⚠️  No chats found. This is normal the first time!");
-                    println!();
-                    println!("WhatsApp needs to sync messages first. You have two options:");
-                    println!();
-                    println!("Option 1 (Recommended): Run sync manually in another terminal:");
-                    println!("  {} --store {:?} sync", cli_path.display(), store_path);
-                    println!();
-                    println!("Option 2: Wait - the client will sync in the background, but it may take a while.");
-                    println!("         Press Ctrl+C and run sync manually if you want faster results.");
-                    println!();
-                    println!("Press Enter to continue anyway, or Ctrl+C to exit and run sync first...");
-                    use std::io;
-                    let _ = io::stdin().read_line(&mut String::new());
-                }
-                
-                // Start sync in background
-                client.start_sync_background().await;
-            }
-            Err(_) => {
-                println!();
-                println!("❌ WhatsApp not authenticated!");
-                println!();
-                println!("Please run:");
-                println!("  {} --store {:?} auth", cli_path.display(), store_path);
-                println!();
-                println!("Then scan the QR code with your phone.");
-                println!();
-            }
+    }
+
+    fn from_parts(cli_path: PathBuf, store_path: PathBuf, max_concurrent_cli: usize) -> Self {
+        Self {
+            cli_path: Arc::new(std::sync::RwLock::new(cli_path)),
+            store_path,
+            pending_updates: Arc::new(Mutex::new(Vec::new())),
+            my_jid: Arc::new(Mutex::new(None)),
+            last_synced_message_id: Arc::new(Mutex::new(None)),
+            contact_cache: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            cli_semaphore: Arc::new(Semaphore::new(max_concurrent_cli.max(1))),
+            reply_flag_supported: Arc::new(AtomicBool::new(true)),
+            presence_supported: Arc::new(AtomicBool::new(true)),
+            typing_supported: Arc::new(AtomicBool::new(true)),
+            read_receipts_supported: Arc::new(AtomicBool::new(true)),
+            edit_supported: Arc::new(AtomicBool::new(true)),
+            forward_supported: Arc::new(AtomicBool::new(true)),
+            delete_supported: Arc::new(AtomicBool::new(true)),
+            reactions_supported: Arc::new(AtomicBool::new(true)),
+            from_flag_supported: Arc::new(AtomicBool::new(true)),
+            mention_flag_supported: Arc::new(AtomicBool::new(true)),
+            presence_cache: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            receipts_supported: Arc::new(AtomicBool::new(true)),
+            receipts_cache: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            dialogs_cache: Arc::new(Mutex::new(None)),
+            cli_spawns: Arc::new(AtomicU64::new(0)),
+            connection: Arc::new(Mutex::new(ConnectionState::Connecting)),
+            sync_alive: Arc::new(AtomicBool::new(false)),
+            sync_started: Arc::new(AtomicBool::new(false)),
+            sync_restart: Arc::new(AtomicBool::new(false)),
+            group_syncs: Arc::new(Mutex::new(std::collections::HashMap::new())),
+            force_synced_groups: Arc::new(Mutex::new(std::collections::HashSet::new())),
+            cli_version: Arc::new(std::sync::OnceLock::new()),
+            send_queue: Arc::new(std::sync::OnceLock::new()),
         }
-        
-        Ok(client)
     }
-    
+
+    /// Client pointing at a CLI that doesn't exist, so every call fails fast without touching a real store
+    #[cfg(test)]
+    pub fn new_for_test() -> Self {
+        Self::from_parts(
+            PathBuf::from("/nonexistent/whatsapp-cli"),
+            std::env::temp_dir().join("whatsapp_client_rs_test_store"),
+            1,
+        )
+    }
+
+    /// Client whose store holds a messages.db with the given
+    /// (id, chat_jid, sender, content, timestamp, is_from_me) rows
+    #[cfg(test)]
+    pub fn new_for_test_with_messages(name: &str, rows: &[(&str, &str, &str, &str, &str, bool)]) -> Self {
+        let store_path = std::env::temp_dir().join(format!("whatsapp_client_rs_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&store_path);
+        std::fs::create_dir_all(&store_path).unwrap();
+        let conn = Connection::open(store_path.join("messages.db")).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE messages (id TEXT, chat_jid TEXT, sender TEXT, content TEXT,
+                                    timestamp TEXT, is_from_me BOOLEAN, media_type TEXT)",
+        )
+        .unwrap();
+        for (id, chat_jid, sender, content, timestamp, is_from_me) in rows {
+            conn.execute(
+                "INSERT INTO messages VALUES (?, ?, ?, ?, ?, ?, NULL)",
+                params![id, chat_jid, sender, content, timestamp, is_from_me],
+            )
+            .unwrap();
+        }
+        Self::from_parts(PathBuf::from("/nonexistent/whatsapp-cli"), store_path, 1)
+    }
+
+    /// Wait for a free whatsapp-cli slot
+    async fn acquire_cli_slot(&self) -> Result<SemaphorePermit<'_>> {
+        Ok(self.cli_semaphore.acquire().await?)
+    }
+
+    /// Run a short-lived whatsapp-cli command against our store.
+    /// Holds a semaphore slot for the lifetime of the process so rapid sends and
+    /// background syncs can't pile up processes all fighting over the store DB lock.
+    async fn run_cli(&self, args: &[&str]) -> Result<std::process::Output> {
+        let _slot = self.acquire_cli_slot().await?;
+        let cli_path = self.cli_path();
+        self.cli_spawns.fetch_add(1, Ordering::Relaxed);
+        let output = TokioCommand::new(&cli_path)
+            .arg("--store")
+            .arg(&self.store_path)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| match check_cli(&cli_path) {
+                // Moved, deleted or made unrunnable since startup
+                Err(why) => anyhow::anyhow!("{}, then /reload config", why),
+                Ok(()) => e.into(),
+            })?;
+        Ok(output)
+    }
+
+    /// How many short-lived whatsapp-cli processes have been started so far
+    pub fn cli_spawn_count(&self) -> u64 {
+        self.cli_spawns.load(Ordering::Relaxed)
+    }
+
+    /// The whatsapp-cli binary every call runs
+    pub fn cli_path(&self) -> PathBuf {
+        self.cli_path.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Run a different whatsapp-cli binary from now on; the sync process picks it up when it restarts
+    pub fn set_cli_path(&self, path: PathBuf) {
+        *self.cli_path.write().unwrap_or_else(|e| e.into_inner()) = path;
+    }
+
     pub async fn get_me(&self) -> Result<String> {
         // Try to get chats list to verify authentication
         // We'll extract our own JID from messages later
-        let output = Command::new(&self.cli_path)
-            .args(&["--store", &self.store_path.to_string_lossy(), "chats", "list", "--limit", "1"])
-            .output()?;
+        let output = self.run_cli(&["chats", "list", "--limit", "1"]).await?;
         
         if !output.status.success() {
-            anyhow::bail!("Not authenticated. Run: {} auth", self.cli_path.display());
+            anyhow::bail!("Not authenticated. Run: {} auth", self.cli_path().display());
         }
         
         let response: WhatsAppResponse = serde_json::from_slice(&output.stdout)?;
@@ -210,18 +1371,70 @@ impl WhatsAppClient {
             anyhow::bail!("Failed to verify authentication: {:?}", response.error);
         }
         
-        // For now, return a placeholder - we'll get the real JID from messages
-        // WhatsApp JID format: phone@s.whatsapp.net
-        // We'll extract it from messages when we receive them
-        Ok("unknown@s.whatsapp.net".to_string())
+        // The account's JID comes from the store; without one we fall back to a
+        // placeholder and pick the real JID up from outgoing messages later
+        Ok(self
+            .own_jid_from_store()
+            .await
+            .unwrap_or_else(|| UNKNOWN_JID.to_string()))
+    }
+
+    /// Our own user JID: the logged-in device in whatsapp.db, or else the sender of an
+    /// outgoing message in messages.db
+    async fn own_jid_from_store(&self) -> Option<String> {
+        let device_db = self.store_path.join("whatsapp.db");
+        let messages_db = self.store_path.join("messages.db");
+        tokio::task::spawn_blocking(move || {
+            // Connection::open would create a missing database, so check first
+            let open = |path: &Path| path.exists().then(|| Connection::open(path).ok()).flatten();
+            let from_device = open(&device_db).and_then(|conn| {
+                conn.query_row("SELECT jid FROM whatsmeow_device LIMIT 1", [], |row| row.get::<_, String>(0))
+                    .ok()
+            });
+            let jid = from_device.or_else(|| {
+                open(&messages_db).and_then(|conn| {
+                    conn.query_row(
+                        "SELECT sender FROM messages
+                         WHERE is_from_me = 1 AND sender LIKE '%@s.whatsapp.net'
+                         ORDER BY timestamp DESC LIMIT 1",
+                        [],
+                        |row| row.get::<_, String>(0),
+                    )
+                    .ok()
+                })
+            })?;
+            Some(user_jid(&jid))
+        })
+        .await
+        .ok()
+        .flatten()
     }
     
+    /// The chat list. Answers are reused for `DIALOGS_CACHE_SECS`, so the chat list refresh,
+    /// chat name lookups and target resolution close together share one `chats list` call;
+    /// `invalidate_dialogs` forces the next call to ask again.
     pub async fn get_dialogs(&self) -> Result<Vec<ChatInfo>> {
+        // Held while fetching, so callers arriving meanwhile wait for this answer
+        let mut cache = self.dialogs_cache.lock().await;
+        if let Some((at, chats)) = cache.as_ref() {
+            if at.elapsed() < std::time::Duration::from_secs(DIALOGS_CACHE_SECS) {
+                return Ok(chats.clone());
+            }
+        }
+        let chats = self.fetch_dialogs().await?;
+        *cache = Some((std::time::Instant::now(), chats.clone()));
+        Ok(chats)
+    }
+
+    /// Drop the cached chat list, e.g. after creating or leaving a group
+    pub async fn invalidate_dialogs(&self) {
+        *self.dialogs_cache.lock().await = None;
+    }
+
+    async fn fetch_dialogs(&self) -> Result<Vec<ChatInfo>> {
         crate::debug_log!("get_dialogs: Requesting chat list");
         
-        let output = Command::new(&self.cli_path)
-            .args(&["--store", &self.store_path.to_string_lossy(), "chats", "list"])
-            .output()?;
+        let output = self.run_cli(&["chats", "list"]).await?;
         
         if !output.status.success() {
             crate::warn_log!("get_dialogs: Command failed: {:?}", output.status);
@@ -236,7 +1449,6 @@ impl WhatsAppClient {
         }
         
         let mut chats = Vec::new();
-        let mut seen_names: std::collections::HashMap<String, String> = std::collections::HashMap::new();
         let mut temp_chats: Vec<ChatListItem> = Vec::new();
         
         if let Some(data) = response.data {
@@ -263,78 +1475,24 @@ impl WhatsAppClient {
                     } else {
                         crate::warn_log!("get_dialogs: Failed to parse chat item: {:?}", chat_val);
                     }
-                }
-                
-                // Second pass: filter out @lid chats if we have a @s.whatsapp.net version
-                // Build a map of @s.whatsapp.net chats
-                let mut phone_chats: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-                for chat in &temp_chats {
-                    if chat.jid.ends_with("@s.whatsapp.net") {
-                        phone_chats.insert(chat.name.clone(), chat.jid.clone());
-                    }
-                }
-                
-                // Now process chats, skipping @lid if we have a phone version
-                for chat in temp_chats {
-                    // Skip @lid chats if we have a @s.whatsapp.net version with same or similar name
-                    if chat.jid.ends_with("@lid") {
-                        if phone_chats.contains_key(&chat.name) {
-                            crate::debug_log!("get_dialogs: Skipping @lid chat '{}' - have @s.whatsapp.net version", chat.name);
-                            continue;
-                        }
-                        // Also check if any phone chat name starts with this name (e.g., "P" vs "Patrik Wellner")
-                        let mut found_match = false;
-                        for phone_name in phone_chats.keys() {
-                            if phone_name.starts_with(&chat.name) || chat.name.starts_with(phone_name) {
-                                crate::debug_log!("get_dialogs: Skipping @lid chat '{}' - similar to '{}'", chat.name, phone_name);
-                                found_match = true;
-                                break;
-                            }
-                        }
-                        if found_match {
-                            continue;
-                        }
-                    }
-                        
-                        // Deduplicate: if we've seen this name before, keep the one with more unread or the group
-                        if let Some(existing_jid) = seen_names.get(&chat.name) {
-                            // Find the existing chat
-                            if let Some(existing_idx) = chats.iter().position(|c: &ChatInfo| c.id == *existing_jid) {
-                                let existing = &chats[existing_idx];
-                                // Prefer groups over individual chats, or higher unread count
-                                let is_group = chat.jid.ends_with("@g.us");
-                                let keep_new = is_group && !existing.is_group || 
-                                               (is_group == existing.is_group && chat.unread > existing.unread);
-                                
-                                if keep_new {
-                                    crate::debug_log!("get_dialogs: Replacing duplicate '{}': {} -> {}", 
-                                        chat.name, existing_jid, chat.jid);
-                                    chats.remove(existing_idx);
-                                    seen_names.insert(chat.name.clone(), chat.jid.clone());
-                                } else {
-                                    crate::debug_log!("get_dialogs: Skipping duplicate '{}': keeping {}", 
-                                        chat.name, existing_jid);
-                                    continue;
-                                }
-                            }
-                        } else {
-                            seen_names.insert(chat.name.clone(), chat.jid.clone());
-                        }
-                        
-                        // Determine if it's a group (group JIDs end with @g.us)
-                        let is_group = chat.jid.ends_with("@g.us");
-                        
+                }
+                
+                for chat in temp_chats {
+                    let is_group = chat.jid.ends_with("@g.us");
+                    let is_channel = chat.read_only.unwrap_or_else(|| is_channel_jid(&chat.jid));
+                    crate::debug_log!("get_dialogs: Chat {}: '{}' (unread={}, is_group={})", 
+                        chat.jid, chat.name, chat.unread, is_group);
                     chats.push(ChatInfo {
-                        id: chat.jid.clone(),
-                        name: chat.name.clone(),
+                        id: chat.jid,
+                        name: chat.name,
                         username: None, // WhatsApp doesn't have usernames
                         unread: chat.unread,
-                        _is_channel: false,
+                        is_channel,
                         is_group,
+                        last_message_ts: chat.last_message_time.as_ref().and_then(chat_timestamp).unwrap_or(0),
                     });
-                    crate::debug_log!("get_dialogs: Chat {}: '{}' (unread={}, is_group={})", 
-                        chat.jid, chat.name, chat.unread, is_group);
                 }
+                chats = dedupe_and_normalize_chats(chats);
             } else {
                 crate::warn_log!("get_dialogs: Response data is not an array");
             }
@@ -342,9 +1500,52 @@ impl WhatsAppClient {
             crate::warn_log!("get_dialogs: No data in response");
         }
         
+        // The payload's time may be missing or lag behind what sync has stored
+        let db_times = self.last_message_times_from_db().await;
+        for chat in &mut chats {
+            if let Some(&ts) = db_times.get(&chat.id) {
+                chat.last_message_ts = chat.last_message_ts.max(ts);
+            }
+        }
+
         crate::debug_log!("get_dialogs: Returning {} chats after filtering", chats.len());
         Ok(chats)
     }
+
+    /// Newest message time per chat in messages.db
+    async fn last_message_times_from_db(&self) -> std::collections::HashMap<String, i64> {
+        let db_path = self.store_path.join("messages.db");
+        if !db_path.exists() {
+            return std::collections::HashMap::new();
+        }
+        let result = tokio::task::spawn_blocking(move || -> Result<std::collections::HashMap<String, i64>> {
+            let conn = Connection::open(&db_path)?;
+            let mut stmt = conn.prepare("SELECT chat_jid, MAX(timestamp) FROM messages GROUP BY chat_jid")?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?;
+            let mut times = std::collections::HashMap::new();
+            for row in rows {
+                let (chat_jid, timestamp) = row?;
+                if let Some(ts) = timestamp.as_deref().and_then(try_parse_cli_timestamp) {
+                    times.insert(chat_jid, ts);
+                }
+            }
+            Ok(times)
+        })
+        .await;
+        match result {
+            Ok(Ok(times)) => times,
+            Ok(Err(e)) => {
+                crate::warn_log!("last_message_times_from_db: {}", e);
+                std::collections::HashMap::new()
+            }
+            Err(e) => {
+                crate::warn_log!("last_message_times_from_db: {}", e);
+                std::collections::HashMap::new()
+            }
+        }
+    }
     
     pub async fn get_messages(
         &self,
@@ -353,13 +1554,6 @@ impl WhatsAppClient {
     ) -> Result<Vec<(String, String, String, String, Option<String>, Option<String>, std::collections::HashMap<String, u32>, i64)>> {
         crate::debug_log!("get_messages: Requesting {} messages for chat {}", limit, chat_jid);
         
-        // Get chat name for better matching (since @lid and @s.whatsapp.net might have different IDs)
-        let chat_name = self.get_dialogs().await?
-            .iter()
-            .find(|c| c.id == chat_jid)
-            .map(|c| c.name.clone());
-        crate::debug_log!("get_messages: Chat name for {} is {:?}", chat_jid, chat_name);
-        
         // CRITICAL: whatsapp-cli --chat flag doesn't work correctly for groups
         // For groups, read directly from SQLite database since whatsapp-cli doesn't return them correctly
         // For individual chats, use whatsapp-cli (even though broken, we filter by name)
@@ -367,22 +1561,31 @@ impl WhatsAppClient {
         
         if is_group {
             // Read directly from SQLite database for groups
-            return self.get_messages_from_db(chat_jid, limit, chat_name).await;
+            let messages = self
+                .get_messages_from_db(chat_jid, limit, None, None, None)
+                .await?
+                .unwrap_or_default();
+            // Nothing stored yet: sync once in the background. The pane header shows the
+            // progress and the app reloads the pane when messages arrive.
+            if messages.is_empty() && !self.force_synced_groups.lock().await.contains(chat_jid) {
+                crate::warn_log!("get_messages: No messages for group {} in the database, syncing it", chat_jid);
+                self.force_sync_group(chat_jid).await;
+            }
+            return Ok(messages);
         }
         
+        // Get chat name for better matching (since @lid and @s.whatsapp.net might have different IDs)
+        let chat_name = self.get_dialogs().await?
+            .iter()
+            .find(|c| c.id == chat_jid)
+            .map(|c| c.name.clone());
+        crate::debug_log!("get_messages: Chat name for {} is {:?}", chat_jid, chat_name);
+        
         // For individual chats, use whatsapp-cli (even though broken, we filter by name)
-        let store_path_str = self.store_path.to_string_lossy().to_string();
         let limit_str = limit.to_string();
-        
-        let mut cmd = Command::new(&self.cli_path);
-        cmd.args(&[
-            "--store", &store_path_str,
-            "messages", "list",
-            "--chat", chat_jid,
-            "--limit", &limit_str,
-        ]);
-        
-        let output = cmd.output()?;
+        let output = self
+            .run_cli(&["messages", "list", "--chat", chat_jid, "--limit", &limit_str])
+            .await?;
         
         if !output.status.success() {
             crate::warn_log!("get_messages: Command failed for chat {}: {:?}", chat_jid, output.status);
@@ -481,26 +1684,19 @@ impl WhatsAppClient {
                             }
                         };
                         
-                        // Parse timestamp from string (format: "2024-01-01T12:00:00Z" or similar)
-                        let timestamp = msg.timestamp.parse::<i64>()
-                            .or_else(|_| {
-                                // Try parsing as ISO 8601 datetime
-                                chrono::DateTime::parse_from_rfc3339(&msg.timestamp)
-                                    .map(|dt| dt.timestamp())
-                                    .or_else(|_| chrono::NaiveDateTime::parse_from_str(&msg.timestamp, "%Y-%m-%d %H:%M:%S")
-                                        .map(|dt| dt.and_utc().timestamp()))
-                            })
-                            .unwrap_or_else(|_| {
-                                // Fallback to current time if parsing fails
-                                chrono::Utc::now().timestamp()
-                            });
+                        let timestamp = parse_cli_timestamp(&msg.timestamp);
                         
                         let media_type = msg.media_type.clone();
+                        // A contact share's content may be only the name; the card has it too
+                        let content = match msg.vcard {
+                            Some(vcard) if !msg.content.to_ascii_uppercase().contains("BEGIN:VCARD") => vcard,
+                            _ => msg.content,
+                        };
                         messages.push((
                             msg.id,
                             msg.sender,
                             sender_name,
-                            msg.content,
+                            content,
                             None, // reply_to_id - TODO: extract from message
                             media_type, // media_type
                             std::collections::HashMap::new(), // reactions - TODO: extract reactions
@@ -515,96 +1711,6 @@ impl WhatsAppClient {
                     crate::debug_log!("get_messages: Filtered out {} messages that didn't match chat {} (kept {})", filtered_count, chat_jid, messages.len());
                 }
                 
-                // If this is a group chat and we got 0 messages, try to force sync
-                if chat_jid.ends_with("@g.us") && messages.is_empty() {
-                    let has_group_messages = chat_jids_seen.iter().any(|jid| jid.ends_with("@g.us"));
-                    if !has_group_messages {
-                        crate::warn_log!("get_messages: No group messages found in database for chat {}. whatsapp-cli may not have synced historical messages from this group.", chat_jid);
-                        crate::warn_log!("get_messages: Note: whatsapp-cli sync only syncs NEW messages, not historical ones. Group messages will appear once new messages arrive.");
-                        // Force sync for this group by running sync in background (in case there are new messages)
-                        self.force_sync_group(chat_jid).await;
-                        // Wait longer for sync to fetch messages (groups may take more time)
-                        tokio::time::sleep(tokio::time::Duration::from_secs(8)).await;
-                        // Try fetching messages again by re-running the same logic (but not recursively)
-                        // We'll fetch again with the same parameters
-                        let fetch_limit = (limit * 50).max(2000);
-                        let store_path_str = self.store_path.to_string_lossy().to_string();
-                        let limit_str = fetch_limit.to_string();
-                        
-                        let output = Command::new(&self.cli_path)
-                            .args(&[
-                                "--store", &store_path_str,
-                                "messages", "list",
-                                "--limit", &limit_str,
-                            ])
-                            .output()?;
-                        
-                        if output.status.success() {
-                            let response: WhatsAppResponse = serde_json::from_slice(&output.stdout)?;
-                            if let Some(data) = response.data {
-                                if let Some(msgs_array) = data.as_array() {
-                                    let mut retry_messages = Vec::new();
-                                    let mut retry_chat_jids_seen: std::collections::HashSet<String> = std::collections::HashSet::new();
-                                    
-                                    for msg_val in msgs_array {
-                                        if let Ok(msg) = serde_json::from_value::<MessageItem>(msg_val.clone()) {
-                                            retry_chat_jids_seen.insert(msg.chat_jid.clone());
-                                            if msg.chat_jid == chat_jid {
-                                                // Same parsing logic as above...
-                                                let sender_name = if msg.from_me {
-                                                    "You".to_string()
-                                                } else if let Some(name) = msg.sender_name {
-                                                    name
-                                                } else if let Some(chat_name) = &msg.chat_name {
-                                                    if !msg.chat_jid.ends_with("@g.us") {
-                                                        chat_name.clone()
-                                                    } else {
-                                                        let cache = self.contact_cache.lock().await;
-                                                        cache.get(&msg.sender)
-                                                            .cloned()
-                                                            .unwrap_or_else(|| format_phone_number(&msg.sender))
-                                                    }
-                                                } else {
-                                                    let cache = self.contact_cache.lock().await;
-                                                    cache.get(&msg.sender)
-                                                        .cloned()
-                                                        .unwrap_or_else(|| format_phone_number(&msg.sender))
-                                                };
-                                                
-                                                let timestamp = msg.timestamp.parse::<i64>()
-                                                    .unwrap_or_else(|_| chrono::Utc::now().timestamp());
-                                                
-                                                let media_type = msg.media_type.clone();
-                                                retry_messages.push((
-                                                    msg.id,
-                                                    msg.sender,
-                                                    sender_name,
-                                                    msg.content,
-                                                    None,
-                                                    media_type,
-                                                    std::collections::HashMap::new(),
-                                                    timestamp,
-                                                ));
-                                            }
-                                        }
-                                    }
-                                    
-                                    if !retry_messages.is_empty() {
-                                        crate::info_log!("get_messages: Found {} messages after sync for group {}", retry_messages.len(), chat_jid);
-                                        retry_messages.sort_by_key(|m| m.7);
-                                        if retry_messages.len() > limit {
-                                            retry_messages.reverse();
-                                            retry_messages.truncate(limit);
-                                            retry_messages.reverse();
-                                        }
-                                        messages = retry_messages;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                
                 // Sort by timestamp (oldest first) and take only the requested limit
                 if messages.len() > limit {
                     // Keep only the most recent messages
@@ -637,15 +1743,19 @@ impl WhatsAppClient {
         Ok(messages)
     }
     
-    pub async fn send_message(&self, chat_jid: &str, text: &str) -> Result<()> {
-        let output = Command::new(&self.cli_path)
-            .args(&[
-                "--store", &self.store_path.to_string_lossy(),
-                "send",
-                "--to", chat_jid,
-                "--message", text,
-            ])
-            .output()?;
+    /// Send `text`, from the linked identity `from` if given and this whatsapp-cli can
+    /// (`send --from`); otherwise from the account's own number. Mentions in a group are
+    /// passed as `send --mention` while this whatsapp-cli accepts it.
+    pub async fn send_message(&self, chat_jid: &str, text: &str, from: Option<&str>) -> Result<()> {
+        // Each rejected flag is dropped for good, so this ends after at most three tries
+        let output = loop {
+            let from = self.send_from(from);
+            let mentions = self.send_mentions(chat_jid, text);
+            let output = self.run_cli(&with_mentions(send_args(chat_jid, text, from), &mentions)).await?;
+            if output.status.success() || !self.send_flags_rejected(&output, from, &mentions) {
+                break output;
+            }
+        };
         
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
@@ -661,66 +1771,413 @@ impl WhatsAppClient {
         Ok(())
     }
     
+    /// Send a message written earlier, as a reply if it was one
+    async fn send_queued(&self, message: &QueuedMessage, quoted: Option<&QuotedMessage>) -> Result<()> {
+        match &message.reply_to {
+            Some(reply_to_id) => {
+                self.reply_to_message(&message.chat_id, reply_to_id, &message.text, quoted, message.send_as.as_deref())
+                    .await
+            }
+            None => {
+                self.send_message(&message.chat_id, &message.text, message.send_as.as_deref())
+                    .await
+            }
+        }
+    }
+
+    /// Hand a message to the send queue, which sends one message at a time in the order
+    /// they were queued and retries failures with backoff. The outcome comes back as a
+    /// `SendFinished` update.
+    pub fn queue_send(&self, message: QueuedMessage, quoted: Option<QuotedMessage>) {
+        let queue = self.send_queue.get_or_init(|| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            tokio::spawn(self.clone().run_send_queue(receiver));
+            sender
+        });
+        if let Err(mpsc::error::SendError((message, _))) = queue.send((message, quoted)) {
+            crate::warn_log!("queue_send: send queue stopped, message to {} dropped", message.chat_id);
+        }
+    }
+
+    async fn run_send_queue(self, mut receiver: mpsc::UnboundedReceiver<OutgoingSend>) {
+        while let Some((message, quoted)) = receiver.recv().await {
+            let mut attempt = 1;
+            let error = loop {
+                match self.send_queued(&message, quoted.as_ref()).await {
+                    Ok(()) => break None,
+                    Err(e) if attempt >= SEND_ATTEMPTS => break Some(e.to_string()),
+                    Err(e) => {
+                        crate::warn_log!("send to {} failed (attempt {}): {}", message.chat_id, attempt, e);
+                        let delay = SEND_RETRY_BASE_MS << (attempt - 1);
+                        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                        attempt += 1;
+                    }
+                }
+            };
+            if let Some(ref e) = error {
+                crate::warn_log!("send to {} gave up after {} attempts: {}", message.chat_id, attempt, e);
+            }
+            self.pending_updates
+                .lock()
+                .await
+                .push(WhatsAppUpdate::SendFinished { message, error });
+        }
+    }
+
+    /// Reply to `message_id`, quoting it natively via `send --reply-to`. If this whatsapp-cli
+    /// has no such flag, the reply is sent as a plain message starting with an inline quote.
     pub async fn reply_to_message(
         &self,
         chat_jid: &str,
-        _message_id: &str,
+        message_id: &str,
         text: &str,
+        quoted: Option<&QuotedMessage>,
+        from: Option<&str>,
     ) -> Result<()> {
-        // WhatsApp CLI doesn't have a direct reply command, so we send a regular message
-        // TODO: Check if whatsapp-cli supports --reply-to flag
-        let output = Command::new(&self.cli_path)
-            .args(&[
-                "--store", &self.store_path.to_string_lossy(),
-                "send",
-                "--to", chat_jid,
-                "--message", text,
-            ])
-            .output()?;
-        
-        if !output.status.success() {
+        if !message_id.is_empty() && self.reply_flag_supported.load(Ordering::Relaxed) {
+            let output = loop {
+                let from = self.send_from(from);
+                let mentions = self.send_mentions(chat_jid, text);
+                let args = with_mentions(reply_args(chat_jid, message_id, text, from), &mentions);
+                let output = self.run_cli(&args).await?;
+                if output.status.success() || !self.send_flags_rejected(&output, from, &mentions) {
+                    break output;
+                }
+            };
+
+            let response: Option<WhatsAppResponse> = serde_json::from_slice(&output.stdout).ok();
+            if output.status.success() {
+                let Some(response) = response else {
+                    anyhow::bail!("Failed to send reply: unexpected output from whatsapp-cli");
+                };
+                if !response.success {
+                    anyhow::bail!("Failed to send reply: {:?}", response.error);
+                }
+                if !reply_quote_confirmed(response.data.as_ref(), message_id) {
+                    crate::warn_log!("reply_to_message: whatsapp-cli did not confirm quoted message {}", message_id);
+                }
+                return Ok(());
+            }
+
             let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!("Failed to send reply: {}", stderr);
+            let error = response.and_then(|r| r.error).unwrap_or_default();
+            if !is_unknown_flag_error(&stderr) && !is_unknown_flag_error(&error) {
+                anyhow::bail!("Failed to send reply: {}{}", stderr, error);
+            }
+            crate::info_log!("reply_to_message: whatsapp-cli has no --reply-to flag, quoting inline");
+            self.reply_flag_supported.store(false, Ordering::Relaxed);
         }
-        
-        let response: WhatsAppResponse = serde_json::from_slice(&output.stdout)?;
-        
-        if !response.success {
-            anyhow::bail!("Failed to send reply: {:?}", response.error);
+
+        let body = match quoted {
+            Some(quoted) => quote_inline(quoted, text),
+            None => text.to_string(),
+        };
+        self.send_message(chat_jid, &body, from).await
+    }
+
+    /// The identity to pass as `send --from`, unless whatsapp-cli has already rejected the flag
+    fn send_from<'a>(&self, from: Option<&'a str>) -> Option<&'a str> {
+        from.filter(|_| self.from_flag_supported.load(Ordering::Relaxed))
+    }
+
+    /// The JIDs to pass as `send --mention` for `text`: mentions in a group chat, unless
+    /// whatsapp-cli has already rejected the flag
+    fn send_mentions(&self, chat_jid: &str, text: &str) -> Vec<String> {
+        if chat_jid.ends_with("@g.us") && self.mention_flag_supported.load(Ordering::Relaxed) {
+            mention_jids(text)
+        } else {
+            Vec::new()
         }
-        
-        Ok(())
     }
-    
-    pub async fn edit_message(
-        &self,
-        _chat_jid: &str,
-        _message_id: &str,
-        _new_text: &str,
-    ) -> Result<()> {
-        // WhatsApp doesn't support editing messages
-        anyhow::bail!("WhatsApp does not support editing messages")
+
+    /// Whether a failed send was rejected for the `--from` or `--mention` flags it passed;
+    /// a rejected flag is not passed again
+    fn send_flags_rejected(&self, output: &std::process::Output, from: Option<&str>, mentions: &[String]) -> bool {
+        if from.is_some() && self.send_from_rejected(output) {
+            return true;
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if mentions.is_empty() || !is_unknown_flag(&stderr, "mention") {
+            return false;
+        }
+        crate::info_log!("send: whatsapp-cli has no --mention flag, mentions go as plain text");
+        self.mention_flag_supported.store(false, Ordering::Relaxed);
+        true
+    }
+
+    /// Whether a failed send was rejected for its `--from` flag; if so the flag is not
+    /// passed again and messages go out from the account's own number
+    fn send_from_rejected(&self, output: &std::process::Output) -> bool {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !is_unknown_flag(&stderr, "from") {
+            return false;
+        }
+        crate::info_log!("send: whatsapp-cli has no --from flag, sending from the default identity");
+        self.from_flag_supported.store(false, Ordering::Relaxed);
+        true
     }
     
-    pub async fn delete_message(&self, _chat_jid: &str, _message_id: &str) -> Result<()> {
-        // WhatsApp CLI doesn't support deleting messages yet
-        anyhow::bail!("Message deletion is not supported by whatsapp-cli yet")
+    /// Send a file as an image, video, audio or document message, with an optional caption.
+    /// The CLI picks the WhatsApp message type from the file itself.
+    pub async fn send_media(&self, chat_jid: &str, path: &Path, caption: Option<&str>) -> Result<()> {
+        let path_str = path.to_string_lossy();
+        let mut args = vec!["send", "--to", chat_jid, "--file", &path_str];
+        if let Some(caption) = caption {
+            args.extend(["--message", caption]);
+        }
+        let output = self.run_cli(&args).await?;
+
+        let response: Option<WhatsAppResponse> = serde_json::from_slice(&output.stdout).ok();
+        let error = match response {
+            Some(response) if output.status.success() && response.success => return Ok(()),
+            Some(response) => response.error.unwrap_or_default(),
+            None if output.status.success() => {
+                anyhow::bail!("Failed to send file: unexpected output from whatsapp-cli")
+            }
+            None => String::new(),
+        };
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("{}", describe_send_media_error(&format!("{} {}", error, stderr)))
+    }
+
+    /// React to a message with an emoji
+    pub async fn send_reaction(&self, chat_jid: &str, message_id: &str, emoji: &str) -> Result<()> {
+        let unsupported = "This whatsapp-cli can't send reactions";
+        if !self.reactions_supported.load(Ordering::Relaxed) {
+            anyhow::bail!("{}", unsupported);
+        }
+        let output = self
+            .run_cli(&["react", "--to", chat_jid, "--message-id", message_id, "--emoji", emoji])
+            .await?;
+        if !output.status.success() && is_unsupported_error(&String::from_utf8_lossy(&output.stderr)) {
+            crate::info_log!("send_reaction: whatsapp-cli has no react command, disabling");
+            self.reactions_supported.store(false, Ordering::Relaxed);
+        }
+        action_result(&output, unsupported)
+    }
+
+    /// Online status of an individual chat. Answers are cached for `PRESENCE_CACHE_SECS`, and
+    /// failures count as `Unknown` so a missing `presence` command isn't retried every tick.
+    pub async fn get_presence(&self, chat_jid: &str) -> Presence {
+        if chat_jid.ends_with("@g.us") || !self.presence_supported.load(Ordering::Relaxed) {
+            return Presence::Unknown;
+        }
+        if let Some((at, presence)) = self.presence_cache.lock().await.get(chat_jid) {
+            if at.elapsed() < std::time::Duration::from_secs(PRESENCE_CACHE_SECS) {
+                return *presence;
+            }
+        }
+
+        let presence = match self.run_cli(&["presence", "--jid", chat_jid]).await {
+            Ok(output) if output.status.success() => serde_json::from_slice::<WhatsAppResponse>(&output.stdout)
+                .ok()
+                .filter(|r| r.success)
+                .and_then(|r| r.data)
+                .map_or(Presence::Unknown, |data| Presence::from_response(&data)),
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if is_unsupported_error(&stderr) {
+                    crate::info_log!("get_presence: whatsapp-cli has no presence command, not polling again");
+                    self.presence_supported.store(false, Ordering::Relaxed);
+                }
+                Presence::Unknown
+            }
+            Err(e) => {
+                crate::debug_log!("get_presence: {}", e);
+                Presence::Unknown
+            }
+        };
+        self.presence_cache
+            .lock()
+            .await
+            .insert(chat_jid.to_string(), (std::time::Instant::now(), presence));
+        presence
+    }
+
+    /// Tell a chat that we started (`composing`) or stopped typing. Callers debounce; a
+    /// whatsapp-cli without a `typing` command turns this into a no-op after the first try.
+    pub async fn send_typing(&self, chat_jid: &str, composing: bool) -> Result<()> {
+        if !self.typing_supported.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let state = if composing { "composing" } else { "paused" };
+        let output = self
+            .run_cli(&["typing", "--to", chat_jid, "--state", state])
+            .await?;
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_unsupported_error(&stderr) {
+            crate::info_log!("send_typing: whatsapp-cli has no typing command, not sending again");
+            self.typing_supported.store(false, Ordering::Relaxed);
+            return Ok(());
+        }
+        anyhow::bail!("typing failed: {}", stderr.trim())
+    }
+
+    /// Mark a chat as read up to `last_message_id` on the server, so the phone clears its
+    /// unread badge too. A whatsapp-cli without `mark-read` is logged once and not asked
+    /// again; other failures are returned and the next chat read tries again.
+    pub async fn mark_read(&self, chat_jid: &str, last_message_id: &str) -> Result<()> {
+        if !self.read_receipts_supported.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let output = self
+            .run_cli(&["mark-read", "--chat", chat_jid, "--message-id", last_message_id])
+            .await?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let error = serde_json::from_str::<WhatsAppResponse>(&stdout)
+            .ok()
+            .filter(|r| !r.success)
+            .map(|r| r.error.unwrap_or_default());
+        if output.status.success() && error.is_none() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if is_unsupported_error(&stderr) {
+            crate::warn_log!("mark_read: whatsapp-cli has no mark-read command, not sending more");
+            self.read_receipts_supported.store(false, Ordering::Relaxed);
+            return Ok(());
+        }
+        anyhow::bail!("mark-read failed: {} {}", error.unwrap_or_default(), stderr.trim())
+    }
+
+    /// Delivery and read receipts of our messages in a chat. Answers are cached for
+    /// `RECEIPTS_CACHE_SECS`; a whatsapp-cli without a `receipts` command gives none.
+    pub async fn get_receipts(&self, chat_jid: &str) -> ReceiptMap {
+        if !self.receipts_supported.load(Ordering::Relaxed) {
+            return ReceiptMap::new();
+        }
+        if let Some((at, receipts)) = self.receipts_cache.lock().await.get(chat_jid) {
+            if at.elapsed() < std::time::Duration::from_secs(RECEIPTS_CACHE_SECS) {
+                return receipts.clone();
+            }
+        }
+
+        let receipts = match self.run_cli(&["receipts", "--chat", chat_jid]).await {
+            Ok(output) if output.status.success() => serde_json::from_slice::<WhatsAppResponse>(&output.stdout)
+                .ok()
+                .filter(|r| r.success)
+                .and_then(|r| r.data)
+                .map(|data| parse_receipts(&data, chat_jid.ends_with("@g.us")))
+                .unwrap_or_default(),
+            Ok(output) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if is_unsupported_error(&stderr) {
+                    crate::info_log!("get_receipts: whatsapp-cli has no receipts command, not polling again");
+                    self.receipts_supported.store(false, Ordering::Relaxed);
+                }
+                ReceiptMap::new()
+            }
+            Err(e) => {
+                crate::debug_log!("get_receipts: {}", e);
+                ReceiptMap::new()
+            }
+        };
+        self.receipts_cache
+            .lock()
+            .await
+            .insert(chat_jid.to_string(), (std::time::Instant::now(), receipts.clone()));
+        receipts
+    }
+
+    /// Who got and read our message `message_id` in a chat, asked fresh from whatsapp-cli.
+    /// The chat's cached receipts are updated from the same answer.
+    pub async fn get_message_info(&self, chat_jid: &str, message_id: &str) -> Result<MessageInfo> {
+        if !self.receipts_supported.load(Ordering::Relaxed) {
+            anyhow::bail!("{}", RECEIPTS_UNSUPPORTED);
+        }
+        let output = self.run_cli(&["receipts", "--chat", chat_jid]).await?;
+        if let Err(e) = action_result(&output, RECEIPTS_UNSUPPORTED) {
+            if e.to_string() == RECEIPTS_UNSUPPORTED {
+                self.receipts_supported.store(false, Ordering::Relaxed);
+            }
+            return Err(e);
+        }
+        let data = serde_json::from_slice::<WhatsAppResponse>(&output.stdout)
+            .ok()
+            .and_then(|r| r.data)
+            .unwrap_or_default();
+        self.receipts_cache.lock().await.insert(
+            chat_jid.to_string(),
+            (std::time::Instant::now(), parse_receipts(&data, chat_jid.ends_with("@g.us"))),
+        );
+        let names = self.contact_cache.lock().await.clone();
+        Ok(parse_message_info(&data, chat_jid, message_id, &names))
+    }
+
+    /// Seed the receipts cache, as if whatsapp-cli had just answered
+    #[cfg(test)]
+    pub async fn set_cached_receipts(&self, chat_jid: &str, receipts: ReceiptMap) {
+        self.receipts_cache
+            .lock()
+            .await
+            .insert(chat_jid.to_string(), (std::time::Instant::now(), receipts));
+    }
+
+    /// Seed the presence cache, as if whatsapp-cli had just answered
+    #[cfg(test)]
+    pub async fn set_cached_presence(&self, chat_jid: &str, presence: Presence) {
+        self.presence_cache
+            .lock()
+            .await
+            .insert(chat_jid.to_string(), (std::time::Instant::now(), presence));
+    }
+
+    /// Whether `edit_message` may work; false once whatsapp-cli has rejected `edit`
+    pub fn edit_supported(&self) -> bool {
+        self.edit_supported.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub fn set_edit_supported(&self, supported: bool) {
+        self.edit_supported.store(supported, Ordering::Relaxed);
+    }
+
+    /// Edit one of our own messages in place via `whatsapp-cli edit`
+    pub async fn edit_message(&self, chat_jid: &str, message_id: &str, new_text: &str) -> Result<()> {
+        let unsupported = "This whatsapp-cli can't edit messages";
+        if !self.edit_supported() {
+            anyhow::bail!("{}", unsupported);
+        }
+        let output = self
+            .run_cli(&["edit", "--to", chat_jid, "--message-id", message_id, "--message", new_text])
+            .await?;
+        if !output.status.success() && is_unsupported_error(&String::from_utf8_lossy(&output.stderr)) {
+            crate::info_log!("edit_message: whatsapp-cli has no edit command, disabling");
+            self.edit_supported.store(false, Ordering::Relaxed);
+        }
+        action_result(&output, unsupported)
+    }
+
+    /// Delete one of our own messages for everyone via `whatsapp-cli revoke`
+    pub async fn delete_message(&self, chat_jid: &str, message_id: &str) -> Result<()> {
+        let unsupported = "This whatsapp-cli can't delete messages";
+        if !self.delete_supported.load(Ordering::Relaxed) {
+            anyhow::bail!("{}", unsupported);
+        }
+        let output = self.run_cli(&revoke_args(chat_jid, message_id)).await?;
+        if !output.status.success() && is_unsupported_error(&String::from_utf8_lossy(&output.stderr)) {
+            crate::info_log!("delete_message: whatsapp-cli has no revoke command, disabling");
+            self.delete_supported.store(false, Ordering::Relaxed);
+        }
+        action_result(&output, unsupported).map_err(|e| {
+            if is_too_old_error(&e.to_string()) {
+                anyhow::anyhow!(DELETE_TOO_OLD)
+            } else {
+                e
+            }
+        })
     }
     
-    pub async fn resolve_username(&self, phone: &str) -> Result<Option<(String, String, bool)>> {
-        // WhatsApp uses phone numbers, not usernames
-        // Format: +1234567890 -> 1234567890@s.whatsapp.net
-        let clean_phone = phone.trim_start_matches('+').replace(['-', ' ', '(', ')'], "");
-        let jid = format!("{}@s.whatsapp.net", clean_phone);
-        
-        // Try to get chat info
+    /// Resolve a forward or new-chat target against the current chat list, see
+    /// `resolve_chat_target`
+    pub async fn resolve_username(&self, target: &str) -> Result<Option<(String, String, bool)>> {
         let chats = self.get_dialogs().await?;
-        if let Some(chat) = chats.iter().find(|c| c.id == jid) {
-            Ok(Some((chat.id.clone(), chat.name.clone(), chat.is_group)))
-        } else {
-            // Create JID anyway - it might be a valid contact
-            Ok(Some((jid.clone(), phone.to_string(), false)))
-        }
+        Ok(resolve_chat_target(&chats, target))
     }
     
     pub async fn search_messages(
@@ -730,14 +2187,29 @@ impl WhatsAppClient {
         limit: usize,
     ) -> Result<Vec<(String, String, String, String, Option<String>, std::collections::HashMap<String, u32>)>> {
         // WhatsApp CLI search doesn't support --chat filter, so we search all and filter manually
-        let output = Command::new(&self.cli_path)
-            .args(&[
-                "--store", &self.store_path.to_string_lossy(),
-                "messages", "search",
-                "--query", query,
-                "--limit", &limit.to_string(),
-            ])
-            .output()?;
+        let hits = self.search_all_messages(query, limit).await?;
+        Ok(hits
+            .into_iter()
+            .filter(|hit| hit.chat_jid == chat_jid)
+            .map(|hit| {
+                (
+                    hit.msg_id,
+                    hit.sender_id,
+                    hit.sender_name,
+                    hit.text,
+                    None, // reply_to_id
+                    std::collections::HashMap::new(), // reactions
+                )
+            })
+            .collect())
+    }
+
+    /// Search messages in every chat, in the order the CLI returns them
+    pub async fn search_all_messages(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let limit_str = limit.to_string();
+        let output = self
+            .run_cli(&["messages", "search", "--query", query, "--limit", &limit_str])
+            .await?;
         
         if !output.status.success() {
             return Ok(Vec::new());
@@ -749,50 +2221,48 @@ impl WhatsAppClient {
             return Ok(Vec::new());
         }
         
-        let mut messages = Vec::new();
+        let mut hits = Vec::new();
         
         if let Some(data) = response.data {
             if let Some(msgs_array) = data.as_array() {
                 for msg_val in msgs_array {
                     if let Ok(msg) = serde_json::from_value::<MessageItem>(msg_val.clone()) {
-                        // Filter by chat_jid since search doesn't support --chat
-                        if msg.chat_jid == chat_jid {
-                            // Use same logic as get_messages for sender name
-                            let sender_name = if msg.from_me {
-                                "You".to_string()
-                            } else if let Some(name) = msg.sender_name {
-                                name
-                            } else if let Some(chat_name) = &msg.chat_name {
-                                if !msg.chat_jid.ends_with("@g.us") {
-                                    chat_name.clone()
-                                } else {
-                                    let cache = self.contact_cache.lock().await;
-                                    cache.get(&msg.sender)
-                                        .cloned()
-                                        .unwrap_or_else(|| format_phone_number(&msg.sender))
-                                }
+                        // Use same logic as get_messages for sender name
+                        let sender_name = if msg.from_me {
+                            "You".to_string()
+                        } else if let Some(name) = msg.sender_name {
+                            name
+                        } else if let Some(chat_name) = &msg.chat_name {
+                            if !msg.chat_jid.ends_with("@g.us") {
+                                chat_name.clone()
                             } else {
                                 let cache = self.contact_cache.lock().await;
                                 cache.get(&msg.sender)
                                     .cloned()
                                     .unwrap_or_else(|| format_phone_number(&msg.sender))
-                            };
-                            
-                            messages.push((
-                                msg.id,
-                                msg.sender,
-                                sender_name,
-                                msg.content,
-                                None, // reply_to_id
-                                std::collections::HashMap::new(), // reactions
-                            ));
-                        }
+                            }
+                        } else {
+                            let cache = self.contact_cache.lock().await;
+                            cache.get(&msg.sender)
+                                .cloned()
+                                .unwrap_or_else(|| format_phone_number(&msg.sender))
+                        };
+                        
+                        hits.push(SearchHit {
+                            timestamp: parse_cli_timestamp(&msg.timestamp),
+                            chat_jid: msg.chat_jid,
+                            chat_name: msg.chat_name,
+                            msg_id: msg.id,
+                            sender_id: msg.sender,
+                            sender_name,
+                            text: msg.content,
+                        });
                     }
                 }
             }
         }
         
-        Ok(messages)
+        Ok(hits)
     }
     
     #[allow(dead_code)]
@@ -813,18 +2283,15 @@ impl WhatsAppClient {
         path: &std::path::Path,
     ) -> Result<String> {
         // Use whatsapp-cli media download command
-        let output = Command::new(&self.cli_path)
-            .arg("--store")
-            .arg(&self.store_path)
-            .arg("media")
-            .arg("download")
-            .arg("--message-id")
-            .arg(message_id)
-            .arg("--chat")
-            .arg(chat_jid)
-            .arg("--output")
-            .arg(path)
-            .output()?;
+        let path_str = path.to_string_lossy();
+        let output = self
+            .run_cli(&[
+                "media", "download",
+                "--message-id", message_id,
+                "--chat", chat_jid,
+                "--output", &path_str,
+            ])
+            .await?;
 
         if !output.status.success() {
             let error_msg = String::from_utf8_lossy(&output.stderr);
@@ -852,47 +2319,104 @@ impl WhatsAppClient {
         anyhow::bail!("Media download succeeded but no path in response")
     }
     
-    pub async fn create_group(&self, _title: &str, _user_jids: Vec<String>) -> Result<String> {
-        // TODO: Implement group creation via whatsapp-cli
-        anyhow::bail!("Group creation not yet implemented")
+    /// Create a group via `whatsapp-cli group create`, returning the new group's JID
+    pub async fn create_group(&self, title: &str, user_jids: Vec<String>) -> Result<String> {
+        let mut args = vec!["group", "create", "--name", title];
+        for jid in &user_jids {
+            args.extend(["--user", jid.as_str()]);
+        }
+        let output = self.run_cli(&args).await?;
+        action_result(&output, GROUPS_UNSUPPORTED)?;
+        self.invalidate_dialogs().await;
+        serde_json::from_slice::<WhatsAppResponse>(&output.stdout)
+            .ok()
+            .and_then(|r| r.data)
+            .and_then(|data| parse_created_group(&data))
+            .ok_or_else(|| anyhow::anyhow!("whatsapp-cli didn't say which group it created"))
+    }
+    
+    /// Add a phone number or JID to a group via `whatsapp-cli group participants add`
+    pub async fn add_member(&self, chat_jid: &str, phone: &str) -> Result<()> {
+        self.change_participant(chat_jid, phone, "add").await
     }
     
-    pub async fn add_member(&self, _chat_jid: &str, _phone: &str) -> Result<()> {
-        // TODO: Implement add member via whatsapp-cli
-        anyhow::bail!("Add member not yet implemented")
+    /// Remove a phone number or JID from a group via `whatsapp-cli group participants remove`
+    pub async fn remove_member(&self, chat_jid: &str, phone: &str) -> Result<()> {
+        self.change_participant(chat_jid, phone, "remove").await
+    }
+
+    async fn change_participant(&self, chat_jid: &str, phone: &str, action: &str) -> Result<()> {
+        if !chat_jid.ends_with("@g.us") {
+            anyhow::bail!("Not a group chat");
+        }
+        let Some(user_jid) = participant_jid(phone) else {
+            anyhow::bail!("'{}' is not a phone number or JID", phone);
+        };
+        let output = self
+            .run_cli(&["group", "participants", action, "--jid", chat_jid, "--user", &user_jid])
+            .await?;
+        action_result(&output, GROUPS_UNSUPPORTED)?;
+        let data = serde_json::from_slice::<WhatsAppResponse>(&output.stdout).ok().and_then(|r| r.data);
+        match data.as_ref().and_then(participant_change_error) {
+            Some(code) => anyhow::bail!("WhatsApp refused the change (error {})", code),
+            None => Ok(()),
+        }
     }
     
-    pub async fn remove_member(&self, _chat_jid: &str, _phone: &str) -> Result<()> {
-        // TODO: Implement remove member via whatsapp-cli
-        anyhow::bail!("Remove member not yet implemented")
+    /// Leave a group via `whatsapp-cli group leave`
+    pub async fn leave_group(&self, chat_jid: &str) -> Result<()> {
+        if !chat_jid.ends_with("@g.us") {
+            anyhow::bail!("Not a group chat");
+        }
+        let output = self.run_cli(&["group", "leave", "--jid", chat_jid]).await?;
+        action_result(&output, GROUPS_UNSUPPORTED)?;
+        self.invalidate_dialogs().await;
+        Ok(())
     }
     
-    pub async fn get_members(&self, _chat_jid: &str) -> Result<Vec<(String, String, String)>> {
-        // TODO: Implement get members via whatsapp-cli
-        // Returns (jid, name, role)
-        Ok(Vec::new())
+    /// A group's members as (jid, name, role) from `whatsapp-cli group info`, owners and
+    /// admins first. Names come from the contact cache, then whatever whatsapp-cli knows.
+    pub async fn get_members(&self, chat_jid: &str) -> Result<Vec<(String, String, String)>> {
+        if !chat_jid.ends_with("@g.us") {
+            anyhow::bail!("Not a group chat");
+        }
+        let output = self.run_cli(&["group", "info", "--jid", chat_jid]).await?;
+        action_result(&output, GROUPS_UNSUPPORTED)?;
+        let Some(data) = serde_json::from_slice::<WhatsAppResponse>(&output.stdout).ok().and_then(|r| r.data) else {
+            return Ok(Vec::new());
+        };
+        let names = self.contact_cache.lock().await.clone();
+        Ok(parse_group_members(&data, &names))
     }
     
     /// Get messages directly from SQLite database for groups
+    /// Read a chat's newest messages from the sync database, oldest first. With `after_msg_id`,
+    /// only messages newer than it are returned, or `None` if that message isn't in the database.
+    /// With `before_ts`, only the `limit` newest messages sent before that time are returned.
     async fn get_messages_from_db(
         &self,
         chat_jid: &str,
         limit: usize,
         _chat_name: Option<String>,
-    ) -> Result<Vec<(String, String, String, String, Option<String>, Option<String>, std::collections::HashMap<String, u32>, i64)>> {
+        after_msg_id: Option<&str>,
+        before_ts: Option<i64>,
+    ) -> Result<Option<Vec<RawMessage>>> {
         let db_path = self.store_path.join("messages.db");
         let contacts_db_path = self.store_path.join("whatsapp.db");
         
         if !db_path.exists() {
             crate::warn_log!("get_messages_from_db: Database not found at {:?}", db_path);
-            return Ok(Vec::new());
+            return Ok(after_msg_id.is_none().then(Vec::new));
         }
         
         // Open database connection (we need to do this in a blocking task)
         let db_path_clone = db_path.clone();
         let contacts_db_path_clone = contacts_db_path.clone();
         let chat_jid_clone = chat_jid.to_string();
-        let limit_clone = limit * 2; // Get more to account for filtering out reactions
+        // Get more to account for filtering out reactions. An incremental load reads every row
+        // after the known message.
+        let limit_clone: i64 = if after_msg_id.is_some() { -1 } else { (limit * 2) as i64 };
+        let after_msg_id = after_msg_id.map(|id| id.to_string());
         let contact_cache = self.contact_cache.clone();
         
         let (messages, contacts_map) = tokio::task::spawn_blocking(move || {
@@ -923,15 +2447,38 @@ impl WhatsAppClient {
                 }
             }
             
+            // For incremental loads, only rows at or after the known message's time. Stored
+            // timestamps don't all share one text format, so they are compared as times.
+            let anchor_timestamp = match after_msg_id {
+                Some(ref id) => {
+                    let ts = conn
+                        .query_row(
+                            "SELECT timestamp, julianday(timestamp) FROM messages WHERE id = ? AND chat_jid = ?",
+                            params![id, chat_jid_clone],
+                            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<f64>>(1)?)),
+                        )
+                        .optional()?;
+                    // A time SQLite can't read can't anchor the query either
+                    let readable = ts.filter(|(_, day)| day.is_some()).map(|(text, _)| text);
+                    match readable.and_then(|text| parse_db_timestamp(&text).map(|parsed| (text, parsed))) {
+                        Some(anchor) => Some(anchor),
+                        None => return Ok((None, contacts_map)),
+                    }
+                }
+                None => None,
+            };
+            
             let mut stmt = conn.prepare(
                 "SELECT id, sender, content, timestamp, is_from_me, media_type 
                  FROM messages 
-                 WHERE chat_jid = ? 
-                 ORDER BY timestamp DESC 
-                 LIMIT ?"
+                 WHERE chat_jid = ?1 AND (?2 IS NULL OR julianday(timestamp) IS NULL OR julianday(timestamp) >= julianday(?2)) 
+                   AND (?4 IS NULL OR julianday(timestamp) IS NULL OR julianday(timestamp) < julianday(?4, 'unixepoch')) 
+                 ORDER BY julianday(timestamp) DESC, timestamp DESC 
+                 LIMIT ?3"
             )?;
             
-            let rows = stmt.query_map(params![chat_jid_clone, limit_clone], |row| {
+            let anchor_text = anchor_timestamp.as_ref().map(|(text, _)| text.clone());
+            let rows = stmt.query_map(params![chat_jid_clone, anchor_text, limit_clone, before_ts], |row| {
                 Ok((
                     row.get::<_, String>(0)?, // id
                     row.get::<_, String>(1)?, // sender
@@ -945,6 +2492,9 @@ impl WhatsAppClient {
             let mut messages = Vec::new();
             for row in rows {
                 let (id, sender, content, timestamp_str, is_from_me, media_type) = row?;
+                if after_msg_id.as_deref() == Some(id.as_str()) {
+                    continue;
+                }
                 
                 // Get content string
                 let content_str = content.unwrap_or_default();
@@ -971,18 +2521,13 @@ impl WhatsAppClient {
                     continue;
                 }
                 
-                // Parse timestamp - try different formats
-                let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.timestamp())
-                    .or_else(|_| {
-                        chrono::NaiveDateTime::parse_from_str(&timestamp_str, "%Y-%m-%d %H:%M:%S%.f%z")
-                            .map(|dt| dt.and_utc().timestamp())
-                    })
-                    .or_else(|_| {
-                        chrono::NaiveDateTime::parse_from_str(&timestamp_str, "%Y-%m-%d %H:%M:%S%z")
-                            .map(|dt| dt.and_utc().timestamp())
-                    })
-                    .unwrap_or_else(|_| chrono::Utc::now().timestamp());
+                let timestamp = parse_db_timestamp(&timestamp_str).unwrap_or_else(|| chrono::Utc::now().timestamp());
+                if before_ts.is_some_and(|before| timestamp >= before) {
+                    continue;
+                }
+                if anchor_timestamp.as_ref().is_some_and(|(_, anchor)| timestamp < *anchor) {
+                    continue;
+                }
                 
                 // Get sender name from contacts map
                 let sender_name = if is_from_me {
@@ -1003,12 +2548,21 @@ impl WhatsAppClient {
                     std::collections::HashMap::new(), // reactions
                     timestamp,
                 ));
+                if before_ts.is_some() && messages.len() >= limit {
+                    break;
+                }
+                if anchor_timestamp.is_some() && messages.len() > limit {
+                    // More arrived than a pane loads; the caller reloads the newest instead
+                    return Ok((None, contacts_map));
+                }
             }
             
-            // Reverse to get oldest first
+            // Reverse to get oldest first, then order by the parsed times in case the stored
+            // formats sorted differently as text
             messages.reverse();
+            messages.sort_by_key(|m| m.7);
             
-            Ok::<(Vec<_>, std::collections::HashMap<String, String>), rusqlite::Error>((messages, contacts_map))
+            Ok::<(Option<Vec<_>>, std::collections::HashMap<String, String>), rusqlite::Error>((Some(messages), contacts_map))
         }).await??;
         
         // Update contact cache with names we found
@@ -1019,96 +2573,222 @@ impl WhatsAppClient {
             }
         }
         
-        crate::debug_log!("get_messages_from_db: Found {} messages for group {} (after filtering reactions)", messages.len(), chat_jid);
+        crate::debug_log!("get_messages_from_db: Found {:?} messages for group {} (after filtering reactions)", messages.as_ref().map(Vec::len), chat_jid);
         Ok(messages)
     }
+
+    /// Messages newer than `after_msg_id`, oldest first. `Ok(None)` means `after_msg_id` isn't
+    /// among the chat's known messages, and the caller should fall back to a full reload.
+    pub async fn get_messages_since(
+        &self,
+        chat_jid: &str,
+        after_msg_id: &str,
+        limit: usize,
+    ) -> Result<Option<Vec<RawMessage>>> {
+        if chat_jid.ends_with("@g.us") {
+            return self.get_messages_from_db(chat_jid, limit, None, Some(after_msg_id), None).await;
+        }
+
+        // The CLI can't list from a given message, so cut its recent tail at the known one,
+        // asking for a longer tail only while the known message isn't in it
+        let mut fetch = INCREMENTAL_CLI_FETCH.min(limit);
+        loop {
+            let messages = self.get_messages(chat_jid, fetch).await?;
+            if let Some(pos) = messages.iter().position(|m| m.0 == after_msg_id) {
+                return Ok(Some(messages[pos + 1..].to_vec()));
+            }
+            if messages.len() < fetch || fetch >= limit {
+                return Ok(None);
+            }
+            fetch = (fetch * 4).min(limit);
+        }
+    }
+    
+    /// Up to `limit` messages sent before `before_ts`, oldest first; empty once the chat has
+    /// nothing older. `Ok(None)` means there may be older messages, but whatsapp-cli would
+    /// have to list more than `MAX_CLI_HISTORY_FETCH` to reach them.
+    pub async fn get_messages_before(
+        &self,
+        chat_jid: &str,
+        before_ts: i64,
+        limit: usize,
+    ) -> Result<Option<Vec<RawMessage>>> {
+        if chat_jid.ends_with("@g.us") {
+            return Ok(Some(
+                self.get_messages_from_db(chat_jid, limit, None, None, Some(before_ts))
+                    .await?
+                    .unwrap_or_default(),
+            ));
+        }
+
+        // The CLI only lists the newest messages, so ask for more until enough older ones show up
+        let mut fetch = (limit * 2).min(MAX_CLI_HISTORY_FETCH);
+        loop {
+            let messages = self.get_messages(chat_jid, fetch).await?;
+            let older: Vec<RawMessage> = messages.iter().filter(|m| m.7 < before_ts).cloned().collect();
+            let capped = fetch >= MAX_CLI_HISTORY_FETCH && messages.len() >= fetch;
+            if older.is_empty() && capped {
+                return Ok(None);
+            }
+            if older.len() >= limit || messages.len() < fetch || capped {
+                return Ok(Some(older[older.len().saturating_sub(limit)..].to_vec()));
+            }
+            fetch = (fetch * 2).min(MAX_CLI_HISTORY_FETCH);
+        }
+    }
     
+    /// Forward a message via `whatsapp-cli forward`. A whatsapp-cli without `forward` gets
+    /// a copy of `text` sent instead, marked as forwarded; media can't be copied that way.
     pub async fn forward_message(
         &self,
-        _from_chat_jid: &str,
-        _message_id: &str,
-        _to_chat_jid: &str,
+        from_chat_jid: &str,
+        message_id: &str,
+        to_chat_jid: &str,
+        text: Option<&str>,
     ) -> Result<()> {
-        // TODO: Implement forward message via whatsapp-cli
-        anyhow::bail!("Forward message not yet implemented")
+        if self.forward_supported.load(Ordering::Relaxed) {
+            let output = self
+                .run_cli(&["forward", "--chat", from_chat_jid, "--message-id", message_id, "--to", to_chat_jid])
+                .await?;
+            if output.status.success() || !is_unsupported_error(&String::from_utf8_lossy(&output.stderr)) {
+                return action_result(&output, "This whatsapp-cli can't forward messages");
+            }
+            crate::info_log!("forward_message: whatsapp-cli has no forward command, sending copies");
+            self.forward_supported.store(false, Ordering::Relaxed);
+        }
+
+        let Some(text) = text.filter(|t| !t.trim().is_empty()) else {
+            anyhow::bail!("This whatsapp-cli can't forward media");
+        };
+        let copy = forwarded_copy(text);
+        let output = self.run_cli(&send_args(to_chat_jid, &copy, None)).await?;
+        action_result(&output, "This whatsapp-cli can't send messages")
     }
     
+    /// Progress of every forced group sync. Finished ones are reported once, then forgotten.
+    pub async fn group_sync_progress(&self) -> std::collections::HashMap<String, GroupSyncProgress> {
+        let mut syncs = self.group_syncs.lock().await;
+        let snapshot = syncs.clone();
+        syncs.retain(|_, p| !p.done);
+        snapshot
+    }
+
+    /// Record how far a group's forced sync has got
+    pub async fn set_group_sync(&self, chat_jid: &str, found: usize, done: bool) {
+        self.force_synced_groups.lock().await.insert(chat_jid.to_string());
+        let mut syncs = self.group_syncs.lock().await;
+        let started = syncs.get(chat_jid).map_or_else(std::time::Instant::now, |p| p.started);
+        syncs.insert(chat_jid.to_string(), GroupSyncProgress { started, found, done });
+    }
+
+    /// How many messages the sync database holds for one chat
+    async fn count_messages_in_db(&self, chat_jid: &str) -> Result<usize> {
+        let db_path = self.store_path.join("messages.db");
+        if !db_path.exists() {
+            return Ok(0);
+        }
+        let chat_jid = chat_jid.to_string();
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = Connection::open(&db_path)?;
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM messages WHERE chat_jid = ?",
+                params![chat_jid],
+                |row| row.get(0),
+            )?;
+            Ok(count as usize)
+        })
+        .await?
+    }
+
     /// Force sync for a specific group chat
     async fn force_sync_group(&self, chat_jid: &str) {
-        let cli_path = self.cli_path.clone();
-        let store_path = self.store_path.clone();
+        let client = self.clone();
         let chat_jid = chat_jid.to_string();
+        self.group_syncs.lock().await.remove(&chat_jid);
+        self.set_group_sync(&chat_jid, 0, false).await;
         
         tokio::spawn(async move {
             crate::info_log!("force_sync_group: Starting sync for group {}", chat_jid);
             // Run sync for longer to fetch messages from this group
             // whatsapp-cli sync runs continuously, so we'll kill it after enough time
-            let mut sync_process = match TokioCommand::new(&cli_path)
+            let mut sync_process = match TokioCommand::new(client.cli_path())
                 .arg("--store")
-                .arg(&store_path)
+                .arg(&client.store_path)
                 .arg("sync")
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
+                .kill_on_drop(true)
                 .spawn()
             {
                 Ok(p) => p,
                 Err(e) => {
                     crate::warn_log!("force_sync_group: Failed to start sync: {}", e);
+                    client.set_group_sync(&chat_jid, 0, true).await;
                     return;
                 }
             };
             
             // Wait longer for sync to fetch messages (groups may take more time)
-            // Check periodically if messages have been synced
+            // Check periodically if messages have been synced, until no more come in
+            let mut found = 0;
             for _ in 0..10 {
                 tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
                 
-                // Check if messages exist for this group
-                let check_output = match Command::new(&cli_path)
-                    .arg("--store")
-                    .arg(&store_path)
-                    .arg("messages")
-                    .arg("list")
-                    .arg("--limit")
-                    .arg("100")
-                    .output()
-                {
-                    Ok(o) => o,
-                    Err(_) => continue,
-                };
-                
-                if check_output.status.success() {
-                    if let Ok(response) = serde_json::from_slice::<WhatsAppResponse>(&check_output.stdout) {
-                        if let Some(data) = response.data {
-                            if let Some(msgs_array) = data.as_array() {
-                                let has_group_msgs = msgs_array.iter().any(|msg_val| {
-                                    if let Ok(msg) = serde_json::from_value::<MessageItem>(msg_val.clone()) {
-                                        msg.chat_jid == chat_jid
-                                    } else {
-                                        false
-                                    }
-                                });
-                                
-                                if has_group_msgs {
-                                    crate::info_log!("force_sync_group: Found messages for group {}, stopping sync", chat_jid);
-                                    let _ = sync_process.kill().await;
-                                    return;
-                                }
-                            }
-                        }
+                // Count this group's rows only; listing every chat's messages is slow on large stores
+                let now_found = match client.count_messages_in_db(&chat_jid).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        crate::warn_log!("force_sync_group: {}", e);
+                        continue;
                     }
+                };
+                if now_found > 0 && now_found == found {
+                    crate::info_log!("force_sync_group: Found {} messages for group {}, stopping sync", found, chat_jid);
+                    let _ = sync_process.kill().await;
+                    client.set_group_sync(&chat_jid, found, true).await;
+                    return;
                 }
+                found = now_found;
+                client.set_group_sync(&chat_jid, found, false).await;
             }
             
             // Kill the sync process after timeout
             let _ = sync_process.kill().await;
-            crate::info_log!("force_sync_group: Sync completed for group {} (timeout reached)", chat_jid);
+            client.set_group_sync(&chat_jid, found, true).await;
+            if found == 0 {
+                crate::warn_log!("force_sync_group: Still no messages for group {}; whatsapp-cli stores a group's history only as new messages arrive", chat_jid);
+            } else {
+                crate::info_log!("force_sync_group: Sync completed for group {} (timeout reached)", chat_jid);
+            }
         });
     }
     
+    /// Our own JID, or `None` before we're logged in
+    pub async fn my_jid(&self) -> Option<String> {
+        self.my_jid.lock().await.clone()
+    }
+
+    /// Remember our own JID, e.g. after logging in again
+    pub async fn set_my_jid(&self, jid: &str) {
+        *self.my_jid.lock().await = Some(jid.to_string());
+    }
+
+    /// Start the background sync loop, or restart its process if it's already running so a
+    /// new login is picked up
+    pub async fn restart_sync(&self) {
+        if self.sync_started.load(Ordering::Relaxed) {
+            self.sync_restart.store(true, Ordering::Relaxed);
+        } else {
+            self.start_sync_background().await;
+        }
+    }
+
     /// Start sync process in background
     async fn start_sync_background(&self) {
-        let cli_path = self.cli_path.clone();
+        if self.sync_started.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let client = self.clone();
         let store_path = self.store_path.clone();
         let pending_updates = self.pending_updates.clone();
         let last_synced_message_id = self.last_synced_message_id.clone();
@@ -1116,21 +2796,27 @@ impl WhatsAppClient {
         let contact_cache = self.contact_cache.clone();
         
         tokio::spawn(async move {
-            // Start whatsapp-cli sync in background
-            let mut sync_process = match TokioCommand::new(&cli_path)
+            // Start whatsapp-cli sync in background; it is killed when the runtime shuts down
+            let mut sync_process = match TokioCommand::new(client.cli_path())
                 .arg("--store")
                 .arg(&store_path)
                 .arg("sync")
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped())
+                .kill_on_drop(true)
                 .spawn()
             {
                 Ok(p) => p,
                 Err(e) => {
-                    eprintln!("Failed to start whatsapp-cli sync: {}", e);
+                    // Shown as offline in the status line; stderr would draw over the TUI
+                    crate::error_log!("Failed to start whatsapp-cli sync: {}", e);
+                    client.sync_started.store(false, Ordering::Relaxed);
+                    client.set_connection(ConnectionState::Disconnected).await;
                     return;
                 }
             };
+            client.sync_alive.store(true, Ordering::Relaxed);
+            client.watch_sync_output(&mut sync_process);
             
             // Wait a bit for initial sync to settle before we start polling
             tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
@@ -1143,22 +2829,34 @@ impl WhatsAppClient {
                 interval.tick().await;
                 crate::debug_log!("Sync: Polling for new messages");
                 
+                // A killed process counts as failed below, so it is started again
+                if client.sync_restart.swap(false, Ordering::Relaxed) {
+                    crate::info_log!("Sync: restarting sync process");
+                    let _ = sync_process.kill().await;
+                }
+
                 // Check if sync process is still running
                 if let Ok(Some(status)) = sync_process.try_wait() {
+                    client.sync_alive.store(false, Ordering::Relaxed);
                     if !status.success() {
                         crate::error_log!("WhatsApp sync process exited with error: {:?}", status);
+                        client.set_connection(ConnectionState::Disconnected).await;
                         // Try to restart
-                        match TokioCommand::new(&cli_path)
+                        match TokioCommand::new(client.cli_path())
                             .arg("--store")
                             .arg(&store_path)
                             .arg("sync")
                             .stdout(Stdio::piped())
                             .stderr(Stdio::piped())
+                            .kill_on_drop(true)
                             .spawn()
                         {
                             Ok(p) => {
                                 crate::info_log!("Sync: Restarted sync process");
+                                client.set_connection(ConnectionState::Connecting).await;
+                                client.sync_alive.store(true, Ordering::Relaxed);
                                 sync_process = p;
+                                client.watch_sync_output(&mut sync_process);
                                 // Wait a bit after restart
                                 tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
                             },
@@ -1175,14 +2873,9 @@ impl WhatsAppClient {
                 // Use a small delay to let sync process finish writing
                 tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
                 
-                let output = match Command::new(&cli_path)
-                    .arg("--store")
-                    .arg(&store_path)
-                    .arg("messages")
-                    .arg("list")
-                    .arg("--limit")
-                    .arg("20")
-                    .output()
+                let output = match client
+                    .run_cli(&["messages", "list", "--limit", "20"])
+                    .await
                 {
                     Ok(o) => o,
                     Err(e) => {
@@ -1235,7 +2928,7 @@ impl WhatsAppClient {
                                 }
                                 
                                 // Check if this is a new message
-                                if last_id.as_ref().map_or(true, |id| &msg.id != id) {
+                                if last_id.as_ref() != Some(&msg.id) {
                                     new_message_count += 1;
                                     crate::debug_log!("Sync: Found new message: id={}, chat={}, sender={}, text_len={}, from_me={}", 
                                         msg.id, msg.chat_jid, msg.sender, msg.content.len(), msg.from_me);
@@ -1264,7 +2957,7 @@ impl WhatsAppClient {
                                     // Update our JID if this is an outgoing message
                                     if msg.from_me {
                                         let mut my_jid_guard = my_jid.lock().await;
-                                        if my_jid_guard.is_none() || my_jid_guard.as_ref().unwrap() == "unknown@s.whatsapp.net" {
+                                        if my_jid_guard.is_none() || my_jid_guard.as_ref().unwrap() == UNKNOWN_JID {
                                             *my_jid_guard = Some(msg.sender.clone());
                                         }
                                     }
@@ -1309,6 +3002,194 @@ impl WhatsAppClient {
         });
     }
     
+    /// Read the sync process's stdout line by line so chat-state and connection events
+    /// reach the UI as they happen instead of waiting for the next database poll
+    fn watch_sync_output(&self, sync_process: &mut tokio::process::Child) {
+        let Some(stdout) = sync_process.stdout.take() else {
+            return;
+        };
+        let client = self.clone();
+        tokio::spawn(async move {
+            client.forward_sync_events(stdout).await;
+            crate::debug_log!("Sync: stdout closed");
+        });
+    }
+
+    /// Queue a `UserTyping` update for every composing event in `reader`, skipping our own,
+    /// and a `ConnectionChanged` update whenever the connection state changes
+    async fn forward_sync_events<R: AsyncRead + Unpin>(&self, reader: R) {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(state) = parse_connection_event(&line) {
+                self.set_connection(state).await;
+                continue;
+            }
+            let Some((chat_jid, sender_jid)) = parse_typing_event(&line) else {
+                continue;
+            };
+            if self.my_jid.lock().await.as_deref() == Some(sender_jid.as_str()) {
+                continue;
+            }
+
+            let user_name = self
+                .contact_cache
+                .lock()
+                .await
+                .get(&sender_jid)
+                .cloned()
+                .unwrap_or_else(|| format_phone_number(&sender_jid));
+            crate::debug_log!("Sync: {} is typing in {}", user_name, chat_jid);
+            self.pending_updates
+                .lock()
+                .await
+                .push(WhatsAppUpdate::UserTyping { chat_jid, user_name });
+        }
+    }
+
+    /// Login state, checked against whatsapp-cli now, plus the last connection state
+    /// reported by the sync process
+    pub async fn connection_status(&self) -> ConnectionStatus {
+        ConnectionStatus {
+            authenticated: self.get_me().await.is_ok(),
+            state: *self.connection.lock().await,
+        }
+    }
+
+    /// whatsapp-cli's version as reported at startup, once it has answered
+    pub fn cli_version(&self) -> Option<&str> {
+        self.cli_version.get().map(String::as_str)
+    }
+
+    /// Reads whatsapp-cli's version for /whoami, /diag and the log, and warns in the
+    /// status bar if it's a version this client wasn't tested with
+    async fn check_cli_version(&self) {
+        let version = self.detect_cli_version().await;
+        crate::info_log!("whatsapp-cli version: {}", version.as_deref().unwrap_or("unknown"));
+        if let Some(warning) = cli_version_warning(version.as_deref()) {
+            crate::warn_log!("{}", warning);
+            self.pending_updates.lock().await.push(WhatsAppUpdate::Notice(warning));
+        }
+        if let Some(version) = version {
+            let _ = self.cli_version.set(version);
+        }
+    }
+
+    /// First line of `whatsapp-cli --version`, if it answers within a few seconds
+    async fn detect_cli_version(&self) -> Option<String> {
+        let output = TokioCommand::new(self.cli_path()).arg("--version").kill_on_drop(true).output();
+        let output = tokio::time::timeout(std::time::Duration::from_secs(3), output).await.ok()?.ok()?;
+        let text = if output.stdout.is_empty() { output.stderr } else { output.stdout };
+        let line = String::from_utf8_lossy(&text).lines().next()?.trim().to_string();
+        (!line.is_empty()).then_some(line)
+    }
+
+    /// Row count of every table in the store's databases, as (file, table, rows)
+    pub async fn store_table_counts(&self) -> Vec<(String, String, i64)> {
+        let store_path = self.store_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut counts = Vec::new();
+            for file in ["messages.db", "whatsapp.db"] {
+                let path = store_path.join(file);
+                // Connection::open would create a missing database
+                let Some(conn) = path.exists().then(|| Connection::open(&path).ok()).flatten() else {
+                    continue;
+                };
+                let tables: Vec<String> = conn
+                    .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+                    .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+                    .unwrap_or_default();
+                for table in tables {
+                    let query = format!("SELECT COUNT(*) FROM \"{}\"", table.replace('"', "\"\""));
+                    if let Ok(rows) = conn.query_row(&query, [], |row| row.get(0)) {
+                        counts.push((file.to_string(), table, rows));
+                    }
+                }
+            }
+            counts
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Run `whatsapp-cli auth` in the background, collecting its output (the QR code to
+    /// scan) for the login overlay until it exits
+    pub fn reauthenticate(&self) -> Result<AuthSession> {
+        let mut child = TokioCommand::new(self.cli_path())
+            .arg("--store")
+            .arg(&self.store_path)
+            .arg("auth")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let progress = Arc::new(std::sync::Mutex::new(AuthProgress::default()));
+        let collect = |reader: Option<Box<dyn AsyncRead + Send + Unpin>>| {
+            let progress = progress.clone();
+            async move {
+                let Some(reader) = reader else { return };
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let mut progress = progress.lock().unwrap_or_else(|e| e.into_inner());
+                    match crate::qr::extract_payload(&line) {
+                        Some(code) => progress.qr = Some(code),
+                        None => progress.lines.push(strip_ansi(&line)),
+                    }
+                }
+            }
+        };
+        let stdout = collect(child.stdout.take().map(|r| Box::new(r) as Box<dyn AsyncRead + Send + Unpin>));
+        let stderr = collect(child.stderr.take().map(|r| Box::new(r) as Box<dyn AsyncRead + Send + Unpin>));
+
+        let task_progress = progress.clone();
+        let task = tokio::spawn(async move {
+            tokio::join!(stdout, stderr);
+            let finished = match child.wait().await {
+                Ok(status) if status.success() => Ok(()),
+                Ok(status) => Err(format!("whatsapp-cli auth exited with {}", status)),
+                Err(e) => Err(e.to_string()),
+            };
+            crate::info_log!("reauthenticate: {:?}", finished);
+            task_progress.lock().unwrap_or_else(|e| e.into_inner()).finished = Some(finished);
+        });
+        Ok(AuthSession { progress, task })
+    }
+
+    /// The command that logs this store in
+    pub fn auth_command(&self) -> String {
+        format!("{} --store {:?} auth", self.cli_path().display(), self.store_path)
+    }
+
+    /// Whether the background `sync` process is running
+    pub fn sync_alive(&self) -> bool {
+        self.sync_alive.load(Ordering::Relaxed)
+    }
+
+    #[cfg(test)]
+    pub fn set_sync_alive(&self, alive: bool) {
+        self.sync_alive.store(alive, Ordering::Relaxed);
+    }
+
+    /// Last connection state reported by the sync process
+    #[cfg(test)]
+    pub async fn connection_state(&self) -> ConnectionState {
+        *self.connection.lock().await
+    }
+
+    /// Record a connection state, queueing a `ConnectionChanged` update if it differs
+    async fn set_connection(&self, state: ConnectionState) {
+        let mut current = self.connection.lock().await;
+        if *current == state {
+            return;
+        }
+        crate::info_log!("Sync: connection {:?} -> {:?}", *current, state);
+        *current = state;
+        self.pending_updates
+            .lock()
+            .await
+            .push(WhatsAppUpdate::ConnectionChanged(state));
+    }
+
     fn parse_message_item(value: &serde_json::Value) -> Option<MessageItem> {
         serde_json::from_value(value.clone()).ok()
     }
@@ -1319,4 +3200,1008 @@ impl WhatsAppClient {
         let updates = std::mem::take(&mut *pending);
         Ok(updates)
     }
+
+    /// Queue an update as if it had come from the sync process
+    #[cfg(test)]
+    pub async fn push_update(&self, update: WhatsAppUpdate) {
+        self.pending_updates.lock().await.push(update);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A fresh temp dir for one test, removed on drop so a failing test doesn't leave it behind
+    pub(crate) struct TempDirGuard(PathBuf);
+
+    impl TempDirGuard {
+        pub(crate) fn new(name: &str) -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let n = NEXT.fetch_add(1, Ordering::Relaxed);
+            let dir = std::env::temp_dir().join(format!("whatsapp_client_rs_{}_{}_{}", name, std::process::id(), n));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        pub(crate) fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDirGuard {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A test client whose whatsapp-cli is `script`, written to `whatsapp-cli` in the returned
+    /// dir. run_cli puts `--store <path>` first, so the command itself starts at `$3`.
+    #[cfg(unix)]
+    pub(crate) fn fake_cli(script: &str) -> (TempDirGuard, WhatsAppClient) {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = TempDirGuard::new("cli");
+        let cli = dir.path().join("whatsapp-cli");
+        std::fs::write(&cli, script).unwrap();
+        std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let client = WhatsAppClient::new_for_test();
+        client.set_cli_path(cli);
+        (dir, client)
+    }
+
+    #[test]
+    fn test_revoke_args_and_too_old_errors() {
+        assert_eq!(
+            revoke_args("123@s.whatsapp.net", "ABC"),
+            ["revoke", "--to", "123@s.whatsapp.net", "--message-id", "ABC"]
+        );
+        assert!(is_too_old_error("revoke failed: message too old"));
+        assert!(is_too_old_error("Error: Revoke Time Limit exceeded"));
+        assert!(!is_too_old_error("not connected"));
+    }
+
+    #[test]
+    fn test_reply_args_and_inline_quote_fallback() {
+        assert_eq!(
+            reply_args("123@s.whatsapp.net", "ABC", "sure", None),
+            ["send", "--to", "123@s.whatsapp.net", "--message", "sure", "--reply-to", "ABC"]
+        );
+        assert_eq!(
+            send_args("123@s.whatsapp.net", "hi", Some("+46700000001")),
+            ["send", "--to", "123@s.whatsapp.net", "--message", "hi", "--from", "+46700000001"]
+        );
+        assert!(is_unknown_flag("Error: unknown flag: --from", "from"));
+        assert!(is_unknown_flag("flag provided but not defined: -from", "from"));
+        assert!(!is_unknown_flag("Error: unknown flag: --reply-to", "from"));
+
+        assert!(is_unknown_flag_error("Error: unknown flag: --reply-to"));
+        assert!(is_unknown_flag_error("flag provided but not defined: -reply-to"));
+        assert!(!is_unknown_flag_error("not connected"));
+
+        let confirmed = serde_json::json!({"id": "NEW", "quoted_id": "ABC"});
+        assert!(reply_quote_confirmed(Some(&confirmed), "ABC"));
+        assert!(!reply_quote_confirmed(Some(&serde_json::json!({"id": "NEW"})), "ABC"));
+        assert!(!reply_quote_confirmed(None, "ABC"));
+
+        let quoted = QuotedMessage {
+            sender_name: "Alice".to_string(),
+            text: "lunch at noon?\nor later".to_string(),
+        };
+        assert_eq!(quote_inline(&quoted, "sure"), "> Alice: lunch at noon?\nsure");
+    }
+
+    #[tokio::test]
+    async fn test_cli_concurrency_never_exceeds_limit() {
+        let client = WhatsAppClient::from_parts(
+            PathBuf::from("whatsapp-cli"),
+            std::env::temp_dir(),
+            2,
+        );
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for _ in 0..16 {
+            let client = client.clone();
+            let running = running.clone();
+            let peak = peak.clone();
+            handles.push(tokio::spawn(async move {
+                let _slot = client.acquire_cli_slot().await.unwrap();
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_cli_limit_is_at_least_one() {
+        let client = WhatsAppClient::from_parts(PathBuf::from("whatsapp-cli"), std::env::temp_dir(), 0);
+        assert_eq!(client.cli_semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_messages_before_returns_the_newest_older_rows() {
+        let group = "team@g.us";
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "before",
+            &[
+                ("m1", group, "a@s.whatsapp.net", "one", "2024-05-01T10:00:00Z", false),
+                ("m2", group, "b@s.whatsapp.net", "two", "2024-05-01T10:01:00Z", false),
+                ("x1", "other@g.us", "a@s.whatsapp.net", "elsewhere", "2024-05-01T10:01:30Z", false),
+                ("m3", group, "a@s.whatsapp.net", "three", "2024-05-01T10:02:00Z", false),
+            ],
+        );
+        let m3_ts = chrono::DateTime::parse_from_rfc3339("2024-05-01T10:02:00Z").unwrap().timestamp();
+        let m1_ts = m3_ts - 120;
+
+        let ids = |rows: Vec<RawMessage>| rows.into_iter().map(|m| m.0).collect::<Vec<_>>();
+        let before = |ts, limit| client.get_messages_before(group, ts, limit);
+        assert_eq!(ids(before(m3_ts, 1).await.unwrap().unwrap()), vec!["m2"]);
+        assert_eq!(ids(before(m3_ts, 50).await.unwrap().unwrap()), vec!["m1", "m2"]);
+        assert!(before(m1_ts, 50).await.unwrap().unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_get_messages_before_reports_history_past_the_fetch_cap() {
+        // Lists as many messages as asked for, all of them newer than the one asked about
+        let (_dir, client) = fake_cli(
+            "#!/bin/sh\n\
+             if [ \"$3\" != messages ]; then echo '{\"success\":true,\"data\":[]}'; exit 0; fi\n\
+             seq \"$8\" | awk 'BEGIN { printf \"{\\\"success\\\":true,\\\"data\\\":[\" } \
+             { if (NR > 1) printf \",\"; printf \"{\\\"id\\\":\\\"m%d\\\",\\\"chat_jid\\\":\\\"111@s.whatsapp.net\\\",\\\"sender\\\":\\\"111\\\",\\\"content\\\":\\\"hi\\\",\\\"timestamp\\\":\\\"2024-05-01T10:00:00Z\\\",\\\"is_from_me\\\":false}\", $1 } \
+             END { print \"]}\" }'\n",
+        );
+        let before_ts = chrono::DateTime::parse_from_rfc3339("2024-05-01T09:00:00Z").unwrap().timestamp();
+        let older = client.get_messages_before("111@s.whatsapp.net", before_ts, 50).await.unwrap();
+        assert!(older.is_none(), "the start of the chat is not known past the cap");
+    }
+
+    #[tokio::test]
+    async fn test_get_messages_since_returns_only_newer_rows() {
+        let group = "team@g.us";
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "since",
+            &[
+                ("m1", group, "a@s.whatsapp.net", "one", "2024-05-01T10:00:00Z", false),
+                ("m2", group, "b@s.whatsapp.net", "two", "2024-05-01T10:01:00Z", false),
+                ("m3", group, "a@s.whatsapp.net", "three", "2024-05-01T10:01:00Z", false),
+                ("x1", "other@g.us", "a@s.whatsapp.net", "elsewhere", "2024-05-01T10:05:00Z", false),
+                ("m4", group, "b@s.whatsapp.net", "four", "2024-05-01T10:02:00Z", false),
+            ],
+        );
+
+        let newer = client.get_messages_since(group, "m2", 50).await.unwrap().unwrap();
+        let ids: Vec<&str> = newer.iter().map(|m| m.0.as_str()).collect();
+        // m3 shares m2's timestamp but is still picked up
+        assert_eq!(ids, vec!["m3", "m4"]);
+
+        assert!(client.get_messages_since(group, "m4", 50).await.unwrap().unwrap().is_empty());
+        assert!(client.get_messages_since(group, "unknown", 50).await.unwrap().is_none());
+        // More new messages than a pane loads: reload instead
+        assert!(client.get_messages_since(group, "m1", 2).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_get_messages_since_compares_stored_times_not_text() {
+        let group = "team@g.us";
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "since_formats",
+            &[
+                ("m1", group, "a@s.whatsapp.net", "one", "2024-05-01T10:00:00Z", false),
+                // Sorts before m1 as text, but is two minutes later
+                ("m2", group, "b@s.whatsapp.net", "two", "2024-05-01 10:02:00+00:00", false),
+                ("m3", group, "a@s.whatsapp.net", "three", "2024-05-01T10:01:00Z", false),
+                ("m0", group, "a@s.whatsapp.net", "zero", "2024-05-01 09:59:00.5+00:00", false),
+            ],
+        );
+
+        let newer = client.get_messages_since(group, "m1", 50).await.unwrap().unwrap();
+        let ids: Vec<&str> = newer.iter().map(|m| m.0.as_str()).collect();
+        assert_eq!(ids, vec!["m3", "m2"]);
+    }
+
+    #[tokio::test]
+    async fn test_group_without_history_syncs_once_without_scanning_other_chats() {
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "group_history",
+            &[
+                ("x1", "other@g.us", "a@s.whatsapp.net", "elsewhere", "2024-05-01T10:05:00Z", false),
+                ("x2", "other@g.us", "b@s.whatsapp.net", "again", "2024-05-01T10:06:00Z", false),
+            ],
+        );
+        assert_eq!(client.count_messages_in_db("other@g.us").await.unwrap(), 2);
+        assert_eq!(client.count_messages_in_db("team@g.us").await.unwrap(), 0);
+
+        assert!(client.get_messages("team@g.us", 50).await.unwrap().is_empty());
+        assert!(client.group_sync_progress().await.contains_key("team@g.us"));
+        // The sync can't start with this CLI, so it finishes at once with nothing found
+        for _ in 0..50 {
+            if client.group_sync_progress().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        }
+        assert!(client.group_sync_progress().await.is_empty());
+
+        // Reloading the empty group later doesn't sync it again
+        assert!(client.get_messages("team@g.us", 50).await.unwrap().is_empty());
+        assert!(client.group_sync_progress().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_cli_is_reported_with_a_fix() {
+        let dir = TempDirGuard::new("check_cli");
+        let missing = dir.path().join("whatsapp-cli");
+        let err = check_cli(&missing).unwrap_err().to_string();
+        assert!(err.starts_with("whatsapp-cli not found at") && err.contains("whatsapp_cli_path"), "{}", err);
+        assert!(check_cli(dir.path()).unwrap_err().to_string().contains("is a directory"));
+        std::fs::write(&missing, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert!(check_cli(&missing).unwrap_err().to_string().contains("chmod +x"));
+            std::fs::set_permissions(&missing, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        assert!(check_cli(&missing).is_ok());
+
+        // A call after the binary went away says so and how to recover
+        let client = WhatsAppClient::new_for_test();
+        let err = client.run_cli(&["chats", "list"]).await.unwrap_err().to_string();
+        assert!(err.contains("whatsapp-cli not found at /nonexistent/whatsapp-cli"), "{}", err);
+        assert!(err.ends_with("then /reload config"), "{}", err);
+
+        // Sync that can't start (/reconnect) reports the connection as lost
+        client.restart_sync().await;
+        let mut updates = Vec::new();
+        for _ in 0..100 {
+            updates.extend(client.poll_updates().await.unwrap());
+            if !updates.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(matches!(updates.as_slice(), [WhatsAppUpdate::ConnectionChanged(ConnectionState::Disconnected)]));
+        assert!(!client.sync_started.load(Ordering::Relaxed));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_startup_only_waits_for_the_login_check() {
+        // Every call but the login check is held until `release` exists (or the dir is gone),
+        // and each call is logged once it's done
+        let (dir, _) = fake_cli(
+            "#!/bin/sh\n\
+             dir=$(dirname \"$0\")\n\
+             case \"$*\" in *'chats list --limit 1') ;; *) while [ -d \"$dir\" ] && [ ! -e \"$dir/release\" ]; do sleep 0.01; done;; esac\n\
+             echo \"$*\" >> \"$dir/calls\"\n\
+             echo '{\"success\":true,\"data\":[{\"jid\":\"a@s.whatsapp.net\",\"name\":\"Alice\"}]}'\n",
+        );
+        let calls = dir.path().join("calls");
+        let config = Config {
+            version: crate::config::CONFIG_VERSION,
+            whatsapp_cli_path: dir.path().join("whatsapp-cli"),
+            store_path: Some(dir.path().join("store")),
+            settings: crate::config::Settings::default(),
+            config_dir: dir.path().to_path_buf(),
+            keymap: crate::keymap::Keymap::default(),
+            keymap_note: None,
+            theme: crate::theme::Theme::default(),
+            theme_note: None,
+            settings_note: None,
+        };
+
+        // new can only return while the other calls are held if it doesn't wait for them
+        let client = WhatsAppClient::new(&config).await.unwrap();
+        let done = std::fs::read_to_string(&calls).unwrap();
+        let done: Vec<&str> = done.lines().collect();
+        assert_eq!(done.len(), 1, "{:?}", done);
+        assert!(done[0].ends_with("chats list --limit 1"), "{:?}", done);
+        assert!(client.my_jid().await.is_some());
+        assert!(!client.contact_cache.lock().await.contains_key("a@s.whatsapp.net"));
+
+        // Once released, the chat list fills the contact cache in the background
+        std::fs::write(dir.path().join("release"), "").unwrap();
+        for _ in 0..500 {
+            if client.contact_cache.lock().await.contains_key("a@s.whatsapp.net") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(client.contact_cache.lock().await.contains_key("a@s.whatsapp.net"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mark_read_stops_only_when_the_command_is_unknown() {
+        let script = |stderr: &str| format!("#!/bin/sh\necho '{}' >&2\nexit 1\n", stderr);
+        let (dir, client) = fake_cli(&script("Error: not connected"));
+        let cli = dir.path().join("whatsapp-cli");
+
+        // A failed attempt is reported and the next read tries again
+        assert!(client.mark_read("111@s.whatsapp.net", "M1").await.is_err());
+        assert!(client.read_receipts_supported.load(Ordering::Relaxed));
+
+        std::fs::write(&cli, script("Error: unknown command \"mark-read\" for \"whatsapp-cli\"")).unwrap();
+        assert!(client.mark_read("111@s.whatsapp.net", "M1").await.is_ok());
+        assert!(!client.read_receipts_supported.load(Ordering::Relaxed));
+        let before = client.cli_spawn_count();
+        client.mark_read("111@s.whatsapp.net", "M2").await.unwrap();
+        assert_eq!(client.cli_spawn_count(), before);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_delete_stops_once_revoke_is_unknown() {
+        let (_dir, client) = fake_cli("#!/bin/sh\necho 'Error: unknown command \"revoke\" for \"whatsapp-cli\"' >&2\nexit 1\n");
+        let err = client.delete_message("111@s.whatsapp.net", "M1").await.unwrap_err();
+        assert_eq!(err.to_string(), "This whatsapp-cli can't delete messages");
+        let before = client.cli_spawn_count();
+        let err = client.delete_message("111@s.whatsapp.net", "M2").await.unwrap_err();
+        assert_eq!(err.to_string(), "This whatsapp-cli can't delete messages");
+        assert_eq!(client.cli_spawn_count(), before);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reactions_stop_once_react_is_unknown() {
+        let (_dir, client) = fake_cli("#!/bin/sh\necho 'Error: unknown command \"react\" for \"whatsapp-cli\"' >&2\nexit 1\n");
+        let err = client.send_reaction("111@s.whatsapp.net", "M1", "👍").await.unwrap_err();
+        assert_eq!(err.to_string(), "This whatsapp-cli can't send reactions");
+        let before = client.cli_spawn_count();
+        let err = client.send_reaction("111@s.whatsapp.net", "M2", "👍").await.unwrap_err();
+        assert_eq!(err.to_string(), "This whatsapp-cli can't send reactions");
+        assert_eq!(client.cli_spawn_count(), before);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_chat_list_is_reused_briefly_and_refetched_when_invalidated() {
+        let (_dir, client) =
+            fake_cli("#!/bin/sh\necho '{\"success\":true,\"data\":[{\"jid\":\"111@s.whatsapp.net\",\"name\":\"Alice\"}]}'\n");
+        let before = client.cli_spawn_count();
+        // A chat list refresh followed by a name lookup and a /new target: one process
+        assert_eq!(client.get_dialogs().await.unwrap()[0].name, "Alice");
+        assert_eq!(client.resolve_username("Alice").await.unwrap().map(|t| t.0).as_deref(), Some("111@s.whatsapp.net"));
+        assert_eq!(client.get_dialogs().await.unwrap().len(), 1);
+        assert_eq!(client.cli_spawn_count() - before, 1);
+
+        client.invalidate_dialogs().await;
+        client.get_dialogs().await.unwrap();
+        assert_eq!(client.cli_spawn_count() - before, 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_reauthenticate_collects_auth_output() {
+        let (_dir, client) = fake_cli("#!/bin/sh\nprintf '\\033[7m  \\033[0m▄▄\\n'\necho 'Waiting for scan' >&2\necho 'QR code: 2@old,k1,k2,s'\necho 'QR code: 2@new,k1,k2,s'\nexit 0\n");
+        let session = client.reauthenticate().unwrap();
+        let mut progress = session.progress();
+        for _ in 0..100 {
+            if progress.finished.is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            progress = session.progress();
+        }
+        assert_eq!(progress.finished, Some(Ok(())));
+        assert!(progress.lines.contains(&"  ▄▄".to_string()), "{:?}", progress.lines);
+        assert!(progress.lines.contains(&"Waiting for scan".to_string()));
+        // Codes become the one to draw, the newest winning, instead of output lines
+        assert_eq!(progress.qr.as_deref(), Some("2@new,k1,k2,s"));
+        assert!(!progress.lines.iter().any(|l| l.contains("2@")));
+    }
+
+    #[test]
+    fn test_phone_numbers_are_grouped_by_country() {
+        assert_eq!(format_phone_number("46760789806@s.whatsapp.net"), "+46 76 078 98 06");
+        assert_eq!(format_phone_number("46760789806:12@s.whatsapp.net"), "+46 76 078 98 06");
+        assert_eq!(format_phone_number("14155550132@c.us"), "+1 415 555 0132");
+        assert_eq!(group_phone_number("447911123456"), "+44 7911 123456");
+        assert_eq!(group_phone_number("+33612345678"), "+33 6 12 34 56 78");
+        assert_eq!(group_phone_number("4915123456789"), "+49 151 23456789");
+        assert_eq!(group_phone_number("358401234567"), "+358 40 123 4567");
+        // Extra digits become one more group rather than being dropped
+        assert_eq!(group_phone_number("4670123456789"), "+46 70 123 45 67 89");
+
+        // Unknown countries, odd lengths and linked-device IDs keep the raw form
+        assert_eq!(group_phone_number("9995551234"), "+9995551234");
+        assert_eq!(group_phone_number("46123"), "+46123");
+        assert_eq!(format_phone_number("123456789012345@lid"), "+123456789012345");
+        assert_eq!(format_phone_number("status"), "status");
+    }
+
+    #[test]
+    fn test_cli_version_check_and_message_field_aliases() {
+        assert_eq!(parse_cli_version("whatsapp-cli v1.4.2 (go1.22)"), Some((1, 4, 2)));
+        assert_eq!(parse_cli_version("2.0"), Some((2, 0, 0)));
+        assert_eq!(parse_cli_version("dev build"), None);
+        assert_eq!(cli_version_warning(Some("whatsapp-cli 1.0.3")), None);
+        assert_eq!(cli_version_warning(None), None);
+        assert_eq!(cli_version_warning(Some("devel")), None);
+        let warning = cli_version_warning(Some("whatsapp-cli v2.1.0")).unwrap();
+        assert!(warning.starts_with("whatsapp-cli 2.1.0 is untested (expected 1.x)"));
+
+        let camel = serde_json::json!({
+            "message_id": "M1", "chatJid": "a@g.us", "chatName": "Team", "sender": "b@s.whatsapp.net",
+            "senderName": "Bob", "content": "hi", "timestamp": "2024-05-01T10:00:00Z",
+            "isFromMe": true, "mediaType": "image"
+        });
+        let msg = WhatsAppClient::parse_message_item(&camel).unwrap();
+        assert_eq!((msg.id.as_str(), msg.chat_jid.as_str()), ("M1", "a@g.us"));
+        assert_eq!(msg.sender_name.as_deref(), Some("Bob"));
+        assert!(msg.from_me);
+        assert_eq!(msg.media_type.as_deref(), Some("image"));
+    }
+
+    #[test]
+    fn test_parse_location() {
+        let cases: &[(&str, Option<(f64, f64)>)] = &[
+            (r#"{"latitude": 59.3293, "longitude": 18.0686, "name": "Stockholm"}"#, Some((59.3293, 18.0686))),
+            (r#"{"location": {"lat": "-33.8688", "lng": "151.2093"}}"#, Some((-33.8688, 151.2093))),
+            (r#"{"degreesLatitude": 40.7128, "degreesLongitude": -74.006}"#, Some((40.7128, -74.006))),
+            ("geo:51.5074,-0.1278", Some((51.5074, -0.1278))),
+            ("https://maps.google.com/?q=48.8584,2.2945", Some((48.8584, 2.2945))),
+            ("https://www.google.com/maps/@35.6762,139.6503,15z", Some((35.6762, 139.6503))),
+            ("https://www.openstreetmap.org/?mlat=52.52&mlon=13.405#map=16/52.52/13.405", Some((52.52, 13.405))),
+            ("Location: 59.3293, 18.0686", Some((59.3293, 18.0686))),
+            // Out of range, whole numbers and no coordinates at all
+            (r#"{"latitude": 91.0, "longitude": 0.0}"#, None),
+            ("159.3293, 18.0686", None),
+            ("see you at 5, 6", None),
+            ("", None),
+        ];
+        for (content, expected) in cases {
+            assert_eq!(parse_location(content), *expected, "{}", content);
+        }
+        assert_eq!(message_location(Some("location"), "geo:1.5,2.5"), Some((1.5, 2.5)));
+        assert_eq!(message_location(None, "geo:1.5,2.5"), None);
+    }
+
+    #[test]
+    fn test_parse_contacts_from_vcards() {
+        let anna = "BEGIN:VCARD\r\nVERSION:3.0\r\nN:Berg;Anna;;;\r\nFN:Anna Berg\r\nitem1.TEL;type=CELL;waid=46701234567:+46 70 123 45 67\r\nitem1.X-ABLabel:Mobile\r\nEND:VCARD";
+        let contacts = parse_contacts(anna);
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].name, "Anna Berg");
+        assert_eq!(contacts[0].phone.as_deref(), Some("+46 70 123 45 67"));
+        assert_eq!(contacts[0].vcard, anna);
+        assert_eq!(contacts[0].summary(), "Anna Berg (+46 70 123 45 67)");
+
+        // JSON with a display name, a folded N and no FN, a vCard 4 tel: URI
+        let bob = "BEGIN:VCARD\nVERSION:4.0\nN:Smith;Bo\n b;;;\nTEL;VALUE=uri:tel:+1-415-555-0132\nEND:VCARD";
+        let json = serde_json::json!({"contacts": [
+            {"displayName": "Anna", "vcard": anna},
+            {"vcard": bob},
+            {"displayName": "No card"},
+        ]});
+        let contacts = parse_contacts(&json.to_string());
+        let summaries: Vec<String> = contacts.iter().map(SharedContact::summary).collect();
+        assert_eq!(summaries, vec!["Anna (+46 70 123 45 67)", "Bob Smith (+1-415-555-0132)"]);
+
+        // Several cards pasted one after the other, one with only an escaped name
+        let multi = format!("{}\nBEGIN:VCARD\nFN:Dr. Who\\, Jr.\nEND:VCARD\n", bob);
+        let contacts = parse_contacts(&multi);
+        assert_eq!(contacts.len(), 2);
+        assert_eq!(contacts[1].summary(), "Dr. Who, Jr.");
+        assert_eq!(contacts[1].phone, None);
+
+        assert!(parse_contacts("Anna Berg").is_empty());
+        assert!(message_contacts(None, anna).is_empty());
+        assert_eq!(message_contacts(Some("contact"), anna).len(), 1);
+    }
+
+    #[test]
+    fn test_media_type_for_path() {
+        let cases = [
+            ("a.jpg", "photo"),
+            ("a.JPEG", "photo"),
+            ("shot.png", "photo"),
+            ("anim.gif", "gif"),
+            ("sticker.webp", "sticker"),
+            ("clip.mp4", "video"),
+            ("clip.MOV", "video"),
+            ("song.mp3", "audio"),
+            ("note.opus", "voice"),
+            ("report.pdf", "document"),
+            ("archive.tar.gz", "document"),
+            ("README", "document"),
+        ];
+        for (file, expected) in cases {
+            assert_eq!(media_type_for_path(Path::new(file)), expected, "{}", file);
+        }
+    }
+
+    #[test]
+    fn test_resolve_chat_target() {
+        let chat = |id: &str, name: &str, is_group: bool| ChatInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            username: None,
+            unread: 0,
+            is_channel: false,
+            is_group,
+            last_message_ts: 0,
+        };
+        let chats = vec![
+            chat("4670123456@s.whatsapp.net", "Alice", false),
+            chat("team@g.us", "Team", true),
+            chat("1@s.whatsapp.net", "Bob", false),
+            chat("2@s.whatsapp.net", "bob", false),
+        ];
+        let resolve = |target: &str| resolve_chat_target(&chats, target);
+
+        assert_eq!(resolve("+46 70-123456"), Some(("4670123456@s.whatsapp.net".to_string(), "Alice".to_string(), false)));
+        assert_eq!(resolve("@alice").map(|t| t.0), Some("4670123456@s.whatsapp.net".to_string()));
+        assert_eq!(resolve("team@g.us").map(|t| t.2), Some(true));
+        // Numbers and JIDs outside the chat list start a new chat
+        assert_eq!(resolve("@4679999999").map(|t| t.0), Some("4679999999@s.whatsapp.net".to_string()));
+        assert_eq!(resolve("new@g.us").map(|t| t.2), Some(true));
+        // Unknown and ambiguous names don't resolve
+        assert_eq!(resolve("@carol"), None);
+        assert_eq!(resolve("bob"), None);
+        assert_eq!(resolve("123"), None);
+        assert_eq!(forwarded_copy("hi"), "↪ Forwarded:\nhi");
+    }
+
+    #[test]
+    fn test_dedupe_and_normalize_chats() {
+        let chat = |id: &str, name: &str, unread: u32| ChatInfo {
+            id: id.to_string(),
+            name: name.to_string(),
+            username: None,
+            unread,
+            is_channel: false,
+            is_group: id.ends_with("@g.us"),
+            last_message_ts: unread as i64 * 100,
+        };
+        // (case, input, expected (id, name, unread) after dedupe)
+        type Case = (
+            &'static str,
+            Vec<ChatInfo>,
+            Vec<(&'static str, &'static str, u32)>,
+        );
+        let cases: Vec<Case> = vec![
+            (
+                "@lid folds into the phone chat with its name, either order",
+                vec![
+                    chat("99@lid", "Alice", 3),
+                    chat("4670@s.whatsapp.net", "Alice", 1),
+                ],
+                vec![("4670@s.whatsapp.net", "Alice", 3)],
+            ),
+            (
+                "@lid folds into the only phone chat with a similar name",
+                vec![
+                    chat("4670@s.whatsapp.net", "Patrik Wellner", 0),
+                    chat("99@lid", "Patrik", 2),
+                ],
+                vec![("4670@s.whatsapp.net", "Patrik Wellner", 2)],
+            ),
+            (
+                "an ambiguous similar name stays its own chat",
+                vec![
+                    chat("1@s.whatsapp.net", "Ann Lee", 0),
+                    chat("2@s.whatsapp.net", "Ann Smith", 0),
+                    chat("99@lid", "Ann", 1),
+                ],
+                vec![
+                    ("1@s.whatsapp.net", "Ann Lee", 0),
+                    ("2@s.whatsapp.net", "Ann Smith", 0),
+                    ("99@lid", "Ann", 1),
+                ],
+            ),
+            (
+                "@lid chats with one name are one chat",
+                vec![chat("98@lid", "Bob", 1), chat("99@lid", "Bob", 4)],
+                vec![("98@lid", "Bob", 4)],
+            ),
+            (
+                "the same JID twice keeps the higher unread",
+                vec![
+                    chat("1@s.whatsapp.net", "Carol", 5),
+                    chat("1@s.whatsapp.net", "Carol", 2),
+                ],
+                vec![("1@s.whatsapp.net", "Carol", 5)],
+            ),
+            (
+                "a group wins a name collision, listed first or last",
+                vec![
+                    chat("1@s.whatsapp.net", "Family", 2),
+                    chat("f@g.us", "Family", 0),
+                    chat("98@lid", "Family", 7),
+                ],
+                vec![("f@g.us", "Family", 7)],
+            ),
+            (
+                "different numbers and different groups with one name all stay",
+                vec![
+                    chat("1@s.whatsapp.net", "Mom", 0),
+                    chat("2@s.whatsapp.net", "Mom", 0),
+                    chat("a@g.us", "Team", 0),
+                    chat("b@g.us", "Team", 1),
+                ],
+                vec![
+                    ("1@s.whatsapp.net", "Mom", 0),
+                    ("2@s.whatsapp.net", "Mom", 0),
+                    ("a@g.us", "Team", 0),
+                    ("b@g.us", "Team", 1),
+                ],
+            ),
+        ];
+        for (case, chats, expected) in cases {
+            let deduped = dedupe_and_normalize_chats(chats);
+            let got: Vec<(&str, &str, u32)> = deduped
+                .iter()
+                .map(|c| (c.id.as_str(), c.name.as_str(), c.unread))
+                .collect();
+            assert_eq!(got, expected, "{}", case);
+            // Merged entries keep the newest activity too
+            for c in &deduped {
+                assert_eq!(c.last_message_ts, c.unread as i64 * 100, "{}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn test_channels_are_read_only() {
+        assert!(is_channel_jid("120363025@newsletter"));
+        assert!(is_channel_jid("status@broadcast"));
+        assert!(!is_channel_jid("1234@broadcast"));
+        assert!(!is_channel_jid("team@g.us"));
+
+        let item = |json: serde_json::Value| serde_json::from_value::<ChatListItem>(json).unwrap().read_only;
+        assert_eq!(item(serde_json::json!({"jid": "1@newsletter", "name": "News"})), None);
+        assert_eq!(item(serde_json::json!({"jid": "1@newsletter", "name": "News", "readOnly": false})), Some(false));
+        assert_eq!(item(serde_json::json!({"jid": "a@g.us", "name": "Announcements", "read_only": true})), Some(true));
+    }
+
+    #[test]
+    fn test_mention_tokens_in_the_input() {
+        assert_eq!(mention_query("hi @an"), Some((3, "an")));
+        assert_eq!(mention_query("@"), Some((0, "")));
+        assert_eq!(mention_query("ok\u{a0}@björn"), Some((4, "björn")));
+        assert_eq!(mention_query("hi @anna "), None);
+        assert_eq!(mention_query("mail me@example.com"), None);
+        assert_eq!(mention_query("no mention"), None);
+
+        assert_eq!(
+            mention_jids("@46711111111, meet @46722222222 and @46711111111 again"),
+            vec!["46711111111@s.whatsapp.net", "46722222222@s.whatsapp.net"]
+        );
+        // Too short, part of a word, or not starting a word
+        assert!(mention_jids("@123 @46711111111abc a@46711111111 room @").is_empty());
+        assert_eq!(
+            with_mentions(send_args("a@g.us", "hi", None), &mention_jids("hi @46711111111")),
+            ["send", "--to", "a@g.us", "--message", "hi", "--mention", "46711111111@s.whatsapp.net"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mentions_are_sent_until_the_flag_is_rejected() {
+        let (dir, client) = fake_cli(
+            "#!/bin/sh\necho \"$*\" >> \"$(dirname \"$0\")/calls\"\n\
+             case \"$*\" in *--mention*) echo 'Error: unknown flag: --mention' >&2; exit 1;; esac\necho '{\"success\":true}'\n",
+        );
+        client.send_message("a@g.us", "hi @46711111111", None).await.unwrap();
+        client.send_message("a@g.us", "again @46711111111", None).await.unwrap();
+        client.send_message("46711111111@s.whatsapp.net", "@46722222222", None).await.unwrap();
+        let calls = std::fs::read_to_string(dir.path().join("calls")).unwrap();
+        let calls: Vec<&str> = calls.lines().filter_map(|c| c.find("send ").map(|at| &c[at..])).collect();
+        assert_eq!(
+            calls,
+            [
+                "send --to a@g.us --message hi @46711111111 --mention 46711111111@s.whatsapp.net",
+                "send --to a@g.us --message hi @46711111111",
+                "send --to a@g.us --message again @46711111111",
+                "send --to 46711111111@s.whatsapp.net --message @46722222222",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_phone_number_jid_uses_the_default_country_code() {
+        let jid = |input: &str, code: &str| phone_number_jid(input, code);
+        assert_eq!(jid("+46 70-123 45 67", ""), Some("46701234567@s.whatsapp.net".to_string()));
+        assert_eq!(jid("0046 (70) 1234567", "1"), Some("46701234567@s.whatsapp.net".to_string()));
+        // A national number drops its trunk zero for the country code
+        assert_eq!(jid("070-123 45 67", "+46"), Some("46701234567@s.whatsapp.net".to_string()));
+        assert_eq!(jid("46701234567", ""), Some("46701234567@s.whatsapp.net".to_string()));
+        assert_eq!(jid("+46 70", ""), None);
+        assert_eq!(jid("+46 70 abc", ""), None);
+        assert_eq!(jid("+1234567890123456", ""), None);
+    }
+
+    #[test]
+    fn test_group_responses_parsing() {
+        let names = std::collections::HashMap::from([("111@s.whatsapp.net".to_string(), "Zed".to_string())]);
+        // whatsmeow-style GroupInfo
+        let info = serde_json::json!({
+            "JID": "team@g.us",
+            "Participants": [
+                {"JID": "111@s.whatsapp.net", "IsAdmin": false, "IsSuperAdmin": false},
+                {"JID": "222@s.whatsapp.net", "IsAdmin": true, "IsSuperAdmin": true, "DisplayName": "Owner"},
+                {"JID": "333@s.whatsapp.net", "IsAdmin": true, "IsSuperAdmin": false}
+            ]
+        });
+        assert_eq!(
+            parse_group_members(&info, &names),
+            vec![
+                ("222@s.whatsapp.net".to_string(), "Owner".to_string(), "superadmin".to_string()),
+                ("333@s.whatsapp.net".to_string(), "+333".to_string(), "admin".to_string()),
+                ("111@s.whatsapp.net".to_string(), "Zed".to_string(), "member".to_string()),
+            ]
+        );
+        // A plain list with role strings
+        let list = serde_json::json!([
+            {"jid": "444@s.whatsapp.net", "name": "bea", "role": "member"},
+            {"jid": "555@s.whatsapp.net", "name": "Al", "role": "ADMIN"},
+            {"name": "no jid"}
+        ]);
+        let roles: Vec<(String, String)> = parse_group_members(&list, &names).into_iter().map(|m| (m.1, m.2)).collect();
+        assert_eq!(roles, vec![("Al".to_string(), "admin".to_string()), ("bea".to_string(), "member".to_string())]);
+
+        assert_eq!(parse_created_group(&serde_json::json!({"jid": "120363@g.us", "name": "Trip"})), Some("120363@g.us".to_string()));
+        assert_eq!(parse_created_group(&serde_json::json!({"jid": "111@s.whatsapp.net"})), None);
+
+        assert_eq!(participant_change_error(&serde_json::json!([{"jid": "111@s.whatsapp.net", "error": 0}])), None);
+        assert_eq!(
+            participant_change_error(&serde_json::json!({"participants": [{"JID": "111@s.whatsapp.net", "Error": 403}]})),
+            Some(403)
+        );
+        assert_eq!(participant_jid("+46 70-123 45"), Some("467012345@s.whatsapp.net".to_string()));
+        assert_eq!(participant_jid("bob"), None);
+    }
+
+    #[test]
+    fn test_send_media_errors_are_readable() {
+        assert!(describe_send_media_error("error: file too large (limit 100MB)").starts_with("File is too large"));
+        assert!(describe_send_media_error("unsupported mime type application/x-foo").starts_with("File type not supported"));
+        assert_eq!(
+            describe_send_media_error("flag provided but not defined: -file"),
+            "This whatsapp-cli can't send files (no --file flag)"
+        );
+        assert_eq!(describe_send_media_error(" \n"), "Failed to send file");
+    }
+
+    #[test]
+    fn test_presence_parsing_and_labels() {
+        let parse = |json: &str| Presence::from_response(&serde_json::from_str(json).unwrap());
+        assert_eq!(parse(r#"{"available": true}"#), Presence::Online);
+        assert_eq!(parse(r#"{"online": false, "last_seen": 1714557600}"#), Presence::LastSeen(1_714_557_600));
+        assert_eq!(parse(r#"{"last_seen": "2024-05-01T10:00:00Z"}"#), Presence::LastSeen(1_714_557_600));
+        assert_eq!(parse(r#"{"available": false, "last_seen": 0}"#), Presence::Unknown);
+        assert_eq!(parse("{}"), Presence::Unknown);
+
+        assert_eq!(Presence::Online.label(), "online");
+        assert!(Presence::LastSeen(1_714_557_600).label().starts_with("last seen 2024-05-01"));
+        assert_eq!(Presence::Unknown.label(), "");
+    }
+
+    #[tokio::test]
+    async fn test_presence_is_cached_and_skips_groups() {
+        let client = WhatsAppClient::new_for_test();
+        assert_eq!(client.get_presence("team@g.us").await, Presence::Unknown);
+
+        client.set_cached_presence("111@s.whatsapp.net", Presence::Online).await;
+        assert_eq!(client.get_presence("111@s.whatsapp.net").await, Presence::Online);
+        // The CLI is missing, so an uncached chat is unknown (and now cached as such)
+        assert_eq!(client.get_presence("222@s.whatsapp.net").await, Presence::Unknown);
+        assert!(client.presence_cache.lock().await.contains_key("222@s.whatsapp.net"));
+    }
+
+    #[tokio::test]
+    async fn test_receipts_parsing_and_cache() {
+        let data = serde_json::json!([
+            {"id": "A", "status": "read"},
+            {"message_id": "B", "delivered": ["1@s.whatsapp.net", "2@s.whatsapp.net"], "read": 1, "recipients": 3},
+            {"id": "C", "status": "server_ack"},
+            {"status": "read"}
+        ]);
+        let direct = parse_receipts(&data, false);
+        assert_eq!(direct["A"], Receipts { delivered: 1, read: 1, recipients: Some(1) });
+        assert_eq!(direct["C"], Receipts { delivered: 0, read: 0, recipients: Some(1) });
+        assert_eq!(direct.len(), 3);
+        let group = parse_receipts(&data, true);
+        assert_eq!(group["B"], Receipts { delivered: 2, read: 1, recipients: Some(3) });
+        assert_eq!(group["A"].recipients, None);
+
+        let client = WhatsAppClient::from_parts(PathBuf::from("whatsapp-cli"), std::env::temp_dir(), 1);
+        client.set_cached_receipts("team@g.us", group.clone()).await;
+        assert_eq!(client.get_receipts("team@g.us").await, group);
+        // The CLI is missing, so an uncached chat has no receipts
+        assert!(client.get_receipts("111@s.whatsapp.net").await.is_empty());
+    }
+
+    #[test]
+    fn test_message_info_lists_each_recipient() {
+        let data = serde_json::json!([
+            {"id": "A", "recipients": 4,
+             "receipts": [{"jid": "1@s.whatsapp.net", "status": "delivered", "timestamp": 100},
+                          {"jid": "1@s.whatsapp.net", "status": "read", "timestamp": 160}],
+             "delivered": ["2@s.whatsapp.net", {"jid": "3@s.whatsapp.net", "timestamp": 90}]},
+            {"id": "B", "status": "delivered"}
+        ]);
+        let names = std::collections::HashMap::from([("2@s.whatsapp.net".to_string(), "Bob".to_string())]);
+
+        let group = parse_message_info(&data, "team@g.us", "A", &names);
+        assert_eq!(group.totals, Receipts { delivered: 3, read: 1, recipients: Some(4) });
+        let listed: Vec<(&str, ReceiptStatus, Option<i64>)> =
+            group.recipients.iter().map(|(_, name, status, at)| (name.as_str(), *status, *at)).collect();
+        assert_eq!(
+            listed,
+            vec![("+1", ReceiptStatus::Read, Some(160)), ("+3", ReceiptStatus::Delivered, Some(90)), ("Bob", ReceiptStatus::Delivered, None)]
+        );
+
+        // A one-to-one chat without lists has the chat itself as its one recipient
+        let direct = parse_message_info(&data, "46701234567@s.whatsapp.net", "B", &names);
+        assert_eq!(direct.recipients.len(), 1);
+        assert_eq!(direct.recipients[0].0, "46701234567@s.whatsapp.net");
+        assert_eq!(direct.recipients[0].2, ReceiptStatus::Delivered);
+        assert_eq!(parse_message_info(&data, "team@g.us", "missing", &names), MessageInfo::default());
+    }
+
+    #[test]
+    fn test_parse_typing_event() {
+        assert_eq!(
+            parse_typing_event(r#"{"type":"chat_presence","chat":"team@g.us","sender":"111@s.whatsapp.net","state":"composing"}"#),
+            Some(("team@g.us".to_string(), "111@s.whatsapp.net".to_string()))
+        );
+        // Individual chats may omit the sender; the event can also be wrapped in "data"
+        assert_eq!(
+            parse_typing_event(r#"{"event":"presence","data":{"chat_jid":"222@s.whatsapp.net","chat_state":"Composing"}}"#),
+            Some(("222@s.whatsapp.net".to_string(), "222@s.whatsapp.net".to_string()))
+        );
+        assert_eq!(parse_typing_event(r#"{"chat":"team@g.us","state":"paused"}"#), None);
+        assert_eq!(parse_typing_event("[Client INFO] Successfully authenticated"), None);
+        assert_eq!(parse_typing_event(r#"{"state":"composing"}"#), None);
+    }
+
+    #[tokio::test]
+    async fn test_connection_events_from_sync_output() {
+        assert_eq!(
+            parse_connection_event(r#"{"event":"Disconnected"}"#),
+            Some(ConnectionState::Disconnected)
+        );
+        assert_eq!(
+            parse_connection_event(r#"{"data":{"type":"keepalive_restored"}}"#),
+            Some(ConnectionState::Connected)
+        );
+        assert_eq!(parse_connection_event(r#"{"chat":"a@g.us","state":"connected"}"#), None);
+        assert_eq!(parse_connection_event("[Client WARN] Connection lost"), Some(ConnectionState::Disconnected));
+        assert_eq!(parse_connection_event("Syncing history"), None);
+
+        let client = WhatsAppClient::new_for_test();
+        let output = ["Connected", "[Client INFO] Connected", "Disconnected"].join("\n");
+        client
+            .forward_sync_events(std::io::Cursor::new(output.into_bytes()))
+            .await;
+        assert_eq!(client.connection_state().await, ConnectionState::Disconnected);
+        let changes: Vec<ConnectionState> = client
+            .poll_updates()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|u| match u {
+                WhatsAppUpdate::ConnectionChanged(state) => Some(state),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(changes, vec![ConnectionState::Connected, ConnectionState::Disconnected]);
+    }
+
+    #[tokio::test]
+    async fn test_forward_typing_events_from_sync_output() {
+        let client = WhatsAppClient::new_for_test();
+        *client.my_jid.lock().await = Some("me@s.whatsapp.net".to_string());
+        client
+            .contact_cache
+            .lock()
+            .await
+            .insert("111@s.whatsapp.net".to_string(), "Alice".to_string());
+
+        let output = [
+            "[Client INFO] Connected",
+            r#"{"chat":"team@g.us","sender":"111@s.whatsapp.net","state":"composing"}"#,
+            r#"{"chat":"team@g.us","sender":"me@s.whatsapp.net","state":"composing"}"#,
+            r#"{"chat":"333@s.whatsapp.net","state":"composing"}"#,
+        ]
+        .join("\n");
+        client
+            .forward_sync_events(std::io::Cursor::new(output.into_bytes()))
+            .await;
+
+        let updates = client.poll_updates().await.unwrap();
+        let typing: Vec<(String, String)> = updates
+            .into_iter()
+            .filter_map(|u| match u {
+                WhatsAppUpdate::UserTyping { chat_jid, user_name } => Some((chat_jid, user_name)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            typing,
+            vec![
+                ("team@g.us".to_string(), "Alice".to_string()),
+                ("333@s.whatsapp.net".to_string(), "+333".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_own_jid_from_store() {
+        assert_eq!(user_jid("46700000000:12@s.whatsapp.net"), "46700000000@s.whatsapp.net");
+        assert_eq!(user_jid("46700000000.0:3@s.whatsapp.net"), "46700000000@s.whatsapp.net");
+        assert_eq!(user_jid("46700000000@s.whatsapp.net"), "46700000000@s.whatsapp.net");
+
+        // No device table: the sender of our newest outgoing message
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "own_jid",
+            &[
+                ("1", "team@g.us", "111@s.whatsapp.net", "hi", "2024-01-01 10:00:00", false),
+                ("2", "111@s.whatsapp.net", "46700000000@s.whatsapp.net", "hello", "2024-01-01 10:01:00", true),
+            ],
+        );
+        assert_eq!(client.own_jid_from_store().await.as_deref(), Some("46700000000@s.whatsapp.net"));
+
+        // The logged-in device wins when whatsapp.db has one
+        let conn = Connection::open(client.store_path.join("whatsapp.db")).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE whatsmeow_device (jid TEXT PRIMARY KEY);
+             INSERT INTO whatsmeow_device VALUES ('46799999999:7@s.whatsapp.net');",
+        )
+        .unwrap();
+        assert_eq!(client.own_jid_from_store().await.as_deref(), Some("46799999999@s.whatsapp.net"));
+        let _ = std::fs::remove_dir_all(&client.store_path);
+
+        assert_eq!(WhatsAppClient::new_for_test().own_jid_from_store().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_store_table_counts() {
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "table_counts",
+            &[
+                ("1", "team@g.us", "111@s.whatsapp.net", "hi", "2024-01-01 10:00:00", false),
+                ("2", "team@g.us", "222@s.whatsapp.net", "yo", "2024-01-01 10:01:00", false),
+            ],
+        );
+        assert_eq!(
+            client.store_table_counts().await,
+            vec![("messages.db".to_string(), "messages".to_string(), 2)]
+        );
+        let _ = std::fs::remove_dir_all(&client.store_path);
+    }
+
+    #[tokio::test]
+    async fn test_chat_timestamps_from_payload_and_db() {
+        assert_eq!(chat_timestamp(&serde_json::json!(1_700_000_000)), Some(1_700_000_000));
+        assert_eq!(chat_timestamp(&serde_json::json!(1_700_000_000_123_i64)), Some(1_700_000_000));
+        assert_eq!(chat_timestamp(&serde_json::json!("2024-01-01T00:00:00Z")), Some(1_704_067_200));
+        assert_eq!(chat_timestamp(&serde_json::json!("soon")), None);
+        assert_eq!(chat_timestamp(&serde_json::Value::Null), None);
+
+        let client = WhatsAppClient::new_for_test_with_messages(
+            "chat_times",
+            &[
+                ("1", "team@g.us", "111@s.whatsapp.net", "a", "2024-01-01 10:00:00", false),
+                ("2", "team@g.us", "111@s.whatsapp.net", "b", "2024-01-02 10:00:00", false),
+                ("3", "111@s.whatsapp.net", "111@s.whatsapp.net", "c", "2024-01-01 09:00:00", false),
+            ],
+        );
+        let times = client.last_message_times_from_db().await;
+        let _ = std::fs::remove_dir_all(&client.store_path);
+        assert_eq!(times.get("team@g.us"), Some(&1_704_189_600));
+        assert_eq!(times.get("111@s.whatsapp.net"), Some(&1_704_099_600));
+    }
 }
@@ -1,10 +1,129 @@
+use ratatui::layout::Rect;
+use std::cell::{Cell, RefCell};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::formatting::{LineLayout, WrapStyle};
+use crate::whatsapp::{RawMessage, SearchHit, SharedContact};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FilterType {
     Sender,
     Media,
     Link,
+    TimeRange { since: Option<i64>, before: Option<i64> }, // Unix seconds, `before` is exclusive
+}
+
+impl FilterType {
+    /// Name used in the filter indicator and in saved layouts
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterType::Sender => "sender",
+            FilterType::Media => "media",
+            FilterType::Link => "link",
+            FilterType::TimeRange { .. } => "time",
+        }
+    }
+
+    /// Rebuild a filter from a saved layout's `filter_type`/`filter_value`
+    pub fn from_saved(name: &str, value: Option<&str>) -> Option<Self> {
+        match name {
+            "sender" => Some(FilterType::Sender),
+            "media" => Some(FilterType::Media),
+            "link" => Some(FilterType::Link),
+            "time" => {
+                let (since, before) = value?.split_once("..")?;
+                Some(FilterType::TimeRange {
+                    since: since.parse().ok(),
+                    before: before.parse().ok(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// `filter_value` stored alongside a time range, "since..before" with empty open ends
+    pub fn time_range_value(since: Option<i64>, before: Option<i64>) -> String {
+        let bound = |b: Option<i64>| b.map(|t| t.to_string()).unwrap_or_default();
+        format!("{}..{}", bound(since), bound(before))
+    }
+}
+
+/// One condition of a pane's filter; messages must match every filter on the pane
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Filter {
+    pub kind: FilterType,
+    pub value: String,
+}
+
+impl Filter {
+    pub fn new(kind: FilterType, value: impl Into<String>) -> Self {
+        Self {
+            kind,
+            value: value.into(),
+        }
+    }
+
+    pub fn matches(&self, data: &MessageData) -> bool {
+        match &self.kind {
+            FilterType::Sender => {
+                data.sender_name.to_lowercase().contains(&self.value.to_lowercase())
+            }
+            FilterType::Media => {
+                match self.value.as_str() {
+                    "photo" => data.media_type.as_deref() == Some("photo"),
+                    "video" => data.media_type.as_deref() == Some("video"),
+                    "audio" => data.media_type.as_deref() == Some("audio"),
+                    "voice" => data.media_type.as_deref() == Some("voice"),
+                    "document" => data.media_type.as_deref() == Some("document"),
+                    "sticker" => data.media_type.as_deref() == Some("sticker"),
+                    "gif" => data.media_type.as_deref() == Some("gif"),
+                    _ => data.media_type.is_some(),
+                }
+            }
+            FilterType::Link => {
+                data.text.contains("http://") || data.text.contains("https://")
+            }
+            FilterType::TimeRange { since, before } => {
+                since.is_none_or(|t| data.timestamp >= t) && before.is_none_or(|t| data.timestamp < t)
+            }
+        }
+    }
+}
+
+/// Where one of our messages is on its way to WhatsApp
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SendStatus {
+    #[default]
+    Sent,    // Handed over to WhatsApp, or not ours to send
+    Pending, // Written here and not yet accepted by whatsapp-cli
+    Failed,  // The send queue gave up after its retries
+}
+
+/// Marker for a message everyone it went to has read, drawn in the theme's `receipt_read`
+/// color. Delivered messages get a plain "✓✓".
+pub const READ_MARKER: &str = "✔✔";
+
+/// Delivery and read receipts of one of our messages. In a group the counts are how many
+/// members got / read it, out of `recipients` when whatsapp-cli says.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Receipts {
+    pub delivered: u32,
+    pub read: u32,
+    pub recipients: Option<u32>,
+}
+
+impl Receipts {
+    /// ✓ sent, ✓✓ delivered, READ_MARKER read by everyone, or "✓✓ 2/5" read by some
+    pub fn marker(&self) -> String {
+        match self.recipients {
+            _ if self.read == 0 && self.delivered == 0 => "✓".to_string(),
+            _ if self.read == 0 => "✓✓".to_string(),
+            Some(total) if self.read >= total => READ_MARKER.to_string(),
+            Some(total) => format!("✓✓ {}/{}", self.read, total),
+            None => format!("✓✓ {} read", self.read),
+        }
+    }
 }
 
 /// Represents a single message with all its metadata for display
@@ -18,10 +137,60 @@ pub struct MessageData {
     pub timestamp: i64,        // Unix timestamp
     pub media_type: Option<String>,
     pub media_label: Option<String>,  // e.g. "[YouTube: title]"
+    pub location: Option<(f64, f64)>, // Latitude and longitude of a location message
+    pub contacts: Vec<SharedContact>, // Contacts shared in a contact message
     pub reactions: HashMap<String, u32>,
     pub reply_to_msg_id: Option<String>,
     pub reply_sender: Option<String>,
     pub reply_text: Option<String>,
+    pub pending: bool,         // Written offline and waiting in the outbox
+    pub sending: bool,         // Held back for the undo-send window
+    pub send_status: SendStatus,
+    pub receipts: Option<Receipts>, // None until whatsapp-cli has reported them
+}
+
+impl MessageData {
+    /// Whether this is the optimistic copy (empty `msg_id`) of `synced`, the same sent
+    /// message as later loaded from the store. Queued and held messages haven't been sent yet
+    pub fn is_local_echo_of(&self, synced: &MessageData) -> bool {
+        self.msg_id.is_empty()
+            && !self.pending
+            && !self.sending
+            && self.is_outgoing
+            && synced.is_outgoing
+            && self.text == synced.text
+            && (self.timestamp - synced.timestamp).abs() <= 60
+    }
+
+    pub fn from_raw(raw: &RawMessage, my_user_jid: &str) -> Self {
+        let (msg_id, sender_id, sender_name, text, reply_to_id, media_type, reactions, timestamp) = raw;
+        Self {
+            msg_id: msg_id.clone(),
+            sender_id: sender_id.clone(),
+            sender_name: sender_name.clone(),
+            text: text.clone(),
+            is_outgoing: sender_id == my_user_jid,
+            timestamp: *timestamp,
+            media_type: media_type.clone(),
+            media_label: None,
+            location: crate::whatsapp::message_location(media_type.as_deref(), text),
+            contacts: crate::whatsapp::message_contacts(media_type.as_deref(), text),
+            reactions: reactions.clone(),
+            reply_to_msg_id: reply_to_id.clone(),
+            reply_sender: None,
+            reply_text: None,
+            pending: false,
+            sending: false,
+            send_status: SendStatus::Sent,
+            receipts: None,
+        }
+    }
+
+    /// Our own message shown before whatsapp-cli has it: no ID yet and `Pending`
+    pub fn echo(my_user_jid: &str, text: &str, timestamp: i64) -> Self {
+        let raw = (String::new(), my_user_jid.to_string(), "You".to_string(), text.to_string(), None, None, HashMap::new(), timestamp);
+        Self { send_status: SendStatus::Pending, ..Self::from_raw(&raw, my_user_jid) }
+    }
 }
 
 pub struct ChatPane {
@@ -30,20 +199,34 @@ pub struct ChatPane {
     pub username: Option<String>,
     pub messages: Vec<String>,         // Formatted display lines
     pub msg_data: Vec<MessageData>,    // Raw message data for formatting
-    pub scroll_offset: usize,
+    pub history_exhausted: bool,       // Scrolling up found nothing older than msg_data's first message
+    pub history_unavailable: bool,     // whatsapp-cli can't reach back past msg_data's first message
+    pub scroll_offset: usize,          // 0 pins to the bottom, otherwise 1 + the first visible line
     pub reply_to_message: Option<String>,  // Telegram message ID to reply to
     pub reply_preview: Option<String>, // Text shown in reply preview bar
-    pub filter_type: Option<FilterType>,
-    pub filter_value: Option<String>,
+    pub filters: Vec<Filter>,          // All must match (AND)
+    pub last_filters: Vec<Filter>,     // Restored by the filter toggle key
     pub typing_indicator: Option<String>, // "Name is typing..."
+    pub sync_progress: Option<String>, // "Syncing group: found 12 messages…" while a group is force-synced
     pub typing_expire: Option<std::time::Instant>,
     pub online_status: String,
     pub pinned_message: Option<String>,
     pub _unread_count: u32,
     pub unread_count_at_load: u32,
+    pub unread_only: bool,             // Show only unread messages plus a little context
+    pub list_mode: bool,               // One truncated line per message
+    pub send_as: Option<String>,       // Linked identity messages are sent from (`send --from`)
+    pub selected_msg: Option<usize>,   // List-mode cursor, index into msg_data
+    pub expanded_msg: Option<usize>,   // List-mode message shown in full
     pub format_cache: HashMap<FormatCacheKey, Vec<String>>,
     pub input_buffer: String,          // Per-pane input buffer
     pub input_cursor: usize,           // Cursor byte position in input_buffer
+    pub last_message_width: Cell<usize>, // Wrap width used by the last render
+    pub line_to_msg: RefCell<Vec<Option<usize>>>, // Message index per rendered line, for mouse clicks
+    pub message_area: Cell<Rect>,      // Inner message area of the last render
+    pub message_scroll: Cell<usize>,   // First rendered line visible in `message_area`
+    pub max_scroll: Cell<usize>,       // First visible line when pinned to the bottom, as of the last render
+    pub search_hits: Vec<SearchHit>,   // Results shown by /searchall, numbered for /open
 }
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
@@ -55,8 +238,7 @@ pub struct FormatCacheKey {
     pub show_timestamps: bool,
     pub show_line_numbers: bool,
     pub msg_count: usize,
-    pub filter_type: Option<String>,
-    pub filter_value: Option<String>,
+    pub filters: Vec<Filter>,
 }
 
 impl ChatPane {
@@ -67,19 +249,33 @@ impl ChatPane {
             username: None,
             messages: Vec::new(),
             msg_data: Vec::new(),
+            history_exhausted: false,
+            history_unavailable: false,
             scroll_offset: 0,
             reply_to_message: None,
             reply_preview: None,
-            filter_type: None,
-            filter_value: None,
+            filters: Vec::new(),
+            last_filters: Vec::new(),
             typing_indicator: None,
+            sync_progress: None,
             typing_expire: None,
             online_status: String::new(),
             pinned_message: None,
             _unread_count: 0,
             unread_count_at_load: 0,
+            unread_only: false,
+            list_mode: false,
+            send_as: None,
+            selected_msg: None,
+            expanded_msg: None,
             input_buffer: String::new(),
             input_cursor: 0,
+            last_message_width: Cell::new(0),
+            line_to_msg: RefCell::new(Vec::new()),
+            message_area: Cell::new(Rect::default()),
+            message_scroll: Cell::new(0),
+            max_scroll: Cell::new(0),
+            search_hits: Vec::new(),
             format_cache: HashMap::new(),
         }
     }
@@ -91,17 +287,217 @@ impl ChatPane {
     pub fn clear(&mut self) {
         self.messages.clear();
         self.msg_data.clear();
+        self.history_exhausted = false;
+        self.history_unavailable = false;
         self.scroll_offset = 0;
         self.input_buffer.clear();
         self.format_cache.clear();
+        self.search_hits.clear();
+        self.selected_msg = None;
+        self.expanded_msg = None;
+    }
+
+    /// Switch between wrapped messages and list mode. Returns whether list mode is now on.
+    pub fn toggle_list_mode(&mut self) -> bool {
+        self.list_mode = !self.list_mode;
+        self.expanded_msg = None;
+        if !self.list_mode {
+            self.selected_msg = None;
+        }
+        self.list_mode
+    }
+
+    /// Expand the selected message in list mode, or collapse it if it already is
+    pub fn toggle_expanded(&mut self) {
+        self.expanded_msg = match self.selected_msg {
+            Some(idx) if self.expanded_msg != Some(idx) => Some(idx),
+            _ => None,
+        };
+    }
+
+    /// How this pane lays out its messages at `width` columns
+    pub fn line_layout(&self, width: usize, wrap_style: WrapStyle) -> LineLayout {
+        LineLayout {
+            list_mode: self.list_mode,
+            expanded: self.expanded_msg,
+            ..LineLayout::new(width, wrap_style)
+        }
+    }
+
+    /// Append newly synced messages, skipping ones already shown. An outgoing message that
+    /// matches a local echo (empty `msg_id`, same text, within a minute) replaces the echo,
+    /// taking over the server's timestamp, and the list is put back in time order.
+    /// Returns how many messages were added.
+    pub fn merge_new_messages(&mut self, new_messages: Vec<MessageData>) -> usize {
+        let mut added = 0;
+        let mut reconciled = false;
+        for msg in new_messages {
+            if self.msg_data.iter().any(|m| !m.msg_id.is_empty() && m.msg_id == msg.msg_id) {
+                continue;
+            }
+            let echo = self.msg_data.iter_mut().find(|m| m.is_local_echo_of(&msg));
+            match echo {
+                Some(echo) => {
+                    *echo = msg;
+                    reconciled = true;
+                }
+                None => {
+                    // Keep the unread divider in place while the reader is still catching up
+                    if self.unread_count_at_load > 0 && !msg.is_outgoing {
+                        self.unread_count_at_load += 1;
+                    }
+                    self.msg_data.push(msg);
+                    added += 1;
+                }
+            }
+        }
+        if reconciled {
+            self.sort_by_timestamp();
+        }
+        self.format_cache.clear();
+        added
+    }
+
+    /// Stable sort of the messages by timestamp, keeping the list-mode selection and the
+    /// expanded message on the same messages
+    fn sort_by_timestamp(&mut self) {
+        if self.msg_data.windows(2).all(|w| w[0].timestamp <= w[1].timestamp) {
+            return;
+        }
+        let mut order: Vec<usize> = (0..self.msg_data.len()).collect();
+        order.sort_by_key(|&i| self.msg_data[i].timestamp);
+        let new_index = |old: usize| order.iter().position(|&i| i == old);
+        self.selected_msg = self.selected_msg.and_then(new_index);
+        self.expanded_msg = self.expanded_msg.and_then(new_index);
+
+        let mut old: Vec<Option<MessageData>> = std::mem::take(&mut self.msg_data).into_iter().map(Some).collect();
+        self.msg_data = order.iter().filter_map(|&i| old[i].take()).collect();
+    }
+
+    /// Replace the pane's messages with a freshly loaded list. Local echoes that the list
+    /// already contains are dropped in favour of the synced copy; the rest stay at the end.
+    pub fn reload_messages(&mut self, messages: Vec<MessageData>) {
+        let echoes: Vec<MessageData> = std::mem::replace(&mut self.msg_data, messages)
+            .into_iter()
+            .filter(|m| m.msg_id.is_empty() && m.is_outgoing)
+            .collect();
+        for echo in echoes {
+            if !self.msg_data.iter().any(|m| echo.is_local_echo_of(m)) {
+                self.msg_data.push(echo);
+            }
+        }
+        self.history_exhausted = false;
+        self.history_unavailable = false;
+        self.format_cache.clear();
+    }
+
+    /// Put an older batch of messages before the loaded ones, skipping any already loaded.
+    /// Message indices such as the list-mode cursor move along. Returns how many were added.
+    pub fn prepend_messages(&mut self, older: Vec<MessageData>) -> usize {
+        let mut older: Vec<MessageData> = older
+            .into_iter()
+            .filter(|m| m.msg_id.is_empty() || !self.msg_data.iter().any(|known| known.msg_id == m.msg_id))
+            .collect();
+        older.sort_by_key(|m| m.timestamp);
+        let added = older.len();
+        if added == 0 {
+            return 0;
+        }
+        older.append(&mut self.msg_data);
+        self.msg_data = older;
+        self.selected_msg = self.selected_msg.map(|idx| idx + added);
+        self.expanded_msg = self.expanded_msg.map(|idx| idx + added);
+        self.format_cache.clear();
+        added
+    }
+
+    /// Toggle the unread-only view. Returns whether it is now on; it can't be turned on
+    /// without unread messages.
+    pub fn toggle_unread_only(&mut self) -> bool {
+        self.unread_only = !self.unread_only && self.unread_count_at_load > 0;
+        self.format_cache.clear();
+        self.unread_only
+    }
+
+    /// Drop the unread divider and the unread-only view once the reader has caught up
+    pub fn mark_caught_up(&mut self) {
+        self.unread_count_at_load = 0;
+        self.unread_only = false;
+        self.format_cache.clear();
+    }
+
+    /// Replace all filters with a single one
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filters.clear();
+        self.add_filter(filter);
+    }
+
+    /// Add a filter, replacing any existing filter of the same kind, and remember the set for the toggle key
+    pub fn add_filter(&mut self, filter: Filter) {
+        self.filters.retain(|f| f.kind.name() != filter.kind.name());
+        self.filters.push(filter);
+        self.last_filters = self.filters.clone();
+        self.format_cache.clear();
+    }
+
+    /// Remove the filter of the given kind ("sender", "media", "link", "time"). Returns whether one was removed.
+    pub fn remove_filter(&mut self, kind: &str) -> bool {
+        let before = self.filters.len();
+        self.filters.retain(|f| f.kind.name() != kind);
+        if self.filters.len() == before {
+            return false;
+        }
+        if !self.filters.is_empty() {
+            self.last_filters = self.filters.clone();
+        }
+        self.format_cache.clear();
+        true
+    }
+
+    /// Flip between the last-used filters and the full view. Returns whether filters are now active.
+    pub fn toggle_filter(&mut self) -> bool {
+        if !self.filters.is_empty() {
+            self.clear_filter();
+            false
+        } else if !self.last_filters.is_empty() {
+            self.filters = self.last_filters.clone();
+            self.format_cache.clear();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Drop all active filters; filters belong to the chat they were set in
+    pub fn clear_filter(&mut self) {
+        self.filters.clear();
+        self.format_cache.clear();
     }
 
     pub fn scroll_up(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_sub(3);
+        let first_line = match self.scroll_offset {
+            0 => self.max_scroll.get(),
+            offset => offset - 1,
+        };
+        self.scroll_offset = first_line.saturating_sub(3) + 1;
     }
 
     pub fn scroll_down(&mut self) {
-        self.scroll_offset = self.scroll_offset.saturating_add(3);
+        if self.scroll_offset == 0 {
+            return;
+        }
+        let first_line = self.scroll_offset - 1 + 3;
+        self.scroll_offset = if first_line >= self.max_scroll.get() { 0 } else { first_line + 1 };
+    }
+
+    /// Show the first line of the loaded history
+    pub fn scroll_to_top(&mut self) {
+        self.scroll_offset = 1;
+    }
+
+    /// Pin to the newest message
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_offset = 0;
     }
 
     pub fn show_typing_indicator(&mut self, name: &str) {
@@ -114,12 +510,16 @@ impl ChatPane {
         self.typing_expire = None;
     }
 
-    pub fn check_typing_expired(&mut self) {
+    /// Hide the typing indicator once it has gone 5 seconds without a fresh event.
+    /// Returns whether it was hidden
+    pub fn check_typing_expired(&mut self) -> bool {
         if let Some(expire) = self.typing_expire {
             if std::time::Instant::now() >= expire {
                 self.hide_typing_indicator();
+                return true;
             }
         }
+        false
     }
 
     pub fn show_reply_preview(&mut self, text: String) {
@@ -144,6 +544,10 @@ impl ChatPane {
             }
         }
 
+        if let Some(ref send_as) = self.send_as {
+            header.push_str(&format!(" [as {}]", send_as));
+        }
+
         if let Some(ref pinned) = self.pinned_message {
             header.push_str(&format!(" | Pinned: {}", pinned));
         }
@@ -152,33 +556,26 @@ impl ChatPane {
             header.push_str(&format!(" {}", typing));
         }
 
+        if let Some(ref progress) = self.sync_progress {
+            header.push_str(&format!(" | {}", progress));
+        }
+
         header
     }
 
-    /// Check if a message matches the current filter
-    pub fn _message_matches_filter(&self, data: &MessageData) -> bool {
-        match (&self.filter_type, &self.filter_value) {
-            (None, _) => true,
-            (Some(FilterType::Sender), Some(value)) => {
-                data.sender_name.to_lowercase().contains(&value.to_lowercase())
-            }
-            (Some(FilterType::Media), Some(value)) => {
-                match value.as_str() {
-                    "photo" => data.media_type.as_deref() == Some("photo"),
-                    "video" => data.media_type.as_deref() == Some("video"),
-                    "audio" => data.media_type.as_deref() == Some("audio"),
-                    "voice" => data.media_type.as_deref() == Some("voice"),
-                    "document" => data.media_type.as_deref() == Some("document"),
-                    "sticker" => data.media_type.as_deref() == Some("sticker"),
-                    "gif" => data.media_type.as_deref() == Some("gif"),
-                    _ => data.media_type.is_some(),
-                }
-            }
-            (Some(FilterType::Link), _) => {
-                data.text.contains("http://") || data.text.contains("https://")
-            }
-            _ => true,
+    /// Index into `msg_data` of the message drawn at screen position (x, y) in the last render
+    pub fn message_at(&self, x: u16, y: u16) -> Option<usize> {
+        let area = self.message_area.get();
+        if x < area.x || x >= area.x + area.width || y < area.y || y >= area.y + area.height {
+            return None;
         }
+        let line = self.message_scroll.get() + (y - area.y) as usize;
+        self.line_to_msg.borrow().get(line).copied().flatten()
+    }
+
+    /// Check if a message matches all of the pane's filters
+    pub fn message_matches_filter(&self, data: &MessageData) -> bool {
+        self.filters.iter().all(|f| f.matches(data))
     }
 }
 
@@ -187,3 +584,104 @@ impl Default for ChatPane {
         Self::new()
     }
 }
+
+/// Reactions offered by the picker after the recently used ones
+const COMMON_REACTIONS: [&str; 6] = ["👍", "❤️", "😂", "😮", "😢", "🙏"];
+
+/// Most choices the picker shows, so each has a number key
+const MAX_PICKER_CHOICES: usize = 9;
+
+/// Quick-pick overlay for reacting to one message: recently used emoji first, then common ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionPicker {
+    pub pane_idx: usize,
+    pub msg_idx: usize,
+    pub choices: Vec<String>,
+    pub selected: usize,
+}
+
+impl ReactionPicker {
+    pub fn new(pane_idx: usize, msg_idx: usize, recent: &[String]) -> Self {
+        let mut choices: Vec<String> = Vec::new();
+        for emoji in recent.iter().map(String::as_str).chain(COMMON_REACTIONS) {
+            if choices.len() == MAX_PICKER_CHOICES {
+                break;
+            }
+            if !choices.iter().any(|c| c == emoji) {
+                choices.push(emoji.to_string());
+            }
+        }
+        Self { pane_idx, msg_idx, choices, selected: 0 }
+    }
+
+    /// Move the highlight, wrapping around at either end
+    pub fn move_by(&mut self, delta: isize) {
+        let len = self.choices.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+
+    pub fn selected_emoji(&self) -> &str {
+        &self.choices[self.selected]
+    }
+}
+
+/// Overlay for hopping between recently opened chats. Each press of the switcher key moves
+/// the highlight down; the chat opens once the key is left alone (see `due`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChatSwitcher {
+    pub chats: Vec<(String, String)>, // (JID, name), most recent first
+    pub selected: usize,
+    /// When the switcher key last moved the highlight. Cleared when the arrow keys take
+    /// over, after which only a key press opens the chat.
+    pub last_press: Option<std::time::Instant>,
+}
+
+impl ChatSwitcher {
+    /// Highlights the second chat when the first is already on screen, like Alt+Tab
+    pub fn new(chats: Vec<(String, String)>, current: Option<&str>) -> Self {
+        let selected = usize::from(chats.len() > 1 && chats.first().map(|(jid, _)| jid.as_str()) == current);
+        Self { chats, selected, last_press: Some(std::time::Instant::now()) }
+    }
+
+    /// Move the highlight, wrapping around at either end
+    pub fn move_by(&mut self, delta: isize) {
+        let len = self.chats.len() as isize;
+        self.selected = (self.selected as isize + delta).rem_euclid(len) as usize;
+    }
+
+    pub fn selected_chat(&self) -> &(String, String) {
+        &self.chats[self.selected]
+    }
+
+    /// Whether the key was last pressed at least `hold` ago, the stand-in for letting go
+    /// of it: terminals don't report key releases
+    pub fn due(&self, hold: std::time::Duration) -> bool {
+        self.last_press.is_some_and(|at| at.elapsed() >= hold)
+    }
+}
+
+/// Which question the new chat wizard is asking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewChatStep {
+    #[default]
+    Number,
+    Message,
+}
+
+/// Overlay that starts a chat step by step: the phone number, then an optional first message
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct NewChatWizard {
+    pub step: NewChatStep,
+    pub number: String,
+    pub message: String,
+}
+
+impl NewChatWizard {
+    /// The text the current step edits
+    pub fn input_mut(&mut self) -> &mut String {
+        match self.step {
+            NewChatStep::Number => &mut self.number,
+            NewChatStep::Message => &mut self.message,
+        }
+    }
+}